@@ -0,0 +1,253 @@
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use tauri::AppHandle;
+
+use crate::cancel;
+use crate::convert::{self, ConvertOptions, Destination};
+use crate::{naming, scan};
+
+/// Errors meaning the archive needs a password, or a different one, start with this, so
+/// the frontend knows to ask and try again
+const PASSWORD_REQUIRED: &str = "Archive password required";
+
+/// Common 7-Zip locations to check on macOS
+#[cfg(target_os = "macos")]
+const SEVEN_ZIP_PATHS: &[&str] = &["/opt/homebrew/bin/7zz", "/usr/local/bin/7zz", "/opt/homebrew/bin/7z", "/usr/local/bin/7z"];
+
+/// Common 7-Zip locations to check on Windows
+#[cfg(target_os = "windows")]
+const SEVEN_ZIP_PATHS: &[&str] = &[
+    "7z",  // In PATH
+    "C:\\Program Files\\7-Zip\\7z.exe",
+];
+
+/// Common 7-Zip locations to check on Linux
+#[cfg(target_os = "linux")]
+const SEVEN_ZIP_PATHS: &[&str] = &["/usr/bin/7zz", "/usr/bin/7z", "/usr/bin/7za", "/usr/local/bin/7zz"];
+
+/// A video inside an archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveEntry {
+    /// Path inside the archive; pass it back to `convert_from_archive`
+    pub name: String,
+    pub size: u64,
+    /// Extracting it needs the password
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveConversion {
+    pub name: String,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+}
+
+fn password_error(wrong: bool) -> String {
+    if wrong {
+        format!("{}: the password is wrong", PASSWORD_REQUIRED)
+    } else {
+        PASSWORD_REQUIRED.to_string()
+    }
+}
+
+enum Format {
+    Zip,
+    /// RAR and 7z, through the 7-Zip command line tool
+    SevenZip,
+}
+
+fn format(path: &Path) -> Result<Format, String> {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match ext.as_str() {
+        "zip" => Ok(Format::Zip),
+        "rar" | "7z" => Ok(Format::SevenZip),
+        _ => Err(format!("Only .zip, .rar and .7z archives can be opened, not .{}", ext)),
+    }
+}
+
+fn find_seven_zip() -> Option<String> {
+    SEVEN_ZIP_PATHS
+        .iter()
+        .find(|path| (PathBuf::from(path).exists() || **path == "7z") && crate::tool_runs(path, "i"))
+        .map(|path| path.to_string())
+}
+
+fn open_zip(path: &Path) -> Result<zip::ZipArchive<File>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    zip::ZipArchive::new(file).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+fn list_zip(path: &Path) -> Result<Vec<ArchiveEntry>, String> {
+    let mut archive = open_zip(path)?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        // Raw, so listing works without the password
+        let file = archive.by_index_raw(i).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        entries.push(ArchiveEntry { name: file.name().to_string(), size: file.size(), encrypted: file.encrypted() });
+    }
+    Ok(entries)
+}
+
+fn extract_zip(path: &Path, name: &str, password: Option<&str>, target: &Path) -> Result<(), String> {
+    let mut archive = open_zip(path)?;
+    let mut file = match password {
+        Some(password) => archive.by_name_decrypt(name, password.as_bytes()),
+        None => archive.by_name(name),
+    }
+    .map_err(|e| match e {
+        zip::result::ZipError::UnsupportedArchive(zip::result::ZipError::PASSWORD_REQUIRED) => password_error(false),
+        zip::result::ZipError::InvalidPassword => password_error(true),
+        e => format!("Failed to extract {}: {}", name, e),
+    })?;
+    let mut out = File::create(target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+    // A wrong ZipCrypto password can get past the header check and only fail the checksum here
+    io::copy(&mut file, &mut out).map_err(|e| match file.encrypted() {
+        true => password_error(true),
+        false => format!("Failed to extract {}: {}", name, e),
+    })?;
+    Ok(())
+}
+
+/// Run 7-Zip non-interactively. It only takes passwords on the command line, where other
+/// processes of the same user can read them while it runs; an empty one keeps it from
+/// waiting on a prompt. Started directly, not through `cmd /C` like other tools on Windows:
+/// entry names come from the archive, and cmd would run `a&calc.exe.mkv` as two commands.
+fn seven_zip(args: &[&str], password: Option<&str>) -> Result<Output, String> {
+    let tool = find_seven_zip().ok_or("7-Zip not found; install it to open .rar and .7z archives")?;
+    let password_arg = format!("-p{}", password.unwrap_or_default());
+
+    let result = Command::new(&tool)
+        .args(args)
+        .arg(&password_arg)
+        .stdin(Stdio::null())
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run 7-Zip: {}", e))?;
+    if !output.status.success() {
+        let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        if text.contains("Wrong password") || text.contains("encrypted archive") {
+            return Err(password_error(password.is_some()));
+        }
+        return Err(format!("7-Zip failed: {}", text.trim()));
+    }
+    Ok(output)
+}
+
+/// Entries from `7z l -slt`, a block of "Key = value" lines per entry
+fn list_seven_zip(path: &Path, password: Option<&str>) -> Result<Vec<ArchiveEntry>, String> {
+    let path = path.to_string_lossy();
+    let output = seven_zip(&["l", "-slt", "--", &path], password)?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    // Entries follow the "----------" line; the archive's own block comes before it
+    let listing = text.split_once("\n----------").map_or("", |(_, rest)| rest);
+    let mut entries = Vec::new();
+    for block in listing.split("\n\n") {
+        let value = |key: &str| {
+            block
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(" = "))
+                .map(str::trim)
+        };
+        let Some(name) = value("Path").filter(|_| value("Folder") != Some("+")) else {
+            continue;
+        };
+        entries.push(ArchiveEntry {
+            name: name.to_string(),
+            size: value("Size").and_then(|s| s.parse().ok()).unwrap_or_default(),
+            encrypted: value("Encrypted") == Some("+"),
+        });
+    }
+    Ok(entries)
+}
+
+fn extract_seven_zip(path: &Path, name: &str, password: Option<&str>, dir: &Path) -> Result<(), String> {
+    let path = path.to_string_lossy();
+    let out_dir = format!("-o{}", dir.display());
+    // "e" leaves out the folders inside the archive
+    seven_zip(&["e", "-y", &out_dir, "--", &path, name], password).map(|_| ())
+}
+
+/// The videos in a .zip, .rar or .7z archive. Listing a .7z with encrypted file names
+/// fails with a password error until `password` is given. For .rar and .7z the password
+/// goes on 7-Zip's command line, visible in the process list while it runs.
+#[tauri::command(async)]
+pub fn list_archive(path: String, password: Option<String>) -> Result<Vec<ArchiveEntry>, String> {
+    let path = PathBuf::from(path);
+    let entries = match format(&path)? {
+        Format::Zip => list_zip(&path)?,
+        Format::SevenZip => list_seven_zip(&path, password.as_deref())?,
+    };
+    Ok(entries.into_iter().filter(|e| scan::is_video(Path::new(&e.name))).collect())
+}
+
+/// Extract one entry to its own temp folder and convert it from there
+fn convert_entry(
+    app: &AppHandle,
+    archive: &Path,
+    name: &str,
+    password: Option<&str>,
+    options: Option<ConvertOptions>,
+    output_dir: &Path,
+) -> Result<String, String> {
+    let file_name = Path::new(name).file_name().ok_or_else(|| format!("{} isn't a file", name))?;
+    let dir = crate::unique_temp_path("archive", "tmp");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create temp folder: {}", e))?;
+    let extracted = dir.join(file_name);
+    let result = match format(archive)? {
+        Format::Zip => extract_zip(archive, name, password, &extracted),
+        Format::SevenZip => extract_seven_zip(archive, name, password, &dir),
+    }
+    .and_then(|_| {
+        tracing::info!(archive = %archive.display(), name, "extracted video from archive");
+        let input = extracted.to_string_lossy().to_string();
+        convert::convert_into(app, input, options, Destination::Folder(Some(output_dir.to_path_buf())))
+    });
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        tracing::warn!(error = %e, dir = %dir.display(), "failed to remove extracted video");
+    }
+    result
+}
+
+/// Convert the chosen videos of an archive one at a time: each is extracted to temp
+/// storage, converted, and deleted again. Outputs go to `output_directory`, the output
+/// folder setting or next to the archive. A password error on the first entry stops the
+/// rest, so the frontend can ask for the password and call again.
+#[tauri::command(async)]
+pub fn convert_from_archive(
+    app: AppHandle,
+    path: String,
+    entries: Vec<String>,
+    password: Option<String>,
+    options: Option<ConvertOptions>,
+    output_directory: Option<String>,
+) -> Result<Vec<ArchiveConversion>, String> {
+    let archive = PathBuf::from(&path);
+    format(&archive)?;
+    let output_dir = output_directory
+        .map(PathBuf::from)
+        .or_else(naming::output_directory)
+        .or_else(|| archive.parent().map(Path::to_path_buf))
+        .ok_or("No folder to write the outputs to")?;
+
+    let mut results = Vec::new();
+    for name in entries {
+        let result = convert_entry(&app, &archive, &name, password.as_deref(), options.clone(), &output_dir);
+        let stop = match &result {
+            Err(e) if e.starts_with(PASSWORD_REQUIRED) && results.is_empty() => return Err(e.clone()),
+            Err(e) => cancel::is_cancelled(e),
+            Ok(_) => false,
+        };
+        let (output_path, error) = match result {
+            Ok(output) => (Some(output), None),
+            Err(e) => (None, Some(e)),
+        };
+        results.push(ArchiveConversion { name, output_path, error });
+        if stop {
+            break;
+        }
+    }
+    Ok(results)
+}
@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+
+/// Audio formats a conversion can re-encode to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioCodec {
+    Aac,
+    /// Extended HE-AAC (USAC); only some ffmpeg builds can encode it
+    XheAac,
+    Opus,
+    Ac3,
+    Eac3,
+    Flac,
+    Alac,
+    Mp3,
+}
+
+/// Re-encode the audio while the video is copied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioTarget {
+    pub codec: AudioCodec,
+    /// Defaults per codec; ignored for lossless codecs
+    pub bitrate_kbps: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioCodecSupport {
+    pub codec: AudioCodec,
+    /// The encoder that would be used, or None if this ffmpeg can't encode the codec
+    pub encoder: Option<String>,
+    /// Whether the codec can be stored in the container that was asked about
+    pub allowed: bool,
+}
+
+const ALL_CODECS: &[AudioCodec] = &[
+    AudioCodec::Aac,
+    AudioCodec::XheAac,
+    AudioCodec::Opus,
+    AudioCodec::Ac3,
+    AudioCodec::Eac3,
+    AudioCodec::Flac,
+    AudioCodec::Alac,
+    AudioCodec::Mp3,
+];
+
+impl AudioCodec {
+    /// Encoders in order of preference
+    fn encoders(self) -> &'static [&'static str] {
+        match self {
+            AudioCodec::Aac => &["libfdk_aac", "aac"],
+            // Found by description instead, see `encoder`
+            AudioCodec::XheAac => &[],
+            AudioCodec::Opus => &["libopus"],
+            AudioCodec::Ac3 => &["ac3"],
+            AudioCodec::Eac3 => &["eac3"],
+            AudioCodec::Flac => &["flac"],
+            AudioCodec::Alac => &["alac"],
+            AudioCodec::Mp3 => &["libmp3lame"],
+        }
+    }
+
+    /// Containers that can carry the codec
+    fn containers(self) -> &'static [&'static str] {
+        match self {
+            // WebM only allows Opus and Vorbis
+            AudioCodec::Opus => &["mp4", "mkv", "webm"],
+            AudioCodec::Aac | AudioCodec::XheAac | AudioCodec::Ac3 | AudioCodec::Eac3 | AudioCodec::Flac
+            | AudioCodec::Alac | AudioCodec::Mp3 => &["mp4", "mkv"],
+        }
+    }
+
+    fn default_kbps(self) -> Option<u32> {
+        match self {
+            AudioCodec::Aac => Some(160),
+            // USAC stays clean at rates where plain AAC falls apart
+            AudioCodec::XheAac => Some(64),
+            AudioCodec::Opus => Some(128),
+            AudioCodec::Ac3 => Some(448),
+            AudioCodec::Eac3 => Some(384),
+            AudioCodec::Mp3 => Some(192),
+            AudioCodec::Flac | AudioCodec::Alac => None,
+        }
+    }
+
+    /// The encoder this ffmpeg build would use
+    fn encoder(self) -> Option<String> {
+        let encoders = crate::ffmpeg_encoders();
+        // Lines look like " A....D libopus              libopus Opus (codec opus)"
+        let mut lines = encoders.lines().filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let flags = fields.next()?;
+            let name = fields.next()?;
+            flags.starts_with('A').then_some((name, line))
+        });
+        if self == AudioCodec::XheAac {
+            return lines
+                .find(|(_, line)| line.contains("USAC") || line.contains("xHE-AAC"))
+                .map(|(name, _)| name.to_string());
+        }
+        let available: Vec<&str> = lines.map(|(name, _)| name).collect();
+        self.encoders().iter().find(|e| available.contains(e)).map(|e| e.to_string())
+    }
+}
+
+/// ffmpeg arguments that encode every audio track to `target` for a `container` output
+pub fn encode_args(target: &AudioTarget, container: &str) -> Result<Vec<String>, String> {
+    codec_args(target, container, "a")
+}
+
+/// Like `encode_args`, for the single output stream `output_index` (counting all kinds)
+pub fn stream_encode_args(target: &AudioTarget, container: &str, output_index: usize) -> Result<Vec<String>, String> {
+    codec_args(target, container, &output_index.to_string())
+}
+
+fn codec_args(target: &AudioTarget, container: &str, specifier: &str) -> Result<Vec<String>, String> {
+    if !target.codec.containers().contains(&container) {
+        return Err(format!("{:?} audio can't be stored in {}", target.codec, container.to_uppercase()));
+    }
+    let encoder = target
+        .codec
+        .encoder()
+        .ok_or_else(|| format!("This ffmpeg build can't encode {:?} audio", target.codec))?;
+
+    let mut args = vec![format!("-c:{}", specifier), encoder];
+    if let Some(default) = target.codec.default_kbps() {
+        args.extend([format!("-b:{}", specifier), format!("{}k", target.bitrate_kbps.unwrap_or(default))]);
+    }
+    Ok(args)
+}
+
+/// Which audio codecs this ffmpeg can encode and whether each fits `container`
+/// ("mp4", "mkv" or "webm"; defaults to mp4)
+#[tauri::command]
+pub fn list_audio_codecs(container: Option<String>) -> Vec<AudioCodecSupport> {
+    let container = container.unwrap_or_else(|| "mp4".to_string()).to_lowercase();
+    ALL_CODECS
+        .iter()
+        .map(|&codec| AudioCodecSupport {
+            codec,
+            encoder: codec.encoder(),
+            allowed: codec.containers().contains(&container.as_str()),
+        })
+        .collect()
+}
+
+/// Channel layouts a track can be mixed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    #[serde(rename = "5.1")]
+    Surround51,
+    #[serde(rename = "7.1")]
+    Surround71,
+}
+
+impl ChannelLayout {
+    pub fn channels(self) -> u32 {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Surround51 => 6,
+            ChannelLayout::Surround71 => 8,
+        }
+    }
+
+    pub fn ffmpeg_name(self) -> &'static str {
+        match self {
+            ChannelLayout::Mono => "mono",
+            ChannelLayout::Stereo => "stereo",
+            ChannelLayout::Surround51 => "5.1",
+            ChannelLayout::Surround71 => "7.1",
+        }
+    }
+}
+
+/// One audio track of the output and the source track it's made from. The same source
+/// can feed several outputs, e.g. the original 5.1 copied plus a stereo AAC mixed from it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioTrackOutput {
+    /// Source audio track number (0 = first audio track)
+    pub source: u32,
+    /// Copy the source track as is when unset
+    pub codec: Option<AudioCodec>,
+    pub bitrate_kbps: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<ChannelLayout>,
+    pub title: Option<String>,
+    /// Mark this track as the one players pick; when no track sets it, ffmpeg decides
+    #[serde(default)]
+    pub default: bool,
+}
+
+impl AudioCodec {
+    /// The codec name ffprobe reports for streams of this codec
+    pub fn codec_name(self) -> &'static str {
+        match self {
+            AudioCodec::Aac | AudioCodec::XheAac => "aac",
+            AudioCodec::Opus => "opus",
+            AudioCodec::Ac3 => "ac3",
+            AudioCodec::Eac3 => "eac3",
+            AudioCodec::Flac => "flac",
+            AudioCodec::Alac => "alac",
+            AudioCodec::Mp3 => "mp3",
+        }
+    }
+}
+
+/// ffmpeg arguments that build the listed output tracks, in order, from the first input:
+/// a filter graph for the resampled or remixed ones plus a map and codec per track.
+/// Only audio is mapped; the caller maps the other streams it wants.
+pub fn track_args(tracks: &[AudioTrackOutput], container: &str) -> Result<Vec<String>, String> {
+    if tracks.is_empty() {
+        return Err("No output audio tracks given".to_string());
+    }
+
+    let mut filters = Vec::new();
+    let mut maps = Vec::new();
+    let mut codecs = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        let input = format!("0:a:{}", track.source);
+        let Some(codec) = track.codec else {
+            if track.sample_rate.is_some() || track.channels.is_some() || track.bitrate_kbps.is_some() {
+                return Err(format!("Audio track {} is copied, so its bitrate, sample rate and channels can't change", i + 1));
+            }
+            maps.extend(["-map".to_string(), input]);
+            codecs.extend([format!("-c:a:{}", i), "copy".to_string()]);
+            continue;
+        };
+
+        let mut chain = Vec::new();
+        if let Some(rate) = track.sample_rate {
+            if !(8000..=192_000).contains(&rate) {
+                return Err(format!("Unsupported sample rate {} Hz for audio track {}", rate, i + 1));
+            }
+            chain.push(format!("aresample={}", rate));
+        }
+        if let Some(layout) = track.channels {
+            chain.push(format!("aformat=channel_layouts={}", layout.ffmpeg_name()));
+        }
+        if chain.is_empty() {
+            maps.extend(["-map".to_string(), input]);
+        } else {
+            let label = format!("a{}", i);
+            filters.push(format!("[{}]{}[{}]", input, chain.join(","), label));
+            maps.extend(["-map".to_string(), format!("[{}]", label)]);
+        }
+
+        let target = AudioTarget { codec, bitrate_kbps: track.bitrate_kbps };
+        // encode_args targets every audio stream; narrow each flag to this output track
+        for arg in encode_args(&target, container)? {
+            codecs.push(match arg.as_str() {
+                "-c:a" | "-b:a" => format!("{}:{}", arg, i),
+                _ => arg,
+            });
+        }
+    }
+    for (i, title) in tracks.iter().enumerate().filter_map(|(i, t)| Some((i, t.title.as_ref()?))) {
+        codecs.extend([format!("-metadata:s:a:{}", i), format!("title={}", title)]);
+    }
+    if tracks.iter().any(|t| t.default) {
+        for (i, track) in tracks.iter().enumerate() {
+            let disposition = if track.default { "default" } else { "0" };
+            codecs.extend([format!("-disposition:a:{}", i), disposition.to_string()]);
+        }
+    }
+
+    let mut args = Vec::new();
+    if !filters.is_empty() {
+        args.extend(["-filter_complex".to_string(), filters.join(";")]);
+    }
+    args.extend(maps);
+    args.extend(codecs);
+    Ok(args)
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::presets::Preset;
+use crate::probe;
+
+/// Rough SVT-AV1 speed at 1080p in frames per second for presets 0-13, on a mid-range
+/// 8-core desktop. Enough to tell a 2-hour job from a 20-hour one.
+const SVT_FPS_1080P: [f64; 14] = [0.1, 0.2, 0.5, 1.0, 2.0, 4.0, 8.0, 13.0, 20.0, 30.0, 45.0, 60.0, 90.0, 120.0];
+
+/// Warn when an encode will take longer than this many times the video's duration
+const SLOW_WARNING_FACTOR: f64 = 2.0;
+
+/// Speed/size trade-off, mapped to an SVT-AV1 preset and CRF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Av1Quality {
+    /// Smallest files at high quality; very slow
+    Archive,
+    Balanced,
+    /// Near real time on most machines
+    Fast,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Av1Estimate {
+    pub estimated_secs: f64,
+    /// Encode time divided by playback time
+    pub realtime_factor: f64,
+    pub warning: Option<String>,
+}
+
+/// Whether this ffmpeg was built with libsvtav1
+pub fn svtav1_available() -> bool {
+    crate::ffmpeg_encoders().contains("libsvtav1")
+}
+
+/// (SVT preset, CRF) for a quality level
+fn preset_and_crf(quality: Av1Quality) -> (u8, u8) {
+    match quality {
+        Av1Quality::Archive => (4, 24),
+        Av1Quality::Balanced => (6, 30),
+        Av1Quality::Fast => (10, 35),
+    }
+}
+
+fn human_duration(secs: f64) -> String {
+    let minutes = (secs / 60.0).round() as u64;
+    if minutes < 90 {
+        format!("{} minutes", minutes.max(1))
+    } else {
+        format!("{:.1} hours", secs / 3600.0)
+    }
+}
+
+/// Whether this machine's ffmpeg can encode AV1
+#[tauri::command]
+pub fn get_av1_support() -> bool {
+    svtav1_available()
+}
+
+/// An AV1 preset for a quality level, 10-bit, with optional film-grain synthesis (1-50)
+#[tauri::command]
+pub fn av1_preset(quality: Av1Quality, film_grain: Option<u8>) -> Result<Preset, String> {
+    let (svt_preset, crf) = preset_and_crf(quality);
+    let (name, description) = match quality {
+        Av1Quality::Archive => ("AV1 Archive", "Smallest files at high quality; expect hours per film"),
+        Av1Quality::Balanced => ("AV1 Balanced", "Good compression at a practical speed"),
+        Av1Quality::Fast => ("AV1 Fast", "Quick AV1 encodes, still smaller than H.264"),
+    };
+    let preset = Preset {
+        name: name.to_string(),
+        description: description.to_string(),
+        video_codec: "libsvtav1".to_string(),
+        audio_codec: "copy".to_string(),
+        crf: Some(crf),
+        video_bitrate_kbps: None,
+        audio_bitrate_kbps: None,
+        encoder_preset: Some(svt_preset.to_string()),
+        max_height: None,
+        // 10-bit avoids banding and compresses better even from 8-bit sources
+        extra_args: vec!["-pix_fmt".to_string(), "yuv420p10le".to_string()],
+        h264: None,
+        film_grain,
+        quality_critical: false,
+    };
+    preset.validate()?;
+    Ok(preset)
+}
+
+/// How long an AV1 encode of `path` will roughly take, with a warning when it's far slower
+/// than real time
+#[tauri::command(async)]
+pub fn estimate_av1_time(path: String, quality: Av1Quality) -> Result<Av1Estimate, String> {
+    let info = probe::probe(&path)?;
+    let duration = info.duration.ok_or("Can't estimate: duration is unknown")?;
+    let video = info.streams_of("video").next().ok_or("No video stream")?;
+    let frames = duration * video.frame_rate.unwrap_or(24.0);
+    let pixels = f64::from(video.width.unwrap_or(1920) * video.height.unwrap_or(1080));
+
+    let (svt_preset, _) = preset_and_crf(quality);
+    let fps = SVT_FPS_1080P[svt_preset as usize] * (1920.0 * 1080.0) / pixels;
+    let estimated_secs = frames / fps;
+    let realtime_factor = estimated_secs / duration;
+
+    let warning = (realtime_factor > SLOW_WARNING_FACTOR).then(|| {
+        format!(
+            "AV1 at this quality encodes at about {:.2}x real time; this {} video will take roughly {}",
+            1.0 / realtime_factor,
+            human_duration(duration),
+            human_duration(estimated_secs),
+        )
+    });
+    Ok(Av1Estimate { estimated_secs, realtime_factor, warning })
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::migrate::{self, SchemaInfo};
+use crate::{plugins, presets, queue, remote, settings};
+
+/// Settings that run code, open a port or decide which signed presets to trust. A bundle
+/// could come from anyone, so importing keeps this machine's own instead.
+const KEPT_ON_IMPORT: &[&str] = &[
+    plugins::ENABLED_PLUGINS_FILE,
+    remote::REMOTE_FILE,
+    presets::TRUSTED_KEYS_FILE,
+    queue::QUEUE_FILE,
+    queue::QUARANTINE_FILE,
+];
+
+/// The live queue's own files, which it rewrites on its next save; they aren't settings
+const NOT_EXPORTED: &[&str] = &[queue::QUEUE_FILE, queue::QUARANTINE_FILE];
+
+/// Name inside the zip for a settings file: its path under the app data directory, with forward slashes
+fn entry_name(root: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(root).unwrap_or(file);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Only `<file>.json` and `profiles/<name>/<file>.json` are restored; anything else in the zip is ignored
+fn restorable(path: &Path) -> bool {
+    let parts: Vec<_> = path.components().collect();
+    let is_json = path.extension().is_some_and(|e| e == "json");
+    is_json && (parts.len() == 1 || (parts.len() == 3 && parts[0].as_os_str() == "profiles"))
+}
+
+fn kept_on_import(name: &Path) -> bool {
+    KEPT_ON_IMPORT.iter().any(|kept| name == Path::new(kept))
+}
+
+fn exported(name: &Path) -> bool {
+    !NOT_EXPORTED.iter().any(|skipped| name == Path::new(skipped))
+}
+
+/// Bundle every settings file (presets, folder options, profiles, plugin and log settings, and
+/// whatever later releases add) into a zip, to move to another machine. The remote control
+/// token and the queue are left out.
+#[tauri::command(async)]
+pub fn export_app_data(path: String) -> Result<usize, String> {
+    let root = crate::app_data_dir()?;
+    let file = fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default();
+
+    let mut count = 0;
+    for dir in migrate::data_dirs(&root) {
+        for file in migrate::json_files(&dir).into_iter().filter(|f| exported(f.strip_prefix(&root).unwrap_or(f))) {
+            let mut contents = fs::read(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+            if file == root.join(remote::REMOTE_FILE) {
+                contents = remote::without_token(&contents)?;
+            }
+            zip.start_file(entry_name(&root, &file), options)
+                .map_err(|e| format!("Failed to write zip: {}", e))?;
+            zip.write_all(&contents).map_err(|e| format!("Failed to write zip: {}", e))?;
+            count += 1;
+        }
+    }
+    zip.finish().map_err(|e| format!("Failed to write zip: {}", e))?;
+
+    tracing::info!(path = %path, files = count, "exported app data");
+    Ok(count)
+}
+
+/// Replace the current settings with the ones in a zip from `export_app_data`.
+/// The current files are backed up first, and older bundles are migrated after import.
+/// Enabled plugins and remote control settings aren't imported; see `KEPT_ON_IMPORT`.
+#[tauri::command(async)]
+pub fn import_app_data(path: String) -> Result<usize, String> {
+    let root = crate::app_data_dir()?;
+    let file = fs::File::open(&path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("Failed to read zip: {}", e))?;
+
+    let mut entries: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| format!("Failed to read zip entry: {}", e))?;
+        let Some(name) = entry.enclosed_name().filter(|n| restorable(n)) else {
+            tracing::warn!(entry = entry.name(), "skipping unexpected file in app data zip");
+            continue;
+        };
+        if kept_on_import(&name) {
+            tracing::info!(entry = entry.name(), "keeping this machine's settings instead of the imported ones");
+            continue;
+        }
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents).map_err(|e| format!("Failed to read zip entry: {}", e))?;
+        entries.push((name, contents));
+    }
+    if entries.is_empty() {
+        return Err("No settings found in this zip".to_string());
+    }
+
+    let version = entries
+        .iter()
+        .find(|(name, _)| name.as_path() == Path::new(migrate::SCHEMA_FILE))
+        .and_then(|(_, contents)| serde_json::from_slice::<SchemaInfo>(contents).ok())
+        .map(|info| info.version)
+        .unwrap_or(0);
+    if version > migrate::SCHEMA_VERSION {
+        return Err("This backup is from a newer version of the app; update before importing it".to_string());
+    }
+
+    let backup = migrate::back_up(&root, "before-import")?;
+    tracing::info!(path = %path, backup = %backup.display(), "importing app data");
+
+    // Start from a clean slate so settings missing from the bundle don't linger
+    for dir in migrate::data_dirs(&root) {
+        let cleared = migrate::json_files(&dir).into_iter().filter(|f| !kept_on_import(f.strip_prefix(&root).unwrap_or(f)));
+        for file in cleared {
+            fs::remove_file(&file).map_err(|e| format!("Failed to remove {}: {}", file.display(), e))?;
+        }
+    }
+    for (name, contents) in &entries {
+        let target = root.join(name);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&target, contents).map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+    }
+
+    migrate::run();
+    settings::forget_cached();
+    Ok(entries.len())
+}
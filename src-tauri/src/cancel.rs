@@ -0,0 +1,219 @@
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::suspend;
+
+/// Errors from cancelled conversions start with this, so callers can tell them from failures
+const CANCELLED: &str = "Conversion cancelled";
+
+/// How long ffmpeg gets to finalize the container after being asked to stop
+const STOP_GRACE_SECS: u64 = 15;
+
+/// What happens to the output of a cancelled conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelMode {
+    /// Stop at once and delete the output
+    Discard,
+    /// Let ffmpeg finish the file up to where it got, and keep it
+    KeepPartial,
+}
+
+/// Conversions in progress by input path: the run's id and any cancel request for it
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningConversion {
+    /// Pass to `cancel_conversion`; also sent with every progress event
+    pub id: u64,
+    pub input_path: String,
+    /// Suspended by `pause_queue`
+    pub paused: bool,
+    #[serde(skip)]
+    cancel: Option<CancelMode>,
+    /// ffmpeg's process, once it's started
+    #[serde(skip)]
+    pid: Option<u32>,
+    /// The queue item this conversion is for, when the queue started it
+    #[serde(skip)]
+    queue_item: Option<u64>,
+}
+
+/// Conversions in progress by id, with the cancel request if one came in
+static RUNNING: Mutex<BTreeMap<u64, RunningConversion>> = Mutex::new(BTreeMap::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The queue is paused, so ffmpeg runs the queue starts now are suspended right away
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Queue item that jobs started on this thread belong to, see `for_queue_item`
+    static QUEUE_ITEM: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Suspend or resume one running conversion's ffmpeg, if it's started and not already so
+fn set_paused(job: &mut RunningConversion, paused: bool) {
+    let Some(pid) = job.pid.filter(|_| job.paused != paused) else {
+        return;
+    };
+    let result = if paused { suspend::suspend(pid) } else { suspend::resume(pid) };
+    match result {
+        Ok(()) => job.paused = paused,
+        Err(e) => tracing::warn!(error = %e, id = job.id, paused, "failed to pause or resume conversion"),
+    }
+}
+
+/// Registration of a running conversion; dropping it unregisters
+pub struct Job {
+    id: u64,
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        RUNNING.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.id);
+    }
+}
+
+impl Job {
+    pub fn start(input_path: &str) -> Job {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let running = RunningConversion {
+            id,
+            input_path: input_path.to_string(),
+            paused: false,
+            cancel: None,
+            pid: None,
+            queue_item: QUEUE_ITEM.get(),
+        };
+        RUNNING.lock().unwrap_or_else(|e| e.into_inner()).insert(id, running);
+        Job { id }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn requested(&self) -> Option<CancelMode> {
+        RUNNING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&self.id)
+            .and_then(|job| job.cancel)
+    }
+
+    /// Wait for ffmpeg to exit, stopping it if the job is cancelled meanwhile. Keeping the
+    /// partial output sends ffmpeg "q", which makes it write out the MP4 index as if the
+    /// input had ended there; discarding kills it.
+    pub fn wait(&self, child: &mut Child) -> Result<(ExitStatus, Option<CancelMode>), String> {
+        if let Some(job) = RUNNING.lock().unwrap_or_else(|e| e.into_inner()).get_mut(&self.id) {
+            job.pid = Some(child.id());
+            if job.queue_item.is_some() && SUSPENDED.load(Ordering::SeqCst) {
+                set_paused(job, true);
+            }
+        }
+        let mut stopping: Option<Instant> = None;
+        loop {
+            if let Some(status) = child.try_wait().map_err(|e| format!("Failed to wait for ffmpeg: {}", e))? {
+                // A run that ended on its own before the request was acted on isn't cancelled
+                return Ok((status, stopping.map(|_| CancelMode::KeepPartial)));
+            }
+            let requested = self.requested();
+            if requested.is_some() {
+                // A suspended ffmpeg can't read the "q" or finish the file
+                if let Some(job) = RUNNING.lock().unwrap_or_else(|e| e.into_inner()).get_mut(&self.id) {
+                    set_paused(job, false);
+                }
+            }
+            match (requested, stopping) {
+                (Some(CancelMode::Discard), _) => {
+                    suspend::kill(child);
+                    let status = child.wait().map_err(|e| format!("Failed to wait for ffmpeg: {}", e))?;
+                    return Ok((status, Some(CancelMode::Discard)));
+                }
+                (Some(CancelMode::KeepPartial), None) => {
+                    if let Some(stdin) = child.stdin.as_mut() {
+                        let _ = stdin.write_all(b"q").and_then(|_| stdin.flush());
+                    }
+                    stopping = Some(Instant::now());
+                }
+                (Some(CancelMode::KeepPartial), Some(since)) if since.elapsed() > Duration::from_secs(STOP_GRACE_SECS) => {
+                    tracing::warn!("ffmpeg didn't stop, killing it");
+                    suspend::kill(child);
+                }
+                _ => {}
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+}
+
+/// The error a cancelled conversion returns, naming the partial output if one was kept
+pub fn cancelled_error(kept: Option<&str>) -> String {
+    match kept {
+        Some(path) => format!("{}; partial output kept at {}", CANCELLED, path),
+        None => CANCELLED.to_string(),
+    }
+}
+
+pub fn is_cancelled(error: &str) -> bool {
+    error.starts_with(CANCELLED)
+}
+
+/// Cancel a running conversion: ffmpeg is killed and the partial output deleted. With
+/// `keep_partial`, the output is instead finished up to the point reached and kept as
+/// `<name>_partial.mp4`.
+#[tauri::command]
+pub fn cancel_conversion(id: u64, keep_partial: Option<bool>) -> Result<(), String> {
+    let mode = if keep_partial.unwrap_or(false) { CancelMode::KeepPartial } else { CancelMode::Discard };
+    let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    let job = running
+        .get_mut(&id)
+        .ok_or_else(|| format!("No running conversion with id {}", id))?;
+    tracing::info!(id, input = %job.input_path, ?mode, "cancelling conversion");
+    job.cancel = Some(mode);
+    Ok(())
+}
+
+/// Run `convert` with the jobs it starts on this thread marked as queue item `item_id`'s
+pub fn for_queue_item<T>(item_id: u64, convert: impl FnOnce() -> T) -> T {
+    let outer = QUEUE_ITEM.replace(Some(item_id));
+    let result = convert();
+    QUEUE_ITEM.set(outer);
+    result
+}
+
+/// Cancel and discard the running jobs of queue item `item_id`, returning whether there was one
+pub fn cancel_queue_item(item_id: u64) -> bool {
+    let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    let mut found = false;
+    for job in running.values_mut().filter(|job| job.queue_item == Some(item_id)) {
+        job.cancel = Some(CancelMode::Discard);
+        found = true;
+    }
+    found
+}
+
+/// Suspend the ffmpeg of every conversion the queue is running, and any it starts until
+/// they're resumed, or resume them all. Returns how many there were; conversions started
+/// outside the queue carry on either way.
+pub fn pause_queue_jobs(paused: bool) -> usize {
+    SUSPENDED.store(paused, Ordering::SeqCst);
+    let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    let mut count = 0;
+    for job in running.values_mut().filter(|job| job.queue_item.is_some()) {
+        set_paused(job, paused);
+        count += 1;
+    }
+    count
+}
+
+/// Conversions in progress, oldest first
+#[tauri::command]
+pub fn get_running_conversions() -> Vec<RunningConversion> {
+    RUNNING.lock().unwrap_or_else(|e| e.into_inner()).values().cloned().collect()
+}
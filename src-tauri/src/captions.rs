@@ -0,0 +1,64 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::probe;
+
+/// Whether any video stream carries embedded CEA-608/708 captions
+pub fn has_closed_captions(path: &str) -> Result<bool, String> {
+    Ok(probe::probe(path)?.streams_of("video").any(|s| s.closed_captions))
+}
+
+/// Extract embedded captions from `input_path` to an SRT file using lavfi's subcc output.
+/// Returns None (and leaves nothing behind) if the source had no captions.
+pub fn extract_to_srt(input_path: &str, srt_path: &Path) -> Result<Option<PathBuf>, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let graph = format!("movie={}[out0+subcc]", crate::escape_filter_path(input_path));
+    let srt_str = srt_path.to_string_lossy().to_string();
+    let args = ["-f", "lavfi", "-i", &graph, "-map", "0:s", "-y", &srt_str];
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Caption extraction failed: {}", stderr));
+    }
+
+    // ffmpeg still writes an empty file when the caption stream has no cues
+    if fs::metadata(srt_path).map(|m| m.len()).unwrap_or(0) == 0 {
+        let _ = fs::remove_file(srt_path);
+        return Ok(None);
+    }
+
+    Ok(Some(srt_path.to_path_buf()))
+}
+
+/// Sidecar path for captions next to a converted file, e.g. movie.cc.srt
+pub fn sidecar_path(output_path: &str) -> PathBuf {
+    let path = Path::new(output_path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.cc.srt", stem))
+}
+
+/// Extract embedded closed captions to an SRT next to the source (or at `output_path`)
+#[tauri::command(async)]
+pub fn extract_captions(path: String, output_path: Option<String>) -> Result<String, String> {
+    if !has_closed_captions(&path)? {
+        return Err("No embedded closed captions found".to_string());
+    }
+
+    let srt_path = output_path.map(PathBuf::from).unwrap_or_else(|| sidecar_path(&path));
+    extract_to_srt(&path, &srt_path)?
+        .map(|p| p.to_string_lossy().to_string())
+        .ok_or_else(|| "Caption stream is empty".to_string())
+}
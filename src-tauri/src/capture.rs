@@ -0,0 +1,662 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::cancel::{self, CancelMode, Job};
+use crate::convert;
+use crate::naming::{self, FilenamePolicy};
+use crate::progress;
+use crate::stream;
+use crate::suspend;
+
+/// How long ffmpeg gets to finalize the file after being asked to stop
+const STOP_GRACE_SECS: u64 = 10;
+
+/// Lines of ffmpeg's stderr kept for the error when a recording fails
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Emitted about once a second with a `RecordingProgress` while a recording runs
+const RECORDING_PROGRESS_EVENT: &str = "recording-progress";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    Video,
+    Audio,
+}
+
+/// A camera, capture card or microphone ffmpeg can record from
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureDevice {
+    pub kind: DeviceKind,
+    /// What to pass back in `CaptureSelection`: an index on macOS, a name on Windows, a path on Linux
+    pub id: String,
+    pub name: String,
+}
+
+/// Devices to record from; at least one must be set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CaptureSelection {
+    pub video: Option<String>,
+    pub audio: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RecordingOptions {
+    /// Defaults to the user's Videos folder
+    pub output_dir: Option<String>,
+    /// File name without extension; defaults to a timestamp
+    pub name: Option<String>,
+    pub filename_policy: FilenamePolicy,
+    pub framerate: Option<u32>,
+    /// e.g. "1280x720"; the device default when unset
+    pub video_size: Option<String>,
+    /// x264 CRF for the recording, default 23
+    pub crf: Option<u8>,
+}
+
+/// Part of the screen to record, in pixels from the top-left of the display
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ScreenRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ScreenCapture {
+    /// X display (":0.0") on Linux or avfoundation screen index on macOS; the main screen when unset
+    pub display: Option<String>,
+    /// The whole screen when unset
+    pub region: Option<ScreenRegion>,
+    /// Audio device id from `list_capture_devices` to record alongside
+    pub audio: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingProgress {
+    pub id: u64,
+    pub duration_secs: u64,
+    pub size_bytes: u64,
+    /// False once ffmpeg has stopped on its own, e.g. at a duration cap or when the stream ended
+    pub running: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Recording {
+    pub id: u64,
+    pub output_path: String,
+    /// Unix timestamp in seconds
+    pub started_at: i64,
+}
+
+struct Session {
+    recording: Recording,
+    child: Child,
+    /// Reads ffmpeg's stderr until it exits, returning the last `STDERR_TAIL_LINES`
+    stderr: Option<JoinHandle<VecDeque<String>>>,
+}
+
+static RECORDINGS: Mutex<BTreeMap<u64, Session>> = Mutex::new(BTreeMap::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Run `ffmpeg -list_devices` for an input format; ffmpeg prints the list to stderr and exits non-zero
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn list_devices_output(format: &str, dummy_input: &str) -> Result<String, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let args = ["-hide_banner", "-f", format, "-list_devices", "true", "-i", dummy_input];
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    Ok(String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+/// Strip the "[AVFoundation indev @ 0x...] " style prefix from a log line
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn log_message(line: &str) -> &str {
+    match line.find("] ") {
+        Some(i) if line.starts_with('[') => &line[i + 2..],
+        _ => line,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn enumerate_devices() -> Result<Vec<CaptureDevice>, String> {
+    let log = list_devices_output("avfoundation", "")?;
+    let mut devices = Vec::new();
+    let mut kind = None;
+    for line in log.lines().map(log_message) {
+        if line.contains("video devices:") {
+            kind = Some(DeviceKind::Video);
+        } else if line.contains("audio devices:") {
+            kind = Some(DeviceKind::Audio);
+        } else if let (Some(kind), Some(rest)) = (kind, line.strip_prefix('[')) {
+            // "[0] FaceTime HD Camera"
+            if let Some((index, name)) = rest.split_once("] ") {
+                devices.push(CaptureDevice {
+                    kind,
+                    id: index.to_string(),
+                    name: name.trim().to_string(),
+                });
+            }
+        }
+    }
+    Ok(devices)
+}
+
+#[cfg(target_os = "windows")]
+fn enumerate_devices() -> Result<Vec<CaptureDevice>, String> {
+    let log = list_devices_output("dshow", "dummy")?;
+    let mut devices = Vec::new();
+    let mut section = None;
+    for line in log.lines().map(log_message) {
+        let line = line.trim();
+        if line.starts_with("DirectShow video devices") {
+            section = Some(DeviceKind::Video);
+        } else if line.starts_with("DirectShow audio devices") {
+            section = Some(DeviceKind::Audio);
+        } else if line.starts_with('"') {
+            // Newer ffmpeg: "Integrated Camera" (video); older: "Integrated Camera" under a section header
+            let Some((name, rest)) = line[1..].split_once('"') else {
+                continue;
+            };
+            let kind = if rest.contains("(video)") {
+                Some(DeviceKind::Video)
+            } else if rest.contains("(audio)") {
+                Some(DeviceKind::Audio)
+            } else {
+                section
+            };
+            if let Some(kind) = kind {
+                devices.push(CaptureDevice {
+                    kind,
+                    id: name.to_string(),
+                    name: name.to_string(),
+                });
+            }
+        }
+    }
+    Ok(devices)
+}
+
+/// v4l2 has no -list_devices, so read the kernel's device names; audio goes through ALSA's default
+#[cfg(target_os = "linux")]
+fn enumerate_devices() -> Result<Vec<CaptureDevice>, String> {
+    let mut devices: Vec<CaptureDevice> = std::fs::read_dir("/sys/class/video4linux")
+        .map(|entries| entries.flatten().collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            let node = entry.file_name().to_string_lossy().to_string();
+            let name = std::fs::read_to_string(entry.path().join("name"))
+                .map(|n| n.trim().to_string())
+                .unwrap_or_else(|_| node.clone());
+            CaptureDevice {
+                kind: DeviceKind::Video,
+                id: format!("/dev/{}", node),
+                name,
+            }
+        })
+        .collect();
+    devices.sort_by(|a, b| a.id.cmp(&b.id));
+
+    devices.push(CaptureDevice {
+        kind: DeviceKind::Audio,
+        id: "default".to_string(),
+        name: "Default microphone".to_string(),
+    });
+    Ok(devices)
+}
+
+/// ffmpeg input arguments for the selected devices
+fn device_input_args(selection: &CaptureSelection, options: &RecordingOptions) -> Vec<String> {
+    let mut video_opts = Vec::new();
+    if let Some(framerate) = options.framerate {
+        video_opts.extend(["-framerate".to_string(), framerate.to_string()]);
+    }
+    if let Some(size) = &options.video_size {
+        video_opts.extend(["-video_size".to_string(), size.clone()]);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut args = vec!["-f".to_string(), "avfoundation".to_string()];
+        if selection.video.is_some() {
+            args.extend(video_opts);
+        }
+        args.extend([
+            "-i".to_string(),
+            format!(
+                "{}:{}",
+                selection.video.as_deref().unwrap_or("none"),
+                selection.audio.as_deref().unwrap_or("none")
+            ),
+        ]);
+        args
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let mut args = vec!["-f".to_string(), "dshow".to_string()];
+        if selection.video.is_some() {
+            args.extend(video_opts);
+        }
+        let mut input = Vec::new();
+        if let Some(video) = &selection.video {
+            input.push(format!("video={}", video));
+        }
+        if let Some(audio) = &selection.audio {
+            input.push(format!("audio={}", audio));
+        }
+        args.extend(["-i".to_string(), input.join(":")]);
+        args
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let mut args = Vec::new();
+        if let Some(video) = &selection.video {
+            args.extend(["-f".to_string(), "v4l2".to_string()]);
+            args.extend(video_opts);
+            args.extend(["-i".to_string(), video.clone()]);
+        }
+        if let Some(audio) = &selection.audio {
+            args.extend(["-f".to_string(), "alsa".to_string(), "-i".to_string(), audio.clone()]);
+        }
+        args
+    }
+}
+
+/// ffmpeg input arguments for a screen capture, plus any filter that does the region cropping
+fn screen_input_args(screen: &ScreenCapture, options: &RecordingOptions) -> Result<(Vec<String>, Option<String>), String> {
+    // x264 needs even dimensions
+    let region = screen.region.map(|r| ScreenRegion {
+        width: r.width & !1,
+        height: r.height & !1,
+        ..r
+    });
+    if region.is_some_and(|r| r.width == 0 || r.height == 0) {
+        return Err("Screen region is too small".to_string());
+    }
+    let framerate = options.framerate.unwrap_or(30).to_string();
+
+    #[cfg(target_os = "macos")]
+    {
+        let screen_index = match &screen.display {
+            Some(display) => display.clone(),
+            None => enumerate_devices()?
+                .into_iter()
+                .find(|d| d.kind == DeviceKind::Video && d.name.starts_with("Capture screen"))
+                .map(|d| d.id)
+                .ok_or("No screen capture device found; check Screen Recording permission")?,
+        };
+        let args = vec![
+            "-f".to_string(),
+            "avfoundation".to_string(),
+            "-capture_cursor".to_string(),
+            "1".to_string(),
+            "-framerate".to_string(),
+            framerate,
+            "-i".to_string(),
+            format!("{}:{}", screen_index, screen.audio.as_deref().unwrap_or("none")),
+        ];
+        // avfoundation always grabs the whole screen
+        let filter = region.map(|r| format!("crop={}:{}:{}:{}", r.width, r.height, r.x, r.y));
+        Ok((args, filter))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let mut args = vec!["-f".to_string(), "gdigrab".to_string(), "-framerate".to_string(), framerate];
+        if let Some(r) = region {
+            args.extend([
+                "-offset_x".to_string(),
+                r.x.to_string(),
+                "-offset_y".to_string(),
+                r.y.to_string(),
+                "-video_size".to_string(),
+                format!("{}x{}", r.width, r.height),
+            ]);
+        }
+        args.extend(["-i".to_string(), "desktop".to_string()]);
+        if let Some(audio) = &screen.audio {
+            args.extend(["-f".to_string(), "dshow".to_string(), "-i".to_string(), format!("audio={}", audio)]);
+        }
+        Ok((args, None))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let display = screen
+            .display
+            .clone()
+            .or_else(|| std::env::var("DISPLAY").ok())
+            .ok_or("No X display found; screen recording on Wayland isn't supported")?;
+        let mut args = vec!["-f".to_string(), "x11grab".to_string(), "-framerate".to_string(), framerate];
+        let input = match region {
+            Some(r) => {
+                args.extend(["-video_size".to_string(), format!("{}x{}", r.width, r.height)]);
+                format!("{}+{},{}", display, r.x, r.y)
+            }
+            None => display,
+        };
+        args.extend(["-i".to_string(), input]);
+        if let Some(audio) = &screen.audio {
+            args.extend(["-f".to_string(), "alsa".to_string(), "-i".to_string(), audio.clone()]);
+        }
+        Ok((args, None))
+    }
+}
+
+/// Emit duration and file size until the recording is stopped
+fn watch_recording(app: AppHandle, recording: Recording) {
+    let started = Instant::now();
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        let running = match RECORDINGS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(&recording.id)
+        {
+            Some(session) => matches!(session.child.try_wait(), Ok(None)),
+            // Stopped by stop_recording
+            None => return,
+        };
+
+        let progress = RecordingProgress {
+            id: recording.id,
+            duration_secs: started.elapsed().as_secs(),
+            size_bytes: std::fs::metadata(&recording.output_path).map(|m| m.len()).unwrap_or(0),
+            running,
+        };
+        let _ = app.emit(RECORDING_PROGRESS_EVENT, &progress);
+        if !running {
+            return;
+        }
+    }
+}
+
+/// Read `stderr` to the end, keeping the last `STDERR_TAIL_LINES` lines. Read all along so
+/// a long recording can't fill the pipe and stall ffmpeg.
+fn read_tail(stderr: impl Read) -> VecDeque<String> {
+    let mut tail = VecDeque::new();
+    let mut reader = BufReader::new(stderr);
+    let mut bytes = Vec::new();
+    while reader.read_until(b'\n', &mut bytes).is_ok_and(|n| n > 0) {
+        let line = String::from_utf8_lossy(&bytes).trim_end().to_string();
+        bytes.clear();
+        if line.is_empty() {
+            continue;
+        }
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+    tail
+}
+
+/// H.264/AAC encode fast enough to keep up with a live source
+fn encode_args(options: &RecordingOptions) -> Vec<String> {
+    [
+        "-c:v", "libx264",
+        "-preset", "veryfast",
+        "-crf", &options.crf.unwrap_or(23).to_string(),
+        "-pix_fmt", "yuv420p",
+        "-c:a", "aac",
+        "-b:a", "160k",
+        "-movflags", stream::LIVE_MOVFLAGS,
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+/// A free path for a recording started at `now`, in the chosen folder or the Videos folder
+fn output_path(options: &RecordingOptions, ext: &str, now: &chrono::DateTime<chrono::Local>) -> Result<String, String> {
+    let dir = options
+        .output_dir
+        .clone()
+        .map(PathBuf::from)
+        .or_else(dirs::video_dir)
+        .or_else(dirs::home_dir)
+        .ok_or("No output directory")?;
+    let name = options
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("recording-{}", now.format("%Y%m%d-%H%M%S")));
+    let output_path = naming::get_unique_output_path(&dir.join(&name).to_string_lossy(), ext, options.filename_policy);
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// Spawn ffmpeg with `input_args` and `output_args`, writing a file with extension `ext`,
+/// and track it until `stop_recording`
+fn spawn_recording(
+    app: &AppHandle,
+    input_args: Vec<String>,
+    output_args: Vec<String>,
+    ext: &str,
+    options: &RecordingOptions,
+) -> Result<Recording, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let now = chrono::Local::now();
+    let output_str = output_path(options, ext, &now)?;
+
+    let mut args = vec!["-hide_banner".to_string(), "-loglevel".to_string(), "error".to_string()];
+    args.extend(input_args);
+    args.extend(output_args);
+    args.extend(["-y".to_string(), output_str.clone()]);
+    tracing::debug!(?args, "ffmpeg arguments");
+
+    // stdin stays open: writing "q" is how ffmpeg is asked to stop cleanly
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    let stderr = child.stderr.take().map(|pipe| thread::spawn(move || read_tail(pipe)));
+    let recording = Recording {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        output_path: output_str,
+        started_at: now.timestamp(),
+    };
+    tracing::info!(id = recording.id, output = %recording.output_path, "started recording");
+    RECORDINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(recording.id, Session { recording: recording.clone(), child, stderr });
+
+    let watched = recording.clone();
+    let app = app.clone();
+    thread::spawn(move || watch_recording(app, watched));
+    Ok(recording)
+}
+
+/// Cameras, capture cards and microphones ffmpeg can record from
+#[tauri::command(async)]
+pub fn list_capture_devices() -> Result<Vec<CaptureDevice>, String> {
+    enumerate_devices()
+}
+
+/// Start recording the selected devices straight to MP4, or M4A for audio alone
+#[tauri::command]
+pub fn start_recording(
+    app: AppHandle,
+    device: CaptureSelection,
+    options: Option<RecordingOptions>,
+) -> Result<Recording, String> {
+    if device.video.is_none() && device.audio.is_none() {
+        return Err("Select a video or audio device to record".to_string());
+    }
+    let options = options.unwrap_or_default();
+    let ext = if device.video.is_some() { "mp4" } else { "m4a" };
+    spawn_recording(&app, device_input_args(&device, &options), encode_args(&options), ext, &options)
+}
+
+/// Start recording the screen, or a region of it, straight to MP4
+#[tauri::command]
+pub fn start_screen_recording(
+    app: AppHandle,
+    screen: Option<ScreenCapture>,
+    options: Option<RecordingOptions>,
+) -> Result<Recording, String> {
+    let screen = screen.unwrap_or_default();
+    let options = options.unwrap_or_default();
+    let (input_args, filter) = screen_input_args(&screen, &options)?;
+    let mut output_args = Vec::new();
+    if let Some(filter) = filter {
+        output_args.extend(["-vf".to_string(), filter]);
+    }
+    output_args.extend(encode_args(&options));
+    spawn_recording(&app, input_args, output_args, "mp4", &options)
+}
+
+/// Input arguments for a network stream, with reconnects where the protocol supports them
+fn network_input_args(url: &str) -> Result<Vec<String>, String> {
+    let scheme = url.split("://").next().unwrap_or_default().to_lowercase();
+    let mut args: Vec<&str> = match scheme.as_str() {
+        // UDP drops packets on busy networks; cameras all speak RTSP over TCP
+        "rtsp" | "rtsps" => vec!["-rtsp_transport", "tcp"],
+        "http" | "https" => vec![
+            "-reconnect", "1",
+            "-reconnect_streamed", "1",
+            "-reconnect_on_network_error", "1",
+            "-reconnect_delay_max", "10",
+        ],
+        "rtmp" | "rtmps" => Vec::new(),
+        _ => return Err(format!("Unsupported stream URL: {}", url)),
+    };
+    args.extend(["-i", url]);
+    Ok(args.into_iter().map(str::to_string).collect())
+}
+
+/// Dump an RTSP camera, HLS playlist or RTMP stream to MP4 without re-encoding, returning
+/// the file's path. Stops on its own after `duration` seconds or `max_size_mb`, whichever
+/// comes first. It runs as a conversion, with the usual progress and log events:
+/// `cancel_conversion` with `keep_partial` ends it early keeping what was recorded, and
+/// without it deletes the file.
+#[tauri::command(async)]
+pub fn record_stream(
+    app: AppHandle,
+    url: String,
+    duration: Option<u64>,
+    max_size_mb: Option<u64>,
+    options: Option<RecordingOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let output = output_path(&options, "mp4", &chrono::Local::now())?;
+
+    let mut args = vec!["-hide_banner".to_string(), "-loglevel".to_string(), "error".to_string()];
+    args.extend(network_input_args(&url)?);
+    args.extend(["-codec".to_string(), "copy".to_string()]);
+    if let Some(duration) = duration {
+        args.extend(["-t".to_string(), duration.to_string()]);
+    }
+    if let Some(max_size_mb) = max_size_mb {
+        args.extend(["-fs".to_string(), max_size_mb.saturating_mul(1024 * 1024).to_string()]);
+    }
+    args.extend(["-movflags".to_string(), stream::LIVE_MOVFLAGS.to_string()]);
+    args.extend(progress::args());
+    args.extend(["-y".to_string(), output.clone()]);
+    tracing::debug!(?args, "ffmpeg arguments");
+
+    let job = Job::start(&url);
+    tracing::info!(id = job.id(), %output, "started recording stream");
+    let child = convert::spawn_ffmpeg(&ffmpeg_path, &args, &[])?;
+    let run = convert::finish_ffmpeg(&app, &job, &url, &output, duration.map(|d| d as f64), None, child)?;
+    let log = run.log.join("\n");
+    if run.cancelled == Some(CancelMode::Discard) {
+        let _ = fs::remove_file(&output);
+        return Err(cancel::cancelled_error(None));
+    }
+    if !PathBuf::from(&output).exists() {
+        let error = "Recording failed: no output was written".to_string();
+        return Err(if log.is_empty() { error } else { format!("{}: {}", error, log) });
+    }
+    if run.cancelled.is_none() && !run.status.success() {
+        // What was written before the stream dropped is still a playable recording
+        tracing::warn!(id = job.id(), status = %run.status, %log, "ffmpeg failed during the recording");
+    }
+    tracing::info!(id = job.id(), %output, "finished recording stream");
+    Ok(output)
+}
+
+/// Stop a recording and return the finished file's path
+#[tauri::command(async)]
+pub fn stop_recording(id: u64) -> Result<String, String> {
+    let mut session = RECORDINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id)
+        .ok_or_else(|| format!("No recording with id {}", id))?;
+
+    if let Some(mut stdin) = session.child.stdin.take() {
+        let _ = stdin.write_all(b"q");
+    }
+
+    let started = Instant::now();
+    let status = loop {
+        match session.child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if started.elapsed() > Duration::from_secs(STOP_GRACE_SECS) => {
+                tracing::warn!(id, "ffmpeg didn't stop, killing it");
+                suspend::kill(&mut session.child);
+                let _ = session.child.wait();
+                break None;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(200)),
+            Err(e) => return Err(format!("Failed to wait for ffmpeg: {}", e)),
+        }
+    };
+    let stderr = session.stderr.take().and_then(|reader| reader.join().ok()).unwrap_or_default();
+    let stderr = Vec::from(stderr).join("\n");
+
+    let output = session.recording.output_path;
+    if !PathBuf::from(&output).exists() {
+        let error = "Recording failed: no output was written".to_string();
+        return Err(if stderr.is_empty() { error } else { format!("{}: {}", error, stderr) });
+    }
+    if let Some(status) = status.filter(|s| !s.success()) {
+        // What was written before the failure is still a playable recording
+        tracing::warn!(id, %status, %stderr, "ffmpeg failed during the recording");
+    }
+    tracing::info!(id, %output, "stopped recording");
+    Ok(output)
+}
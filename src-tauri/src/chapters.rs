@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::probe::{self, Chapter};
+
+/// Don't start a new chapter closer than this to the previous one
+const DEFAULT_MIN_CHAPTER_SECS: f64 = 60.0;
+
+/// What counts as a chapter boundary
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ChapterDetection {
+    /// Cut where the picture changes by more than `threshold` (0-1, default 0.4)
+    SceneChange { threshold: Option<f64> },
+    /// Cut where speech resumes after at least `min_silence_secs` below `noise_db`
+    Silence { min_silence_secs: Option<f64>, noise_db: Option<f64> },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AutoChapters {
+    #[serde(flatten)]
+    pub detection: ChapterDetection,
+    /// Minimum chapter length in seconds
+    pub min_chapter_secs: Option<f64>,
+}
+
+/// Parse the number following `key` on a log line, e.g. "pts_time:12.5" or "silence_end: 80.1"
+fn parse_after(line: &str, key: &str) -> Option<f64> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.trim_start()
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Run a detection pass over the input and return candidate chapter start times
+fn detect_points(input_path: &str, detection: ChapterDetection) -> Result<Vec<f64>, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+
+    let (filter_flag, filter, key) = match detection {
+        ChapterDetection::SceneChange { threshold } => (
+            "-filter:v",
+            format!("select='gt(scene,{})',showinfo", threshold.unwrap_or(0.4)),
+            "pts_time:",
+        ),
+        ChapterDetection::Silence { min_silence_secs, noise_db } => (
+            "-filter:a",
+            format!(
+                "silencedetect=noise={}dB:d={}",
+                noise_db.unwrap_or(-35.0),
+                min_silence_secs.unwrap_or(2.0)
+            ),
+            "silence_end:",
+        ),
+    };
+    let args = ["-hide_banner", "-i", input_path, filter_flag, &filter, "-f", "null", "-"];
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Chapter detection failed: {}", stderr));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter_map(|line| parse_after(line, key))
+        .collect())
+}
+
+/// Drop points that would make chapters shorter than `min_secs`
+fn space_points(mut points: Vec<f64>, min_secs: f64, duration: f64) -> Vec<f64> {
+    points.sort_by(|a, b| a.total_cmp(b));
+    let mut starts = vec![0.0];
+    for point in points {
+        let last = *starts.last().unwrap_or(&0.0);
+        if point - last >= min_secs && duration - point >= min_secs {
+            starts.push(point);
+        }
+    }
+    starts
+}
+
+/// Render chapter start times as an ffmetadata file
+fn ffmetadata(starts: &[f64], duration: f64) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(duration);
+        out.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle=Chapter {}\n",
+            (start * 1000.0) as u64,
+            (end * 1000.0) as u64,
+            i + 1
+        ));
+    }
+    out
+}
+
+/// Detect chapter points and write them to a temporary ffmetadata file for ffmpeg to read.
+/// Returns None when nothing worth chaptering was found.
+pub fn generate_chapter_file(input_path: &str, options: &AutoChapters) -> Result<Option<PathBuf>, String> {
+    let duration = probe::probe(input_path)?
+        .duration
+        .ok_or("Can't generate chapters: input duration is unknown")?;
+
+    let points = detect_points(input_path, options.detection)?;
+    let starts = space_points(points, options.min_chapter_secs.unwrap_or(DEFAULT_MIN_CHAPTER_SECS), duration);
+    if starts.len() < 2 {
+        return Ok(None);
+    }
+
+    let path = crate::unique_temp_path("chapters", "txt");
+    fs::write(&path, ffmetadata(&starts, duration))
+        .map_err(|e| format!("Failed to write chapter file: {}", e))?;
+    tracing::info!(count = starts.len(), "generated chapters");
+    Ok(Some(path))
+}
+
+/// The chapters of a video, e.g. to compare a source with its MP4
+#[tauri::command(async)]
+pub fn get_chapters(path: String) -> Result<Vec<Chapter>, String> {
+    Ok(probe::probe(&path)?.chapters)
+}
@@ -0,0 +1,1168 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter};
+
+use crate::audio::{self, AudioCodec, AudioTarget, AudioTrackOutput};
+use crate::cancel::{self, CancelMode, Job};
+use crate::chapters::{self, AutoChapters};
+use crate::encrypt::{self, Encryption};
+use crate::metadata::{self, MetadataOverrides};
+use crate::naming::{self, ConflictPolicy, FilenamePolicy, NameFields};
+use crate::notes::{ConversionNote, NoteCollector, NoteKind};
+use crate::planner::SubtitleMode;
+use crate::plugins::{self, PluginStage};
+use crate::presets::{self, EncodeQuality, Preset};
+use crate::social::SocialFormat;
+use crate::transcribe::{self, Transcription};
+use crate::probe::{Composition, MediaInfo};
+use crate::{av1, captions, destinations, energy, estimate, expectations, folders, fonts, guardrails, health, history, idle, language, metrics, overlay, planner, probe, progress, queue, share, stream, twopass, verify, volume};
+
+/// Where a conversion writes its output
+#[derive(Debug, Clone)]
+pub enum Destination {
+    /// A unique name in this folder; without one, in the output folder setting or next to
+    /// the source
+    Folder(Option<PathBuf>),
+    /// This file, replacing whatever is there
+    File(PathBuf),
+}
+
+/// Emitted with the `ConversionPlan` a plain remux follows, before ffmpeg starts
+const CONVERSION_PLAN_EVENT: &str = "conversion-plan";
+
+/// Emitted with a `ConversionLogLine` for each line ffmpeg prints while converting
+const CONVERSION_LOG_EVENT: &str = "conversion-log";
+
+/// ffmpeg lines kept for a conversion's error message and history entry
+const MAX_LOG_LINES: usize = 200;
+
+/// Emitted with a `ConversionRetry` when a remux failed to copy streams and is tried again
+/// re-encoding them
+const CONVERSION_RETRY_EVENT: &str = "conversion-retry";
+
+/// ffmpeg errors meaning a stream couldn't be copied into MP4 as it is, which re-encoding
+/// it gets around
+const COPY_FAILURES: &[&str] = &[
+    "Could not find tag for codec",
+    "codec not currently supported in container",
+    "incompatible with output codec id",
+    "Could not write header",
+];
+
+/// How much of a plain remux is re-encoded. As an option, how far a remux may go when
+/// copying fails: audio first, then everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyStrategy {
+    /// Streams are copied as the plan says; as an option, failures aren't retried
+    Copy,
+    /// The video is copied and the audio re-encoded
+    TranscodeAudio,
+    /// Video to H.264 and audio to the fallback codec
+    #[default]
+    TranscodeAll,
+}
+
+impl CopyStrategy {
+    fn describe(self) -> &'static str {
+        match self {
+            CopyStrategy::Copy => "Streams were copied",
+            CopyStrategy::TranscodeAudio => "Copying a stream failed, so the audio was re-encoded",
+            CopyStrategy::TranscodeAll => "Copying a stream failed, so the video and audio were re-encoded",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionRetry {
+    pub input_path: String,
+    /// What the next attempt re-encodes
+    pub strategy: CopyStrategy,
+    /// The ffmpeg error that made the last attempt fail
+    pub reason: String,
+}
+
+/// Errors for audio-only inputs under `AudioOnlyOutput::Prompt` start with this, so the
+/// frontend knows to ask and call again with `M4a`
+pub const AUDIO_ONLY_INPUT: &str = "The input has only audio";
+
+/// What to make of an input with audio but no video, such as an MKA
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioOnlyOutput {
+    /// Convert it to an .m4a
+    #[default]
+    M4a,
+    /// Fail with an `AUDIO_ONLY_INPUT` error instead
+    Prompt,
+}
+
+/// Emitted with a `ReadCheck` when the source drive looks slow or failing
+const SOURCE_READ_WARNING_EVENT: &str = "source-read-warning";
+
+/// Optional knobs for a single conversion; everything defaults to a plain remux
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConvertOptions {
+    /// Hold off until the user has been idle this many seconds
+    pub wait_for_idle: Option<u64>,
+    /// Which characters may appear in the output name
+    pub filename_policy: FilenamePolicy,
+    /// What to do when the output already exists
+    pub on_conflict: ConflictPolicy,
+    /// Outputs too big for a FAT32 destination are refused unless this is set, in which
+    /// case they're written as numbered parts and the first part's path is returned
+    pub split_oversize: bool,
+    /// Fail the conversion if copied AC3/EAC3/DTS/TrueHD tracks don't match the source
+    pub verify_audio: bool,
+    /// Write this title or comment instead of the source's
+    pub set_metadata: Option<MetadataOverrides>,
+    /// Carry the source's SMPTE timecode into the MP4 as a tmcd track
+    pub preserve_timecode: bool,
+    /// Also write embedded CEA-608/708 captions to a `.cc.srt` sidecar
+    pub extract_captions: bool,
+    /// Write the output to each of these folders instead of the output folder: converted once
+    /// into temp storage, then copied to every folder and checked there
+    pub destinations: Vec<String>,
+    /// For multi-program transport streams, convert this program instead of ffmpeg's pick
+    pub program: Option<u32>,
+    /// Keep only audio in this language ("jpn", "ja" or "Japanese"); untagged tracks are
+    /// matched by title or, with whisper.cpp installed, by listening to a sample
+    pub audio_language: Option<String>,
+    /// Keep only these audio streams, by the input index `probe_file` lists, e.g. to drop a
+    /// commentary track or a dub; an empty list keeps no audio
+    pub audio_streams: Option<Vec<u32>>,
+    /// Re-frame for social platforms (9:16 or 1:1) instead of remuxing
+    pub social_format: Option<SocialFormat>,
+    /// Add chapter markers at detected scene changes or long silences
+    pub auto_chapters: Option<AutoChapters>,
+    /// Generate subtitles from the audio with a local whisper.cpp
+    pub transcribe: Option<Transcription>,
+    /// Don't convert a file whose content was already converted, even under another name;
+    /// the earlier output is returned instead
+    pub skip_duplicates: bool,
+    /// Upload a low-bitrate preview of the output to the share destination and send its link
+    /// to the frontend, for review copies
+    pub share_preview: bool,
+    /// Encrypt the output with MP4 Common Encryption or for age recipients. Refused along
+    /// with caption or transcript sidecars and shared previews, which would stay plain.
+    pub encrypt: Option<Encryption>,
+    /// Burn this subtitle track (0 = first) into the picture, re-encoding the video.
+    /// ASS styling is kept, using the fonts embedded in the source.
+    pub burn_subtitles: Option<u32>,
+    /// Encode with this preset instead of copying the streams
+    pub preset: Option<Preset>,
+    /// A built-in or saved preset to use, by name, instead of spelling out `preset`
+    pub preset_name: Option<String>,
+    /// Encode the preset's target bitrate in two passes, the first only measuring the video,
+    /// so bits go where the picture needs them; takes about twice as long
+    pub two_pass: bool,
+    /// CRF or bitrate, speed and audio bitrate for re-encodes the app picks itself, where
+    /// there's no preset
+    pub quality: Option<EncodeQuality>,
+    /// Re-encode the audio to this codec, copying the video
+    pub audio: Option<AudioTarget>,
+    /// Build the output's audio tracks one by one instead of copying the source's
+    pub audio_tracks: Option<Vec<AudioTrackOutput>>,
+    /// Keep the original audio and add a default stereo AAC track for devices that can't play it
+    pub compatibility_audio: bool,
+    /// Write fragmented MP4, so the output can be played while it's still being converted
+    pub fragmented: bool,
+    /// In a plain remux, encode audio MP4 can't carry (DTS, TrueHD, PCM) to this instead
+    /// of AAC, e.g. AC-3 to keep 5.1 for a receiver; the video is still copied
+    pub audio_fallback: Option<AudioTarget>,
+    /// Convert text subtitles (SRT, ASS, WebVTT) to mov_text, or drop them
+    pub subtitles: SubtitleMode,
+    /// Input indices of video streams to keep besides the primary one, such as cover art;
+    /// see `ConversionPlan::optional_video`. Only plain remuxes, which follow the plan, use it
+    pub include_video: Vec<u32>,
+    /// Before converting a large source, time reading its start and end. Reads that fail
+    /// stop the conversion; slow ones only warn.
+    pub check_source_read: bool,
+    /// Copy the source to local temp storage, check the copy's hash, and convert from it,
+    /// for sources on drives or shares that might drop out partway through
+    pub local_copy: bool,
+    /// When a plain remux fails because a stream won't copy into MP4, retry re-encoding the
+    /// audio and then the video, up to this far
+    pub copy_fallback: CopyStrategy,
+    /// The user confirmed this job may go above the guardrails; never remembered for the folder
+    pub exceed_guardrails: bool,
+    /// What to do with an input that has no video
+    pub audio_only: AudioOnlyOutput,
+}
+
+impl ConvertOptions {
+    /// Nothing re-encodes or picks streams, so the planner decides per stream what's copied,
+    /// what's encoded and what's dropped
+    pub fn follows_plan(&self) -> bool {
+        self.social_format.is_none()
+            && self.program.is_none()
+            && self.audio_language.is_none()
+            && self.preset.is_none()
+            && self.burn_subtitles.is_none()
+            && self.audio.is_none()
+            && self.audio_tracks.is_none()
+            && !self.compatibility_audio
+    }
+
+    /// These options with `preset_name` looked up into `preset`
+    pub fn with_named_preset(mut self) -> Result<Self, String> {
+        if let Some(name) = self.preset_name.as_deref() {
+            if self.preset.is_some() {
+                return Err("Choose either a preset or a preset name, not both".to_string());
+            }
+            self.preset = Some(presets::find_preset(name).ok_or_else(|| format!("No preset named {}", name))?);
+        }
+        Ok(self)
+    }
+
+    /// `audio_fallback` with the quality's audio bitrate, when it doesn't set its own
+    pub fn audio_fallback(&self) -> Option<AudioTarget> {
+        let kbps = self.quality.as_ref().and_then(|q| q.audio_bitrate_kbps);
+        match (self.audio_fallback.clone(), kbps) {
+            (fallback, None) => fallback,
+            (Some(fallback), Some(kbps)) => Some(AudioTarget { bitrate_kbps: fallback.bitrate_kbps.or(Some(kbps)), ..fallback }),
+            (None, Some(kbps)) => Some(AudioTarget { codec: AudioCodec::Aac, bitrate_kbps: Some(kbps) }),
+        }
+    }
+
+    /// Whether the video is encoded rather than copied
+    pub fn reencodes_video(&self, info: &MediaInfo) -> bool {
+        if self.social_format.is_some() || self.burn_subtitles.is_some() {
+            return true;
+        }
+        match &self.preset {
+            Some(preset) => preset.video_codec != "copy",
+            None => self.follows_plan() && planner::transcodes_video(info),
+        }
+    }
+}
+
+/// Extension of the output for a source probed as `info`: .m4a for audio-only, .mp4 otherwise
+pub fn output_extension(info: Option<&MediaInfo>) -> &'static str {
+    match info.map(|info| info.composition()) {
+        Some(Composition::AudioOnly) => "m4a",
+        _ => "mp4",
+    }
+}
+
+/// Options that need a picture to work on, refused for an audio-only input
+fn check_audio_only(input_path: &str, options: &ConvertOptions) -> Result<(), String> {
+    if options.audio_only == AudioOnlyOutput::Prompt {
+        return Err(format!("{}: {} has no video stream", AUDIO_ONLY_INPUT, input_path));
+    }
+    if options.social_format.is_some() || options.burn_subtitles.is_some() {
+        return Err("Social media framing and burned-in subtitles need a video stream".to_string());
+    }
+    if options.two_pass {
+        return Err("Two-pass encoding needs a video stream".to_string());
+    }
+    Ok(())
+}
+
+/// What a filename template is filled in with when converting `input_path`, probed as
+/// `info`, with `options`: the codec and height the output's video will have
+pub fn name_fields(input_path: &str, info: Option<&MediaInfo>, options: &ConvertOptions) -> NameFields {
+    let stem = Path::new(input_path).file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let Some(info) = info else {
+        return NameFields { stem, ..Default::default() };
+    };
+    let video = info.streams_of("video").next();
+    let mut vcodec = video.map(|v| v.codec.clone());
+    let mut height = video.and_then(|v| v.height);
+    if options.reencodes_video(info) {
+        let preset = options.preset.as_ref().filter(|_| options.social_format.is_none());
+        vcodec = match preset {
+            Some(preset) => expectations::encoder_codec(&preset.video_codec),
+            None => Some("h264".to_string()),
+        };
+        if let Some(max) = preset.and_then(|p| p.max_height) {
+            height = height.map(|h| h.min(max));
+        }
+    }
+    if let Some(social) = &options.social_format {
+        height = Some(social.dimensions().1);
+    }
+    if let Some(max) = guardrails::applying(options, info).and_then(|g| g.max_height) {
+        height = height.map(|h| h.min(max));
+    }
+    NameFields { stem, vcodec, height }
+}
+
+/// Arguments that write the source timecode into the output, if it has one
+fn timecode_args(info: &MediaInfo) -> Vec<String> {
+    let Some(timecode) = info.timecode.clone() else {
+        tracing::info!("no timecode found in source");
+        return Vec::new();
+    };
+
+    tracing::info!(%timecode, "preserving timecode");
+    vec![
+        "-timecode".to_string(),
+        timecode,
+        "-write_tmcd".to_string(),
+        "1".to_string(),
+    ]
+}
+
+/// Turn failed passthrough checks into an error, keeping the output for inspection
+fn check_audio_integrity(input_path: &str, output_path: &str) -> Result<(), String> {
+    let problems: Vec<String> = verify::verify_audio_passthrough(input_path, output_path)?
+        .into_iter()
+        .filter(|track| !track.ok)
+        .map(|track| format!("track {} ({}): {}", track.output_index, track.codec, track.problems.join(", ")))
+        .collect();
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        tracing::warn!(?problems, "audio bitstream check failed");
+        Err(format!(
+            "Audio bitstream check failed, output kept at {}: {}",
+            output_path,
+            problems.join("; ")
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionLogLine {
+    /// The conversion's id, as in `ConversionProgress`
+    pub id: u64,
+    pub input_path: String,
+    pub line: String,
+}
+
+/// How a spawned ffmpeg run ended
+pub struct FfmpegRun {
+    pub status: ExitStatus,
+    /// The last `MAX_LOG_LINES` lines ffmpeg printed
+    pub log: Vec<String>,
+    pub cancelled: Option<CancelMode>,
+    pub notes: Vec<ConversionNote>,
+}
+
+/// Emit each line of ffmpeg's stderr as it's printed, keeping the last `MAX_LOG_LINES`
+/// and the notes they add up to
+fn read_log(app: &AppHandle, id: u64, input_path: &str, stderr: impl Read) -> (VecDeque<String>, NoteCollector) {
+    let mut log = VecDeque::new();
+    let mut collector = NoteCollector::default();
+    let mut reader = BufReader::new(stderr);
+    let mut bytes = Vec::new();
+    // Not `lines()`: that stops at the first non-UTF-8 line, such as a mangled title tag,
+    // and the unread pipe would then stall ffmpeg
+    while reader.read_until(b'\n', &mut bytes).is_ok_and(|n| n > 0) {
+        let line = String::from_utf8_lossy(&bytes).trim_end().to_string();
+        bytes.clear();
+        if line.is_empty() {
+            continue;
+        }
+        collector.line(&line);
+        let _ = app.emit(
+            CONVERSION_LOG_EVENT,
+            &ConversionLogLine { id, input_path: input_path.to_string(), line: line.clone() },
+        );
+        if log.len() == MAX_LOG_LINES {
+            log.pop_front();
+        }
+        log.push_back(line);
+    }
+    (log, collector)
+}
+
+/// Start ffmpeg with its output piped for `finish_ffmpeg`. stdin stays open so a cancel that
+/// keeps the partial output can ask ffmpeg to stop.
+pub fn spawn_ffmpeg(ffmpeg_path: &str, args: &[String], env: &[(String, String)]) -> Result<Child, String> {
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", ffmpeg_path])
+        .args(args)
+        .envs(env.iter().cloned())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(ffmpeg_path)
+        .args(args)
+        .envs(env.iter().cloned())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    result.map_err(|e| format!("Failed to run ffmpeg: {}", e))
+}
+
+/// Wait for a spawned ffmpeg, reporting its progress and streaming its stderr for the
+/// error message and conversion notes. `pass` is 1 or 2 in a two-pass encode; `duration`
+/// is the source's, for the progress percentage.
+pub fn finish_ffmpeg(
+    app: &AppHandle,
+    job: &Job,
+    input_path: &str,
+    output_path: &str,
+    duration: Option<f64>,
+    pass: Option<u8>,
+    mut child: Child,
+) -> Result<FfmpegRun, String> {
+    let stdout = child.stdout.take();
+    let progress_app = app.clone();
+    let (id, input, output) = (job.id(), input_path.to_string(), output_path.to_string());
+    let progress_reader = thread::spawn(move || {
+        stdout.map(|stdout| progress::report(&progress_app, id, &input, &output, duration, pass, stdout))
+    });
+    let stderr = child.stderr.take();
+    let (log_app, input) = (app.clone(), input_path.to_string());
+    // Read alongside the wait so a chatty ffmpeg can't fill the pipe and stall
+    let reader = thread::spawn(move || stderr.map(|stderr| read_log(&log_app, id, &input, stderr)));
+    let (status, cancelled) = job.wait(&mut child)?;
+    let last = progress_reader.join().ok().flatten();
+    let (log, collector) = reader.join().ok().flatten().unwrap_or_default();
+    let (dropped, duplicated) = last.map_or((0, 0), |p| (p.dropped_frames, p.duplicated_frames));
+    let notes = collector.finish(dropped, duplicated);
+    Ok(FfmpegRun { status, log: log.into(), cancelled, notes })
+}
+
+/// Every file an ffmpeg run writes: the output, or all its numbered parts when split
+fn written_outputs(output_path: &Path, split: bool) -> Vec<PathBuf> {
+    let name = output_path.file_name().unwrap_or_default().to_string_lossy();
+    let ext = output_path.extension().unwrap_or_default().to_string_lossy();
+    let (Some(prefix), true) = (name.strip_suffix(&format!("000.{}", ext)), split) else {
+        return vec![output_path.to_path_buf()];
+    };
+    let dir = output_path.parent().unwrap_or_else(|| Path::new("."));
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            let name = p.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with(prefix) && name.ends_with(&format!(".{}", ext))
+        })
+        .collect()
+}
+
+/// Delete or keep what a cancelled ffmpeg run wrote, returning the cancellation error
+fn finish_cancelled(mode: CancelMode, output_path: &Path, split: bool, policy: FilenamePolicy) -> String {
+    let output = output_path.to_string_lossy().to_string();
+    if mode == CancelMode::KeepPartial {
+        match probe::probe(&output).ok().and_then(|info| info.duration) {
+            // Numbered parts keep their names so they still sort together
+            Some(duration) if duration > 0.0 && split => return cancel::cancelled_error(Some(&output)),
+            Some(duration) if duration > 0.0 => {
+                let ext = output_path.extension().unwrap_or_default().to_string_lossy();
+                let partial = naming::output_with_suffix(&output, "_partial", &ext, policy);
+                tracing::info!(duration, partial = %partial.display(), "keeping partial output");
+                return match fs::rename(output_path, &partial) {
+                    Ok(()) => cancel::cancelled_error(Some(&partial.to_string_lossy())),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to rename partial output");
+                        cancel::cancelled_error(Some(&output))
+                    }
+                };
+            }
+            _ => tracing::warn!("nothing playable was written before the cancel"),
+        }
+    }
+    for file in written_outputs(output_path, split) {
+        let _ = fs::remove_file(file);
+    }
+    cancel::cancelled_error(None)
+}
+
+/// Convert a video file to MP4 using ffmpeg. Without options, whatever was last used
+/// for the same source folder applies. `output_directory` overrides the output folder
+/// setting for this conversion.
+#[tauri::command(async)]
+pub fn convert_file(
+    app: AppHandle,
+    input_path: String,
+    options: Option<ConvertOptions>,
+    output_directory: Option<String>,
+) -> Result<String, String> {
+    convert_into(&app, input_path, options, Destination::Folder(output_directory.map(PathBuf::from)))
+}
+
+/// The line of an ffmpeg error saying a stream couldn't be copied, if it does
+fn copy_failure(error: &str) -> Option<&str> {
+    error
+        .lines()
+        .map(str::trim)
+        .find(|line| COPY_FAILURES.iter().any(|pattern| line.contains(pattern)))
+}
+
+/// What to try after `strategy` failed with `error`, if re-encoding more would help.
+/// Only plain remuxes are retried; other options already say how each stream is handled.
+fn retry_strategy(info: &MediaInfo, options: &ConvertOptions, strategy: CopyStrategy, error: &str) -> Option<(CopyStrategy, String)> {
+    if !options.follows_plan() || cancel::is_cancelled(error) {
+        return None;
+    }
+    let reason = copy_failure(error)?;
+    // "Could not find tag for codec vc1 in stream #0": re-encoding the audio won't help
+    let video_failed = reason
+        .split_once("for codec ")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .is_some_and(|codec| info.streams_of("video").any(|v| v.codec == codec));
+    let next = match strategy {
+        CopyStrategy::Copy if !video_failed => CopyStrategy::TranscodeAudio,
+        CopyStrategy::Copy | CopyStrategy::TranscodeAudio => CopyStrategy::TranscodeAll,
+        CopyStrategy::TranscodeAll => return None,
+    };
+    (next <= options.copy_fallback).then(|| (next, reason.to_string()))
+}
+
+/// What a conversion sets up once, before its first attempt. Retries re-encode more of the
+/// same source into the same output, so the slow steps here aren't repeated for them.
+struct Prepared {
+    input_path: String,
+    /// The source, probed once for every attempt to share
+    info: MediaInfo,
+    options: ConvertOptions,
+    /// The caller passed the options, rather than them being recalled for the folder
+    explicit: bool,
+    job: Job,
+    output_path: PathBuf,
+    /// Held to the end: everything that reads the whole source reads the copy
+    local_copy: Option<health::LocalCopy>,
+    chapter_file: Option<PathBuf>,
+    /// A transcript to mux in, made up front from the source
+    transcript_file: Option<PathBuf>,
+    /// Tags the pre-convert plugins set
+    plugin_metadata: BTreeMap<String, String>,
+}
+
+impl Prepared {
+    /// The file ffmpeg and the source checks read: the local copy, if there is one
+    fn source_path(&self) -> String {
+        self.local_copy
+            .as_ref()
+            .map(|copy| copy.path.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.input_path.clone())
+    }
+}
+
+impl Drop for Prepared {
+    fn drop(&mut self) {
+        for file in [&self.chapter_file, &self.transcript_file].into_iter().flatten() {
+            let _ = fs::remove_file(file);
+        }
+    }
+}
+
+enum Preparation {
+    Ready(Box<Prepared>),
+    /// Nothing to convert: the output, or an earlier conversion of the same content, exists
+    Skipped(String),
+}
+
+/// One attempt's outcome, with what the conversion's history entry records about it
+struct Attempt {
+    outcome: Result<String, String>,
+    output_path: PathBuf,
+    notes: Vec<ConversionNote>,
+    log: Vec<String>,
+    command: Vec<String>,
+}
+
+/// Like `convert_file`, but with the output going to `destination`. A remux whose streams
+/// won't copy is tried again re-encoding them, as far as the options' `copy_fallback` allows.
+/// A folder destination gives way to the options' `destinations`, when there are any.
+pub fn convert_into(
+    app: &AppHandle,
+    input_path: String,
+    options: Option<ConvertOptions>,
+    destination: Destination,
+) -> Result<String, String> {
+    convert_probed(app, input_path, None, options, destination)
+}
+
+/// `convert_into` for a source the caller may already have probed as `info`
+pub fn convert_probed(
+    app: &AppHandle,
+    input_path: String,
+    info: Option<MediaInfo>,
+    options: Option<ConvertOptions>,
+    destination: Destination,
+) -> Result<String, String> {
+    if let Destination::Folder(_) = destination {
+        let resolved = options.clone().or_else(|| folders::recall(&input_path)).unwrap_or_default();
+        if !resolved.destinations.is_empty() {
+            let info = info.map_or_else(|| probe::probe_input(&input_path), Ok)?;
+            return destinations::convert_to_folders(app, input_path, info, options, &resolved);
+        }
+    }
+    let started = Instant::now();
+    let result = match prepare(app, input_path, info, options, destination) {
+        Ok(Preparation::Ready(prepared)) => convert_prepared(app, &prepared, started),
+        Ok(Preparation::Skipped(output)) => Ok(output),
+        Err(e) => Err(e),
+    };
+    overlay::record_outcome(app, &result);
+    result
+}
+
+/// Attempts at a prepared conversion until one succeeds or re-encoding more won't help. The
+/// history entry and metrics are recorded once, for the last attempt.
+fn convert_prepared(app: &AppHandle, prepared: &Prepared, started: Instant) -> Result<String, String> {
+    let _span = tracing::info_span!("job", input = %prepared.input_path).entered();
+    let mut strategy = CopyStrategy::Copy;
+    loop {
+        let attempt = convert_attempt(app, prepared, strategy)?;
+        if let Err(error) = &attempt.outcome {
+            if let Some((next, reason)) = retry_strategy(&prepared.info, &prepared.options, strategy, error) {
+                tracing::warn!(%reason, ?next, "stream copy failed, retrying with re-encoding");
+                let _ = app.emit(
+                    CONVERSION_RETRY_EVENT,
+                    &ConversionRetry { input_path: prepared.input_path.clone(), strategy: next, reason },
+                );
+                strategy = next;
+                continue;
+            }
+        }
+        history::record(
+            &prepared.input_path,
+            &attempt.output_path.to_string_lossy(),
+            &attempt.outcome,
+            started.elapsed().as_secs_f64(),
+            attempt.notes,
+            attempt.log,
+            attempt.command,
+        );
+        metrics::record_conversion(&prepared.info, &attempt.outcome);
+        return attempt.outcome;
+    }
+}
+
+/// Resolve the options and output path and run the slow steps every attempt shares: the
+/// source checks and local copy, chapter detection, muxed transcription and pre-convert plugins
+fn prepare(
+    app: &AppHandle,
+    input_path: String,
+    info: Option<MediaInfo>,
+    options: Option<ConvertOptions>,
+    destination: Destination,
+) -> Result<Preparation, String> {
+    let explicit = options.is_some();
+    let options = options
+        .or_else(|| folders::recall(&input_path))
+        .unwrap_or_default()
+        .with_named_preset()?;
+    let _span = tracing::info_span!("job", input = %input_path).entered();
+    let job = Job::start(&input_path);
+
+    if options.skip_duplicates {
+        if let Some(previous) = history::previous_conversion(Path::new(&input_path)) {
+            tracing::info!(previous_input = %previous.input_path, output = %previous.output_path, "already converted, skipping");
+            return Ok(Preparation::Skipped(previous.output_path));
+        }
+    }
+
+    if let Some(threshold) = options.wait_for_idle {
+        idle::wait_for_idle(threshold, &job)?;
+    }
+
+    let source_size = fs::metadata(&input_path).map(|m| m.len()).unwrap_or(0);
+    if options.check_source_read && source_size >= health::LARGE_SOURCE_BYTES {
+        let check = health::check_read(&input_path)?;
+        if let Some(warning) = &check.warning {
+            tracing::warn!(%warning, "source drive may be failing");
+            let _ = app.emit(SOURCE_READ_WARNING_EVENT, &check);
+            if !check.errors.is_empty() {
+                return Err(warning.clone());
+            }
+        }
+    }
+
+    // Corrupt headers and files with nothing to convert fail here, not halfway into ffmpeg
+    let info = info.map_or_else(|| probe::probe_input(&input_path), Ok)?;
+    if info.composition() == Composition::AudioOnly {
+        check_audio_only(&input_path, &options)?;
+    }
+    if let Some(quality) = &options.quality {
+        if options.preset.is_some() {
+            return Err("Set the quality in the preset, not both".to_string());
+        }
+        quality.validate()?;
+    }
+    if options.two_pass {
+        twopass::check(options.preset.as_ref())?;
+    }
+    let plain_sidecars = options.extract_captions || options.transcribe.as_ref().is_some_and(|t| !t.mux);
+    if options.encrypt.is_some() && (plain_sidecars || options.share_preview) {
+        // They'd be written or uploaded unencrypted, next to the encrypted output
+        return Err("Encrypted outputs can't have caption or transcript sidecars or a shared preview".to_string());
+    }
+
+    let output_path = match destination {
+        Destination::File(path) => path,
+        Destination::Folder(dir) => {
+            let dir = dir.or_else(naming::output_directory);
+            if let Some(dir) = &dir {
+                fs::create_dir_all(dir).map_err(|e| format!("Failed to create output folder {}: {}", dir.display(), e))?;
+            }
+            let target = naming::conversion_target(&input_path, Some(&info), dir.as_deref(), &options);
+            match naming::resolve_conflict(&input_path, &target, options.on_conflict)? {
+                Some(path) => path,
+                None => {
+                    tracing::info!(output = %target.display(), "output already exists, skipping");
+                    return Ok(Preparation::Skipped(target.to_string_lossy().to_string()));
+                }
+            }
+        }
+    };
+    let local_copy = options.local_copy.then(|| health::local_copy(&input_path, &job)).transpose()?;
+    // Made into `Prepared` straight away, so its temp files are removed however this ends
+    let mut prepared = Prepared {
+        input_path,
+        info,
+        options,
+        explicit,
+        job,
+        output_path,
+        local_copy,
+        chapter_file: None,
+        transcript_file: None,
+        plugin_metadata: BTreeMap::new(),
+    };
+    let source_path = prepared.source_path();
+    if let Some(auto) = &prepared.options.auto_chapters {
+        prepared.chapter_file = chapters::generate_chapter_file(&source_path, auto)?;
+    }
+    // Sidecar transcripts wait for a successful convert
+    if let Some(transcription) = prepared.options.transcribe.as_ref().filter(|t| t.mux) {
+        let srt = crate::unique_temp_path("transcript", "srt");
+        prepared.transcript_file = Some(transcribe::transcribe_to_srt(&source_path, &srt, transcription)?);
+    }
+    // Chapter detection and transcription can take a while, so a cancel may already be in
+    if prepared.job.requested().is_none() {
+        prepared.plugin_metadata = plugins::run_stage(PluginStage::PreConvert, &prepared.input_path, None).metadata;
+    }
+    Ok(Preparation::Ready(Box::new(prepared)))
+}
+
+/// One try at a conversion, re-encoding what `strategy` says on top of the plan
+fn convert_attempt(app: &AppHandle, prepared: &Prepared, strategy: CopyStrategy) -> Result<Attempt, String> {
+    let Prepared { info, options, job, chapter_file, transcript_file, plugin_metadata, .. } = prepared;
+    let input_path = prepared.input_path.clone();
+    let source_path = prepared.source_path();
+    let ffmpeg_path = crate::find_ffmpeg().ok_or_else(|| {
+        tracing::error!("ffmpeg not found");
+        "ffmpeg not found".to_string()
+    })?;
+    let mut output_path = prepared.output_path.clone();
+    let mut args = vec!["-i".to_string(), source_path.clone()];
+    let mut ffmpeg_env: Vec<(String, String)> = Vec::new();
+    // Source subtitles mapped into the output ahead of a muxed transcript
+    let mut mapped_subtitles = 0;
+    // MP4 has no closed caption track, so encoding the video loses the ones embedded in it
+    let mut encodes_video = false;
+    if let Some(social) = &options.social_format {
+        if options.program.is_some() {
+            return Err("Program selection can't be combined with social media framing".to_string());
+        }
+        if options.burn_subtitles.is_some() || options.preset.is_some() {
+            return Err("Burned-in subtitles and presets can't be combined with social media framing".to_string());
+        }
+        args.extend(social.args(options.quality.as_ref()));
+        encodes_video = true;
+    } else {
+        if let Some(program) = options.program {
+            // Only the program's audio/video; its data and teletext streams won't fit in MP4
+            args.extend([
+                "-map".to_string(),
+                format!("0:p:{}:v?", program),
+                "-map".to_string(),
+                format!("0:p:{}:a?", program),
+            ]);
+        }
+        if let Some(preferred) = options.audio_language.as_deref().filter(|_| options.program.is_none()) {
+            let streams = language::matching_audio_streams(info, preferred);
+            if streams.is_empty() {
+                tracing::warn!(language = preferred, "no audio track in the preferred language, keeping defaults");
+            } else {
+                tracing::info!(language = preferred, ?streams, "selected audio by language");
+                args.extend(["-map".to_string(), "0:V?".to_string()]);
+                for index in streams {
+                    args.extend(["-map".to_string(), format!("0:{}", index)]);
+                }
+            }
+        }
+        if let Some(keep) = options.audio_streams.as_ref().filter(|_| !options.follows_plan()) {
+            if options.program.is_some() || options.audio_language.is_some() || options.audio_tracks.is_some() {
+                return Err("Audio streams to keep can't be combined with program, language or per-track audio selection".to_string());
+            }
+            planner::check_audio_streams(info, keep)?;
+            args.extend(["-map".to_string(), "0:V?".to_string()]);
+            for index in keep {
+                args.extend(["-map".to_string(), format!("0:{}", index)]);
+            }
+        }
+        args.extend(["-codec".to_string(), "copy".to_string()]);
+
+        if options.follows_plan() {
+            let audio_fallback = options.audio_fallback();
+            let mut plan = planner::build_plan(
+                info,
+                None,
+                options.audio_streams.as_deref(),
+                audio_fallback.as_ref(),
+                options.subtitles,
+                &options.include_video,
+            )?;
+            if strategy >= CopyStrategy::TranscodeAudio {
+                planner::transcode_copied(&mut plan, "audio", audio_fallback.as_ref());
+            }
+            if strategy == CopyStrategy::TranscodeAll {
+                planner::transcode_copied(&mut plan, "video", audio_fallback.as_ref());
+            }
+            for stream in plan.streams.iter().filter(|s| s.action != planner::StreamAction::Copy) {
+                tracing::info!(index = stream.index, codec = %stream.codec, action = ?stream.action, reason = %stream.reason, "stream can't be copied as is");
+            }
+            args.extend(planner::stream_args(&plan, audio_fallback.as_ref(), options.quality.as_ref())?);
+            encodes_video = plan.streams.iter().any(|s| s.kind == "video" && s.action == planner::StreamAction::Transcode);
+            // stream_args maps the kept streams in order, so their output indexes are known
+            let kept: Vec<u32> = plan
+                .streams
+                .iter()
+                .filter(|s| s.action != planner::StreamAction::Drop)
+                .map(|s| s.index)
+                .collect();
+            args.extend(metadata::stream_args(info, &kept));
+            mapped_subtitles = plan
+                .streams
+                .iter()
+                .filter(|s| s.kind == "subtitle" && s.action != planner::StreamAction::Drop)
+                .count();
+            let _ = app.emit(CONVERSION_PLAN_EVENT, &plan);
+        } else if options.subtitles == SubtitleMode::Convert {
+            // ffmpeg then only picks a subtitle track it can convert, skipping image ones
+            args.extend(["-c:s".to_string(), "mov_text".to_string()]);
+        }
+
+        let audio_tracks = match (&options.audio_tracks, options.compatibility_audio) {
+            (Some(_), true) => return Err("Choose either per-track audio or the compatibility track, not both".to_string()),
+            (Some(tracks), false) => Some(tracks.clone()),
+            (None, true) => Some(planner::compatibility_tracks(info)?),
+            (None, false) => None,
+        };
+        if let Some(tracks) = &audio_tracks {
+            // Each would map the video and a set of audio tracks of its own
+            let selects_audio = options.audio_language.is_some() || options.audio_streams.is_some();
+            if options.program.is_some() || selects_audio || options.audio.is_some() {
+                return Err(
+                    "Per-track audio can't be combined with program, language, audio stream or audio codec selection"
+                        .to_string(),
+                );
+            }
+            if options.preset.is_some() {
+                return Err("Per-track audio can't be combined with a preset".to_string());
+            }
+            args.extend(["-map".to_string(), "0:V?".to_string()]);
+            args.extend(audio::track_args(tracks, "mp4")?);
+        }
+
+        if let Some(target) = &options.audio {
+            if options.preset.is_some() {
+                return Err("Choose the audio codec in the preset, not both".to_string());
+            }
+            args.extend(audio::encode_args(target, "mp4")?);
+        }
+
+        if let Some(preset) = &options.preset {
+            if options.burn_subtitles.is_some() {
+                return Err("Burned-in subtitles can't be combined with a preset".to_string());
+            }
+            preset.validate()?;
+            if preset.video_codec == "libsvtav1" && !av1::svtav1_available() {
+                return Err("This ffmpeg build has no AV1 encoder (libsvtav1)".to_string());
+            }
+            let size = info.streams_of("video").next().and_then(|v| Some((v.width?, v.height?)));
+            let work_secs = info.duration.unwrap_or_default().max(queue::remaining_secs());
+            // A hardware encoder has no two-pass mode, so battery saving leaves those alone
+            let preset = if options.two_pass { preset.clone() } else { energy::adapt(preset, work_secs) };
+            tracing::info!(preset = %preset.name, encoder = %preset.video_codec, "encoding with preset");
+            args.extend(preset.ffmpeg_args(size)?);
+            encodes_video |= preset.video_codec != "copy";
+        }
+
+        if let Some(track) = options.burn_subtitles {
+            // Without the source's fonts, libass silently falls back to a default font
+            let fonts = fonts::prepare(&source_path)?;
+            let filter = format!(
+                "subtitles=filename={}:si={}:fontsdir={}",
+                crate::escape_filter_path(&source_path),
+                track,
+                crate::escape_filter_path(&fonts.fonts_dir.to_string_lossy()),
+            );
+            args.extend(["-vf", &filter, "-c:v", "libx264"].iter().map(|s| s.to_string()));
+            args.extend(presets::x264_rate_args(options.quality.as_ref(), 18, None));
+            args.extend(["-pix_fmt".to_string(), "yuv420p".to_string()]);
+            ffmpeg_env = fonts.env;
+            encodes_video = true;
+        }
+
+        if !options.exceed_guardrails && guardrails::load().is_set() {
+            if let Some(guardrails) = guardrails::applying(options, info) {
+                tracing::info!(violations = ?guardrails.violations(info), "limiting the video to the guardrails");
+                guardrails.apply(info, options.reencodes_video(info), options.quality.as_ref(), &mut args);
+                encodes_video = true;
+            }
+        }
+    }
+
+    if let Some(file) = chapter_file {
+        // Second input right after the source; it has no streams, only chapters
+        args.splice(2..2, ["-i".to_string(), file.to_string_lossy().to_string()]);
+        args.extend(["-map_chapters".to_string(), "1".to_string()]);
+    } else {
+        // Spelled out so no later input, such as a transcript, can take the source's place
+        args.extend(["-map_chapters".to_string(), "0".to_string()]);
+    }
+
+    if let Some(file) = transcript_file {
+        let inputs = args.iter().filter(|a| *a == "-i").count();
+        args.splice(inputs * 2..inputs * 2, ["-i".to_string(), file.to_string_lossy().to_string()]);
+        if !args.iter().any(|a| a == "-map") {
+            // Adding any -map turns off ffmpeg's default picks, so spell them out
+            args.extend(["-map".to_string(), "0:V?".to_string(), "-map".to_string(), "0:a?".to_string()]);
+        }
+        args.extend([
+            "-map".to_string(),
+            format!("{}:s", inputs),
+            "-c:s".to_string(),
+            "mov_text".to_string(),
+        ]);
+        if let Some(language) = options.transcribe.as_ref().and_then(|t| t.language.as_ref()) {
+            // Output streams are numbered in map order, and the transcript is mapped last.
+            // Paths other than the plan's only map video and audio, or add the maps above.
+            args.extend([format!("-metadata:s:s:{}", mapped_subtitles), format!("language={}", language)]);
+        }
+    }
+
+    // A muxed transcript maps its own subtitles, and -sn would drop those too
+    if options.subtitles == SubtitleMode::Drop && !options.follows_plan() && transcript_file.is_none() {
+        args.push("-sn".to_string());
+    }
+
+    args.extend(metadata::global_args(options.set_metadata.as_ref()));
+    for (key, value) in plugin_metadata {
+        args.extend(["-metadata".to_string(), format!("{}={}", key, value)]);
+    }
+
+    if options.preserve_timecode {
+        args.extend(timecode_args(info));
+    }
+
+    if let Some(Encryption::Cenc { key_name }) = &options.encrypt {
+        args.extend(encrypt::cenc_args(key_name)?);
+    }
+
+    let output_dir = output_path.parent().unwrap_or_else(|| Path::new("."));
+    let volume = volume::check_volume(&input_path, output_dir)?;
+    estimate::check_free_space(app, &input_path, info, options, output_dir)?;
+    let mut ffmpeg_output = output_path.to_string_lossy().to_string();
+    let split = volume.exceeds_limit;
+    if split {
+        if options.fragmented {
+            return Err("Fragmented outputs can't be split for a FAT32 destination".to_string());
+        }
+        if options.encrypt.is_some() {
+            return Err("Encrypted outputs can't be split for a FAT32 destination".to_string());
+        }
+        if !options.split_oversize {
+            return Err(format!(
+                "Output ({} MB) exceeds the 4 GB file size limit of the destination's {} filesystem",
+                volume.estimated_size / 1024 / 1024,
+                volume.filesystem.as_deref().unwrap_or("FAT"),
+            ));
+        }
+
+        let duration = info.duration.ok_or("Can't split output: input duration is unknown")?;
+        let segment_time = volume::segment_seconds(&volume, duration)
+            .ok_or("Can't split output: unable to compute segment length")?;
+
+        let stem = output_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let ext = output_path.extension().unwrap_or_default().to_string_lossy().to_string();
+        tracing::info!(segment_time, "splitting output for FAT32 destination");
+        args.extend([
+            "-f".to_string(),
+            "segment".to_string(),
+            "-segment_time".to_string(),
+            format!("{:.0}", segment_time),
+            "-segment_format".to_string(),
+            "mp4".to_string(),
+            "-reset_timestamps".to_string(),
+            "1".to_string(),
+        ]);
+        ffmpeg_output = output_path.with_file_name(format!("{}_part%03d.{}", stem, ext)).to_string_lossy().to_string();
+        output_path = output_path.with_file_name(format!("{}_part000.{}", stem, ext));
+    } else if output_path.extension().is_some_and(|e| e.eq_ignore_ascii_case("m4a")) {
+        // ffmpeg would pick the ipod muxer for .m4a, which refuses codecs MP4 takes
+        args.extend(["-f".to_string(), "mp4".to_string()]);
+    }
+    if options.fragmented {
+        // Last, so it wins over any +faststart a preset asked for
+        args.extend(["-movflags".to_string(), stream::LIVE_MOVFLAGS.to_string()]);
+    }
+    let two_pass = match options.preset.as_ref().filter(|_| options.two_pass) {
+        Some(preset) => {
+            let log = crate::unique_temp_path("passlog", "stats");
+            let first = twopass::first_pass_args(&args, &preset.video_codec, &log);
+            args.extend(twopass::pass_args(&preset.video_codec, 2, &log));
+            Some((first, log))
+        }
+        None => None,
+    };
+    args.extend(progress::args());
+    args.push("-y".to_string());
+    args.push(ffmpeg_output);
+
+    let output_str = output_path.to_string_lossy().to_string();
+    tracing::info!(output = %output_str, "starting conversion");
+    // The CENC key is only in argv; the log file and history get it blanked out
+    let command: Vec<String> = std::iter::once(ffmpeg_path.clone()).chain(encrypt::redacted(&args)).collect();
+    tracing::debug!(args = ?&command[1..], "ffmpeg arguments");
+
+    // Chapter detection and transcription can take a while, so a cancel may already be in
+    let result = if job.requested().is_some() {
+        Ok(None)
+    } else {
+        let first_pass = match &two_pass {
+            Some((first, _)) => {
+                tracing::info!("running the first of two passes");
+                spawn_ffmpeg(&ffmpeg_path, first, &ffmpeg_env)
+                    .and_then(|child| finish_ffmpeg(app, job, &input_path, &output_str, info.duration, Some(1), child))
+                    .map(Some)
+            }
+            None => Ok(None),
+        };
+        match first_pass {
+            // Nothing of the output is written yet, so there's no partial file to keep
+            Ok(Some(mut run)) if run.cancelled.is_some() || !run.status.success() => {
+                run.cancelled = run.cancelled.map(|_| CancelMode::Discard);
+                Ok(Some(run))
+            }
+            Err(e) => Err(e),
+            _ => spawn_ffmpeg(&ffmpeg_path, &args, &ffmpeg_env)
+                .and_then(|child| finish_ffmpeg(app, job, &input_path, &output_str, info.duration, two_pass.as_ref().map(|_| 2), child))
+                .map(Some),
+        }
+    };
+
+    if let Some((_, log)) = &two_pass {
+        twopass::remove_logs(log);
+    }
+
+    let (mut notes, log) = match &result {
+        Ok(Some(run)) => (run.notes.clone(), run.log.clone()),
+        _ => (Vec::new(), Vec::new()),
+    };
+    if strategy != CopyStrategy::Copy {
+        notes.push(ConversionNote {
+            kind: NoteKind::CopyFallback,
+            count: 1,
+            example: strategy.describe().to_string(),
+        });
+    }
+    if encodes_video && info.streams_of("video").any(|v| v.closed_captions) {
+        let example = if options.extract_captions {
+            "Re-encoding the video dropped its closed captions; they are saved to an SRT file next to it"
+        } else {
+            "Re-encoding the video dropped its closed captions; turn on caption extraction to keep them"
+        };
+        tracing::warn!("{}", example);
+        notes.push(ConversionNote { kind: NoteKind::CaptionsStripped, count: 1, example: example.to_string() });
+    }
+    let mut outcome = match result {
+        Ok(None) => Err(cancel::cancelled_error(None)),
+        Ok(Some(FfmpegRun { cancelled: Some(mode), .. })) => {
+            Err(finish_cancelled(mode, &output_path, split, options.filename_policy))
+        }
+        Ok(Some(run)) if run.status.success() => Ok(output_str),
+        Ok(Some(run)) => {
+            let error = format!("ffmpeg failed: {}", run.log.join("\n"));
+            if copy_failure(&error).is_some() {
+                // ffmpeg gave up before writing anything playable; a retry can reuse the name
+                for file in written_outputs(&output_path, split) {
+                    let _ = fs::remove_file(file);
+                }
+            }
+            Err(error)
+        }
+        Err(e) => Err(e),
+    };
+    for note in &notes {
+        tracing::warn!(kind = ?note.kind, count = note.count, example = %note.example, "ffmpeg warning");
+    }
+
+    if options.verify_audio {
+        if let Ok(output) = &outcome {
+            outcome = check_audio_integrity(&source_path, output).map(|_| output.clone());
+        }
+    }
+
+    if let Some(h264) = options.preset.as_ref().and_then(|p| p.h264.as_ref()) {
+        if let Ok(output) = &outcome {
+            outcome = h264.check_output(output).map(|_| output.clone());
+        }
+    }
+
+    if let Ok(output) = &outcome {
+        expectations::check(app, &input_path, info, output, options, split);
+    }
+
+    if options.extract_captions {
+        if let Ok(output) = &outcome {
+            match captions::extract_to_srt(&source_path, &captions::sidecar_path(output)) {
+                Ok(Some(srt)) => tracing::info!(srt = %srt.display(), "extracted closed captions"),
+                Ok(None) => tracing::info!("no closed captions to extract"),
+                Err(e) => tracing::warn!(error = %e, "caption extraction failed"),
+            }
+        }
+    }
+
+    if let Some(transcription) = options.transcribe.as_ref().filter(|t| !t.mux) {
+        if let Ok(output) = &outcome {
+            let srt = transcribe::sidecar_path(output, transcription.language.as_deref());
+            match transcribe::transcribe_to_srt(&source_path, &srt, transcription) {
+                Ok(srt) => tracing::info!(srt = %srt.display(), "wrote transcript"),
+                Err(e) => tracing::warn!(error = %e, "transcription failed"),
+            }
+        }
+    }
+
+    // Last, since the checks and sidecars above need the plain file
+    if let Some(Encryption::Age { recipients }) = &options.encrypt {
+        if let Ok(output) = &outcome {
+            outcome = encrypt::age_encrypt(output, recipients);
+        }
+    }
+
+    match &outcome {
+        Ok(output) => {
+            tracing::info!(output = %output, "conversion finished");
+            if prepared.explicit {
+                folders::remember(&input_path, options);
+            }
+            if options.share_preview {
+                share::share_output(app, &input_path, output);
+            }
+            plugins::run_stage(PluginStage::PostConvert, &input_path, Some(output));
+        }
+        Err(e) if cancel::is_cancelled(e) => tracing::info!(result = %e, "conversion cancelled"),
+        Err(e) => tracing::error!(error = %e, "conversion failed"),
+    }
+
+    // An age-encrypted output is only on disk under its .age name
+    let output_path = outcome.as_ref().map_or(output_path, PathBuf::from);
+    Ok(Attempt { outcome, output_path, notes, log, command })
+}
@@ -0,0 +1,222 @@
+use ring::digest::{Context, SHA256};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+use crate::cancel::{self, Job};
+use crate::convert::{self, ConvertOptions, Destination};
+use crate::probe::MediaInfo;
+use crate::{estimate, health, history, naming, volume};
+
+/// Emitted with a `DestinationStatus` as each destination of a multi-destination
+/// conversion starts copying and when it's done
+const DESTINATION_STATUS_EVENT: &str = "destination-status";
+
+const CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DestinationState {
+    Copying,
+    /// Copied and read back intact
+    Verified,
+    /// The output already exists there and the conflict policy leaves it alone
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DestinationStatus {
+    pub input_path: String,
+    pub folder: String,
+    pub state: DestinationState,
+    /// The output's path in this folder, once it's known
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// One folder an output goes to, and the name it gets there
+struct Target {
+    folder: PathBuf,
+    output: Result<Option<PathBuf>, String>,
+}
+
+fn emit(app: &AppHandle, input_path: &str, folder: &Path, state: DestinationState, output: Option<&Path>, error: Option<&str>) {
+    let status = DestinationStatus {
+        input_path: input_path.to_string(),
+        folder: folder.to_string_lossy().to_string(),
+        state,
+        output_path: output.map(|p| p.to_string_lossy().to_string()),
+        error: error.map(str::to_string),
+    };
+    let _ = app.emit(DESTINATION_STATUS_EVENT, &status);
+}
+
+/// Where the output of `input_path`, probed as `info`, goes in `folder`, or None when it's
+/// skipped there
+fn resolve(input_path: &str, info: &MediaInfo, folder: &Path, options: &ConvertOptions) -> Result<Option<PathBuf>, String> {
+    fs::create_dir_all(folder).map_err(|e| format!("Failed to create output folder {}: {}", folder.display(), e))?;
+    let volume = volume::check_volume(input_path, folder)?;
+    if volume.exceeds_limit {
+        return Err(format!(
+            "Output ({} MB) exceeds the 4 GB file size limit of the destination's {} filesystem",
+            volume.estimated_size / 1024 / 1024,
+            volume.filesystem.as_deref().unwrap_or("FAT"),
+        ));
+    }
+    let target = naming::conversion_target(input_path, Some(info), Some(folder), options);
+    naming::resolve_conflict(input_path, &target, options.on_conflict)
+}
+
+/// Copy `from` to `to` through a `.partial` file, hashing on the way, and only put it in
+/// place once reading the copy back gives the same hash
+fn copy_verified(from: &Path, to: &Path, job: &Job) -> Result<(), String> {
+    let partial = to.with_file_name(format!("{}.partial", to.file_name().unwrap_or_default().to_string_lossy()));
+    let result = (|| {
+        let mut source = File::open(from).map_err(|e| format!("Failed to open {}: {}", from.display(), e))?;
+        let mut target = File::create(&partial).map_err(|e| format!("Failed to create {}: {}", partial.display(), e))?;
+        let mut context = Context::new(&SHA256);
+        let mut buffer = vec![0u8; CHUNK_BYTES];
+        loop {
+            if job.requested().is_some() {
+                return Err(cancel::cancelled_error(None));
+            }
+            let n = source.read(&mut buffer).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?;
+            if n == 0 {
+                break;
+            }
+            context.update(&buffer[..n]);
+            target
+                .write_all(&buffer[..n])
+                .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        }
+        target.sync_all().map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        drop(target);
+        if health::hash_file(&partial, job)?.as_ref() != context.finish().as_ref() {
+            return Err(format!("The copy in {} doesn't match; it wasn't written intact", partial.display()));
+        }
+        fs::rename(&partial, to).map_err(|e| format!("Failed to move {} into place: {}", to.display(), e))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Copy everything the conversion wrote into `staging`, the output plus any sidecars, to
+/// `output`'s folder, renamed to match `output`. Returns where `staged_output` went.
+fn copy_outputs(staging: &Path, staged_stem: &str, staged_output: &Path, output: &Path, job: &Job) -> Result<PathBuf, String> {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let mut files: Vec<PathBuf> = fs::read_dir(staging)
+        .map_err(|e| format!("Failed to read {}: {}", staging.display(), e))?
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    files.sort();
+    // The sizes are known by now, so a folder that's too full fails before any copy starts
+    let needed: u64 = files.iter().filter_map(|f| fs::metadata(f).ok()).map(|m| m.len()).sum();
+    let folder = output.parent().unwrap_or_else(|| Path::new("."));
+    if let Some(free) = volume::free_space(folder).filter(|free| needed > *free) {
+        return Err(format!(
+            "{}: the output needs {} MB but {} has {} MB free",
+            estimate::NOT_ENOUGH_SPACE,
+            needed / 1024 / 1024,
+            folder.display(),
+            free / 1024 / 1024,
+        ));
+    }
+    let mut main = None;
+    for file in files {
+        let name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let suffix = name.strip_prefix(staged_stem).unwrap_or(&name);
+        let copy = output.with_file_name(format!("{}{}", stem, suffix));
+        copy_verified(&file, &copy, job)?;
+        if file == staged_output {
+            main = Some(copy);
+        }
+    }
+    main.ok_or_else(|| "The conversion wrote nothing to copy".to_string())
+}
+
+/// Convert `input_path`, probed as `info`, once into temp storage, then copy the output and
+/// its sidecars to each of the options' `destinations`, checking every copy. Returns the
+/// output's path in the first folder it reached; fails only if it reached none.
+pub fn convert_to_folders(
+    app: &AppHandle,
+    input_path: String,
+    info: MediaInfo,
+    options: Option<ConvertOptions>,
+    resolved: &ConvertOptions,
+) -> Result<String, String> {
+    let targets: Vec<Target> = resolved
+        .destinations
+        .iter()
+        .map(|folder| {
+            let folder = PathBuf::from(folder);
+            let output = resolve(&input_path, &info, &folder, resolved);
+            Target { folder, output }
+        })
+        .collect();
+    for target in &targets {
+        match &target.output {
+            Ok(Some(_)) => {}
+            Ok(None) => emit(app, &input_path, &target.folder, DestinationState::Skipped, None, None),
+            Err(e) => emit(app, &input_path, &target.folder, DestinationState::Failed, None, Some(e)),
+        }
+    }
+    let pending: Vec<(&Path, &Path)> = targets
+        .iter()
+        .filter_map(|t| Some((t.folder.as_path(), t.output.as_ref().ok()?.as_deref()?)))
+        .collect();
+    if pending.is_empty() {
+        return match targets.iter().find_map(|t| t.output.as_ref().err()) {
+            Some(e) => Err(e.clone()),
+            None => {
+                tracing::info!("output already exists in every destination, skipping");
+                Ok(naming::conversion_target(&input_path, Some(&info), Some(&targets[0].folder), resolved).to_string_lossy().to_string())
+            }
+        };
+    }
+
+    let staging = crate::unique_temp_path("destinations", "tmp");
+    fs::create_dir_all(&staging).map_err(|e| format!("Failed to create temp folder: {}", e))?;
+    let staged = naming::conversion_target(&input_path, Some(&info), Some(&staging), resolved);
+    let staged_stem = staged.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let result = convert::convert_probed(app, input_path.clone(), Some(info), options, Destination::File(staged));
+
+    let outcome = result.and_then(|staged_output| {
+        if !Path::new(&staged_output).starts_with(&staging) {
+            // Nothing was converted, e.g. a duplicate of an earlier conversion
+            return Ok(staged_output);
+        }
+        let job = Job::start(&input_path);
+        let mut first: Option<String> = None;
+        let mut errors = Vec::new();
+        for (folder, output) in &pending {
+            emit(app, &input_path, folder, DestinationState::Copying, Some(output), None);
+            match copy_outputs(&staging, &staged_stem, Path::new(&staged_output), output, &job) {
+                Ok(copy) => {
+                    tracing::info!(destination = %copy.display(), "copied output to destination");
+                    emit(app, &input_path, folder, DestinationState::Verified, Some(&copy), None);
+                    first.get_or_insert_with(|| copy.to_string_lossy().to_string());
+                }
+                Err(e) if cancel::is_cancelled(&e) => return Err(e),
+                Err(e) => {
+                    tracing::warn!(error = %e, folder = %folder.display(), "failed to copy output to destination");
+                    emit(app, &input_path, folder, DestinationState::Failed, Some(output), Some(&e));
+                    errors.push(e);
+                }
+            }
+        }
+        let first = first.ok_or_else(|| format!("Failed to copy the output to any destination: {}", errors.join("; ")))?;
+        history::relocate(&staged_output, &first);
+        Ok(first)
+    });
+
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        tracing::warn!(error = %e, staging = %staging.display(), "failed to remove staged output");
+    }
+    outcome
+}
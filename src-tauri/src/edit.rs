@@ -0,0 +1,148 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::language;
+use crate::probe::{self, MediaInfo};
+
+/// New tags for one stream; unset fields are left alone
+#[derive(Debug, Clone, Deserialize)]
+pub struct StreamTags {
+    pub index: u32,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    /// Set or clear the default flag
+    pub default: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Retag {
+    /// Container title
+    pub title: Option<String>,
+    pub streams: Vec<StreamTags>,
+}
+
+/// ffmpeg muxer for files we can rewrite losslessly, from the extension
+fn muxer(path: &Path) -> Result<&'static str, String> {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match ext.as_str() {
+        "mkv" => Ok("matroska"),
+        "mp4" | "m4v" => Ok("mp4"),
+        _ => Err(format!("Only MKV and MP4 files can be edited in place, not .{}", ext)),
+    }
+}
+
+fn check_index(info: &MediaInfo, index: u32) -> Result<(), String> {
+    if info.streams.iter().any(|s| s.index == index) {
+        Ok(())
+    } else {
+        Err(format!("No stream with index {}", index))
+    }
+}
+
+/// Rewrite `path` with every stream copied plus `args`, then swap the result in for the
+/// original. Nothing is re-encoded, so it runs at disk speed and attachments survive.
+fn rewrite(path: &str, args: &[String]) -> Result<String, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let source = PathBuf::from(path);
+    let muxer = muxer(&source)?;
+    // Same folder, so the final rename can't cross filesystems
+    let name = source.file_name().unwrap_or_default().to_string_lossy();
+    let temp = source.with_file_name(format!(".{}.editing", name));
+    let temp_str = temp.to_string_lossy().to_string();
+
+    let mut full_args: Vec<String> = ["-i", path, "-map", "0", "-codec", "copy", "-map_metadata", "0", "-map_chapters", "0"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    full_args.extend(args.iter().cloned());
+    full_args.extend(["-f".to_string(), muxer.to_string(), "-y".to_string(), temp_str.clone()]);
+    tracing::debug!(args = ?full_args, "ffmpeg arguments");
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(&full_args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(&full_args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&temp);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffmpeg failed: {}", stderr));
+    }
+
+    fs::rename(&temp, &source).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("Failed to replace {}: {}", path, e)
+    })?;
+    Ok(path.to_string())
+}
+
+/// Drop the given streams (by index) from an MKV or MP4, keeping everything else as is
+#[tauri::command(async)]
+pub fn remove_tracks_in_place(path: String, indexes: Vec<u32>) -> Result<String, String> {
+    if indexes.is_empty() {
+        return Err("No tracks selected to remove".to_string());
+    }
+    let info = probe::probe(&path)?;
+    for &index in &indexes {
+        check_index(&info, index)?;
+    }
+    if info.streams.iter().all(|s| indexes.contains(&s.index)) {
+        return Err("Can't remove every track".to_string());
+    }
+
+    tracing::info!(%path, ?indexes, "removing tracks");
+    let args: Vec<String> = indexes
+        .iter()
+        .flat_map(|index| ["-map".to_string(), format!("-0:{}", index)])
+        .collect();
+    rewrite(&path, &args)
+}
+
+/// Change the title, stream languages and titles, or default flags of an MKV or MP4
+/// without touching the streams themselves
+#[tauri::command(async)]
+pub fn retag_in_place(path: String, retag: Retag) -> Result<String, String> {
+    let info = probe::probe(&path)?;
+    let mut args = Vec::new();
+    if let Some(title) = &retag.title {
+        args.extend(["-metadata".to_string(), format!("title={}", title)]);
+    }
+    for tags in &retag.streams {
+        check_index(&info, tags.index)?;
+        // Every stream is mapped in order, so output indexes match the input's
+        if let Some(language) = &tags.language {
+            // Codes the language table doesn't know are still fine as long as they look like ISO 639-2
+            let code = language::normalize(language)
+                .map(str::to_string)
+                .or_else(|| {
+                    let code = language.trim().to_lowercase();
+                    (code.len() == 3 && code.chars().all(|c| c.is_ascii_lowercase())).then_some(code)
+                })
+                .ok_or_else(|| format!("Unknown language: {}", language))?;
+            args.extend([format!("-metadata:s:{}", tags.index), format!("language={}", code)]);
+        }
+        if let Some(title) = &tags.title {
+            args.extend([format!("-metadata:s:{}", tags.index), format!("title={}", title)]);
+        }
+        if let Some(default) = tags.default {
+            let disposition = if default { "default" } else { "0" };
+            args.extend([format!("-disposition:{}", tags.index), disposition.to_string()]);
+        }
+    }
+    if args.is_empty() {
+        return Err("Nothing to change".to_string());
+    }
+
+    tracing::info!(%path, "retagging");
+    rewrite(&path, &args)
+}
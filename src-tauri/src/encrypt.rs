@@ -0,0 +1,128 @@
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{keychain, transcribe};
+
+/// Common age locations to check on macOS
+#[cfg(target_os = "macos")]
+const AGE_PATHS: &[&str] = &["/opt/homebrew/bin/age", "/usr/local/bin/age"];
+
+/// Common age locations to check on Windows
+#[cfg(target_os = "windows")]
+const AGE_PATHS: &[&str] = &[
+    "age",  // In PATH
+    "C:\\Program Files\\age\\age.exe",
+];
+
+/// Common age locations to check on Linux
+#[cfg(target_os = "linux")]
+const AGE_PATHS: &[&str] = &["/usr/bin/age", "/usr/local/bin/age"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Encryption {
+    /// MP4 Common Encryption (cenc-aes-ctr). The key is kept in the OS keychain under
+    /// `key_name` and created on first use; players need it and its key id to decrypt.
+    Cenc { key_name: String },
+    /// Encrypt the finished file to `<name>.mp4.age` for these age public keys (age1...),
+    /// removing the plain copy. Needs the age tool.
+    Age { recipients: Vec<String> },
+}
+
+/// A CENC key and key id, as 32 hex digits each
+#[derive(Debug, Clone, Serialize)]
+pub struct CencKey {
+    pub kid: String,
+    pub key: String,
+}
+
+fn random_hex() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| "Failed to generate an encryption key".to_string())?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn keychain_account(key_name: &str) -> Result<String, String> {
+    let valid = !key_name.is_empty()
+        && key_name.len() <= 64
+        && key_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err("Key names may only use letters, digits, - and _".to_string());
+    }
+    Ok(format!("cenc-{}", key_name))
+}
+
+/// The key stored under `key_name`, creating and storing a new one if there isn't one yet
+pub fn cenc_key(key_name: &str) -> Result<CencKey, String> {
+    let account = keychain_account(key_name)?;
+    // Stored as "<kid>:<key>"
+    if let Some((kid, key)) = keychain::load_secret(&account).as_deref().and_then(|s| s.split_once(':')) {
+        return Ok(CencKey { kid: kid.to_string(), key: key.to_string() });
+    }
+
+    let key = CencKey { kid: random_hex()?, key: random_hex()? };
+    keychain::store_secret(&account, &format!("{}:{}", key.kid, key.key))?;
+    tracing::info!(key_name, kid = %key.kid, "created CENC key");
+    Ok(key)
+}
+
+/// mp4 muxer arguments that encrypt every stream with the named key
+pub fn cenc_args(key_name: &str) -> Result<Vec<String>, String> {
+    let key = cenc_key(key_name)?;
+    Ok(vec![
+        "-encryption_scheme".to_string(),
+        "cenc-aes-ctr".to_string(),
+        "-encryption_key".to_string(),
+        key.key,
+        "-encryption_kid".to_string(),
+        key.kid,
+    ])
+}
+
+/// ffmpeg arguments with the CENC key and key id blanked out, for logs and history
+pub fn redacted(args: &[String]) -> Vec<String> {
+    let mut out = args.to_vec();
+    for i in 1..out.len() {
+        if matches!(out[i - 1].as_str(), "-encryption_key" | "-encryption_kid") {
+            out[i] = "<redacted>".to_string();
+        }
+    }
+    out
+}
+
+fn find_age() -> Option<String> {
+    AGE_PATHS
+        .iter()
+        .find(|path| (PathBuf::from(path).exists() || **path == "age") && crate::tool_runs(path, "--version"))
+        .map(|path| path.to_string())
+}
+
+/// Encrypt `output_path` for `recipients` and delete the plain file, returning the .age path
+pub fn age_encrypt(output_path: &str, recipients: &[String]) -> Result<String, String> {
+    if recipients.is_empty() {
+        return Err("age encryption needs at least one recipient".to_string());
+    }
+    let age = find_age().ok_or("age not found; install it to encrypt outputs for recipients")?;
+    let encrypted = format!("{}.age", output_path);
+
+    let mut args: Vec<&str> = Vec::new();
+    for recipient in recipients {
+        args.extend(["-r", recipient.as_str()]);
+    }
+    args.extend(["-o", &encrypted, output_path]);
+    transcribe::run_tool(&age, &args, "age")?;
+
+    fs::remove_file(output_path).map_err(|e| format!("Failed to remove unencrypted output: {}", e))?;
+    tracing::info!(output = %encrypted, recipients = recipients.len(), "encrypted output");
+    Ok(encrypted)
+}
+
+/// The key and key id for a CENC key name, to hand to whoever plays the files
+#[tauri::command]
+pub fn get_cenc_key(key_name: String) -> Result<CencKey, String> {
+    cenc_key(&key_name)
+}
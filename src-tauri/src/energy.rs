@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::presets::Preset;
+use crate::{power, store};
+
+const ENERGY_FILE: &str = "energy.json";
+
+/// Which encoder a preset that asks for x264/x265 really gets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncoderPolicy {
+    /// The hardware encoder for long jobs on battery, the preset's own otherwise
+    #[default]
+    Auto,
+    /// The hardware encoder whenever there is one
+    Efficient,
+    /// Always the preset's own encoder
+    Quality,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergySettings {
+    pub policy: EncoderPolicy,
+    /// Encoding work left, this job and the rest of the queue, above which `Auto` switches
+    pub long_batch_minutes: u64,
+}
+
+impl Default for EnergySettings {
+    fn default() -> Self {
+        Self { policy: EncoderPolicy::Auto, long_batch_minutes: 30 }
+    }
+}
+
+/// Apple Silicon's media engine encodes H.264 and HEVC for a fraction of the power
+/// x264/x265 draw
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+fn efficient_encoder(encoder: &str) -> Option<&'static str> {
+    match encoder {
+        "libx264" => Some("h264_videotoolbox"),
+        "libx265" => Some("hevc_videotoolbox"),
+        _ => None,
+    }
+}
+
+/// Elsewhere hardware encoders aren't reliably more efficient, so nothing is swapped
+#[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
+fn efficient_encoder(_encoder: &str) -> Option<&'static str> {
+    None
+}
+
+fn load() -> EnergySettings {
+    store::load(ENERGY_FILE)
+}
+
+/// The preset to actually encode with under the energy policy. `work_secs` is roughly how
+/// long the encoding left will take. Quality-critical presets and ones with H.264 device
+/// limits always keep their encoder.
+pub fn adapt(preset: &Preset, work_secs: f64) -> Preset {
+    let Some(encoder) = efficient_encoder(&preset.video_codec) else {
+        return preset.clone();
+    };
+    if preset.quality_critical || preset.h264.is_some() {
+        return preset.clone();
+    }
+    let settings = load();
+    let swap = match settings.policy {
+        EncoderPolicy::Quality => false,
+        EncoderPolicy::Efficient => true,
+        EncoderPolicy::Auto => work_secs >= (settings.long_batch_minutes * 60) as f64 && power::on_battery(),
+    };
+    if !swap {
+        return preset.clone();
+    }
+
+    tracing::info!(from = %preset.video_codec, to = encoder, policy = ?settings.policy, "using the energy-efficient encoder");
+    let mut adapted = preset.clone();
+    adapted.video_codec = encoder.to_string();
+    // VideoToolbox has no CRF or speed presets; -q:v is its constant quality, higher is better
+    if let Some(crf) = adapted.crf.take() {
+        let quality = (100 - i32::from(crf) * 2).clamp(1, 100);
+        adapted.extra_args.extend(["-q:v".to_string(), quality.to_string()]);
+    }
+    adapted.encoder_preset = None;
+    adapted
+}
+
+#[tauri::command]
+pub fn get_energy_settings() -> EnergySettings {
+    load()
+}
+
+#[tauri::command]
+pub fn set_energy_settings(settings: EnergySettings) -> Result<(), String> {
+    store::save(ENERGY_FILE, &settings)
+}
@@ -0,0 +1,219 @@
+use serde::Serialize;
+use std::path::Path;
+use tauri::{AppHandle, Emitter};
+
+use crate::convert::ConvertOptions;
+use crate::presets::{self, Preset};
+use crate::probe::MediaInfo;
+use crate::{naming, planner, probe, volume};
+
+/// Emitted with a `SpaceShortfall` when a conversion's output looks too big for its volume
+const DISK_SPACE_EVENT: &str = "disk-space-low";
+
+/// Errors from conversions refused for lack of space start with this
+pub const NOT_ENOUGH_SPACE: &str = "Not enough disk space";
+
+/// MP4 headers and sample tables on top of the streams themselves
+const CONTAINER_OVERHEAD: f64 = 1.01;
+
+/// Audio bitrate encoders fall back to when a preset doesn't set one
+const DEFAULT_AUDIO_KBPS: u32 = 128;
+
+/// Bits per pixel per frame H.264 spends at its default CRF on typical film and TV
+const H264_BITS_PER_PIXEL: f64 = 0.08;
+
+const DEFAULT_FRAME_RATE: f64 = 24.0;
+
+/// How the estimate was made, roughly from most to least reliable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateBasis {
+    /// Streams are copied, so the output is about the input's size
+    Remux,
+    /// Duration times the preset's target bitrates
+    Bitrate,
+    /// A quality-targeted encode, guessed from the frame size and CRF. Content swings this
+    /// a lot: grainy film can come out at twice the estimate, animation at half.
+    Quality,
+}
+
+/// Predicted size of one conversion
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeEstimate {
+    pub estimated_size: u64,
+    pub basis: EstimateBasis,
+    pub video_kbps: Option<u32>,
+    pub audio_kbps: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputEstimate {
+    pub estimated_size: u64,
+    pub basis: EstimateBasis,
+    pub video_kbps: Option<u32>,
+    pub audio_kbps: Option<u32>,
+    /// Where the output will go: the output folder setting, or next to the input
+    pub output_directory: String,
+    pub free_space: Option<u64>,
+    pub max_file_size: Option<u64>,
+    /// Whether the estimate is within the free space; None when that's unknown. Outputs
+    /// over `max_file_size` are split rather than failing.
+    pub fits: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpaceShortfall {
+    pub input_path: String,
+    pub output_directory: String,
+    pub estimated_size: u64,
+    pub free_space: u64,
+    pub basis: EstimateBasis,
+    /// The conversion was stopped; otherwise it went ahead, since the estimate was only a
+    /// rough guess
+    pub refused: bool,
+}
+
+/// (default CRF, size relative to H.264 at the same quality) for each encoder family.
+/// Hardware encoders don't take a CRF, so only the second one applies to them.
+fn encoder_profile(codec: &str) -> (u8, f64) {
+    match codec {
+        "libx265" => (28, 0.6),
+        "libsvtav1" => (35, 0.5),
+        "libvpx-vp9" => (32, 0.65),
+        c if c.starts_with("hevc_") => (28, 0.7),
+        _ => (23, 1.0),
+    }
+}
+
+/// Video kbps a quality-targeted encode tends to land on. Each 6 CRF halves or doubles it.
+fn quality_video_kbps(preset: &Preset, width: u32, height: u32, frame_rate: f64) -> u32 {
+    let (default_crf, efficiency) = encoder_profile(&preset.video_codec);
+    let crf = preset.crf.unwrap_or(default_crf);
+    let (width, height) = preset.output_size(width, height);
+    let bits = f64::from(width * height) * frame_rate * H264_BITS_PER_PIXEL * efficiency;
+    let scaled = bits * 2f64.powf((f64::from(default_crf) - f64::from(crf)) / 6.0);
+    (scaled / 1000.0) as u32
+}
+
+/// A stream's bitrate, measured from its size when the file doesn't say
+fn stream_kbps(stream: &probe::StreamInfo, duration: f64) -> Option<u32> {
+    let bits = stream
+        .bit_rate
+        .or_else(|| stream.size_bytes.map(|bytes| (bytes as f64 * 8.0 / duration) as u64))?;
+    Some((bits / 1000) as u32)
+}
+
+/// Estimate the size of converting `path` with `preset`, or of remuxing it without one.
+/// `info` is the probed source, when the caller already has it.
+pub fn estimate(path: &str, info: Option<&MediaInfo>, preset: Option<&Preset>) -> Result<SizeEstimate, String> {
+    let input_size = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read input file: {}", e))?
+        .len();
+    let Some(preset) = preset.filter(|p| p.video_codec != "copy" || p.audio_codec != "copy") else {
+        // A remux is about the same size as its source
+        return Ok(SizeEstimate { estimated_size: input_size, basis: EstimateBasis::Remux, video_kbps: None, audio_kbps: None });
+    };
+
+    let probed;
+    let info = match info {
+        Some(info) => info,
+        None => {
+            probed = probe::probe(path)?;
+            &probed
+        }
+    };
+    let duration = info
+        .duration
+        .filter(|d| *d > 0.0)
+        .ok_or("Can't estimate the size: the file's duration is unknown")?;
+    let (video_kbps, basis) = match planner::primary_video(info) {
+        None => (0, EstimateBasis::Bitrate),
+        Some(v) if preset.video_codec == "copy" => (stream_kbps(v, duration).unwrap_or_default(), EstimateBasis::Bitrate),
+        Some(v) => match preset.video_bitrate_kbps {
+            Some(kbps) => (kbps, EstimateBasis::Bitrate),
+            None => {
+                let (width, height) = (v.width.unwrap_or(1920), v.height.unwrap_or(1080));
+                let frame_rate = v.frame_rate.filter(|r| *r > 0.0).unwrap_or(DEFAULT_FRAME_RATE);
+                (quality_video_kbps(preset, width, height, frame_rate), EstimateBasis::Quality)
+            }
+        },
+    };
+    let audio_kbps: u32 = info
+        .streams_of("audio")
+        .map(|a| match preset.audio_codec.as_str() {
+            "copy" => stream_kbps(a, duration).unwrap_or(DEFAULT_AUDIO_KBPS),
+            _ => preset.audio_bitrate_kbps.unwrap_or(DEFAULT_AUDIO_KBPS),
+        })
+        .sum();
+    let bytes = f64::from(video_kbps + audio_kbps) * 1000.0 / 8.0 * duration * CONTAINER_OVERHEAD;
+    Ok(SizeEstimate { estimated_size: bytes as u64, basis, video_kbps: Some(video_kbps), audio_kbps: Some(audio_kbps) })
+}
+
+/// Estimate the size of converting `path` with the preset named `preset`, and whether it
+/// will fit where it's going
+#[tauri::command(async)]
+pub fn estimate_output(path: String, preset: String) -> Result<OutputEstimate, String> {
+    let preset = presets::find_preset(&preset).ok_or_else(|| format!("No preset named {}", preset))?;
+    let estimate = estimate(&path, None, Some(&preset))?;
+
+    let output_dir = naming::output_directory()
+        .unwrap_or_else(|| Path::new(&path).parent().unwrap_or_else(|| Path::new(".")).to_path_buf());
+    let free_space = volume::free_space(&output_dir);
+    let max_file_size = volume::filesystem_type(&output_dir).as_deref().and_then(volume::max_file_size);
+    let fits = free_space.map(|free| estimate.estimated_size <= free);
+
+    Ok(OutputEstimate {
+        estimated_size: estimate.estimated_size,
+        basis: estimate.basis,
+        video_kbps: estimate.video_kbps,
+        audio_kbps: estimate.audio_kbps,
+        output_directory: output_dir.to_string_lossy().to_string(),
+        free_space,
+        max_file_size,
+        fits,
+    })
+}
+
+/// Before converting `input_path`, probed as `info`, into `output_dir`, check the volume has
+/// room for the output, rather than letting ffmpeg fail partway through with a write error.
+/// Remuxes and fixed-bitrate encodes are refused when they won't fit; quality-targeted
+/// encodes, whose size can't be known up front, only warn.
+pub fn check_free_space(
+    app: &AppHandle,
+    input_path: &str,
+    info: &MediaInfo,
+    options: &ConvertOptions,
+    output_dir: &Path,
+) -> Result<(), String> {
+    let Some(free_space) = volume::free_space(output_dir) else {
+        return Ok(());
+    };
+    let estimate = estimate(input_path, Some(info), options.preset.as_ref())?;
+    if estimate.estimated_size <= free_space {
+        return Ok(());
+    }
+
+    // Without a preset the estimate assumes a remux, so re-encodes of any other kind are guesses too
+    let refused = estimate.basis != EstimateBasis::Quality
+        && (options.preset.is_some() || !options.reencodes_video(info));
+    let shortfall = SpaceShortfall {
+        input_path: input_path.to_string(),
+        output_directory: output_dir.to_string_lossy().to_string(),
+        estimated_size: estimate.estimated_size,
+        free_space,
+        basis: estimate.basis,
+        refused,
+    };
+    let _ = app.emit(DISK_SPACE_EVENT, &shortfall);
+    if !refused {
+        tracing::warn!(estimated_size = estimate.estimated_size, free_space, "output may not fit on the destination");
+        return Ok(());
+    }
+    Err(format!(
+        "{}: the output needs about {} MB but {} has {} MB free",
+        NOT_ENOUGH_SPACE,
+        estimate.estimated_size / 1024 / 1024,
+        output_dir.display(),
+        free_space / 1024 / 1024,
+    ))
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::cancel::{self, RunningConversion};
+use crate::queue;
+
+/// Emitted with an `ExitCheck` when closing the app was held back because it would cut off
+/// a conversion, so the frontend can ask what to do
+const EXIT_BLOCKED_EVENT: &str = "exit-blocked";
+
+/// How often a requested exit checks whether the conversions it's waiting for are done
+const EXIT_POLL_MS: u64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitMode {
+    /// Let the running conversions finish, start no more queued ones, then quit
+    AfterCurrent,
+    /// Cancel the running conversions, deleting their partial output, and quit. Queued ones
+    /// convert again from the start at the next launch.
+    Now,
+}
+
+/// What quitting now would cut off
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitCheck {
+    pub can_exit: bool,
+    /// Conversions that would stop partway through
+    pub running: Vec<RunningConversion>,
+    /// Items waiting in the queue; they're saved, so they carry on at the next launch
+    pub queued: usize,
+    /// The exit already asked for with `request_exit`, if any
+    pub pending_exit: Option<ExitMode>,
+}
+
+static PENDING: Mutex<Option<ExitMode>> = Mutex::new(None);
+
+/// Set just before a requested exit goes through, so the veto lets it pass
+static EXITING: AtomicBool = AtomicBool::new(false);
+
+fn pending() -> Option<ExitMode> {
+    *PENDING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An exit was requested, so the queue shouldn't start anything new
+pub fn finishing() -> bool {
+    pending().is_some()
+}
+
+/// Conversions are being cancelled to quit; they stay queued instead of counting as cancelled
+pub fn exiting_now() -> bool {
+    pending() == Some(ExitMode::Now)
+}
+
+fn check() -> ExitCheck {
+    let running = cancel::get_running_conversions();
+    ExitCheck {
+        can_exit: running.is_empty() && !queue::worker_busy(),
+        running,
+        queued: queue::pending_items(),
+        pending_exit: pending(),
+    }
+}
+
+/// Whether a close or quit should be held back. Called from the window and app event
+/// handlers; tells the frontend why when it is.
+pub fn veto(app: &AppHandle) -> bool {
+    if EXITING.load(Ordering::SeqCst) {
+        return false;
+    }
+    let check = check();
+    if check.can_exit {
+        return false;
+    }
+    tracing::info!(running = check.running.len(), queued = check.queued, "held back exit while converting");
+    let _ = app.emit(EXIT_BLOCKED_EVENT, &check);
+    true
+}
+
+/// Quit once nothing is converting, unless the exit is withdrawn first
+fn exit_when_idle(app: AppHandle) {
+    loop {
+        if pending().is_none() {
+            return;
+        }
+        if check().can_exit {
+            break;
+        }
+        thread::sleep(Duration::from_millis(EXIT_POLL_MS));
+    }
+    tracing::info!("conversions done, exiting");
+    EXITING.store(true, Ordering::SeqCst);
+    app.exit(0);
+}
+
+/// What would be lost by quitting now
+#[tauri::command]
+pub fn can_exit() -> ExitCheck {
+    check()
+}
+
+/// Quit without abandoning a conversion halfway: after the running ones finish, or after
+/// cancelling them cleanly
+#[tauri::command]
+pub fn request_exit(app: AppHandle, mode: ExitMode) {
+    let previous = PENDING.lock().unwrap_or_else(|e| e.into_inner()).replace(mode);
+    tracing::info!(?mode, "exit requested");
+    if mode == ExitMode::Now {
+        for job in cancel::get_running_conversions() {
+            let _ = cancel::cancel_conversion(job.id, Some(false));
+        }
+    }
+    if previous.is_none() {
+        thread::spawn(move || exit_when_idle(app));
+    }
+}
+
+/// Withdraw a `request_exit` that's still waiting, and carry on with the queue
+#[tauri::command]
+pub fn cancel_exit(app: AppHandle) -> Result<(), String> {
+    if PENDING.lock().unwrap_or_else(|e| e.into_inner()).take().is_none() {
+        return Err("No exit is pending".to_string());
+    }
+    tracing::info!("exit withdrawn");
+    queue::resume(&app);
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::convert::ConvertOptions;
+use crate::{guardrails, planner};
+use crate::probe::{self, Chapter, MediaInfo};
+
+/// Emitted when a finished output doesn't look like what the conversion asked for
+const PLAN_MISMATCH_EVENT: &str = "plan-mismatch";
+
+/// Chapter starts may move this much, e.g. from being rounded to the MP4's timescale
+const CHAPTER_TOLERANCE_SECS: f64 = 0.5;
+
+/// Output duration may drift from the source by this much, or 1%, whichever is more
+const DURATION_TOLERANCE_SECS: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanMismatch {
+    pub input_path: String,
+    pub output_path: String,
+    pub problems: Vec<String>,
+}
+
+/// What the audio of the output should look like
+enum AudioExpectation {
+    /// Exactly these codecs, one track each, in order
+    Tracks(Vec<String>),
+    /// At least `min` tracks, each with one of these codecs
+    Each { codecs: Vec<String>, min: usize },
+}
+
+/// What a conversion is expected to produce, worked out before looking at the output
+struct Expectations {
+    /// Codec of the primary video stream, when it's predictable
+    video_codec: Option<String>,
+    video_size: Option<(u32, u32)>,
+    has_video: bool,
+    audio: AudioExpectation,
+    duration: Option<f64>,
+    /// The source's chapters, unless they're replaced
+    chapters: Option<Vec<Chapter>>,
+}
+
+/// Codec an ffmpeg encoder produces, e.g. "libx264" or "h264_nvenc" to "h264"
+pub fn encoder_codec(encoder: &str) -> Option<String> {
+    let codec = match encoder {
+        "libx264" => "h264",
+        "libx265" => "hevc",
+        "libsvtav1" | "libaom-av1" => "av1",
+        "libopus" => "opus",
+        "libmp3lame" => "mp3",
+        "libfdk_aac" => "aac",
+        hw if hw.contains('_') => hw.split('_').next()?,
+        other => other,
+    };
+    Some(codec.to_string())
+}
+
+fn expectations(source: &MediaInfo, options: &ConvertOptions, split: bool) -> Result<Expectations, String> {
+    let video = planner::primary_video(source);
+    let source_audio: Vec<String> = source.streams_of("audio").map(|s| s.codec.clone()).collect();
+    let copied_size = video.and_then(|v| Some((v.width?, v.height?)));
+    let plan = options
+        .follows_plan()
+        .then(|| {
+            planner::build_plan(
+                source,
+                None,
+                options.audio_streams.as_deref(),
+                options.audio_fallback.as_ref(),
+                options.subtitles,
+                &options.include_video,
+            )
+        })
+        .transpose()?;
+
+    let (mut video_codec, mut video_size) = if options.social_format.is_some() {
+        (Some("h264".to_string()), None)
+    } else if let Some(preset) = &options.preset {
+        match preset.video_codec.as_str() {
+            "copy" => (video.map(|v| v.codec.clone()), copied_size),
+            encoder => (encoder_codec(encoder), None),
+        }
+    } else if options.burn_subtitles.is_some() {
+        (Some("h264".to_string()), copied_size)
+    } else if let Some(plan) = &plan {
+        let planned = plan.streams.iter().find(|s| Some(s.index) == video.map(|v| v.index));
+        (planned.map(|s| s.target_codec.clone().unwrap_or_else(|| s.codec.clone())), copied_size)
+    } else {
+        (video.map(|v| v.codec.clone()), copied_size)
+    };
+
+    if let Some(guardrails) = guardrails::applying(options, source) {
+        if !options.reencodes_video(source) {
+            video_codec = Some("h264".to_string());
+        }
+        if guardrails.too_tall(source) {
+            video_size = None;
+        }
+    }
+
+    let source_audio: Vec<String> = match &options.audio_streams {
+        Some(keep) => source.streams_of("audio").filter(|s| keep.contains(&s.index)).map(|s| s.codec.clone()).collect(),
+        None => source_audio,
+    };
+    let min_audio = usize::from(!source_audio.is_empty());
+    let tracks = match (&options.audio_tracks, options.compatibility_audio) {
+        (Some(tracks), _) => Some(tracks.clone()),
+        (None, true) => Some(planner::compatibility_tracks(source)?),
+        (None, false) => None,
+    };
+    let audio = if let Some(tracks) = tracks {
+        let plan = planner::build_plan(source, Some(&tracks), None, None, options.subtitles, &options.include_video)?;
+        AudioExpectation::Tracks(plan.audio_outputs.into_iter().map(|o| o.codec).collect())
+    } else if options.social_format.is_some() {
+        AudioExpectation::Each { codecs: vec!["aac".to_string()], min: min_audio }
+    } else if let Some(preset) = options.preset.as_ref().filter(|p| p.audio_codec != "copy") {
+        let codecs = encoder_codec(&preset.audio_codec).into_iter().collect();
+        AudioExpectation::Each { codecs, min: min_audio }
+    } else if let Some(target) = &options.audio {
+        AudioExpectation::Each { codecs: vec![target.codec.codec_name().to_string()], min: min_audio }
+    } else if let Some(plan) = plan {
+        AudioExpectation::Tracks(plan.audio_outputs.into_iter().map(|o| o.codec).collect())
+    } else {
+        AudioExpectation::Each { codecs: source_audio, min: min_audio }
+    };
+
+    Ok(Expectations {
+        video_codec,
+        video_size,
+        has_video: video.is_some(),
+        audio,
+        // A split output is checked one part at a time, so only the whole would match
+        duration: source.duration.filter(|_| !split),
+        chapters: Some(source.chapters.clone()).filter(|_| !split && options.auto_chapters.is_none()),
+    })
+}
+
+fn compare(expected: &Expectations, output: &MediaInfo) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let video = planner::primary_video(output);
+    match video {
+        None if expected.has_video => problems.push("the video stream is missing".to_string()),
+        None => {}
+        Some(video) => {
+            if let Some(codec) = expected.video_codec.as_ref().filter(|c| **c != video.codec) {
+                problems.push(format!("video is {} instead of {}", video.codec, codec));
+            }
+            let size = video.width.zip(video.height);
+            if let Some((width, height)) = expected.video_size.filter(|s| size.is_some_and(|o| o != *s)) {
+                let (out_width, out_height) = size.unwrap_or_default();
+                problems.push(format!("video is {}x{} instead of {}x{}", out_width, out_height, width, height));
+            }
+        }
+    }
+
+    let audio: Vec<&str> = output.streams_of("audio").map(|s| s.codec.as_str()).collect();
+    match &expected.audio {
+        AudioExpectation::Tracks(codecs) => {
+            if audio.len() != codecs.len() {
+                problems.push(format!("{} audio tracks instead of {}", audio.len(), codecs.len()));
+            }
+            for (i, (planned, actual)) in codecs.iter().zip(&audio).enumerate() {
+                if planned != actual {
+                    problems.push(format!("audio track {} is {} instead of {}", i + 1, actual, planned));
+                }
+            }
+        }
+        AudioExpectation::Each { codecs, min } => {
+            if audio.len() < *min {
+                problems.push("the audio was dropped".to_string());
+            }
+            for (i, actual) in audio.iter().enumerate() {
+                if !codecs.is_empty() && !codecs.iter().any(|c| c == actual) {
+                    problems.push(format!("audio track {} is {} instead of {}", i + 1, actual, codecs.join(" or ")));
+                }
+            }
+        }
+    }
+
+    if let (Some(planned), Some(actual)) = (expected.duration, output.duration) {
+        if (planned - actual).abs() > DURATION_TOLERANCE_SECS.max(planned * 0.01) {
+            problems.push(format!("output runs {:.1}s instead of {:.1}s", actual, planned));
+        }
+    }
+
+    if let Some(chapters) = &expected.chapters {
+        if output.chapters.len() != chapters.len() {
+            problems.push(format!("{} chapters instead of {}", output.chapters.len(), chapters.len()));
+        }
+        for (i, (planned, actual)) in chapters.iter().zip(&output.chapters).enumerate() {
+            if (planned.start - actual.start).abs() > CHAPTER_TOLERANCE_SECS {
+                problems.push(format!("chapter {} starts at {:.1}s instead of {:.1}s", i + 1, actual.start, planned.start));
+            }
+            if planned.title.is_some() && actual.title != planned.title {
+                let title = actual.title.as_deref().unwrap_or("untitled");
+                problems.push(format!("chapter {} is {} instead of {}", i + 1, title, planned.title.as_deref().unwrap_or_default()));
+            }
+        }
+    }
+    problems
+}
+
+/// Compare a finished output with what the options asked for and warn the UI about any
+/// difference, such as a stream ffmpeg silently dropped. Never fails the conversion.
+pub fn check(
+    app: &AppHandle,
+    input_path: &str,
+    source: &MediaInfo,
+    output_path: &str,
+    options: &ConvertOptions,
+    split: bool,
+) {
+    let problems = expectations(source, options, split)
+        .and_then(|expected| Ok(compare(&expected, &probe::probe(output_path)?)));
+    match problems {
+        Ok(problems) if problems.is_empty() => {}
+        Ok(problems) => {
+            tracing::warn!(?problems, output = output_path, "output doesn't match the plan");
+            let mismatch = PlanMismatch {
+                input_path: input_path.to_string(),
+                output_path: output_path.to_string(),
+                problems,
+            };
+            let _ = app.emit(PLAN_MISMATCH_EVENT, &mismatch);
+        }
+        Err(e) => tracing::warn!(error = %e, "couldn't compare the output with the plan"),
+    }
+}
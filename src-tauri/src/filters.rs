@@ -0,0 +1,120 @@
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::{scan, store};
+
+/// Filters set per scanned or watched folder, keyed by the folder's path
+const SCAN_FILTERS_FILE: &str = "scan_filters.json";
+
+/// Serializes read-modify-write cycles on the filters file
+static FILTERS_LOCK: Mutex<()> = Mutex::new(());
+
+const MATCH: MatchOptions = MatchOptions { case_sensitive: false, require_literal_separator: false, require_literal_leading_dot: false };
+
+/// Which files in a folder scans and watches pick up, e.g. to leave out "*sample*" clips,
+/// an Extras folder or anything under 50 MB
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanFilter {
+    /// Only files matching one of these are picked up; all are when empty. Matched against
+    /// the path under the folder, with `/` between names and ignoring case, so "*" also
+    /// crosses into subfolders.
+    pub include: Vec<String>,
+    /// Files matching any of these are left out, e.g. "*sample*" or "*/Extras/*"
+    pub exclude: Vec<String>,
+    pub min_bytes: Option<u64>,
+    pub max_bytes: Option<u64>,
+    /// Extensions to pick up instead of the usual video ones, e.g. ["mkv"] or ["vob"]
+    pub extensions: Vec<String>,
+}
+
+impl ScanFilter {
+    fn validate(&self) -> Result<(), String> {
+        for pattern in self.include.iter().chain(&self.exclude) {
+            Pattern::new(pattern).map_err(|e| format!("Invalid pattern {}: {}", pattern, e))?;
+        }
+        if let (Some(min), Some(max)) = (self.min_bytes, self.max_bytes) {
+            if min > max {
+                return Err("The minimum size is above the maximum".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `path` has one of the extensions this filter picks up
+    pub fn is_candidate(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            return scan::is_video(path);
+        }
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        self.extensions
+            .iter()
+            .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&extension))
+    }
+
+    /// Why a candidate under `root` is left out by its name, if it is
+    pub fn name_rejection(&self, root: &Path, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let matches = |pattern: &String| Pattern::new(pattern).is_ok_and(|p| p.matches_with(&relative, MATCH));
+        if let Some(pattern) = self.exclude.iter().find(|p| matches(p)) {
+            return Some(format!("excluded by {}", pattern));
+        }
+        if !self.include.is_empty() && !self.include.iter().any(matches) {
+            return Some("not matched by any include pattern".to_string());
+        }
+        None
+    }
+
+    /// Why a finished file is left out by its size, if it is. Checked once a file is
+    /// complete, since one still being written only grows.
+    pub fn size_rejection(&self, path: &Path) -> Option<String> {
+        let size = fs::metadata(path).ok()?.len();
+        match (self.min_bytes, self.max_bytes) {
+            (Some(min), _) if size < min => Some(format!("smaller than {} MB", min / 1024 / 1024)),
+            (_, Some(max)) if size > max => Some(format!("larger than {} MB", max / 1024 / 1024)),
+            _ => None,
+        }
+    }
+}
+
+fn folder_key(dir: &Path) -> String {
+    dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()).to_string_lossy().to_string()
+}
+
+/// The filter set for `dir`; one that picks up every video when none is
+pub fn for_folder(dir: &Path) -> ScanFilter {
+    let _guard = FILTERS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut filters: BTreeMap<String, ScanFilter> = store::load_profile(SCAN_FILTERS_FILE);
+    filters.remove(&folder_key(dir)).unwrap_or_default()
+}
+
+#[tauri::command]
+pub fn get_scan_filter(path: String) -> ScanFilter {
+    for_folder(Path::new(&path))
+}
+
+/// Choose which files scans, enqueues, syncs and watches of the folder at `path` pick up;
+/// no filter picks up every video again
+#[tauri::command]
+pub fn set_scan_filter(path: String, filter: Option<ScanFilter>) -> Result<(), String> {
+    if let Some(filter) = &filter {
+        filter.validate()?;
+    }
+    let key = folder_key(Path::new(&path));
+    let _guard = FILTERS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut filters: BTreeMap<String, ScanFilter> = store::load_profile(SCAN_FILTERS_FILE);
+    match filter.filter(|f| *f != ScanFilter::default()) {
+        Some(filter) => filters.insert(key, filter),
+        None => filters.remove(&key),
+    };
+    store::save_profile(SCAN_FILTERS_FILE, &filters)
+}
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::convert::ConvertOptions;
+use crate::store;
+
+/// Last options used per source folder, keyed by the folder's path
+const FOLDER_OPTIONS_FILE: &str = "folder_options.json";
+
+/// Serializes read-modify-write cycles on the folder options file
+static FOLDER_LOCK: Mutex<()> = Mutex::new(());
+
+fn folder_key(input_path: &str) -> Option<String> {
+    let parent = Path::new(input_path).parent()?;
+    let parent = parent.canonicalize().unwrap_or_else(|_| parent.to_path_buf());
+    Some(parent.to_string_lossy().to_string())
+}
+
+/// Folder-wide settings last used for a file in the same folder as `input_path`. Filtered
+/// again here, since entries saved by older versions kept every option.
+pub fn recall(input_path: &str) -> Option<ConvertOptions> {
+    let key = folder_key(input_path)?;
+    let _guard = FOLDER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut folders: BTreeMap<String, ConvertOptions> = store::load_profile(FOLDER_OPTIONS_FILE);
+    folders.remove(&key).as_ref().map(folder_settings)
+}
+
+/// The settings of `options` that suit any file in a folder: how to convert, not what to
+/// pick out of one file or where to send it. Anything not listed here, such as stream and
+/// track indices, programs, metadata overrides, destinations, encryption, sharing and
+/// confirmed guardrail overrides, is left at its default, including fields added later.
+fn folder_settings(options: &ConvertOptions) -> ConvertOptions {
+    let options = options.clone();
+    ConvertOptions {
+        filename_policy: options.filename_policy,
+        on_conflict: options.on_conflict,
+        split_oversize: options.split_oversize,
+        verify_audio: options.verify_audio,
+        preserve_timecode: options.preserve_timecode,
+        extract_captions: options.extract_captions,
+        audio_language: options.audio_language,
+        skip_duplicates: options.skip_duplicates,
+        preset: options.preset,
+        preset_name: options.preset_name,
+        two_pass: options.two_pass,
+        quality: options.quality,
+        audio: options.audio,
+        compatibility_audio: options.compatibility_audio,
+        fragmented: options.fragmented,
+        audio_fallback: options.audio_fallback,
+        subtitles: options.subtitles,
+        check_source_read: options.check_source_read,
+        local_copy: options.local_copy,
+        copy_fallback: options.copy_fallback,
+        audio_only: options.audio_only,
+        ..ConvertOptions::default()
+    }
+}
+
+/// Remember the folder-wide settings used for `input_path` for the next file from its folder
+pub fn remember(input_path: &str, options: &ConvertOptions) {
+    let Some(key) = folder_key(input_path) else {
+        return;
+    };
+    let options = folder_settings(options);
+
+    let _guard = FOLDER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut folders: BTreeMap<String, ConvertOptions> = store::load_profile(FOLDER_OPTIONS_FILE);
+    folders.insert(key, options);
+    if let Err(e) = store::save_profile(FOLDER_OPTIONS_FILE, &folders) {
+        tracing::warn!(error = %e, "failed to save folder options");
+    }
+}
+
+/// Options to pre-fill when a file is added, from the last conversion in its folder
+#[tauri::command]
+pub fn get_folder_options(path: String) -> Option<ConvertOptions> {
+    recall(&path)
+}
+
+/// Forget the remembered options for the folder containing `path`
+#[tauri::command]
+pub fn forget_folder_options(path: String) -> Result<(), String> {
+    let Some(key) = folder_key(&path) else {
+        return Ok(());
+    };
+
+    let _guard = FOLDER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut folders: BTreeMap<String, ConvertOptions> = store::load_profile(FOLDER_OPTIONS_FILE);
+    if folders.remove(&key).is_some() {
+        store::save_profile(FOLDER_OPTIONS_FILE, &folders)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::history;
+
+/// Font file types libass can load
+const FONT_EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc"];
+
+/// Written once a source's attachments have been extracted, so later jobs reuse them
+const EXTRACTED_MARKER: &str = ".extracted";
+
+/// Fonts for burning one source's subtitles
+pub struct FontSetup {
+    /// The source's embedded fonts, passed to the subtitles filter as `fontsdir`
+    pub fonts_dir: PathBuf,
+    /// Environment for the ffmpeg process: a fontconfig setup that also sees the user's fonts
+    pub env: Vec<(String, String)>,
+}
+
+fn fonts_root() -> Result<PathBuf, String> {
+    let root = crate::app_data_dir()?.join("fonts");
+    fs::create_dir_all(root.join("user")).map_err(|e| format!("Failed to create fonts directory: {}", e))?;
+    Ok(root)
+}
+
+fn is_font(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| FONT_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// Dump the source's attachments into `dir` and keep only the fonts
+fn extract_attachments(input_path: &str, dir: &Path) -> Result<(), String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create fonts directory: {}", e))?;
+    let input = Path::new(input_path)
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", input_path, e))?;
+    let input = input.to_string_lossy();
+
+    // Attachments are written under their own names into the working directory. ffmpeg still
+    // wants an output, so decode nothing into the null muxer.
+    let args = ["-y", "-dump_attachment:t", "", "-i", &input, "-t", "0", "-f", "null", "-"];
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .current_dir(dir)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .current_dir(dir)
+        .output();
+
+    // Sources without attachments make ffmpeg complain, which isn't an error here
+    result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read fonts directory: {}", e))?;
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_file() && !is_font(&path) {
+            let _ = fs::remove_file(path);
+        }
+    }
+    fs::write(dir.join(EXTRACTED_MARKER), "").map_err(|e| format!("Failed to write fonts marker: {}", e))
+}
+
+/// fontconfig setup listing the system fonts, the user's fonts and the source's embedded fonts
+fn write_fonts_conf(root: &Path, attachments: &Path) -> Result<PathBuf, String> {
+    let dir = |p: &Path| format!("  <dir>{}</dir>\n", xml_escape(&p.to_string_lossy()));
+    let mut conf = String::from("<?xml version=\"1.0\"?>\n<!DOCTYPE fontconfig SYSTEM \"fonts.dtd\">\n<fontconfig>\n");
+    #[cfg(not(target_os = "windows"))]
+    conf.push_str("  <include ignore_missing=\"yes\">/etc/fonts/fonts.conf</include>\n");
+    #[cfg(target_os = "windows")]
+    conf.push_str("  <dir>WINDOWSFONTDIR</dir>\n");
+    conf.push_str(&dir(&root.join("user")));
+    conf.push_str(&dir(attachments));
+    // Shared across jobs, so fonts are only scanned once
+    conf.push_str(&format!("  <cachedir>{}</cachedir>\n", xml_escape(&root.join("cache").to_string_lossy())));
+    conf.push_str("</fontconfig>\n");
+
+    let path = attachments.join("fonts.conf");
+    fs::write(&path, conf).map_err(|e| format!("Failed to write fonts.conf: {}", e))?;
+    Ok(path)
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Make the source's embedded fonts and the user's fonts available to libass. Attachments are
+/// extracted once per source content and reused by later jobs.
+pub fn prepare(input_path: &str) -> Result<FontSetup, String> {
+    let root = fonts_root()?;
+    let fingerprint = history::fingerprint(Path::new(input_path))?;
+    let attachments = root.join("attachments").join(&fingerprint[..16]);
+    if !attachments.join(EXTRACTED_MARKER).exists() {
+        extract_attachments(input_path, &attachments)?;
+    }
+    let conf = write_fonts_conf(&root, &attachments)?;
+
+    tracing::info!(fonts_dir = %attachments.display(), "prepared subtitle fonts");
+    Ok(FontSetup {
+        fonts_dir: attachments,
+        env: vec![("FONTCONFIG_FILE".to_string(), conf.to_string_lossy().to_string())],
+    })
+}
+
+/// Folder for fonts the user adds; they're available when burning any subtitles
+#[tauri::command]
+pub fn get_user_fonts_dir() -> Result<String, String> {
+    Ok(fonts_root()?.join("user").to_string_lossy().to_string())
+}
+
+/// Copy a font file into the user fonts folder
+#[tauri::command]
+pub fn add_user_font(path: String) -> Result<String, String> {
+    let source = Path::new(&path);
+    if !is_font(source) {
+        return Err(format!("Not a TrueType or OpenType font: {}", path));
+    }
+    let name = source.file_name().ok_or("Font path has no file name")?;
+    let target = fonts_root()?.join("user").join(name);
+    fs::copy(source, &target).map_err(|e| format!("Failed to copy font: {}", e))?;
+    Ok(target.to_string_lossy().to_string())
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::convert::ConvertOptions;
+use crate::presets::{self, EncodeQuality};
+use crate::probe::{self, MediaInfo};
+use crate::store;
+
+const GUARDRAILS_FILE: &str = "guardrails.json";
+
+/// Limits every conversion's video is kept within, whatever preset it uses, for storage
+/// or upload caps. A job can only go above them with `ConvertOptions::exceed_guardrails`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Guardrails {
+    /// Frame height, e.g. 1080
+    pub max_height: Option<u32>,
+    pub max_video_kbps: Option<u32>,
+}
+
+impl Guardrails {
+    pub fn is_set(&self) -> bool {
+        self.max_height.is_some() || self.max_video_kbps.is_some()
+    }
+
+    fn video_kbps(info: &MediaInfo) -> Option<u64> {
+        let video = info.streams_of("video").next()?;
+        // Matroska rarely has per-stream bitrates; the container's is a close upper bound
+        video.bit_rate.or(info.bit_rate).map(|b| b / 1000)
+    }
+
+    /// The source is taller than the guardrails allow, so it has to be scaled down
+    pub fn too_tall(&self, info: &MediaInfo) -> bool {
+        let height = info.streams_of("video").next().and_then(|v| v.height);
+        matches!((height, self.max_height), (Some(h), Some(max)) if h > max)
+    }
+
+    /// Ways the source's video is above the guardrails; empty when it's within them
+    pub fn violations(&self, info: &MediaInfo) -> Vec<String> {
+        let mut violations = Vec::new();
+        if self.too_tall(info) {
+            let height = info.streams_of("video").next().and_then(|v| v.height).unwrap_or_default();
+            violations.push(format!("{}p is above the {}p limit", height, self.max_height.unwrap_or_default()));
+        }
+        if let (Some(kbps), Some(max)) = (Self::video_kbps(info), self.max_video_kbps) {
+            if kbps > u64::from(max) {
+                violations.push(format!("{:.1} Mbps is above the {:.1} Mbps limit", kbps as f64 / 1000.0, max as f64 / 1000.0));
+            }
+        }
+        violations
+    }
+
+    /// ffmpeg arguments that keep the video within the guardrails, added after everything
+    /// else so they win over a preset's own scaling and rate. `reencoded` says whether the
+    /// video is already being encoded; copied video has to be encoded to be limited, as
+    /// `quality` says.
+    pub fn apply(&self, info: &MediaInfo, reencoded: bool, quality: Option<&EncodeQuality>, args: &mut Vec<String>) {
+        if !reencoded {
+            args.extend(["-c:v".to_string(), "libx264".to_string()]);
+            args.extend(presets::x264_rate_args(quality, 18, None));
+            args.extend(["-pix_fmt".to_string(), "yuv420p".to_string()]);
+        }
+        if let Some(max) = self.max_height.filter(|_| self.too_tall(info)) {
+            let scale = format!("scale=-2:'min(ih,{})'", max);
+            // Only the last -vf counts, so chain onto one a preset or burned subtitles added
+            match args.iter().rposition(|a| a == "-vf") {
+                Some(i) if i + 1 < args.len() => args[i + 1] = format!("{},{}", args[i + 1], scale),
+                _ => args.extend(["-vf".to_string(), scale]),
+            }
+        }
+        if let Some(kbps) = self.max_video_kbps {
+            args.extend([
+                "-maxrate:v".to_string(),
+                format!("{}k", kbps),
+                "-bufsize:v".to_string(),
+                format!("{}k", kbps * 2),
+            ]);
+        }
+    }
+}
+
+pub fn load() -> Guardrails {
+    store::load_profile(GUARDRAILS_FILE)
+}
+
+/// The guardrails a conversion of `info` has to be kept within, if any. Re-encodes are
+/// always held to them, since a preset can raise the bitrate; copies only when the source
+/// is above them. None when the job was confirmed to go above them.
+pub fn applying(options: &ConvertOptions, info: &MediaInfo) -> Option<Guardrails> {
+    // Social framing always makes 1080p H.264
+    if options.exceed_guardrails || options.social_format.is_some() {
+        return None;
+    }
+    let guardrails = load();
+    let needed = guardrails.is_set() && (options.reencodes_video(info) || !guardrails.violations(info).is_empty());
+    needed.then_some(guardrails)
+}
+
+#[tauri::command]
+pub fn get_guardrails() -> Guardrails {
+    load()
+}
+
+#[tauri::command]
+pub fn set_guardrails(guardrails: Guardrails) -> Result<(), String> {
+    if guardrails.max_height == Some(0) || guardrails.max_video_kbps == Some(0) {
+        return Err("Guardrail limits must be above zero".to_string());
+    }
+    store::save_profile(GUARDRAILS_FILE, &guardrails)
+}
+
+/// How a file is above the guardrails, so the user can confirm before a job goes above them
+#[tauri::command(async)]
+pub fn check_guardrails(path: String) -> Result<Vec<String>, String> {
+    Ok(load().violations(&probe::probe(&path)?))
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::probe;
+
+/// Limits from the H.264 spec's level table (Annex A, Table A-1)
+struct Level {
+    name: &'static str,
+    /// Decoded picture buffer size in macroblocks; caps the number of reference frames
+    max_dpb_mbs: u32,
+    max_frame_mbs: u32,
+    /// Baseline/Main figures; High profile allows 1.25x
+    max_kbps: u32,
+    max_cpb_kbits: u32,
+}
+
+const LEVELS: &[Level] = &[
+    Level { name: "3.0", max_dpb_mbs: 8100, max_frame_mbs: 1620, max_kbps: 10_000, max_cpb_kbits: 10_000 },
+    Level { name: "3.1", max_dpb_mbs: 18_000, max_frame_mbs: 3600, max_kbps: 14_000, max_cpb_kbits: 14_000 },
+    Level { name: "3.2", max_dpb_mbs: 20_480, max_frame_mbs: 5120, max_kbps: 20_000, max_cpb_kbits: 20_000 },
+    Level { name: "4.0", max_dpb_mbs: 32_768, max_frame_mbs: 8192, max_kbps: 20_000, max_cpb_kbits: 25_000 },
+    Level { name: "4.1", max_dpb_mbs: 32_768, max_frame_mbs: 8192, max_kbps: 50_000, max_cpb_kbits: 62_500 },
+    Level { name: "4.2", max_dpb_mbs: 34_816, max_frame_mbs: 8704, max_kbps: 50_000, max_cpb_kbits: 62_500 },
+    Level { name: "5.0", max_dpb_mbs: 110_400, max_frame_mbs: 22_080, max_kbps: 135_000, max_cpb_kbits: 135_000 },
+    Level { name: "5.1", max_dpb_mbs: 184_320, max_frame_mbs: 36_864, max_kbps: 240_000, max_cpb_kbits: 240_000 },
+    Level { name: "5.2", max_dpb_mbs: 184_320, max_frame_mbs: 36_864, max_kbps: 240_000, max_cpb_kbits: 240_000 },
+];
+
+const PROFILES: &[&str] = &["baseline", "main", "high"];
+
+/// Profile and level a device's decoder is certified for, e.g. High@4.1 for most older TVs.
+/// Output is also kept at 8-bit 4:2:0, the only format those profiles cover.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct H264Constraints {
+    /// "baseline", "main" or "high"
+    pub profile: String,
+    /// "3.0" through "5.2"
+    pub level: String,
+}
+
+fn macroblocks(width: u32, height: u32) -> u32 {
+    width.div_ceil(16) * height.div_ceil(16)
+}
+
+impl H264Constraints {
+    fn level(&self) -> Result<&'static Level, String> {
+        LEVELS
+            .iter()
+            .find(|l| l.name == self.level)
+            .ok_or_else(|| format!("Unknown H.264 level: {}", self.level))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !PROFILES.contains(&self.profile.as_str()) {
+            return Err(format!("Unknown H.264 profile: {}", self.profile));
+        }
+        self.level().map(|_| ())
+    }
+
+    /// Most reference frames the level's DPB holds at this frame size
+    fn max_refs(&self, width: u32, height: u32) -> Result<u32, String> {
+        Ok((self.level()?.max_dpb_mbs / macroblocks(width, height)).clamp(1, 16))
+    }
+
+    /// Encoder arguments that keep a `width`x`height` output inside the profile and level
+    pub fn args(&self, width: u32, height: u32) -> Result<Vec<String>, String> {
+        let level = self.level()?;
+        if macroblocks(width, height) > level.max_frame_mbs {
+            return Err(format!("{}x{} is too large for H.264 level {}", width, height, level.name));
+        }
+        let scale = if self.profile == "high" { 5 } else { 4 };
+        Ok(vec![
+            "-profile:v".to_string(),
+            self.profile.clone(),
+            "-level:v".to_string(),
+            level.name.to_string(),
+            "-refs".to_string(),
+            self.max_refs(width, height)?.to_string(),
+            "-pix_fmt".to_string(),
+            "yuv420p".to_string(),
+            // Without VBV limits a complex scene can exceed the level's bitrate
+            "-maxrate".to_string(),
+            format!("{}k", level.max_kbps * scale / 4),
+            "-bufsize".to_string(),
+            format!("{}k", level.max_cpb_kbits * scale / 4),
+        ])
+    }
+
+    /// Confirm the encoder really produced a stream within these limits
+    pub fn check_output(&self, output_path: &str) -> Result<(), String> {
+        let info = probe::probe(output_path)?;
+        let video = info
+            .streams_of("video")
+            .find(|s| s.codec == "h264")
+            .ok_or("Output has no H.264 video stream")?;
+
+        let mut problems = Vec::new();
+        let profile = video.profile.as_deref().unwrap_or("unknown").to_lowercase();
+        // ffprobe reports e.g. "Constrained Baseline" or "High"
+        if !profile.ends_with(&self.profile) {
+            problems.push(format!("profile is {}, expected {}", profile, self.profile));
+        }
+        let expected_level = (self.level.parse::<f64>().unwrap_or(0.0) * 10.0).round() as i32;
+        if let Some(level) = video.level.filter(|&l| l > expected_level) {
+            problems.push(format!("level is {:.1}, expected at most {}", level as f64 / 10.0, self.level));
+        }
+        if let (Some(refs), Some(width), Some(height)) = (video.refs, video.width, video.height) {
+            let max = self.max_refs(width, height)?;
+            if refs > max {
+                problems.push(format!("{} reference frames, level allows {}", refs, max));
+            }
+        }
+        if let Some(pix_fmt) = video.pix_fmt.as_deref().filter(|p| *p != "yuv420p" && *p != "yuvj420p") {
+            problems.push(format!("pixel format is {}, expected 8-bit yuv420p", pix_fmt));
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        tracing::warn!(?problems, "output is outside the H.264 device limits");
+        Err(format!(
+            "Output doesn't meet H.264 {}@{}, kept at {}: {}",
+            self.profile,
+            self.level,
+            output_path,
+            problems.join("; ")
+        ))
+    }
+}
@@ -0,0 +1,204 @@
+use ring::digest::{Context, Digest, SHA256};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel::{self, Job};
+
+/// Bytes read from each end of the source
+const SAMPLE_BYTES: u64 = 100 * 1024 * 1024;
+
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Sources smaller than this convert faster than a failing drive would be noticed, so
+/// conversions don't check them
+pub const LARGE_SOURCE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Slower than a USB 2 stick (about 25 MB/s); healthy hard drives read well over 80 MB/s
+const SLOW_BYTES_PER_SEC: f64 = 15.0 * 1024.0 * 1024.0;
+
+/// Times a chunk of the source is read again before a local copy gives up on it
+const COPY_READ_RETRIES: u32 = 3;
+
+/// How reading the start and end of a source went
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadCheck {
+    pub path: String,
+    pub bytes_read: u64,
+    pub seconds: f64,
+    pub bytes_per_sec: f64,
+    /// Read errors, each with the offset it happened at
+    pub errors: Vec<String>,
+    pub slow: bool,
+    /// What to tell the user, when something looks wrong
+    pub warning: Option<String>,
+}
+
+/// Read `len` bytes from `offset`, returning how many were read. A read error stops the
+/// region, since a bad sector usually takes its neighbours with it.
+fn read_region(file: &mut File, offset: u64, len: u64, errors: &mut Vec<String>) -> u64 {
+    if let Err(e) = file.seek(SeekFrom::Start(offset)) {
+        errors.push(format!("seek to {}: {}", offset, e));
+        return 0;
+    }
+    let mut buffer = vec![0u8; CHUNK_BYTES];
+    let mut read = 0;
+    while read < len {
+        let want = (len - read).min(CHUNK_BYTES as u64) as usize;
+        match file.read(&mut buffer[..want]) {
+            Ok(0) => break,
+            Ok(n) => read += n as u64,
+            Err(e) => {
+                errors.push(format!("read at {}: {}", offset + read, e));
+                break;
+            }
+        }
+    }
+    read
+}
+
+/// Time reading the first and last 100 MB of a file. Slow or failing reads suggest a dying
+/// drive that would fail hours into a long job. Recently read files come from the OS cache
+/// and always look fast.
+pub fn check_read(path: &str) -> Result<ReadCheck, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let size = file.metadata().map_err(|e| format!("Failed to read file size: {}", e))?.len();
+
+    let mut errors = Vec::new();
+    let started = Instant::now();
+    let head = SAMPLE_BYTES.min(size);
+    let mut bytes_read = read_region(&mut file, 0, head, &mut errors);
+    // Small files are covered by the first read
+    let tail_start = size.saturating_sub(SAMPLE_BYTES).max(head);
+    bytes_read += read_region(&mut file, tail_start, size - tail_start, &mut errors);
+    let seconds = started.elapsed().as_secs_f64();
+
+    let bytes_per_sec = bytes_read as f64 / seconds.max(f64::EPSILON);
+    // Too little to time reliably
+    let slow = bytes_read >= SAMPLE_BYTES && bytes_per_sec < SLOW_BYTES_PER_SEC;
+    let warning = if !errors.is_empty() {
+        Some(format!(
+            "The source couldn't be read cleanly ({}); the drive may be failing, so copy the file somewhere else before converting",
+            errors.join("; ")
+        ))
+    } else if slow {
+        Some(format!(
+            "The source reads at only {:.1} MB/s, which can mean a failing drive; a long conversion may fail partway through",
+            bytes_per_sec / 1024.0 / 1024.0
+        ))
+    } else {
+        None
+    };
+
+    tracing::info!(path, bytes_read, seconds, errors = errors.len(), slow, "checked source reads");
+    Ok(ReadCheck {
+        path: path.to_string(),
+        bytes_read,
+        seconds,
+        bytes_per_sec,
+        errors,
+        slow,
+        warning,
+    })
+}
+
+/// Benchmark reads from the start and end of a source before a long job
+#[tauri::command(async)]
+pub fn check_source_read(path: String) -> Result<ReadCheck, String> {
+    check_read(&path)
+}
+
+/// A copy of a source in local temp storage, deleted when dropped so every way out of a
+/// conversion cleans it up
+pub struct LocalCopy {
+    pub path: PathBuf,
+}
+
+impl Drop for LocalCopy {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            tracing::warn!(error = %e, path = %self.path.display(), "failed to remove local copy");
+        }
+    }
+}
+
+/// Read a chunk at `offset`, reopening the file between attempts since a drive that dropped
+/// off the bus or a share that reconnected leaves the old handle dead
+fn read_retrying(file: &mut File, path: &str, offset: u64, buffer: &mut [u8]) -> Result<usize, String> {
+    let mut attempt = 0;
+    loop {
+        match file.read(buffer) {
+            Ok(n) => return Ok(n),
+            Err(e) if attempt < COPY_READ_RETRIES => {
+                attempt += 1;
+                tracing::warn!(error = %e, offset, attempt, "source read failed, retrying");
+                thread::sleep(Duration::from_secs(u64::from(attempt)));
+                if let Ok(mut reopened) = File::open(path) {
+                    if reopened.seek(SeekFrom::Start(offset)).is_ok() {
+                        *file = reopened;
+                    }
+                }
+            }
+            Err(e) => return Err(format!("Failed to read {} at {}: {}", path, offset, e)),
+        }
+    }
+}
+
+pub fn hash_file(path: &Path, job: &Job) -> Result<Digest, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut context = Context::new(&SHA256);
+    let mut buffer = vec![0u8; CHUNK_BYTES];
+    loop {
+        if job.requested().is_some() {
+            return Err(cancel::cancelled_error(None));
+        }
+        let n = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            return Ok(context.finish());
+        }
+        context.update(&buffer[..n]);
+    }
+}
+
+/// Copy `path` to local temp storage, hashing it on the way, then hash the copy and make
+/// sure the two match. Conversions then read the copy, so a flaky USB drive or network
+/// share only has to hold up for one sequential read instead of the whole job.
+pub fn local_copy(path: &str, job: &Job) -> Result<LocalCopy, String> {
+    let extension = Path::new(path).extension().unwrap_or_default().to_string_lossy();
+    let copy = LocalCopy { path: crate::unique_temp_path("source", &extension) };
+    let started = Instant::now();
+
+    let mut source = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut target = File::create(&copy.path).map_err(|e| format!("Failed to create local copy: {}", e))?;
+    let mut context = Context::new(&SHA256);
+    let mut buffer = vec![0u8; CHUNK_BYTES];
+    let mut copied = 0u64;
+    loop {
+        if job.requested().is_some() {
+            return Err(cancel::cancelled_error(None));
+        }
+        let n = read_retrying(&mut source, path, copied, &mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        context.update(&buffer[..n]);
+        target
+            .write_all(&buffer[..n])
+            .map_err(|e| format!("Failed to write local copy: {}", e))?;
+        copied += n as u64;
+    }
+    target.sync_all().map_err(|e| format!("Failed to write local copy: {}", e))?;
+    drop(target);
+
+    let expected = context.finish();
+    if hash_file(&copy.path, job)?.as_ref() != expected.as_ref() {
+        return Err(format!("The local copy of {} doesn't match the source; it wasn't read back intact", path));
+    }
+    tracing::info!(path, copy = %copy.path.display(), bytes = copied, seconds = started.elapsed().as_secs_f64(), "made verified local copy");
+    Ok(copy)
+}
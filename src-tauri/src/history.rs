@@ -0,0 +1,221 @@
+use ring::digest::{Context, SHA256};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::cancel;
+use crate::notes::ConversionNote;
+use crate::store;
+
+const HISTORY_FILE: &str = "history.json";
+
+/// Oldest entries are dropped past this, so the file stays quick to load
+const MAX_HISTORY_ENTRIES: usize = 10_000;
+
+/// Bytes hashed from each end of a file for its fingerprint
+const FINGERPRINT_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// Serializes read-modify-write cycles on the history file
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConversionResult {
+    /// Entries from before failures were recorded are all successes
+    #[default]
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub input_path: String,
+    /// Where the output went, or would have gone for a failed conversion
+    pub output_path: String,
+    /// See `fingerprint`; None if the source couldn't be read
+    pub fingerprint: Option<String>,
+    /// RFC 3339
+    pub converted_at: String,
+    #[serde(default)]
+    pub result: ConversionResult,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// How long the conversion took
+    #[serde(default)]
+    pub elapsed_secs: Option<f64>,
+    #[serde(default)]
+    pub input_bytes: Option<u64>,
+    /// The first part's size for a split output
+    #[serde(default)]
+    pub output_bytes: Option<u64>,
+    /// Warnings ffmpeg printed while converting, such as rewritten timestamps
+    #[serde(default)]
+    pub notes: Vec<ConversionNote>,
+    /// The end of what ffmpeg printed, kept for conversions that didn't succeed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log: Vec<String>,
+    /// The ffmpeg command line, kept alongside `log`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+}
+
+/// A file whose content was already converted, possibly under another name
+#[derive(Debug, Clone, Serialize)]
+pub struct Duplicate {
+    pub path: String,
+    pub previous: HistoryEntry,
+}
+
+/// Identify a file by content rather than name: SHA-256 over its size and the first and
+/// last few MB. Cheap on huge files and unchanged by renames or moves.
+pub fn fingerprint(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let size = file.metadata().map_err(|e| format!("Failed to read file size: {}", e))?.len();
+
+    let mut context = Context::new(&SHA256);
+    context.update(&size.to_le_bytes());
+    let mut chunk = Vec::new();
+    (&mut file)
+        .take(FINGERPRINT_CHUNK)
+        .read_to_end(&mut chunk)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    context.update(&chunk);
+    if size > FINGERPRINT_CHUNK * 2 {
+        chunk.clear();
+        file.seek(SeekFrom::End(-(FINGERPRINT_CHUNK as i64)))
+            .and_then(|_| file.read_to_end(&mut chunk))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        context.update(&chunk);
+    }
+
+    Ok(context.finish().as_ref().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn load() -> Vec<HistoryEntry> {
+    store::load_profile(HISTORY_FILE)
+}
+
+/// Record how a conversion to `output_path` ended
+pub fn record(
+    input_path: &str,
+    output_path: &str,
+    outcome: &Result<String, String>,
+    elapsed_secs: f64,
+    notes: Vec<ConversionNote>,
+    log: Vec<String>,
+    command: Vec<String>,
+) {
+    let (result, error) = match outcome {
+        Ok(_) => (ConversionResult::Succeeded, None),
+        Err(e) if cancel::is_cancelled(e) => (ConversionResult::Cancelled, None),
+        Err(e) => (ConversionResult::Failed, Some(e.clone())),
+    };
+    // The output path can change after ffmpeg, e.g. to an encrypted .age file
+    let output_path = outcome.as_deref().unwrap_or(output_path);
+    // Successful runs would fill the history file with logs nobody reads
+    let failed = result == ConversionResult::Failed;
+    // Hash outside the lock, it reads from the source
+    let fingerprint = fingerprint(Path::new(input_path)).ok();
+    let entry = HistoryEntry {
+        input_path: input_path.to_string(),
+        output_path: output_path.to_string(),
+        fingerprint,
+        converted_at: chrono::Local::now().to_rfc3339(),
+        result,
+        error,
+        elapsed_secs: Some(elapsed_secs),
+        input_bytes: fs::metadata(input_path).ok().map(|m| m.len()),
+        output_bytes: fs::metadata(output_path).ok().map(|m| m.len()).filter(|_| result == ConversionResult::Succeeded),
+        notes,
+        log: if failed { log } else { Vec::new() },
+        command: if failed { command } else { Vec::new() },
+    };
+
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history = load();
+    history.push(entry);
+    if history.len() > MAX_HISTORY_ENTRIES {
+        history.drain(..history.len() - MAX_HISTORY_ENTRIES);
+    }
+    if let Err(e) = store::save_profile(HISTORY_FILE, &history) {
+        tracing::warn!(error = %e, "failed to save conversion history");
+    }
+}
+
+/// Point the latest entry written to `from` at `to`, for outputs moved after converting
+pub fn relocate(from: &str, to: &str) {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history = load();
+    let Some(entry) = history.iter_mut().rev().find(|e| e.output_path == from) else {
+        return;
+    };
+    entry.output_path = to.to_string();
+    if let Err(e) = store::save_profile(HISTORY_FILE, &history) {
+        tracing::warn!(error = %e, "failed to save conversion history");
+    }
+}
+
+/// The latest conversion of a file with the same content as `path` whose output still exists
+pub fn previous_conversion(path: &Path) -> Option<HistoryEntry> {
+    let fingerprint = fingerprint(path).ok()?;
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load()
+        .into_iter()
+        .rev()
+        .find(|e| {
+            e.result == ConversionResult::Succeeded
+                && e.fingerprint.as_deref() == Some(fingerprint.as_str())
+                && Path::new(&e.output_path).exists()
+        })
+}
+
+/// Past conversions, newest first: what was converted, how it went and where it went
+#[tauri::command(async)]
+pub fn get_history(limit: Option<usize>) -> Vec<HistoryEntry> {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let history = load();
+    history.into_iter().rev().take(limit.unwrap_or(usize::MAX)).collect()
+}
+
+/// Forget every past conversion, which also stops them counting as duplicates
+#[tauri::command]
+pub fn clear_history() -> Result<(), String> {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    store::save_profile(HISTORY_FILE, &Vec::<HistoryEntry>::new())
+}
+
+/// The warnings ffmpeg printed during the latest conversion from or to `path`, for
+/// looking into an output that converted but looks wrong
+#[tauri::command(async)]
+pub fn get_conversion_notes(path: String) -> Option<HistoryEntry> {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load()
+        .into_iter()
+        .rev()
+        .find(|e| e.output_path == path || e.input_path == path)
+}
+
+/// The latest failed conversion, of `input_path` when given
+pub fn latest_failure(input_path: Option<&str>) -> Option<HistoryEntry> {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load()
+        .into_iter()
+        .rev()
+        .find(|e| e.result == ConversionResult::Failed && input_path.is_none_or(|p| e.input_path == p))
+}
+
+/// Flag files that were already converted, even if they've been renamed or moved since
+#[tauri::command(async)]
+pub fn find_duplicates(paths: Vec<String>) -> Vec<Duplicate> {
+    paths
+        .into_iter()
+        .filter(|p| fs::metadata(p).is_ok_and(|m| m.is_file()))
+        .filter_map(|path| {
+            let previous = previous_conversion(Path::new(&path))?;
+            Some(Duplicate { path, previous })
+        })
+        .collect()
+}
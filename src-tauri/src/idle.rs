@@ -0,0 +1,127 @@
+#[cfg(not(target_os = "windows"))]
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel::{self, Job};
+
+/// How often to re-check while waiting for the user to step away
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often to check for a cancel in between
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Seconds since the last keyboard/mouse input, or None if it can't be determined
+#[cfg(target_os = "macos")]
+pub fn idle_seconds() -> Option<u64> {
+    // IOHIDSystem reports HIDIdleTime in nanoseconds
+    let output = Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .ok()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .find(|line| line.contains("\"HIDIdleTime\""))
+        .and_then(|line| line.split('=').nth(1))
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(|nanos| nanos / 1_000_000_000)
+}
+
+/// Seconds since the last keyboard/mouse input, or None if it can't be determined
+#[cfg(target_os = "windows")]
+pub fn idle_seconds() -> Option<u64> {
+    #[repr(C)]
+    struct LastInputInfo {
+        cb_size: u32,
+        dw_time: u32,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetLastInputInfo(plii: *mut LastInputInfo) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    let mut info = LastInputInfo {
+        cb_size: std::mem::size_of::<LastInputInfo>() as u32,
+        dw_time: 0,
+    };
+
+    // Both values are milliseconds since boot and wrap together after ~49 days
+    let (ok, now) = unsafe { (GetLastInputInfo(&mut info), GetTickCount()) };
+    if ok == 0 {
+        return None;
+    }
+
+    Some(u64::from(now.wrapping_sub(info.dw_time)) / 1000)
+}
+
+/// Seconds since the last keyboard/mouse input, or None if it can't be determined
+#[cfg(target_os = "linux")]
+pub fn idle_seconds() -> Option<u64> {
+    // X11 sessions: xprintidle prints milliseconds
+    if let Ok(output) = Command::new("xprintidle").output() {
+        if output.status.success() {
+            if let Ok(ms) = String::from_utf8_lossy(&output.stdout).trim().parse::<u64>() {
+                return Some(ms / 1000);
+            }
+        }
+    }
+
+    // GNOME (including Wayland): Mutter's idle monitor, prints "(uint64 12345,)"
+    let output = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest", "org.gnome.Mutter.IdleMonitor",
+            "--object-path", "/org/gnome/Mutter/IdleMonitor/Core",
+            "--method", "org.gnome.Mutter.IdleMonitor.GetIdletime",
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .trim()
+        .trim_start_matches("(uint64 ")
+        .trim_end_matches(",)")
+        .parse::<u64>()
+        .ok()
+        .map(|ms| ms / 1000)
+}
+
+/// Whether the user has been idle for at least `threshold_secs`.
+/// Treated as idle when idle time can't be determined, so jobs are never held forever.
+pub fn is_user_idle(threshold_secs: u64) -> bool {
+    idle_seconds().is_none_or(|secs| secs >= threshold_secs)
+}
+
+/// Block until the user has been idle for at least `threshold_secs`, or `job` is cancelled
+pub fn wait_for_idle(threshold_secs: u64, job: &Job) -> Result<(), String> {
+    while !is_user_idle(threshold_secs) {
+        let checked = Instant::now();
+        while checked.elapsed() < IDLE_POLL_INTERVAL {
+            if job.requested().is_some() {
+                return Err(cancel::cancelled_error(None));
+            }
+            thread::sleep(CANCEL_POLL_INTERVAL);
+        }
+    }
+    Ok(())
+}
+
+/// Get seconds since the last user input (None if unsupported on this system)
+#[tauri::command]
+pub fn get_idle_seconds() -> Option<u64> {
+    idle_seconds()
+}
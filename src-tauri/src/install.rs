@@ -0,0 +1,521 @@
+use serde::Serialize;
+use std::io::Read;
+use std::path::Path;
+#[cfg(not(target_os = "windows"))]
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::suspend;
+
+/// Returned by an install that was cancelled part way
+const INSTALL_CANCELLED: &str = "ffmpeg install cancelled";
+
+/// Emitted with the `InstallStatus` whenever the install makes progress
+const INSTALL_PROGRESS_EVENT: &str = "install-progress";
+
+/// Package managers that take longer than this are assumed to be stuck
+const INSTALL_TIMEOUT_SECS: u64 = 20 * 60;
+
+/// Static builds to download when no package manager can install ffmpeg. evermeet.cx
+/// serves ffmpeg and ffprobe separately; the others bundle both.
+#[cfg(target_os = "macos")]
+const DOWNLOADS: &[(&str, &str)] = &[
+    ("ffmpeg", "https://evermeet.cx/ffmpeg/getrelease/ffmpeg/zip"),
+    ("ffprobe", "https://evermeet.cx/ffmpeg/getrelease/ffprobe/zip"),
+];
+
+#[cfg(target_os = "windows")]
+const DOWNLOADS: &[(&str, &str)] = &[("ffmpeg", "https://www.gyan.dev/ffmpeg/builds/ffmpeg-release-essentials.zip")];
+
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+const DOWNLOADS: &[(&str, &str)] = &[("ffmpeg", "https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-arm64-static.tar.xz")];
+
+#[cfg(all(target_os = "linux", not(target_arch = "aarch64")))]
+const DOWNLOADS: &[(&str, &str)] = &[("ffmpeg", "https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-amd64-static.tar.xz")];
+
+#[cfg(not(target_os = "linux"))]
+const ARCHIVE_EXTENSION: &str = "zip";
+
+#[cfg(target_os = "linux")]
+const ARCHIVE_EXTENSION: &str = "tar.xz";
+
+/// Download progress is emitted at most this often
+const PROGRESS_INTERVAL_MS: u64 = 250;
+
+/// Progress of the ffmpeg install, shared by every caller
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallStatus {
+    pub running: bool,
+    /// What the install is doing now, e.g. "Installing with Homebrew"
+    pub step: Option<String>,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    /// Latest line printed by the package manager
+    pub last_line: Option<String>,
+    /// Why the last install failed
+    pub error: Option<String>,
+    pub cancelled: bool,
+}
+
+static STATUS: Mutex<InstallStatus> = Mutex::new(InstallStatus {
+    running: false,
+    step: None,
+    downloaded_bytes: 0,
+    total_bytes: None,
+    last_line: None,
+    error: None,
+    cancelled: false,
+});
+
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+fn status() -> MutexGuard<'static, InstallStatus> {
+    STATUS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn emit(app: &AppHandle) {
+    let snapshot = status().clone();
+    let _ = app.emit(INSTALL_PROGRESS_EVENT, &snapshot);
+}
+
+fn set_step(app: &AppHandle, step: &str) {
+    tracing::info!(step, "ffmpeg install");
+    status().step = Some(step.to_string());
+    emit(app);
+}
+
+fn check_cancelled() -> Result<(), String> {
+    if CANCEL_REQUESTED.load(Ordering::Relaxed) {
+        return Err(INSTALL_CANCELLED.to_string());
+    }
+    Ok(())
+}
+
+/// The one install allowed at a time; marks the status idle when dropped, even on a panic
+struct InstallRun;
+
+impl InstallRun {
+    fn start() -> Option<InstallRun> {
+        let mut status = status();
+        if status.running {
+            return None;
+        }
+        *status = InstallStatus {
+            running: true,
+            step: None,
+            downloaded_bytes: 0,
+            total_bytes: None,
+            last_line: None,
+            error: None,
+            cancelled: false,
+        };
+        CANCEL_REQUESTED.store(false, Ordering::Relaxed);
+        Some(InstallRun)
+    }
+}
+
+impl Drop for InstallRun {
+    fn drop(&mut self) {
+        let mut status = status();
+        status.running = false;
+        status.step = None;
+    }
+}
+
+/// Install ffmpeg automatically. Only one install runs at a time; calling this while one is
+/// in progress returns its status straight away instead of starting another.
+#[tauri::command]
+pub async fn install_ffmpeg(app: AppHandle) -> Result<InstallStatus, String> {
+    let Some(run) = InstallRun::start() else {
+        tracing::info!("ffmpeg install already in progress");
+        return Ok(status().clone());
+    };
+
+    let result = install(&app).await;
+    drop(run);
+
+    let result = {
+        let mut status = status();
+        match result {
+            Ok(()) => Ok(status.clone()),
+            Err(e) if e == INSTALL_CANCELLED => {
+                tracing::info!("ffmpeg install cancelled");
+                status.cancelled = true;
+                Err(e)
+            }
+            Err(e) => {
+                status.error = Some(e.clone());
+                Err(e)
+            }
+        }
+    };
+    emit(&app);
+    result
+}
+
+/// Progress of the current or last ffmpeg install
+#[tauri::command]
+pub fn get_install_status() -> InstallStatus {
+    status().clone()
+}
+
+/// Stop the ffmpeg install in progress at the next chance it gets
+#[tauri::command]
+pub fn cancel_install() -> Result<(), String> {
+    if !status().running {
+        return Err("No ffmpeg install is running".to_string());
+    }
+    CANCEL_REQUESTED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Call `f` with each line of `reader`. Progress bars redraw with a bare carriage return,
+/// so that ends a line too.
+fn for_each_line(mut reader: impl Read, mut f: impl FnMut(&str)) {
+    let mut pending = Vec::new();
+    let mut buf = [0u8; 4096];
+    while let Ok(n) = reader.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        for &byte in &buf[..n] {
+            if byte == b'\n' || byte == b'\r' {
+                let line = String::from_utf8_lossy(&pending);
+                if !line.trim().is_empty() {
+                    f(line.trim());
+                }
+                pending.clear();
+            } else {
+                pending.push(byte);
+            }
+        }
+    }
+    let line = String::from_utf8_lossy(&pending);
+    if !line.trim().is_empty() {
+        f(line.trim());
+    }
+}
+
+/// Run a package manager to completion, passing its output on as progress events. Fails
+/// if it's cancelled or runs past INSTALL_TIMEOUT_SECS.
+fn run_installer(app: &AppHandle, name: &str, mut command: Command) -> Result<(), String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", name, e))?;
+
+    let stdout = child.stdout.take();
+    let stdout_app = app.clone();
+    let stdout_reader = thread::spawn(move || {
+        if let Some(stdout) = stdout {
+            for_each_line(stdout, |line| {
+                status().last_line = Some(line.to_string());
+                emit(&stdout_app);
+            });
+        }
+    });
+    let stderr = child.stderr.take();
+    let stderr_reader = thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(mut stderr) = stderr {
+            let _ = stderr.read_to_string(&mut text);
+        }
+        text
+    });
+
+    let started = Instant::now();
+    let exit = loop {
+        if let Some(exit) = child.try_wait().map_err(|e| format!("Failed to wait for {}: {}", name, e))? {
+            break Ok(exit);
+        }
+        if CANCEL_REQUESTED.load(Ordering::Relaxed) {
+            break Err(INSTALL_CANCELLED.to_string());
+        }
+        if started.elapsed() > Duration::from_secs(INSTALL_TIMEOUT_SECS) {
+            break Err(format!("{} install timed out after {} minutes", name, INSTALL_TIMEOUT_SECS / 60));
+        }
+        thread::sleep(Duration::from_millis(200));
+    };
+    if exit.is_err() {
+        suspend::kill(&mut child);
+        let _ = child.wait();
+    }
+    let _ = stdout_reader.join();
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    if exit?.success() {
+        Ok(())
+    } else {
+        tracing::error!(%stderr, "{} install failed", name);
+        Err(format!("{} install failed: {}", name, stderr))
+    }
+}
+
+/// `run_installer` on a blocking thread, so minutes of brew or winget don't tie up the async runtime
+async fn run_installer_async(app: &AppHandle, name: &'static str, command: Command) -> Result<(), String> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || run_installer(&app, name, command))
+        .await
+        .map_err(|e| format!("{} install didn't finish: {}", name, e))?
+}
+
+async fn install(app: &AppHandle) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        // Check if Homebrew is available
+        let brew_paths = ["/opt/homebrew/bin/brew", "/usr/local/bin/brew"];
+        let mut brew_path: Option<&str> = None;
+        
+        for path in brew_paths {
+            if PathBuf::from(path).exists() {
+                brew_path = Some(path);
+                break;
+            }
+        }
+
+        if let Some(brew) = brew_path {
+            set_step(app, "Installing with Homebrew");
+            let mut command = Command::new(brew);
+            command.args(["install", "ffmpeg"]);
+            return run_installer_async(app, "Homebrew", command).await;
+        }
+
+        // Homebrew not available, download static binary
+        download_ffmpeg_binary(app).await
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Check if winget is available
+        let winget_check = Command::new("cmd")
+            .args(["/C", "winget", "--version"])
+            .output();
+
+        if let Ok(output) = winget_check {
+            if output.status.success() {
+                set_step(app, "Installing with winget");
+                let mut command = Command::new("cmd");
+                command.args(["/C", "winget", "install", "Gyan.FFmpeg", "-e", "--silent", "--accept-package-agreements", "--accept-source-agreements"]);
+
+                match run_installer_async(app, "winget", command).await {
+                    Ok(()) => return Ok(()),
+                    Err(e) if e == INSTALL_CANCELLED => return Err(e),
+                    Err(e) => tracing::warn!(error = %e, "winget install failed, falling back to download"),
+                }
+            }
+        }
+
+        // winget not available, download static binary
+        download_ffmpeg_binary(app).await
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // Try apt-get first (Debian/Ubuntu)
+        if can_elevate() {
+            set_step(app, "Installing with apt-get");
+            let mut command = Command::new("pkexec");
+            command.args(["apt-get", "install", "-y", "ffmpeg"]);
+
+            match run_installer_async(app, "apt-get", command).await {
+                Ok(()) => return Ok(()),
+                Err(e) if e == INSTALL_CANCELLED => return Err(e),
+                Err(e) => tracing::warn!(error = %e, "pkexec apt-get install failed, falling back to download"),
+            }
+        } else {
+            tracing::info!("can't elevate to install system-wide, falling back to download");
+        }
+        check_cancelled()?;
+
+        // No system-wide install, download static binary
+        download_ffmpeg_binary(app).await
+    }
+}
+
+/// pkexec asks for the password through a polkit agent, which only runs in a graphical
+/// session; over SSH or in a kiosk it has nothing to ask with
+#[cfg(target_os = "linux")]
+fn can_elevate() -> bool {
+    let graphical = std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some();
+    graphical && crate::tool_runs("pkexec", "--version") && crate::tool_runs("apt-get", "--version")
+}
+
+async fn download_ffmpeg_binary(app: &AppHandle) -> Result<(), String> {
+    use std::fs;
+
+    // Get the app data directory for storing the binary
+    let ffmpeg_dir = crate::local_bin_dir().ok_or("Could not find home directory")?;
+
+    // Create directory if it doesn't exist
+    fs::create_dir_all(&ffmpeg_dir)
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let mut installed = Vec::new();
+    for (label, url) in DOWNLOADS {
+        // Save to temp file next to the binaries, then extract
+        let temp_archive = ffmpeg_dir.join(format!("{}_temp.{}", label, ARCHIVE_EXTENSION));
+        let downloaded = download(app, label, url, &temp_archive).await;
+        let extracted = downloaded.and_then(|_| {
+            set_step(app, &format!("Extracting {}", label));
+            extract_tools(&temp_archive, &ffmpeg_dir)
+        });
+
+        // Clean up temp file
+        let _ = fs::remove_file(&temp_archive);
+        installed.extend(extracted?);
+    }
+
+    tracing::info!(?installed, dir = %ffmpeg_dir.display(), "installed downloaded tools");
+    if !installed.iter().any(|name| name.starts_with("ffmpeg")) {
+        return Err("Downloaded archive has no ffmpeg binary".to_string());
+    }
+    Ok(())
+}
+
+/// Download `url` to `path` a chunk at a time, so progress shows and a cancel lands quickly
+async fn download(app: &AppHandle, label: &str, url: &str, path: &Path) -> Result<(), String> {
+    use std::fs;
+    use std::io::Write;
+
+    set_step(app, &format!("Downloading {}", label));
+    tracing::info!(url, "downloading {}", label);
+    let mut response = reqwest::get(url)
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", label, e))?;
+
+    if !response.status().is_success() {
+        tracing::error!(status = %response.status(), "{} download failed", label);
+        return Err(format!("Download failed with status: {}", response.status()));
+    }
+    {
+        let mut status = status();
+        status.downloaded_bytes = 0;
+        status.total_bytes = response.content_length();
+    }
+
+    let mut file = fs::File::create(path)
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    let mut last_emit = Instant::now();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read download: {}", e))?
+    {
+        check_cancelled()?;
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write temp file: {}", e))?;
+        status().downloaded_bytes += chunk.len() as u64;
+        if last_emit.elapsed() > Duration::from_millis(PROGRESS_INTERVAL_MS) {
+            emit(app);
+            last_emit = Instant::now();
+        }
+    }
+    Ok(())
+}
+
+/// Name of a binary we install, if `name` is one
+#[cfg(not(target_os = "linux"))]
+fn installed_name(name: &str) -> Option<String> {
+    #[cfg(target_os = "windows")]
+    let names = ["ffmpeg.exe", "ffprobe.exe"];
+
+    #[cfg(not(target_os = "windows"))]
+    let names = ["ffmpeg", "ffprobe"];
+
+    names.iter().find(|n| **n == name).map(|n| n.to_string())
+}
+
+/// Copy every binary we install out of a zip into `dir`, returning their names
+#[cfg(not(target_os = "linux"))]
+fn extract_tools(archive: &Path, dir: &Path) -> Result<Vec<String>, String> {
+    use std::fs;
+
+    // Extract the zip
+    let file = fs::File::open(archive)
+        .map_err(|e| format!("Failed to open zip: {}", e))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("Failed to read zip: {}", e))?;
+
+    let mut extracted = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)
+            .map_err(|e| format!("Failed to read zip entry: {}", e))?;
+
+        // Binaries sit at the root (macOS) or under a versioned bin folder (Windows)
+        let name = file.name().rsplit('/').next().unwrap_or_default().to_string();
+        let Some(name) = installed_name(&name) else {
+            continue;
+        };
+
+        let dest_path = dir.join(&name);
+        let mut dest_file = fs::File::create(&dest_path)
+            .map_err(|e| format!("Failed to create {} binary: {}", name, e))?;
+        std::io::copy(&mut file, &mut dest_file)
+            .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+
+        // Make executable on macOS
+        #[cfg(target_os = "macos")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&dest_path, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Failed to set permissions: {}", e))?;
+        }
+        extracted.push(name);
+    }
+
+    Ok(extracted)
+}
+
+/// The Linux static builds are .tar.xz, which every distribution's tar can unpack
+#[cfg(target_os = "linux")]
+fn extract_tools(archive: &Path, dir: &Path) -> Result<Vec<String>, String> {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let unpack_dir = crate::unique_temp_path("ffmpeg", "d");
+    fs::create_dir_all(&unpack_dir)
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let result = (|| {
+        let status = Command::new("tar")
+            .arg("-xJf")
+            .arg(archive)
+            .arg("-C")
+            .arg(&unpack_dir)
+            .status()
+            .map_err(|e| format!("Failed to run tar: {}", e))?;
+        if !status.success() {
+            return Err(format!("Failed to extract ffmpeg: tar exited with {}", status));
+        }
+
+        // The archive holds a single versioned folder, e.g. ffmpeg-7.0.2-amd64-static/ffmpeg
+        let folders: Vec<PathBuf> = fs::read_dir(&unpack_dir)
+            .map_err(|e| format!("Failed to read extracted files: {}", e))?
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+        let mut extracted = Vec::new();
+        for name in ["ffmpeg", "ffprobe"] {
+            let Some(binary) = folders.iter().map(|f| f.join(name)).find(|p| p.is_file()) else {
+                continue;
+            };
+            let dest_path = dir.join(name);
+            fs::copy(&binary, &dest_path)
+                .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+            fs::set_permissions(&dest_path, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Failed to set permissions: {}", e))?;
+            extracted.push(name.to_string());
+        }
+        Ok(extracted)
+    })();
+
+    let _ = fs::remove_dir_all(&unpack_dir);
+    result
+}
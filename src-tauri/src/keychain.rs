@@ -0,0 +1,115 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Service name secrets are filed under in the OS keychain
+const SERVICE: &str = "com.mkv-to-mp4.app";
+
+/// Run a keychain tool, feeding `input` on stdin so secrets never show up in the process list
+fn run_with_stdin(command: &mut Command, input: &str) -> Result<Output, String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to open the keychain: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("Failed to write to the keychain: {}", e))?;
+    }
+    child.wait_with_output().map_err(|e| format!("Failed to open the keychain: {}", e))
+}
+
+fn check(output: Output, what: &str) -> Result<String, String> {
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to {}: {}", what, stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// security's interactive mode splits its command line itself, so a quote, backslash or line
+/// break in a value would end it early or start another command
+#[cfg(target_os = "macos")]
+fn check_interactive(value: &str) -> Result<(), String> {
+    if value.contains(['"', '\\', '\n', '\r']) {
+        return Err("Secrets saved to the keychain can't contain quotes, backslashes or line breaks".to_string());
+    }
+    Ok(())
+}
+
+/// Save a secret in the login keychain, replacing any previous value
+#[cfg(target_os = "macos")]
+pub fn store_secret(account: &str, secret: &str) -> Result<(), String> {
+    check_interactive(account)?;
+    check_interactive(secret)?;
+    // security's interactive mode reads the command from stdin, which keeps the secret out of argv
+    let command = format!("add-generic-password -U -s \"{}\" -a \"{}\" -w \"{}\"\n", SERVICE, account, secret);
+    check(run_with_stdin(Command::new("security").arg("-i"), &command)?, "save to the keychain").map(|_| ())
+}
+
+/// Read a secret from the login keychain, or None if there isn't one
+#[cfg(target_os = "macos")]
+pub fn load_secret(account: &str) -> Option<String> {
+    let output = Command::new("security")
+        .args(["find-generic-password", "-s", SERVICE, "-a", account, "-w"])
+        .output()
+        .ok()?;
+    check(output, "read from the keychain").ok()
+}
+
+/// Save a secret in the Secret Service keyring (GNOME Keyring, KWallet), replacing any previous value
+#[cfg(target_os = "linux")]
+pub fn store_secret(account: &str, secret: &str) -> Result<(), String> {
+    let label = format!("MKV to MP4: {}", account);
+    let output = run_with_stdin(
+        Command::new("secret-tool").args(["store", "--label", &label, "service", SERVICE, "account", account]),
+        secret,
+    )?;
+    check(output, "save to the keyring").map(|_| ())
+}
+
+/// Read a secret from the Secret Service keyring, or None if there isn't one
+#[cfg(target_os = "linux")]
+pub fn load_secret(account: &str) -> Option<String> {
+    let output = Command::new("secret-tool")
+        .args(["lookup", "service", SERVICE, "account", account])
+        .output()
+        .ok()?;
+    check(output, "read from the keyring").ok().filter(|s| !s.is_empty())
+}
+
+#[cfg(target_os = "windows")]
+const LOAD_VAULT: &str = "[void][Windows.Security.Credentials.PasswordVault,Windows.Security.Credentials,ContentType=WindowsRuntime]; $vault = New-Object Windows.Security.Credentials.PasswordVault;";
+
+/// Save a secret in the Windows Credential Locker, replacing any previous value
+#[cfg(target_os = "windows")]
+pub fn store_secret(account: &str, secret: &str) -> Result<(), String> {
+    let script = format!(
+        "{} $secret = [Console]::In.ReadLine(); $vault.Add((New-Object Windows.Security.Credentials.PasswordCredential('{}', '{}', $secret)))",
+        LOAD_VAULT,
+        SERVICE,
+        account.replace('\'', "''")
+    );
+    let output = run_with_stdin(
+        Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", &script]),
+        &format!("{}\n", secret),
+    )?;
+    check(output, "save to the Credential Locker").map(|_| ())
+}
+
+/// Read a secret from the Windows Credential Locker, or None if there isn't one
+#[cfg(target_os = "windows")]
+pub fn load_secret(account: &str) -> Option<String> {
+    let script = format!(
+        "{} $c = $vault.Retrieve('{}', '{}'); $c.RetrievePassword(); $c.Password",
+        LOAD_VAULT,
+        SERVICE,
+        account.replace('\'', "''")
+    );
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .output()
+        .ok()?;
+    check(output, "read from the Credential Locker").ok().filter(|s| !s.is_empty())
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+use crate::naming::{self, FilenamePolicy};
+use crate::probe;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Rendition {
+    pub height: u32,
+    pub video_kbps: u32,
+    pub audio_kbps: u32,
+}
+
+/// 1080p/720p/480p at bitrates that stream comfortably at each size
+const DEFAULT_LADDER: &[Rendition] = &[
+    Rendition { height: 1080, video_kbps: 5000, audio_kbps: 192 },
+    Rendition { height: 720, video_kbps: 2800, audio_kbps: 128 },
+    Rendition { height: 480, video_kbps: 1200, audio_kbps: 96 },
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LadderOptions {
+    /// Defaults to DEFAULT_LADDER
+    pub renditions: Option<Vec<Rendition>>,
+    pub filename_policy: FilenamePolicy,
+}
+
+/// Renditions worth making from a source `source_height` tall: none are upscaled, and a
+/// source smaller than every rung still gets the smallest one at its own height
+fn usable_renditions(ladder: &[Rendition], source_height: u32) -> Vec<Rendition> {
+    let mut usable: Vec<Rendition> = ladder.iter().copied().filter(|r| r.height <= source_height).collect();
+    if usable.is_empty() {
+        if let Some(smallest) = ladder.iter().min_by_key(|r| r.height) {
+            usable.push(Rendition { height: source_height, ..*smallest });
+        }
+    }
+    usable
+}
+
+/// Encode several renditions of one input in a single ffmpeg run: the video is decoded once
+/// and split into one scaled H.264 encode per rendition, named `<name>_<height>p.mp4`
+#[tauri::command(async)]
+pub fn convert_ladder(input_path: String, options: Option<LadderOptions>) -> Result<Vec<String>, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let options = options.unwrap_or_default();
+    let ladder = options.renditions.as_deref().unwrap_or(DEFAULT_LADDER);
+    if ladder.iter().any(|r| r.height == 0 || r.video_kbps == 0 || r.audio_kbps == 0) {
+        return Err("Renditions need a height and bitrates".to_string());
+    }
+
+    let mut heights: Vec<u32> = ladder.iter().map(|r| r.height).collect();
+    heights.sort_unstable();
+    heights.dedup();
+    if heights.len() != ladder.len() {
+        return Err("Each rendition needs a different height".to_string());
+    }
+
+    let info = probe::probe(&input_path)?;
+    let source_height = info
+        .streams_of("video")
+        .next()
+        .and_then(|s| s.height)
+        .ok_or("No video stream to make renditions from")?;
+    let renditions = usable_renditions(ladder, source_height);
+    if renditions.is_empty() {
+        return Err("No renditions given".to_string());
+    }
+
+    let labels: String = (0..renditions.len()).map(|i| format!("[s{}]", i)).collect();
+    let mut filter = format!("[0:v:0]split={}{}", renditions.len(), labels);
+    for (i, rendition) in renditions.iter().enumerate() {
+        // -2 keeps the aspect ratio with an even width, which x264 needs
+        filter.push_str(&format!(";[s{}]scale=-2:{}[v{}]", i, rendition.height, i));
+    }
+
+    let mut args = vec!["-i".to_string(), input_path.clone(), "-filter_complex".to_string(), filter];
+    let mut outputs = Vec::new();
+    for (i, rendition) in renditions.iter().enumerate() {
+        let output = naming::output_with_suffix(&input_path, &format!("_{}p", rendition.height), "mp4", options.filename_policy);
+        args.extend([
+            "-map".to_string(),
+            format!("[v{}]", i),
+            "-map".to_string(),
+            "0:a:0?".to_string(),
+            "-c:v".to_string(),
+            "libx264".to_string(),
+            "-preset".to_string(),
+            "medium".to_string(),
+            "-b:v".to_string(),
+            format!("{}k", rendition.video_kbps),
+            // Capped so a rendition never bursts past what its viewers can stream
+            "-maxrate".to_string(),
+            format!("{}k", rendition.video_kbps * 107 / 100),
+            "-bufsize".to_string(),
+            format!("{}k", rendition.video_kbps * 2),
+            "-c:a".to_string(),
+            "aac".to_string(),
+            "-b:a".to_string(),
+            format!("{}k", rendition.audio_kbps),
+            "-movflags".to_string(),
+            "+faststart".to_string(),
+            "-y".to_string(),
+            output.to_string_lossy().to_string(),
+        ]);
+        outputs.push(output.to_string_lossy().to_string());
+    }
+
+    tracing::info!(input = %input_path, renditions = ?renditions, "encoding quality ladder");
+    tracing::debug!(?args, "ffmpeg arguments");
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(&args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(&args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffmpeg failed: {}", stderr));
+    }
+    Ok(outputs)
+}
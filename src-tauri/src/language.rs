@@ -0,0 +1,182 @@
+use serde::Serialize;
+use std::fs;
+
+use crate::probe::{self, MediaInfo, StreamInfo};
+use crate::transcribe;
+
+/// ISO 639-2/B code, ISO 639-1 code, then names seen in track titles (lowercase)
+const LANGUAGES: &[(&str, &str, &[&str])] = &[
+    ("eng", "en", &["english"]),
+    ("jpn", "ja", &["japanese", "nihongo"]),
+    ("ger", "de", &["german", "deutsch", "deu"]),
+    ("fre", "fr", &["french", "francais", "français", "fra"]),
+    ("spa", "es", &["spanish", "espanol", "español", "castellano", "latino"]),
+    ("ita", "it", &["italian", "italiano"]),
+    ("por", "pt", &["portuguese", "portugues", "português"]),
+    ("rus", "ru", &["russian", "русский"]),
+    ("chi", "zh", &["chinese", "mandarin", "cantonese", "zho"]),
+    ("kor", "ko", &["korean"]),
+    ("hin", "hi", &["hindi"]),
+    ("ara", "ar", &["arabic"]),
+    ("dut", "nl", &["dutch", "nederlands", "nld"]),
+    ("swe", "sv", &["swedish", "svenska"]),
+    ("nor", "no", &["norwegian", "norsk"]),
+    ("dan", "da", &["danish", "dansk"]),
+    ("fin", "fi", &["finnish", "suomi"]),
+    ("pol", "pl", &["polish", "polski"]),
+    ("tur", "tr", &["turkish", "türkçe"]),
+    ("tha", "th", &["thai"]),
+];
+
+/// Seconds of audio whisper listens to when guessing a language
+const SPEECH_SAMPLE_SECS: u32 = 30;
+
+/// Where a track's language came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageSource {
+    Tag,
+    Title,
+    Speech,
+}
+
+/// Language of one audio track, tagged or guessed
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackLanguage {
+    pub index: u32,
+    pub title: Option<String>,
+    /// ISO 639-2 code, None when even guessing failed
+    pub language: Option<String>,
+    pub source: Option<LanguageSource>,
+}
+
+/// Normalize a language code or name ("en", "eng", "deu", "English") to ISO 639-2/B
+pub fn normalize(code: &str) -> Option<&'static str> {
+    let code = code.trim().to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(three, two, names)| *three == code || *two == code || names.contains(&code.as_str()))
+        .map(|(three, _, _)| *three)
+}
+
+fn is_untagged(stream: &StreamInfo) -> bool {
+    stream
+        .language
+        .as_deref()
+        .is_none_or(|lang| matches!(lang, "" | "und" | "unk" | "mis" | "zxx"))
+}
+
+/// Look for a language name or code among the words of a title like "Japanese 5.1" or "[ENG] Stereo".
+/// Bare codes only count in capitals so words like "Dan" or "Pol" don't match.
+fn from_title(title: &str) -> Option<&'static str> {
+    title.split(|c: char| !c.is_alphanumeric()).find_map(|word| {
+        let lower = word.to_lowercase();
+        LANGUAGES
+            .iter()
+            .find(|(three, _, names)| {
+                names.contains(&lower.as_str()) || (*three == lower && word.chars().all(|c| c.is_ascii_uppercase()))
+            })
+            .map(|(three, _, _)| *three)
+    })
+}
+
+/// Ask whisper to identify the spoken language in a short sample of one audio stream
+fn from_speech(path: &str, stream_index: u32, duration: Option<f64>) -> Result<Option<&'static str>, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let whisper_path = transcribe::find_whisper().ok_or("whisper.cpp not found")?;
+    let model = transcribe::find_model(None)?;
+
+    // Skip past intros and title music when there's room to
+    let start = match duration {
+        Some(d) if d > 600.0 => d / 10.0,
+        _ => 0.0,
+    };
+    let wav = crate::unique_temp_path("langid", "wav");
+    let wav_str = wav.to_string_lossy().to_string();
+    let sample = transcribe::run_tool(
+        &ffmpeg_path,
+        &[
+            "-ss", &format!("{:.0}", start),
+            "-t", &SPEECH_SAMPLE_SECS.to_string(),
+            "-i", path,
+            "-map", &format!("0:{}", stream_index),
+            "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le",
+            "-y", &wav_str,
+        ],
+        "Audio sampling",
+    );
+    if let Err(e) = sample {
+        let _ = fs::remove_file(&wav);
+        return Err(e);
+    }
+
+    let model_str = model.to_string_lossy().to_string();
+    let detected = transcribe::run_tool(
+        &whisper_path,
+        &["-m", &model_str, "-f", &wav_str, "-l", "auto", "-dl"],
+        "Language detection",
+    );
+    let _ = fs::remove_file(&wav);
+
+    // whisper prints e.g. "auto-detected language: en (p = 0.97)"
+    let output = detected?;
+    let log = String::from_utf8_lossy(&output.stderr);
+    Ok(log
+        .lines()
+        .find_map(|line| line.split("auto-detected language:").nth(1))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(normalize))
+}
+
+/// Languages of every audio track, guessing for untagged ones from titles and, if allowed, speech
+pub fn audio_languages(info: &MediaInfo, use_speech: bool) -> Vec<TrackLanguage> {
+    info.streams_of("audio")
+        .map(|stream| {
+            let (language, source) = if !is_untagged(stream) {
+                let tag = stream.language.clone().unwrap_or_default();
+                (Some(normalize(&tag).map(str::to_string).unwrap_or(tag)), Some(LanguageSource::Tag))
+            } else if let Some(lang) = stream.title.as_deref().and_then(from_title) {
+                (Some(lang.to_string()), Some(LanguageSource::Title))
+            } else if use_speech {
+                match from_speech(&info.path, stream.index, info.duration) {
+                    Ok(Some(lang)) => (Some(lang.to_string()), Some(LanguageSource::Speech)),
+                    Ok(None) => (None, None),
+                    Err(e) => {
+                        tracing::warn!(stream = stream.index, error = %e, "speech language detection failed");
+                        (None, None)
+                    }
+                }
+            } else {
+                (None, None)
+            };
+
+            TrackLanguage {
+                index: stream.index,
+                title: stream.title.clone(),
+                language,
+                source,
+            }
+        })
+        .collect()
+}
+
+/// Indexes of audio streams in `preferred`, falling back to guesses for untagged tracks. A
+/// language outside `LANGUAGES`, such as "hun", is matched against the tags as given; titles
+/// and speech can only be guessed for the ones in the table.
+pub fn matching_audio_streams(info: &MediaInfo, preferred: &str) -> Vec<u32> {
+    let known = normalize(preferred);
+    let wanted = known.map(str::to_string).unwrap_or_else(|| preferred.trim().to_string());
+    let use_speech = known.is_some() && transcribe::find_whisper().is_some();
+    audio_languages(info, use_speech)
+        .into_iter()
+        .filter(|track| track.language.as_deref().is_some_and(|lang| lang.eq_ignore_ascii_case(&wanted)))
+        .map(|track| track.index)
+        .collect()
+}
+
+/// Report each audio track's language, guessing for untagged tracks
+#[tauri::command(async)]
+pub fn detect_audio_languages(path: String, use_speech: Option<bool>) -> Result<Vec<TrackLanguage>, String> {
+    let info = probe::probe(&path)?;
+    Ok(audio_languages(&info, use_speech.unwrap_or(true)))
+}
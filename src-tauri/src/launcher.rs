@@ -0,0 +1,75 @@
+/// The Unity LauncherEntry D-Bus API, read by Ubuntu's dock, Dash to Dock and KDE Plasma's task manager
+#[cfg(target_os = "linux")]
+mod unity {
+    use std::collections::HashMap;
+    use std::sync::OnceLock;
+    use zbus::blocking::Connection;
+    use zbus::zvariant::Value;
+
+    /// Desktop entry docks match the update to; the one the Linux bundles install
+    const APP_URI: &str = "application://mkv-to-mp4.desktop";
+
+    const OBJECT_PATH: &str = "/com/canonical/unity/launcherentry/mkv_to_mp4";
+
+    /// Kept open for the app's lifetime: docks drop an entry's progress when its sender disconnects
+    static CONNECTION: OnceLock<Option<Connection>> = OnceLock::new();
+
+    pub fn update(properties: HashMap<&str, Value>) {
+        let connection = CONNECTION.get_or_init(|| match Connection::session() {
+            Ok(connection) => Some(connection),
+            Err(e) => {
+                tracing::debug!(error = %e, "no session bus, dock progress disabled");
+                None
+            }
+        });
+        let Some(connection) = connection else {
+            return;
+        };
+        let result = connection.emit_signal(
+            None::<&str>,
+            OBJECT_PATH,
+            "com.canonical.Unity.LauncherEntry",
+            "Update",
+            &(APP_URI, properties),
+        );
+        if let Err(e) = result {
+            tracing::debug!(error = %e, "failed to send dock progress");
+        }
+    }
+}
+
+/// Show `finished` of `total` as a progress bar on the dock icon, with the number left as a badge
+pub fn set_progress(finished: usize, total: usize) {
+    #[cfg(target_os = "linux")]
+    {
+        use zbus::zvariant::Value;
+        let progress = if total == 0 { 0.0 } else { finished as f64 / total as f64 };
+        unity::update(
+            [
+                ("progress", Value::from(progress)),
+                ("progress-visible", Value::from(true)),
+                ("count", Value::from((total - finished) as i64)),
+                ("count-visible", Value::from(total > finished)),
+            ]
+            .into(),
+        );
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = (finished, total);
+}
+
+/// Hide the progress bar and badge
+pub fn clear() {
+    #[cfg(target_os = "linux")]
+    {
+        use zbus::zvariant::Value;
+        unity::update(
+            [
+                ("progress-visible", Value::from(false)),
+                ("count-visible", Value::from(false)),
+            ]
+            .into(),
+        );
+    }
+}
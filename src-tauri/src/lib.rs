@@ -1,315 +1,250 @@
+mod archive;
+mod audio;
+mod av1;
+mod backup;
+mod cancel;
+mod captions;
+mod capture;
+mod chapters;
+mod convert;
+mod destinations;
+mod edit;
+mod encrypt;
+mod energy;
+mod estimate;
+mod exit;
+mod expectations;
+mod filters;
+mod folders;
+mod fonts;
+mod guardrails;
+mod h264;
+mod health;
+mod history;
+mod idle;
+mod install;
+mod keychain;
+mod ladder;
+mod language;
+mod launcher;
+mod logging;
+mod metadata;
+mod metrics;
+mod migrate;
+mod milestones;
+mod naming;
+mod notes;
+mod overlay;
+mod planner;
+mod player;
+mod plugins;
+mod power;
+mod presets;
+mod probe;
+mod profiles;
+mod progress;
+mod queue;
+mod remote;
+mod rules;
+mod sample;
+mod sandbox;
+mod scan;
+mod settings;
+mod share;
+mod social;
+mod store;
+mod stream;
+mod suggest;
+mod support;
+mod suspend;
+mod sync;
+mod thumbnail;
+mod transcribe;
+mod trim;
+mod twopass;
+mod verify;
+mod volume;
+mod watch;
+mod waveform;
+
+use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tauri::Manager;
 
-/// Common ffmpeg locations to check on macOS
+/// Matches the identifier in tauri.conf.json so we share Tauri's app data location
+const APP_IDENTIFIER: &str = "com.mkv-to-mp4.app";
+
+/// Common folders ffmpeg and its companion tools are installed in on macOS
 #[cfg(target_os = "macos")]
-const FFMPEG_PATHS: &[&str] = &[
-    "/opt/homebrew/bin/ffmpeg",      // Homebrew on Apple Silicon
-    "/usr/local/bin/ffmpeg",          // Homebrew on Intel / manual install
-    "/usr/bin/ffmpeg",                // System install
+const TOOL_DIRS: &[&str] = &[
+    "/opt/homebrew/bin",      // Homebrew on Apple Silicon
+    "/usr/local/bin",          // Homebrew on Intel / manual install
+    "/usr/bin",                // System install
 ];
 
-/// Common ffmpeg locations to check on Windows
+/// Common folders ffmpeg and its companion tools are installed in on Windows
 #[cfg(target_os = "windows")]
-const FFMPEG_PATHS: &[&str] = &[
-    "ffmpeg",  // In PATH
-    "C:\\Program Files\\ffmpeg\\bin\\ffmpeg.exe",
-    "C:\\ffmpeg\\bin\\ffmpeg.exe",
+const TOOL_DIRS: &[&str] = &[
+    "C:\\Program Files\\ffmpeg\\bin",
+    "C:\\ffmpeg\\bin",
 ];
 
-/// Common ffmpeg locations to check on Linux
+/// Common folders ffmpeg and its companion tools are installed in on Linux
 #[cfg(target_os = "linux")]
-const FFMPEG_PATHS: &[&str] = &[
-    "/usr/bin/ffmpeg",
-    "/usr/local/bin/ffmpeg",
-    "/snap/bin/ffmpeg",
+const TOOL_DIRS: &[&str] = &[
+    "/usr/bin",
+    "/usr/local/bin",
+    "/snap/bin",
 ];
 
-/// Find ffmpeg binary path
-fn find_ffmpeg() -> Option<String> {
-    // First check common locations
-    for path in FFMPEG_PATHS {
-        let path_buf = PathBuf::from(path);
-        if path_buf.exists() || path == &"ffmpeg" {
-            // Verify it actually works
-            #[cfg(target_os = "windows")]
-            let result = Command::new("cmd")
-                .args(["/C", path, "-version"])
-                .output();
-
-            #[cfg(not(target_os = "windows"))]
-            let result = Command::new(path)
-                .arg("-version")
-                .output();
-
-            if let Ok(output) = result {
-                if output.status.success() {
-                    return Some(path.to_string());
-                }
-            }
-        }
+/// Where our own ffmpeg download goes: a folder in the home directory, or in our app data
+/// when the app is confined, since writes outside that fail there
+fn local_bin_dir() -> Option<PathBuf> {
+    if sandbox::confinement().is_confined() {
+        return app_data_dir().ok().map(|dir| dir.join("bin"));
     }
+    home_bin_dir()
+}
 
-    // Check user's local bin (for our downloaded binary)
-    if let Some(home) = dirs::home_dir() {
-        #[cfg(target_os = "macos")]
-        let local_ffmpeg = home.join(".local/bin/ffmpeg");
-        
-        #[cfg(target_os = "windows")]
-        let local_ffmpeg = home.join("AppData\\Local\\ffmpeg\\ffmpeg.exe");
-        
-        #[cfg(target_os = "linux")]
-        let local_ffmpeg = home.join(".local/bin/ffmpeg");
-
-        if local_ffmpeg.exists() {
-            let path_str = local_ffmpeg.to_string_lossy().to_string();
-            
-            #[cfg(target_os = "windows")]
-            let result = Command::new("cmd")
-                .args(["/C", &path_str, "-version"])
-                .output();
-
-            #[cfg(not(target_os = "windows"))]
-            let result = Command::new(&path_str)
-                .arg("-version")
-                .output();
-
-            if let Ok(output) = result {
-                if output.status.success() {
-                    return Some(path_str);
-                }
-            }
-        }
-    }
+/// Where ffmpeg downloads went before confined apps got their own folder
+fn home_bin_dir() -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
 
-    None
-}
+    #[cfg(target_os = "windows")]
+    let dir = home.join("AppData\\Local\\ffmpeg");
 
-/// Check if ffmpeg is available
-#[tauri::command]
-fn check_ffmpeg() -> bool {
-    find_ffmpeg().is_some()
+    #[cfg(not(target_os = "windows"))]
+    let dir = home.join(".local/bin");
+
+    Some(dir)
 }
 
-/// Generate a unique output path that doesn't overwrite existing files
-fn get_unique_output_path(input_path: &str) -> PathBuf {
-    let path = PathBuf::from(input_path);
-    let parent = path.parent().unwrap_or_else(|| std::path::Path::new("."));
-    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
-    
-    let mut output_path = parent.join(format!("{}.mp4", stem));
-    
-    // If file exists, append _1, _2, etc.
-    let mut counter = 1;
-    while output_path.exists() {
-        output_path = parent.join(format!("{}_{}.mp4", stem, counter));
-        counter += 1;
+/// Find ffmpeg or a tool that ships with it, such as ffprobe or ffplay
+fn find_tool(name: &str) -> Option<String> {
+    let chosen = settings::ffmpeg_path();
+    if let Some(path) = chosen.as_ref().filter(|p| name == "ffmpeg" && tool_runs(p, "-version")) {
+        return Some(path.clone());
     }
-    
-    output_path
-}
 
-/// Convert a video file to MP4 using ffmpeg
-#[tauri::command]
-fn convert_file(input_path: String) -> Result<String, String> {
-    let ffmpeg_path = find_ffmpeg().ok_or("ffmpeg not found")?;
-    
-    let output_path = get_unique_output_path(&input_path);
-    let output_str = output_path.to_string_lossy().to_string();
+    // Installers on Windows usually put ffmpeg in PATH
+    #[cfg(target_os = "windows")]
+    if tool_runs(name, "-version") {
+        return Some(name.to_string());
+    }
 
     #[cfg(target_os = "windows")]
-    let result = Command::new("cmd")
-        .args(["/C", &ffmpeg_path, "-i", &input_path, "-codec", "copy", "-y", &output_str])
-        .output();
+    let file_name = format!("{}.exe", name);
 
     #[cfg(not(target_os = "windows"))]
-    let result = Command::new(&ffmpeg_path)
-        .args(["-i", &input_path, "-codec", "copy", "-y", &output_str])
-        .output();
+    let file_name = name.to_string();
+
+    // Next to an ffmpeg picked in settings first, then common locations, then our own download
+    chosen
+        .and_then(|path| Some(PathBuf::from(path).parent()?.to_path_buf()))
+        .into_iter()
+        .chain(TOOL_DIRS.iter().map(PathBuf::from))
+        .chain(local_bin_dir())
+        .chain(home_bin_dir())
+        .map(|dir| dir.join(&file_name))
+        .filter(|path| path.exists())
+        .map(|path| path.to_string_lossy().to_string())
+        .find(|path| tool_runs(path, "-version"))
+}
 
-    match result {
-        Ok(output) => {
-            if output.status.success() {
-                Ok(output_str)
-            } else {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                Err(format!("ffmpeg failed: {}", stderr))
-            }
-        }
-        Err(e) => Err(format!("Failed to run ffmpeg: {}", e)),
-    }
+/// Find ffmpeg binary path
+fn find_ffmpeg() -> Option<String> {
+    find_tool("ffmpeg")
 }
 
-/// Install ffmpeg automatically
-#[tauri::command]
-async fn install_ffmpeg() -> Result<(), String> {
-    #[cfg(target_os = "macos")]
-    {
-        // Check if Homebrew is available
-        let brew_paths = ["/opt/homebrew/bin/brew", "/usr/local/bin/brew"];
-        let mut brew_path: Option<&str> = None;
-        
-        for path in brew_paths {
-            if PathBuf::from(path).exists() {
-                brew_path = Some(path);
-                break;
+/// Escape a file path for use as a filter argument such as `movie=` or `subtitles=`.
+/// Filter graphs are unescaped twice (graph, then option level), so `\`, `'` and `:`
+/// need a double escape while graph punctuation needs a single one.
+fn escape_filter_path(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            '\\' | '\'' | ':' => {
+                escaped.push_str("\\\\\\");
+                escaped.push(c);
             }
-        }
-
-        if let Some(brew) = brew_path {
-            let result = Command::new(brew)
-                .args(["install", "ffmpeg"])
-                .output()
-                .map_err(|e| format!("Failed to run brew: {}", e))?;
-
-            if result.status.success() {
-                return Ok(());
-            } else {
-                let stderr = String::from_utf8_lossy(&result.stderr);
-                return Err(format!("Homebrew install failed: {}", stderr));
+            '[' | ']' | ',' | ';' => {
+                escaped.push('\\');
+                escaped.push(c);
             }
+            _ => escaped.push(c),
         }
-
-        // Homebrew not available, download static binary
-        download_ffmpeg_binary().await
     }
+    escaped
+}
 
+/// Check that a binary runs with the given version flag
+fn tool_runs(path: &str, version_flag: &str) -> bool {
     #[cfg(target_os = "windows")]
-    {
-        // Check if winget is available
-        let winget_check = Command::new("cmd")
-            .args(["/C", "winget", "--version"])
-            .output();
-
-        if let Ok(output) = winget_check {
-            if output.status.success() {
-                let result = Command::new("cmd")
-                    .args(["/C", "winget", "install", "Gyan.FFmpeg", "-e", "--silent", "--accept-package-agreements", "--accept-source-agreements"])
-                    .output()
-                    .map_err(|e| format!("Failed to run winget: {}", e))?;
-
-                if result.status.success() {
-                    return Ok(());
-                }
-            }
-        }
-
-        // winget not available, download static binary
-        download_ffmpeg_binary().await
-    }
+    let result = Command::new("cmd")
+        .args(["/C", path, version_flag])
+        .output();
 
-    #[cfg(target_os = "linux")]
-    {
-        // Try apt-get first (Debian/Ubuntu)
-        let apt_result = Command::new("pkexec")
-            .args(["apt-get", "install", "-y", "ffmpeg"])
-            .output();
-
-        if let Ok(output) = apt_result {
-            if output.status.success() {
-                return Ok(());
-            }
-        }
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(path)
+        .arg(version_flag)
+        .output();
 
-        Err("Could not install ffmpeg automatically. Please install it manually using your package manager.".to_string())
-    }
+    result.is_ok_and(|output| output.status.success())
 }
 
-#[cfg(any(target_os = "macos", target_os = "windows"))]
-async fn download_ffmpeg_binary() -> Result<(), String> {
-    use std::fs;
-    use std::io::Write;
-
-    // Get the app data directory for storing the binary
-    let home = dirs::home_dir().ok_or("Could not find home directory")?;
-    
-    #[cfg(target_os = "macos")]
-    let ffmpeg_dir = home.join(".local").join("bin");
-    
-    #[cfg(target_os = "windows")]
-    let ffmpeg_dir = home.join("AppData").join("Local").join("ffmpeg");
-
-    // Create directory if it doesn't exist
-    fs::create_dir_all(&ffmpeg_dir)
-        .map_err(|e| format!("Failed to create directory: {}", e))?;
+/// `ffmpeg -encoders` output, for checking what this build can encode. Cached once ffmpeg
+/// is found; empty until then.
+fn ffmpeg_encoders() -> String {
+    static ENCODERS: OnceLock<String> = OnceLock::new();
+    if let Some(encoders) = ENCODERS.get() {
+        return encoders.clone();
+    }
+    let Some(ffmpeg_path) = find_ffmpeg() else {
+        return String::new();
+    };
 
-    #[cfg(target_os = "macos")]
-    let download_url = "https://evermeet.cx/ffmpeg/getrelease/ffmpeg/zip";
-    
     #[cfg(target_os = "windows")]
-    let download_url = "https://www.gyan.dev/ffmpeg/builds/ffmpeg-release-essentials.zip";
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path, "-hide_banner", "-encoders"])
+        .output();
 
-    // Download the file
-    let response = reqwest::get(download_url)
-        .await
-        .map_err(|e| format!("Failed to download ffmpeg: {}", e))?;
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(["-hide_banner", "-encoders"])
+        .output();
 
-    if !response.status().is_success() {
-        return Err(format!("Download failed with status: {}", response.status()));
+    match result {
+        Ok(output) if output.status.success() => ENCODERS
+            .get_or_init(|| String::from_utf8_lossy(&output.stdout).to_string())
+            .clone(),
+        _ => String::new(),
     }
+}
 
-    let bytes = response.bytes()
-        .await
-        .map_err(|e| format!("Failed to read download: {}", e))?;
-
-    // Save to temp file
-    let temp_zip = ffmpeg_dir.join("ffmpeg_temp.zip");
-    let mut file = fs::File::create(&temp_zip)
-        .map_err(|e| format!("Failed to create temp file: {}", e))?;
-    file.write_all(&bytes)
-        .map_err(|e| format!("Failed to write temp file: {}", e))?;
-
-    // Extract the zip
-    let file = fs::File::open(&temp_zip)
-        .map_err(|e| format!("Failed to open zip: {}", e))?;
-    let mut archive = zip::ZipArchive::new(file)
-        .map_err(|e| format!("Failed to read zip: {}", e))?;
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)
-            .map_err(|e| format!("Failed to read zip entry: {}", e))?;
-        
-        let name = file.name().to_string();
-        
-        // Look for ffmpeg binary
-        #[cfg(target_os = "macos")]
-        let is_ffmpeg = name == "ffmpeg" || name.ends_with("/ffmpeg");
-        
-        #[cfg(target_os = "windows")]
-        let is_ffmpeg = name.ends_with("ffmpeg.exe");
-
-        if is_ffmpeg {
-            #[cfg(target_os = "macos")]
-            let dest_path = ffmpeg_dir.join("ffmpeg");
-            
-            #[cfg(target_os = "windows")]
-            let dest_path = ffmpeg_dir.join("ffmpeg.exe");
-
-            let mut dest_file = fs::File::create(&dest_path)
-                .map_err(|e| format!("Failed to create ffmpeg binary: {}", e))?;
-            std::io::copy(&mut file, &mut dest_file)
-                .map_err(|e| format!("Failed to extract ffmpeg: {}", e))?;
-
-            // Make executable on macOS
-            #[cfg(target_os = "macos")]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let mut perms = fs::metadata(&dest_path)
-                    .map_err(|e| format!("Failed to get permissions: {}", e))?
-                    .permissions();
-                perms.set_mode(0o755);
-                fs::set_permissions(&dest_path, perms)
-                    .map_err(|e| format!("Failed to set permissions: {}", e))?;
-            }
-
-            break;
-        }
-    }
+/// A temp path no other job in this process will use, e.g. mkv2mp4-chapters-1234-7.txt
+fn unique_temp_path(prefix: &str, extension: &str) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    sandbox::temp_dir().join(format!("mkv2mp4-{}-{}-{}.{}", prefix, std::process::id(), n, extension))
+}
 
-    // Clean up temp file
-    let _ = fs::remove_file(temp_zip);
+/// Per-user app data directory, created on first use
+fn app_data_dir() -> Result<PathBuf, String> {
+    let dir = dirs::data_dir()
+        .ok_or("Could not find app data directory")?
+        .join(APP_IDENTIFIER);
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    Ok(dir)
+}
 
-    Ok(())
+/// Check if ffmpeg is available
+#[tauri::command]
+fn check_ffmpeg() -> bool {
+    find_ffmpeg().is_some()
 }
 
 /// Reveal a file in the system file explorer (Finder on macOS, Explorer on Windows)
@@ -350,10 +285,168 @@ fn reveal_file(path: String) -> Result<(), String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
+    migrate::run();
+    sandbox::migrate_tools();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![check_ffmpeg, convert_file, install_ffmpeg, reveal_file])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .setup(|app| {
+            remote::start(app.handle().clone());
+            queue::restore(app.handle());
+            watch::restore(app.handle());
+
+            // Download clients launch us with --enqueue when a download finishes
+            for path in scan::enqueue_args() {
+                if let Err(e) = scan::enqueue_folder(app.handle().clone(), path, None, None, None) {
+                    tracing::warn!(error = %e, "failed to enqueue command line path");
+                }
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                if exit::veto(window.app_handle()) {
+                    api.prevent_close();
+                }
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            check_ffmpeg,
+            reveal_file,
+            archive::convert_from_archive,
+            archive::list_archive,
+            audio::list_audio_codecs,
+            av1::av1_preset,
+            av1::estimate_av1_time,
+            av1::get_av1_support,
+            backup::export_app_data,
+            backup::import_app_data,
+            cancel::cancel_conversion,
+            cancel::get_running_conversions,
+            captions::extract_captions,
+            capture::list_capture_devices,
+            capture::record_stream,
+            capture::start_recording,
+            capture::start_screen_recording,
+            capture::stop_recording,
+            chapters::get_chapters,
+            convert::convert_file,
+            edit::remove_tracks_in_place,
+            edit::retag_in_place,
+            encrypt::get_cenc_key,
+            energy::get_energy_settings,
+            energy::set_energy_settings,
+            estimate::estimate_output,
+            exit::can_exit,
+            exit::cancel_exit,
+            exit::request_exit,
+            filters::get_scan_filter,
+            filters::set_scan_filter,
+            folders::get_folder_options,
+            folders::forget_folder_options,
+            fonts::add_user_font,
+            fonts::get_user_fonts_dir,
+            guardrails::check_guardrails,
+            guardrails::get_guardrails,
+            guardrails::set_guardrails,
+            health::check_source_read,
+            history::clear_history,
+            history::find_duplicates,
+            history::get_conversion_notes,
+            history::get_history,
+            idle::get_idle_seconds,
+            install::cancel_install,
+            install::get_install_status,
+            install::install_ffmpeg,
+            ladder::convert_ladder,
+            language::detect_audio_languages,
+            logging::get_log_settings,
+            logging::set_log_level,
+            logging::set_log_file_output,
+            metrics::get_metrics_enabled,
+            metrics::set_metrics_enabled,
+            metrics::preview_metrics,
+            milestones::get_milestone_settings,
+            milestones::set_milestone_settings,
+            naming::get_output_directory,
+            naming::preview_filename,
+            naming::set_filename_template,
+            naming::set_output_directory,
+            naming::would_conflict,
+            overlay::get_live_overlay_state,
+            overlay::hide_overlay,
+            overlay::show_overlay,
+            planner::explain_plan,
+            planner::plan_conversion,
+            player::preview_with_player,
+            plugins::list_plugins,
+            plugins::set_plugin_enabled,
+            power::cancel_batch_end_action,
+            power::get_batch_end_action,
+            power::set_batch_end_action,
+            presets::add_trusted_preset_key,
+            presets::delete_preset,
+            presets::import_preset_from_url,
+            presets::list_presets,
+            presets::save_preset,
+            probe::list_programs,
+            probe::probe_file,
+            profiles::create_profile,
+            profiles::delete_profile,
+            profiles::list_profiles,
+            profiles::switch_profile,
+            queue::cancel_group,
+            queue::create_group,
+            queue::enqueue_files,
+            queue::get_queue,
+            queue::pause_queue,
+            queue::remove_from_queue,
+            queue::resume_queue,
+            queue::set_group_collapsed,
+            queue::set_group_options,
+            queue::set_queue_order,
+            remote::get_remote_settings,
+            remote::set_remote_settings,
+            rules::get_enqueue_rules,
+            rules::set_enqueue_rules,
+            rules::test_enqueue_rules,
+            sample::generate_sample,
+            sandbox::get_runtime_environment,
+            scan::enqueue_folder,
+            scan::qbittorrent_command,
+            scan::scan_folder,
+            settings::get_settings,
+            settings::update_settings,
+            share::get_share_settings,
+            share::set_share_settings,
+            share::share_preview,
+            stream::close_stream_input,
+            stream::list_stream_inputs,
+            stream::open_stream_input,
+            suggest::suggest_preset,
+            support::create_support_bundle,
+            sync::sync_library,
+            thumbnail::generate_thumbnail,
+            transcribe::transcribe_audio,
+            trim::extract_clips,
+            trim::trim_file,
+            verify::verify_audio_bitstream,
+            volume::check_output_volume,
+            watch::list_watch_folders,
+            watch::start_watching,
+            watch::stop_watching,
+            waveform::get_waveform,
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Quitting from the menu or dock skips the window's close request
+            if let tauri::RunEvent::ExitRequested { api, .. } = event {
+                if exit::veto(app) {
+                    api.prevent_exit();
+                }
+            }
+        });
 }
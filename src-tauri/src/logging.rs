@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+
+use crate::store;
+
+const LOG_SETTINGS_FILE: &str = "logging.json";
+
+/// Rotate the log file once it grows past this size
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Number of rotated files kept next to the active one (app.1.log … app.N.log)
+const MAX_ROTATED_LOGS: u32 = 3;
+
+/// Current verbosity, checked on every event so changes apply immediately
+static LEVEL: AtomicU8 = AtomicU8::new(2);
+
+static FILE_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Spans entered on this thread, innermost last
+    static SPAN_STACK: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Persisted logging preferences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSettings {
+    pub level: String,
+    pub file_output: bool,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file_output: false,
+        }
+    }
+}
+
+fn level_to_u8(level: &str) -> Option<u8> {
+    match level.to_lowercase().as_str() {
+        "error" => Some(0),
+        "warn" => Some(1),
+        "info" => Some(2),
+        "debug" => Some(3),
+        "trace" => Some(4),
+        _ => None,
+    }
+}
+
+fn level_rank(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+fn load_settings() -> LogSettings {
+    store::load(LOG_SETTINGS_FILE)
+}
+
+fn save_settings(settings: &LogSettings) -> Result<(), String> {
+    store::save(LOG_SETTINGS_FILE, settings)
+}
+
+/// Directory holding app.log and its rotated predecessors
+pub fn log_dir() -> Result<PathBuf, String> {
+    let dir = crate::app_data_dir()?.join("logs");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create log directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Size-rotated log file
+struct RotatingFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(dir: PathBuf) -> std::io::Result<Self> {
+        let path = dir.join("app.log");
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { dir, file, size })
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size + line.len() as u64 > MAX_LOG_SIZE {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        for i in (1..MAX_ROTATED_LOGS).rev() {
+            let from = self.dir.join(format!("app.{}.log", i));
+            if from.exists() {
+                fs::rename(&from, self.dir.join(format!("app.{}.log", i + 1)))?;
+            }
+        }
+        fs::rename(self.dir.join("app.log"), self.dir.join("app.1.log"))?;
+        *self = Self::open(self.dir.clone())?;
+        Ok(())
+    }
+}
+
+/// Formats fields as ` key=value`, pulling `message` out separately
+#[derive(Default)]
+struct FieldFormatter {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldFormatter {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+struct SpanData {
+    name: &'static str,
+    fields: String,
+    refs: usize,
+}
+
+/// Minimal subscriber: level filtering, span context, stderr + optional rotating file
+struct AppSubscriber {
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, SpanData>>,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+impl AppSubscriber {
+    fn span_context(&self) -> String {
+        let spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        SPAN_STACK.with(|stack| {
+            stack
+                .borrow()
+                .iter()
+                .filter_map(|id| spans.get(id))
+                .map(|span| format!("{}{{{}}}:", span.name, span.fields.trim_start()))
+                .collect()
+        })
+    }
+
+    fn write(&self, line: &str) {
+        eprint!("{}", line);
+
+        if !FILE_OUTPUT.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if file.is_none() {
+            *file = log_dir().ok().and_then(|dir| RotatingFile::open(dir).ok());
+        }
+        if let Some(f) = file.as_mut() {
+            let _ = f.write_line(line);
+        }
+    }
+}
+
+impl Subscriber for AppSubscriber {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // Never cache the answer, the level can change at runtime
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        level_rank(metadata.level()) <= LEVEL.load(Ordering::Relaxed)
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut formatter = FieldFormatter::default();
+        attrs.record(&mut formatter);
+
+        self.spans.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            id,
+            SpanData {
+                name: attrs.metadata().name(),
+                fields: formatter.fields,
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut formatter = FieldFormatter::default();
+        values.record(&mut formatter);
+
+        if let Some(data) = self.spans.lock().unwrap_or_else(|e| e.into_inner()).get_mut(&span.into_u64()) {
+            data.fields.push_str(&formatter.fields);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut formatter = FieldFormatter::default();
+        event.record(&mut formatter);
+
+        let metadata = event.metadata();
+        let line = format!(
+            "{} {:>5} {}{}: {}{}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            metadata.level(),
+            self.span_context(),
+            metadata.target(),
+            formatter.message,
+            formatter.fields,
+        );
+        self.write(&line);
+    }
+
+    fn enter(&self, span: &Id) {
+        SPAN_STACK.with(|stack| stack.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        SPAN_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if let Some(pos) = stack.iter().rposition(|id| *id == span.into_u64()) {
+                stack.remove(pos);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(data) = self.spans.lock().unwrap_or_else(|e| e.into_inner()).get_mut(&span.into_u64()) {
+            data.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        let id = span.into_u64();
+        match spans.get_mut(&id) {
+            Some(data) if data.refs > 1 => {
+                data.refs -= 1;
+                false
+            }
+            Some(_) => {
+                spans.remove(&id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Install the global subscriber using the persisted settings
+pub fn init() {
+    let settings = load_settings();
+    LEVEL.store(level_to_u8(&settings.level).unwrap_or(2), Ordering::Relaxed);
+    FILE_OUTPUT.store(settings.file_output, Ordering::Relaxed);
+
+    let subscriber = AppSubscriber {
+        next_id: AtomicU64::new(1),
+        spans: Mutex::new(HashMap::new()),
+        file: Mutex::new(None),
+    };
+    let _ = tracing::subscriber::set_global_default(subscriber);
+}
+
+/// Get the current logging settings
+#[tauri::command]
+pub fn get_log_settings() -> LogSettings {
+    load_settings()
+}
+
+/// Change log verbosity (error, warn, info, debug, trace), effective immediately
+#[tauri::command]
+pub fn set_log_level(level: String) -> Result<(), String> {
+    let rank = level_to_u8(&level).ok_or_else(|| format!("Unknown log level: {}", level))?;
+    LEVEL.store(rank, Ordering::Relaxed);
+
+    let mut settings = load_settings();
+    settings.level = level.to_lowercase();
+    save_settings(&settings)
+}
+
+/// Enable or disable writing logs to the rotating file in the app data directory
+#[tauri::command]
+pub fn set_log_file_output(enabled: bool) -> Result<(), String> {
+    FILE_OUTPUT.store(enabled, Ordering::Relaxed);
+
+    let mut settings = load_settings();
+    settings.file_output = enabled;
+    save_settings(&settings)
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use crate::language;
+use crate::probe::MediaInfo;
+
+/// Container tags to write instead of the source's; unset fields keep what the source has
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataOverrides {
+    pub title: Option<String>,
+    pub comment: Option<String>,
+}
+
+/// MP4 wants ISO 639-2 codes; newer MKVs tag streams with BCP 47 ones like "en-US"
+fn mp4_language(language: &str) -> Option<&'static str> {
+    let primary = language.split(['-', '_']).next().unwrap_or(language);
+    language::normalize(primary).filter(|code| !matches!(*code, "und" | "zxx"))
+}
+
+/// The source's title and other container tags, with `overrides` on top
+pub fn global_args(overrides: Option<&MetadataOverrides>) -> Vec<String> {
+    let mut args = vec!["-map_metadata".to_string(), "0".to_string()];
+    let overrides = overrides.cloned().unwrap_or_default();
+    for (key, value) in [("title", overrides.title), ("comment", overrides.comment)] {
+        if let Some(value) = value {
+            args.extend(["-metadata".to_string(), format!("{}={}", key, value)]);
+        }
+    }
+    args
+}
+
+/// Language and name of each output stream, taken from the source stream it's made from.
+/// `kept` lists the input index of every output stream, in output order. Names go in both
+/// the track title and the handler name, since players differ in which they show.
+pub fn stream_args(info: &MediaInfo, kept: &[u32]) -> Vec<String> {
+    let mut args = Vec::new();
+    for (output, index) in kept.iter().enumerate() {
+        let Some(stream) = info.streams.iter().find(|s| s.index == *index) else {
+            continue;
+        };
+        if let Some(code) = stream.language.as_deref().and_then(mp4_language) {
+            args.extend([format!("-metadata:s:{}", output), format!("language={}", code)]);
+        }
+        if let Some(title) = stream.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            for key in ["title", "handler_name"] {
+                args.extend([format!("-metadata:s:{}", output), format!("{}={}", key, title)]);
+            }
+        }
+    }
+    args
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::probe::{self, MediaInfo};
+use crate::store;
+
+const METRICS_FILE: &str = "metrics.json";
+
+/// Serializes read-modify-write cycles on the metrics file
+static METRICS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Aggregate counts only - never paths, file names or titles
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageCounters {
+    pub conversions_succeeded: u64,
+    pub conversions_failed: u64,
+    /// "video codec/audio codec" of the source, e.g. "hevc/truehd"
+    pub codec_pairs: BTreeMap<String, u64>,
+    pub error_classes: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MetricsStore {
+    enabled: bool,
+    #[serde(default)]
+    counters: UsageCounters,
+}
+
+/// Exactly what would be sent if the user has opted in
+#[derive(Debug, Serialize)]
+pub struct MetricsPayload {
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    #[serde(flatten)]
+    pub counters: UsageCounters,
+}
+
+fn load() -> MetricsStore {
+    store::load(METRICS_FILE)
+}
+
+fn save(metrics: &MetricsStore) -> Result<(), String> {
+    store::save(METRICS_FILE, metrics)
+}
+
+/// Bucket an error message into a coarse class so no file details leak into metrics
+pub fn classify_error(message: &str) -> &'static str {
+    let message = message.to_lowercase();
+    if message.contains("not found") && message.contains("ffmpeg") {
+        "ffmpeg_missing"
+    } else if message.contains("could not find tag for codec") || message.contains("not supported") {
+        "unsupported_codec"
+    } else if message.starts_with(&probe::NO_STREAMS.to_lowercase()) {
+        "no_streams"
+    } else if message.starts_with(&probe::UNREADABLE_INPUT.to_lowercase())
+        || message.contains("invalid data")
+        || message.contains("moov atom not found")
+    {
+        "invalid_input"
+    } else if message.contains("permission denied") || message.contains("read-only") {
+        "permission_denied"
+    } else if message.contains("no space left") {
+        "disk_full"
+    } else {
+        "other"
+    }
+}
+
+/// Record the outcome of a conversion of a source probed as `info` (no-op unless the user opted in)
+pub fn record_conversion(info: &MediaInfo, result: &Result<String, String>) {
+    if !get_metrics_enabled() {
+        return;
+    }
+
+    let video = info.streams_of("video").next().map_or("none", |s| s.codec.as_str());
+    let audio = info.streams_of("audio").next().map_or("none", |s| s.codec.as_str());
+    let codec_pair = format!("{}/{}", video, audio);
+
+    let _guard = METRICS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut metrics = load();
+    if !metrics.enabled {
+        return;
+    }
+
+    *metrics.counters.codec_pairs.entry(codec_pair).or_insert(0) += 1;
+
+    match result {
+        Ok(_) => metrics.counters.conversions_succeeded += 1,
+        Err(e) => {
+            metrics.counters.conversions_failed += 1;
+            *metrics.counters.error_classes.entry(classify_error(e).to_string()).or_insert(0) += 1;
+        }
+    }
+
+    let _ = save(&metrics);
+}
+
+/// Whether the user has opted in to usage metrics
+#[tauri::command]
+pub fn get_metrics_enabled() -> bool {
+    let _guard = METRICS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load().enabled
+}
+
+/// Opt in or out of usage metrics. Opting out also discards everything collected.
+#[tauri::command]
+pub fn set_metrics_enabled(enabled: bool) -> Result<(), String> {
+    let _guard = METRICS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut metrics = load();
+    metrics.enabled = enabled;
+    if !enabled {
+        metrics.counters = UsageCounters::default();
+    }
+    save(&metrics)
+}
+
+/// Preview the exact payload that would be sent
+#[tauri::command]
+pub fn preview_metrics() -> MetricsPayload {
+    let _guard = METRICS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    MetricsPayload {
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        counters: load().counters,
+    }
+}
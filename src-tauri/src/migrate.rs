@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::store;
+
+/// Records which schema version the files in the app data directory are in
+pub const SCHEMA_FILE: &str = "schema.json";
+
+/// Upgrades the files in one data directory by one schema version
+type Migration = fn(&Path) -> Result<(), String>;
+
+/// One step per schema version: MIGRATIONS[n] upgrades a data directory from version n to n + 1.
+/// Steps run on the app data directory and on every profile directory.
+/// Add new steps at the end; never change or remove old ones.
+const MIGRATIONS: &[Migration] = &[adopt_versioning];
+
+/// The schema version this build reads and writes
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemaInfo {
+    pub version: u32,
+}
+
+/// 0 -> 1: files from before versioning are already in the version 1 format
+fn adopt_versioning(_dir: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// The app data directory followed by each profile directory
+pub fn data_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(root.join("profiles")) {
+        dirs.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()));
+    }
+    dirs
+}
+
+/// JSON files in `dir`, not recursing
+pub fn json_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "json"))
+        .collect()
+}
+
+/// Copy every settings file into `backups/<label>-<time>`, keeping paths relative to the app data directory
+pub fn back_up(root: &Path, label: &str) -> Result<PathBuf, String> {
+    let backup = root
+        .join("backups")
+        .join(format!("{}-{}", label, chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    for dir in data_dirs(root) {
+        for file in json_files(&dir) {
+            let relative = file.strip_prefix(root).unwrap_or(&file);
+            let target = backup.join(relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+            }
+            fs::copy(&file, &target).map_err(|e| format!("Failed to back up {}: {}", file.display(), e))?;
+        }
+    }
+    Ok(backup)
+}
+
+/// Put the backed up files back after a failed migration
+fn restore(root: &Path, backup: &Path) {
+    for dir in data_dirs(backup) {
+        for file in json_files(&dir) {
+            let relative = file.strip_prefix(backup).unwrap_or(&file);
+            if let Err(e) = fs::copy(&file, root.join(relative)) {
+                tracing::error!(file = %file.display(), error = %e, "failed to restore settings file");
+            }
+        }
+    }
+}
+
+fn upgrade(root: &Path, from: u32) -> Result<(), String> {
+    let backup = back_up(root, &format!("schema-v{}", from))?;
+    tracing::info!(from, to = SCHEMA_VERSION, backup = %backup.display(), "migrating app data");
+
+    let result = (|| {
+        for (version, step) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+            for dir in data_dirs(root) {
+                step(&dir).map_err(|e| format!("Migration to schema version {} failed: {}", version + 1, e))?;
+            }
+        }
+        store::save(SCHEMA_FILE, &SchemaInfo { version: SCHEMA_VERSION })
+    })();
+
+    if result.is_err() {
+        restore(root, &backup);
+    }
+    result
+}
+
+/// Bring the app data directory up to SCHEMA_VERSION. Runs once at startup, before anything
+/// else reads settings; on failure the previous files are restored and left as they were.
+pub fn run() {
+    let Ok(root) = crate::app_data_dir() else {
+        return;
+    };
+
+    let version = if root.join(SCHEMA_FILE).exists() {
+        store::load::<SchemaInfo>(SCHEMA_FILE).version
+    } else if data_dirs(&root).iter().all(|d| json_files(d).is_empty()) {
+        // Fresh install: nothing to migrate
+        if let Err(e) = store::save(SCHEMA_FILE, &SchemaInfo { version: SCHEMA_VERSION }) {
+            tracing::warn!(error = %e, "failed to record schema version");
+        }
+        return;
+    } else {
+        0
+    };
+
+    if version > SCHEMA_VERSION {
+        // Written by a newer release; leave it alone rather than guess at a downgrade
+        tracing::warn!(version, supported = SCHEMA_VERSION, "app data is from a newer version");
+    } else if version < SCHEMA_VERSION {
+        if let Err(e) = upgrade(&root, version) {
+            tracing::error!(error = %e, "app data migration failed");
+        }
+    }
+}
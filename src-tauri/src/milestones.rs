@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::store;
+
+/// Emitted with a `BatchMilestone`, for the frontend to show as a system notification
+const MILESTONE_EVENT: &str = "batch-milestone";
+
+const MILESTONES_FILE: &str = "milestones.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MilestoneSettings {
+    pub enabled: bool,
+    /// Share of the batch's estimated work, e.g. 50 when half of it is done
+    pub percents: Vec<u8>,
+    pub last_file_started: bool,
+    /// Shorter batches finish before notifications would help
+    pub min_batch_minutes: u64,
+}
+
+impl Default for MilestoneSettings {
+    fn default() -> Self {
+        Self { enabled: false, percents: vec![50, 90], last_file_started: true, min_batch_minutes: 60 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Milestone {
+    Percent { percent: u8 },
+    LastFileStarted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchMilestone {
+    pub milestone: Milestone,
+    pub finished: usize,
+    pub total: usize,
+    pub remaining_secs: f64,
+    /// RFC 3339 time the batch should be done by
+    pub estimated_finish: String,
+}
+
+/// Where a batch is, as the queue measures it
+pub struct BatchWork {
+    pub finished: usize,
+    pub total: usize,
+    /// Estimated seconds of the finished items, and of the whole batch
+    pub done_secs: f64,
+    pub total_secs: f64,
+    /// Every item has started, so the one running is the last
+    pub last_started: bool,
+}
+
+pub fn load() -> MilestoneSettings {
+    store::load(MILESTONES_FILE)
+}
+
+/// Milestones `work` has reached that aren't in `sent` yet
+pub fn due(settings: &MilestoneSettings, work: &BatchWork, sent: &[Milestone]) -> Vec<Milestone> {
+    if !settings.enabled || work.total < 2 || work.total_secs < (settings.min_batch_minutes * 60) as f64 {
+        return Vec::new();
+    }
+    let percent_done = work.done_secs / work.total_secs * 100.0;
+    let mut due: Vec<Milestone> = settings
+        .percents
+        .iter()
+        .filter(|&&p| percent_done >= f64::from(p))
+        .map(|&percent| Milestone::Percent { percent })
+        .collect();
+    if settings.last_file_started && work.last_started {
+        due.push(Milestone::LastFileStarted);
+    }
+    due.retain(|m| !sent.contains(m));
+    due
+}
+
+pub fn notify(app: &AppHandle, milestone: Milestone, work: &BatchWork) {
+    let remaining_secs = (work.total_secs - work.done_secs).max(0.0);
+    let estimated_finish = chrono::Local::now() + chrono::Duration::seconds(remaining_secs as i64);
+    tracing::info!(?milestone, finished = work.finished, total = work.total, remaining_secs, "batch milestone");
+    let _ = app.emit(
+        MILESTONE_EVENT,
+        &BatchMilestone {
+            milestone,
+            finished: work.finished,
+            total: work.total,
+            remaining_secs,
+            estimated_finish: estimated_finish.to_rfc3339(),
+        },
+    );
+}
+
+#[tauri::command]
+pub fn get_milestone_settings() -> MilestoneSettings {
+    load()
+}
+
+/// Choose when long batches send a notification: at shares of the work done and when
+/// the last file starts
+#[tauri::command]
+pub fn set_milestone_settings(settings: MilestoneSettings) -> Result<(), String> {
+    if settings.percents.iter().any(|&p| p == 0 || p >= 100) {
+        return Err("Milestones must be between 1% and 99%".to_string());
+    }
+    store::save(MILESTONES_FILE, &settings)
+}
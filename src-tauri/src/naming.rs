@@ -0,0 +1,348 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::convert::{self, ConvertOptions};
+use crate::probe::{self, MediaInfo};
+use crate::{sandbox, store};
+
+const OUTPUT_SETTINGS_FILE: &str = "output.json";
+
+/// Characters FAT32/exFAT and Windows refuse in file names
+const RESERVED_CHARS: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+
+/// Device names Windows won't let you create, regardless of extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Which characters are allowed in output file names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilenamePolicy {
+    /// Keep the source name as-is
+    #[default]
+    Keep,
+    /// Transliterate to ASCII (é → e, ß → ss), replacing anything else with `_`
+    Ascii,
+    /// ASCII plus FAT32/Windows restrictions, for old TVs and USB sticks
+    Portable,
+}
+
+/// What a conversion does when its output file already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Leave the existing file and don't convert
+    Skip,
+    Overwrite,
+    /// Write `<stem>_1.mp4`, `<stem>_2.mp4`, ... instead
+    #[default]
+    AutoRename,
+    /// Fail the job, for the frontend to ask first with `would_conflict`
+    Prompt,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSettings {
+    /// Folder conversions write to; next to the source when unset
+    pub directory: Option<String>,
+    /// Output name, e.g. "{stem} [{resolution} {vcodec}]"; the source's name when unset
+    pub filename_template: Option<String>,
+}
+
+/// Tokens a filename template can use
+const TEMPLATE_TOKENS: &[&str] = &["stem", "date", "vcodec", "resolution"];
+
+/// What a filename template's tokens are filled in with
+#[derive(Debug, Clone, Default)]
+pub struct NameFields {
+    pub stem: String,
+    /// Codec of the output's video, e.g. "hevc"
+    pub vcodec: Option<String>,
+    /// Frame height of the output's video
+    pub height: Option<u32>,
+}
+
+/// ASCII spelling of common accented Latin characters and typographic punctuation
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'ç' | 'ć' | 'č' => "c",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'ď' | 'đ' | 'ð' => "d",
+        'Ď' | 'Đ' | 'Ð' => "D",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'ğ' => "g",
+        'Ğ' => "G",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' | 'Į' | 'İ' => "I",
+        'ł' | 'ľ' | 'ĺ' => "l",
+        'Ł' | 'Ľ' | 'Ĺ' => "L",
+        'ñ' | 'ń' | 'ň' => "n",
+        'Ñ' | 'Ń' | 'Ň' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ő' => "O",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ř' | 'ŕ' => "r",
+        'Ř' | 'Ŕ' => "R",
+        'ś' | 'š' | 'ş' => "s",
+        'Ś' | 'Š' | 'Ş' => "S",
+        'ß' => "ss",
+        'ť' | 'ţ' => "t",
+        'Ť' | 'Ţ' => "T",
+        'þ' => "th",
+        'Þ' => "TH",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'ų' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'ý' | 'ÿ' => "y",
+        'Ý' | 'Ÿ' => "Y",
+        'ź' | 'ż' | 'ž' => "z",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        '‘' | '’' | '′' => "'",
+        '“' | '”' => "\"",
+        '–' | '—' => "-",
+        '…' => "...",
+        _ => return None,
+    })
+}
+
+/// Apply a filename policy to a file stem (the name without extension). A stem that comes
+/// out empty under any policy becomes "video", rather than a hidden `.mp4`.
+pub fn sanitize_stem(stem: &str, policy: FilenamePolicy) -> String {
+    let out = if policy == FilenamePolicy::Keep { stem.to_string() } else { restrict_stem(stem, policy) };
+    if out.is_empty() {
+        "video".to_string()
+    } else {
+        out
+    }
+}
+
+/// `stem` in ASCII, and also safe on Windows under the portable policy
+fn restrict_stem(stem: &str, policy: FilenamePolicy) -> String {
+    let mut out = String::with_capacity(stem.len());
+    for c in stem.chars() {
+        if c.is_ascii() && !c.is_ascii_control() {
+            out.push(c);
+        } else if let Some(ascii) = transliterate(c) {
+            out.push_str(ascii);
+        } else {
+            out.push('_');
+        }
+    }
+
+    if policy == FilenamePolicy::Portable {
+        out = out
+            .chars()
+            .map(|c| if RESERVED_CHARS.contains(&c) { '_' } else { c })
+            .collect();
+        out = out.trim_end_matches(['.', ' ']).to_string();
+
+        if RESERVED_NAMES.iter().any(|name| name.eq_ignore_ascii_case(&out)) {
+            out.push('_');
+        }
+    }
+    out
+}
+
+/// Generate a unique output path with extension `ext` that doesn't overwrite existing files
+pub fn get_unique_output_path(input_path: &str, ext: &str, policy: FilenamePolicy) -> PathBuf {
+    let path = PathBuf::from(input_path);
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let stem = sanitize_stem(&path.file_stem().unwrap_or_default().to_string_lossy(), policy);
+
+    numbered(parent.join(format!("{}.{}", stem, ext)))
+}
+
+/// `path`, or the first of `<stem>_1.<ext>`, `<stem>_2.<ext>`, ... that's free
+fn numbered(path: PathBuf) -> PathBuf {
+    let dir = path.parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let ext = path.extension().unwrap_or_default().to_string_lossy().to_string();
+    let mut output_path = path;
+
+    // If file exists, append _1, _2, etc. This also separates names the policy
+    // made identical, e.g. "café" and "cafe"
+    let mut counter = 1;
+    while output_path.exists() {
+        output_path = dir.join(format!("{}_{}.{}", stem, counter, ext));
+        counter += 1;
+    }
+
+    output_path
+}
+
+/// Tokens in `template` that aren't in `TEMPLATE_TOKENS`
+fn unknown_tokens(template: &str) -> Vec<&str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}').map(|(token, _)| token))
+        .filter(|token| !TEMPLATE_TOKENS.contains(token))
+        .collect()
+}
+
+/// Fill in a filename template. Fields that aren't known, such as the codec of a file
+/// ffprobe can't read, are left empty.
+fn render_template(template: &str, fields: &NameFields, policy: FilenamePolicy) -> String {
+    let name = template
+        .replace("{stem}", &fields.stem)
+        .replace("{date}", &chrono::Local::now().format("%Y-%m-%d").to_string())
+        .replace("{vcodec}", fields.vcodec.as_deref().unwrap_or_default())
+        .replace("{resolution}", &fields.height.map(|h| format!("{}p", h)).unwrap_or_default());
+    // Templates name a file, not a path under the output folder
+    let name = name.replace(['/', '\\'], "_");
+    sanitize_stem(name.trim(), policy)
+}
+
+/// `info` is the probed source, when the caller already has it
+fn target_named(
+    input_path: &str,
+    info: Option<&MediaInfo>,
+    dir: Option<&Path>,
+    template: Option<&str>,
+    options: &ConvertOptions,
+) -> PathBuf {
+    let probed = if info.is_none() { probe::probe(input_path).ok() } else { None };
+    let info = info.or(probed.as_ref());
+    let path = Path::new(input_path);
+    let stem = match template {
+        Some(template) => render_template(template, &convert::name_fields(input_path, info, options), options.filename_policy),
+        None => sanitize_stem(&path.file_stem().unwrap_or_default().to_string_lossy(), options.filename_policy),
+    };
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    dir.unwrap_or(parent).join(format!("{}.{}", stem, convert::output_extension(info)))
+}
+
+/// Where converting `input_path` writes before conflicts are resolved: named by the saved
+/// template or after the source, in `dir` or next to the source. `info` is the probed
+/// source, when the caller already has it.
+pub fn conversion_target(input_path: &str, info: Option<&MediaInfo>, dir: Option<&Path>, options: &ConvertOptions) -> PathBuf {
+    target_named(input_path, info, dir, filename_template().as_deref(), options)
+}
+
+/// Where a conversion of `input_path` to `target` writes under `policy`, or None when the
+/// target exists and should be left alone
+pub fn resolve_conflict(input_path: &str, target: &Path, policy: ConflictPolicy) -> Result<Option<PathBuf>, String> {
+    if !target.exists() {
+        return Ok(Some(target.to_path_buf()));
+    }
+    match policy {
+        ConflictPolicy::AutoRename => Ok(Some(numbered(target.to_path_buf()))),
+        ConflictPolicy::Skip => Ok(None),
+        // An .mp4 source converted next to itself
+        ConflictPolicy::Overwrite if target == Path::new(input_path) => {
+            Err(format!("Can't overwrite {}: it's the file being converted", target.display()))
+        }
+        ConflictPolicy::Overwrite => Ok(Some(target.to_path_buf())),
+        ConflictPolicy::Prompt => Err(format!("{} already exists", target.display())),
+    }
+}
+
+/// The folder set with `set_output_directory`, if any. A sandboxed app that can't write
+/// next to its sources falls back to `sandbox::default_output_dir`.
+pub fn output_directory() -> Option<PathBuf> {
+    store::load_profile::<OutputSettings>(OUTPUT_SETTINGS_FILE)
+        .directory
+        .map(PathBuf::from)
+        .or_else(sandbox::default_output_dir)
+}
+
+/// The template set with `set_filename_template`, if any
+pub fn filename_template() -> Option<String> {
+    store::load_profile::<OutputSettings>(OUTPUT_SETTINGS_FILE).filename_template
+}
+
+/// Write converted files to `path` instead of next to their source, e.g. when sources are
+/// on a read-only drive or a network share. None goes back to writing next to the source.
+#[tauri::command]
+pub fn set_output_directory(path: Option<String>) -> Result<(), String> {
+    if let Some(dir) = path.as_deref().filter(|d| !Path::new(d).is_dir()) {
+        return Err(format!("Not a folder: {}", dir));
+    }
+    let mut settings: OutputSettings = store::load_profile(OUTPUT_SETTINGS_FILE);
+    settings.directory = path;
+    store::save_profile(OUTPUT_SETTINGS_FILE, &settings)
+}
+
+/// Name outputs from `template` instead of after their source, using {stem}, {date},
+/// {vcodec} and {resolution}. None or an empty template goes back to the source's name.
+#[tauri::command]
+pub fn set_filename_template(template: Option<String>) -> Result<(), String> {
+    let template = template.filter(|t| !t.trim().is_empty());
+    if let Some(unknown) = template.as_deref().and_then(|t| unknown_tokens(t).first().copied()) {
+        return Err(format!("Unknown filename token {{{}}}; use {{stem}}, {{date}}, {{vcodec}} or {{resolution}}", unknown));
+    }
+    let mut settings: OutputSettings = store::load_profile(OUTPUT_SETTINGS_FILE);
+    settings.filename_template = template;
+    store::save_profile(OUTPUT_SETTINGS_FILE, &settings)
+}
+
+/// The file name converting `path` with `options` would write, using `template` or the
+/// saved one, so the template can be tried before it's saved
+#[tauri::command(async)]
+pub fn preview_filename(
+    path: String,
+    template: Option<String>,
+    options: Option<ConvertOptions>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default().with_named_preset()?;
+    let template = template.or_else(filename_template);
+    if let Some(unknown) = template.as_deref().and_then(|t| unknown_tokens(t).first().copied()) {
+        return Err(format!("Unknown filename token {{{}}}", unknown));
+    }
+    let mut output = target_named(&path, None, output_directory().as_deref(), template.as_deref(), &options);
+    if options.on_conflict == ConflictPolicy::AutoRename {
+        output = numbered(output);
+    }
+    Ok(output.file_name().unwrap_or_default().to_string_lossy().to_string())
+}
+
+/// The existing file converting `path` would replace, so the frontend can ask before
+/// starting a job whose policy is `prompt`. None when there's no conflict.
+#[tauri::command(async)]
+pub fn would_conflict(
+    path: String,
+    output_directory: Option<String>,
+    options: Option<ConvertOptions>,
+) -> Result<Option<String>, String> {
+    let options = options.unwrap_or_default().with_named_preset()?;
+    let dir = output_directory.map(PathBuf::from).or_else(self::output_directory);
+    let target = conversion_target(&path, None, dir.as_deref(), &options);
+    Ok(target.exists().then(|| target.to_string_lossy().to_string()))
+}
+
+#[tauri::command]
+pub fn get_output_directory() -> OutputSettings {
+    store::load_profile(OUTPUT_SETTINGS_FILE)
+}
+
+/// Output next to the input named `<stem><suffix>.<ext>`, made unique like conversions are
+pub fn output_with_suffix(input_path: &str, suffix: &str, ext: &str, policy: FilenamePolicy) -> PathBuf {
+    let path = Path::new(input_path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    // The extension keeps dots in the stem from being read as one
+    let base = path.with_file_name(format!("{}{}.{}", stem, suffix, ext));
+    get_unique_output_path(&base.to_string_lossy(), ext, policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_empty_render_falls_back_to_video_under_every_policy() {
+        let fields = NameFields { stem: "movie".to_string(), vcodec: None, height: None };
+        for policy in [FilenamePolicy::Keep, FilenamePolicy::Ascii, FilenamePolicy::Portable] {
+            assert_eq!(render_template("{vcodec}", &fields, policy), "video", "{:?}", policy);
+            assert_eq!(render_template(" {resolution} ", &fields, policy), "video", "{:?}", policy);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// Something ffmpeg warned about that can make an output that converted fine look wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteKind {
+    /// The source's timestamps went backwards and ffmpeg rewrote them; can cause stutter or A/V drift
+    NonMonotonicDts,
+    /// Packets had missing or invalid timestamps that ffmpeg guessed or discarded
+    InvalidTimestamps,
+    /// Parts of the source couldn't be decoded or demuxed
+    CorruptInput,
+    DroppedFrames,
+    DuplicatedFrames,
+    /// Copying streams failed, so they were re-encoded instead; see `CopyStrategy`
+    CopyFallback,
+    /// The video was re-encoded, which drops the closed captions embedded in it
+    CaptionsStripped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionNote {
+    pub kind: NoteKind,
+    /// How many times it happened, counting ffmpeg's "Last message repeated" lines
+    pub count: u64,
+    /// The first message of this kind, as ffmpeg printed it
+    pub example: String,
+}
+
+/// Substrings of ffmpeg log lines, by the note they add to
+const PATTERNS: &[(&str, NoteKind)] = &[
+    ("Non-monotonic DTS", NoteKind::NonMonotonicDts),
+    // Spelling used by ffmpeg before 5.0
+    ("Non-monotonous DTS", NoteKind::NonMonotonicDts),
+    ("Invalid DTS", NoteKind::InvalidTimestamps),
+    ("Timestamps are unset", NoteKind::InvalidTimestamps),
+    ("pts has no value", NoteKind::InvalidTimestamps),
+    ("Past duration", NoteKind::InvalidTimestamps),
+    ("corrupt", NoteKind::CorruptInput),
+    ("Error while decoding", NoteKind::CorruptInput),
+    ("Invalid data found", NoteKind::CorruptInput),
+    ("Packet corrupt", NoteKind::CorruptInput),
+];
+
+fn add(notes: &mut Vec<ConversionNote>, kind: NoteKind, count: u64, example: &str) {
+    match notes.iter_mut().find(|n| n.kind == kind) {
+        Some(note) => note.count += count,
+        None => notes.push(ConversionNote { kind, count, example: example.to_string() }),
+    }
+}
+
+/// Gathers notable warnings from an ffmpeg run's stderr a line at a time, as it's printed
+#[derive(Default)]
+pub struct NoteCollector {
+    notes: Vec<ConversionNote>,
+    last: Option<NoteKind>,
+}
+
+impl NoteCollector {
+    pub fn line(&mut self, line: &str) {
+        let line = line.trim();
+        // "Last message repeated 12 times" follows the message it repeats
+        if let Some(times) = line
+            .strip_prefix("Last message repeated ")
+            .and_then(|rest| rest.split_whitespace().next()?.parse::<u64>().ok())
+        {
+            if let Some(kind) = self.last {
+                add(&mut self.notes, kind, times, line);
+            }
+            return;
+        }
+        self.last = PATTERNS.iter().find(|(pattern, _)| line.contains(pattern)).map(|&(_, kind)| kind);
+        if let Some(kind) = self.last {
+            add(&mut self.notes, kind, 1, line);
+        }
+    }
+
+    /// The warnings seen, plus the frames the run's progress output says were dropped or
+    /// duplicated
+    pub fn finish(self, dropped_frames: u64, duplicated_frames: u64) -> Vec<ConversionNote> {
+        let mut notes = self.notes;
+        if dropped_frames > 0 {
+            add(&mut notes, NoteKind::DroppedFrames, dropped_frames, &format!("{} frames dropped", dropped_frames));
+        }
+        if duplicated_frames > 0 {
+            add(&mut notes, NoteKind::DuplicatedFrames, duplicated_frames, &format!("{} frames duplicated", duplicated_frames));
+        }
+        notes
+    }
+}
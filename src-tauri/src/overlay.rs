@@ -0,0 +1,160 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, LogicalSize, Manager, WebviewUrl, WebviewWindowBuilder};
+
+use crate::cancel;
+use crate::progress::ConversionProgress;
+use crate::queue::{self, ItemStatus};
+
+/// Label of the overlay window; the frontend renders its compact view for the `#overlay` hash
+const OVERLAY_LABEL: &str = "overlay";
+
+/// Sent to the overlay window with an `OverlayState` whenever it changes
+const OVERLAY_STATE_EVENT: &str = "overlay-state";
+
+const OVERLAY_SIZE: (f64, f64) = (300.0, 96.0);
+
+/// Just the progress bar
+const MINIMIZED_SIZE: (f64, f64) = (300.0, 28.0);
+
+/// What the overlay shows, gathered since the app started
+struct Session {
+    started: Option<Instant>,
+    finished: u32,
+    failed: u32,
+    /// The running conversion's latest progress
+    current: Option<(Option<f64>, Option<f64>)>,
+    minimized: bool,
+}
+
+static SESSION: Mutex<Session> =
+    Mutex::new(Session { started: None, finished: 0, failed: 0, current: None, minimized: false });
+
+fn lock() -> std::sync::MutexGuard<'static, Session> {
+    SESSION.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Conversion status for showing on a stream or screen share. Counts and percentages
+/// only: no file names or paths, which could give away more than the user means to.
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlayState {
+    pub converting: bool,
+    /// The running conversion, 0-100, when its duration is known
+    pub percent: Option<f64>,
+    /// Multiple of real time
+    pub speed: Option<f64>,
+    /// Place of the running item in the queue, e.g. 3 of `queue_total`
+    pub queue_position: Option<usize>,
+    pub queue_total: usize,
+    pub queue_paused: bool,
+    pub remaining_secs: f64,
+    /// Conversions finished and failed since the app started
+    pub finished: u32,
+    pub failed: u32,
+    /// Since the session's first conversion
+    pub session_secs: f64,
+    /// The overlay window is open
+    pub visible: bool,
+    pub minimized: bool,
+}
+
+fn state(app: &AppHandle) -> OverlayState {
+    let queue = queue::get_queue();
+    let running = queue.items.iter().position(|i| i.status == ItemStatus::Running);
+    let session = lock();
+    let (percent, speed) = session.current.unwrap_or_default();
+    OverlayState {
+        converting: session.current.is_some(),
+        percent,
+        speed,
+        queue_position: running.map(|i| i + 1),
+        queue_total: queue.items.len(),
+        queue_paused: queue.paused,
+        remaining_secs: queue::remaining_secs(),
+        finished: session.finished,
+        failed: session.failed,
+        session_secs: session.started.map_or(0.0, |s| s.elapsed().as_secs_f64()),
+        visible: app.get_webview_window(OVERLAY_LABEL).is_some(),
+        minimized: session.minimized,
+    }
+}
+
+/// Push the current state to the overlay window, if it's open
+fn publish(app: &AppHandle) {
+    if app.get_webview_window(OVERLAY_LABEL).is_some() {
+        let _ = app.emit_to(OVERLAY_LABEL, OVERLAY_STATE_EVENT, state(app));
+    }
+}
+
+/// Note a progress update of the running conversion
+pub fn record_progress(app: &AppHandle, progress: &ConversionProgress) {
+    {
+        let mut session = lock();
+        session.started.get_or_insert_with(Instant::now);
+        session.current = Some((progress.percent, progress.speed));
+    }
+    publish(app);
+}
+
+/// Note how a conversion ended; cancelled ones count as neither finished nor failed
+pub fn record_outcome(app: &AppHandle, outcome: &Result<String, String>) {
+    {
+        let mut session = lock();
+        session.current = None;
+        match outcome {
+            Ok(_) => session.finished += 1,
+            Err(e) if cancel::is_cancelled(e) => {}
+            Err(_) => session.failed += 1,
+        }
+    }
+    publish(app);
+}
+
+#[tauri::command]
+pub fn get_live_overlay_state(app: AppHandle) -> OverlayState {
+    state(&app)
+}
+
+/// Open the always-on-top overlay window, or switch an open one between its full and
+/// minimized size
+#[tauri::command(async)]
+pub fn show_overlay(app: AppHandle, minimized: Option<bool>) -> Result<OverlayState, String> {
+    let minimized = {
+        let mut session = lock();
+        if let Some(minimized) = minimized {
+            session.minimized = minimized;
+        }
+        session.minimized
+    };
+    let (width, height) = if minimized { MINIMIZED_SIZE } else { OVERLAY_SIZE };
+    match app.get_webview_window(OVERLAY_LABEL) {
+        Some(window) => {
+            window
+                .set_size(LogicalSize::new(width, height))
+                .map_err(|e| format!("Failed to resize the overlay: {}", e))?;
+        }
+        // Window creation deadlocks on Windows from a sync command, hence async
+        None => {
+            WebviewWindowBuilder::new(&app, OVERLAY_LABEL, WebviewUrl::App("index.html#overlay".into()))
+                .title("mkv2mp4 status")
+                .inner_size(width, height)
+                .always_on_top(true)
+                .decorations(false)
+                .resizable(false)
+                .skip_taskbar(true)
+                .build()
+                .map_err(|e| format!("Failed to open the overlay: {}", e))?;
+        }
+    }
+    publish(&app);
+    Ok(state(&app))
+}
+
+#[tauri::command]
+pub fn hide_overlay(app: AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(OVERLAY_LABEL) {
+        window.close().map_err(|e| format!("Failed to close the overlay: {}", e))?;
+    }
+    Ok(())
+}
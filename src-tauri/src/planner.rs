@@ -0,0 +1,623 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::audio::{self, AudioCodec, AudioTarget, AudioTrackOutput, ChannelLayout};
+use crate::presets::{self, EncodeQuality};
+use crate::probe::{self, MediaInfo, StreamInfo};
+
+/// Video codecs that can be stream-copied into MP4
+const MP4_VIDEO_CODECS: &[&str] = &["h264", "hevc", "av1", "mpeg4", "mpeg2video", "vp9"];
+
+/// Audio codecs that can be stream-copied into MP4 and play on common devices
+const MP4_AUDIO_CODECS: &[&str] = &["aac", "mp3", "ac3", "eac3", "alac", "opus"];
+
+/// Picture codecs MP4 can store as cover art
+const MP4_COVER_CODECS: &[&str] = &["mjpeg", "png"];
+
+/// Text subtitle codecs that can be converted to mov_text
+const TEXT_SUBTITLE_CODECS: &[&str] = &["subrip", "srt", "ass", "ssa", "webvtt", "mov_text", "text"];
+
+/// Audio codecs that are lossless, so transcoding them loses information worth archiving
+const LOSSLESS_AUDIO_CODECS: &[&str] = &["truehd", "mlp", "flac"];
+
+/// What audio MP4 can't carry is encoded to unless the caller picks something else
+const DEFAULT_AUDIO_FALLBACK: AudioTarget = AudioTarget { codec: AudioCodec::Aac, bitrate_kbps: None };
+
+/// What happens to text subtitle tracks (SRT, ASS, WebVTT); image subtitles such as PGS
+/// can't be stored in MP4 and are always dropped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleMode {
+    /// Convert to mov_text, MP4's own text subtitle format
+    #[default]
+    Convert,
+    Drop,
+}
+
+/// What the planner intends to do with a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamAction {
+    Copy,
+    Transcode,
+    Drop,
+}
+
+/// Why the planner decided what it did with a stream, for showing `reason` in other languages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasonCode {
+    Supported,
+    CoverArt,
+    UnsupportedCoverArt,
+    UnsupportedCodec,
+    SubtitlesOff,
+    TextSubtitles,
+    ImageSubtitles,
+    UnsupportedStream,
+    ExtraVideo,
+    ExtraCoverArt,
+    NotSelected,
+    UnusedByTracks,
+    FeedsTracks,
+    CopyFailed,
+}
+
+/// The decision for a single input stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamPlan {
+    pub index: u32,
+    pub kind: String,
+    pub codec: String,
+    pub action: StreamAction,
+    pub target_codec: Option<String>,
+    pub reason: String,
+    pub reason_code: ReasonCode,
+    /// Cover art, kept as the MP4's attached picture
+    pub attached_pic: bool,
+}
+
+/// One audio track the output will have
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioOutputPlan {
+    /// Input stream the track is made from
+    pub source_index: u32,
+    pub action: StreamAction,
+    pub codec: String,
+    pub channels: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub bitrate_kbps: Option<u32>,
+}
+
+/// Per-stream decisions plus a confidence score (0-100) and advice for the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionPlan {
+    pub input_path: String,
+    pub streams: Vec<StreamPlan>,
+    pub audio_outputs: Vec<AudioOutputPlan>,
+    /// Filter graph, maps and codecs that build the requested audio tracks
+    pub audio_args: Vec<String>,
+    /// Video streams besides the primary one, such as cover art or a second angle; each
+    /// is only kept when asked for with `include_video`
+    pub optional_video: Vec<u32>,
+    pub confidence: u8,
+    pub recommendations: Vec<String>,
+}
+
+/// Human-readable codec name for recommendations
+fn display_codec(stream: &StreamInfo) -> String {
+    match stream.codec.as_str() {
+        "dts" if stream.profile.as_deref().is_some_and(|p| p.contains("MA")) => "DTS-HD MA".to_string(),
+        codec => codec_label(codec),
+    }
+}
+
+/// Human-readable name for an ffprobe codec name
+fn codec_label(codec: &str) -> String {
+    match codec {
+        "truehd" => "TrueHD".to_string(),
+        "dts" => "DTS".to_string(),
+        "hdmv_pgs_subtitle" => "PGS".to_string(),
+        "dvd_subtitle" => "VobSub".to_string(),
+        "mov_text" => "mov_text".to_string(),
+        codec if codec.starts_with("pcm_") => "PCM".to_string(),
+        codec => codec.to_uppercase(),
+    }
+}
+
+fn is_lossless_audio(stream: &StreamInfo) -> bool {
+    LOSSLESS_AUDIO_CODECS.contains(&stream.codec.as_str())
+        || stream.codec.starts_with("pcm_")
+        || (stream.codec == "dts" && stream.profile.as_deref().is_some_and(|p| p.contains("MA")))
+}
+
+/// Decide what to do with a single stream; audio MP4 can't carry is encoded to `audio_fallback`
+fn plan_stream(stream: &StreamInfo, audio_fallback: AudioCodec, subtitles: SubtitleMode) -> StreamPlan {
+    let codec = stream.codec.as_str();
+
+    let (action, target_codec, reason, reason_code) = match stream.kind.as_str() {
+        "video" if stream.attached_pic && MP4_COVER_CODECS.contains(&codec) => {
+            (StreamAction::Copy, None, "cover art is stored as an attached picture".to_string(), ReasonCode::CoverArt)
+        }
+        "video" if stream.attached_pic => (
+            StreamAction::Drop,
+            None,
+            format!("{} cover art can't be stored in MP4", display_codec(stream)),
+            ReasonCode::UnsupportedCoverArt,
+        ),
+        "video" if MP4_VIDEO_CODECS.contains(&codec) => {
+            (StreamAction::Copy, None, "supported in MP4".to_string(), ReasonCode::Supported)
+        }
+        "video" => (
+            StreamAction::Transcode,
+            Some("h264".to_string()),
+            format!("{} video is not supported in MP4", display_codec(stream)),
+            ReasonCode::UnsupportedCodec,
+        ),
+        "audio" if MP4_AUDIO_CODECS.contains(&codec) => {
+            (StreamAction::Copy, None, "supported in MP4".to_string(), ReasonCode::Supported)
+        }
+        "audio" => (
+            StreamAction::Transcode,
+            Some(audio_fallback.codec_name().to_string()),
+            format!("{} audio is not supported in MP4", display_codec(stream)),
+            ReasonCode::UnsupportedCodec,
+        ),
+        "subtitle" if TEXT_SUBTITLE_CODECS.contains(&codec) && subtitles == SubtitleMode::Drop => {
+            (StreamAction::Drop, None, "subtitles were turned off".to_string(), ReasonCode::SubtitlesOff)
+        }
+        "subtitle" if TEXT_SUBTITLE_CODECS.contains(&codec) => (
+            StreamAction::Transcode,
+            Some("mov_text".to_string()),
+            "text subtitles are stored as mov_text in MP4".to_string(),
+            ReasonCode::TextSubtitles,
+        ),
+        "subtitle" => (
+            StreamAction::Drop,
+            None,
+            format!("{} image subtitles can't be stored in MP4", display_codec(stream)),
+            ReasonCode::ImageSubtitles,
+        ),
+        _ => (
+            StreamAction::Drop,
+            None,
+            format!("{} streams can't be stored in MP4", stream.kind),
+            ReasonCode::UnsupportedStream,
+        ),
+    };
+
+    StreamPlan {
+        index: stream.index,
+        kind: stream.kind.clone(),
+        codec: stream.codec.clone(),
+        action,
+        target_codec,
+        reason,
+        reason_code,
+        attached_pic: stream.attached_pic,
+    }
+}
+
+/// Score how faithfully the MP4 will represent the source, with advice for each deduction
+fn score_plan(info: &MediaInfo, streams: &[StreamPlan]) -> (u8, Vec<String>) {
+    let mut score: i32 = 100;
+    let mut recommendations = Vec::new();
+
+    if info.streams_of("video").next().is_none() {
+        score -= 20;
+        recommendations.push("No video stream found; consider converting to an audio format instead".to_string());
+    }
+
+    for (plan, stream) in streams.iter().zip(&info.streams) {
+        let name = display_codec(stream);
+        match (plan.kind.as_str(), plan.action) {
+            ("video", StreamAction::Transcode) => {
+                score -= 30;
+                recommendations.push(format!(
+                    "{} video will be re-encoded, which is slow and lossy; keep the MKV if quality matters",
+                    name
+                ));
+                if stream.closed_captions {
+                    score -= 10;
+                    recommendations.push(
+                        "Re-encoding will strip the embedded closed captions; extract them to SRT first".to_string(),
+                    );
+                }
+            }
+            ("video", StreamAction::Copy) if stream.closed_captions => {
+                recommendations.push(
+                    "Embedded closed captions are kept in the video stream; extract them to SRT for players that ignore them"
+                        .to_string(),
+                );
+            }
+            ("audio", StreamAction::Transcode) if is_lossless_audio(stream) => {
+                score -= 15;
+                recommendations.push(format!(
+                    "{} audio will be transcoded; consider keeping the MKV for archival",
+                    name
+                ));
+            }
+            ("audio", StreamAction::Transcode) => {
+                score -= 10;
+                let target = plan.target_codec.as_deref().unwrap_or("aac").to_uppercase();
+                recommendations.push(format!("{} audio will be transcoded to {}", name, target));
+            }
+            ("subtitle", StreamAction::Transcode) if matches!(stream.codec.as_str(), "ass" | "ssa") => {
+                score -= 5;
+                recommendations.push("ASS/SSA subtitle styling will be lost in mov_text".to_string());
+            }
+            // Only dropped when the user turned subtitles off
+            ("subtitle", StreamAction::Drop) if TEXT_SUBTITLE_CODECS.contains(&stream.codec.as_str()) => {}
+            ("subtitle", StreamAction::Drop) => {
+                score -= 10;
+                recommendations.push(format!(
+                    "{} subtitles will be dropped; burn them in or keep the MKV",
+                    name
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(timecode) = &info.timecode {
+        recommendations.push(format!(
+            "Source has timecode {}; enable timecode preservation if the MP4 is going into an editor",
+            timecode
+        ));
+    }
+
+    (score.clamp(0, 100) as u8, recommendations)
+}
+
+/// Audio outputs of a plain conversion: each kept audio stream, copied or transcoded to AAC
+fn default_audio_outputs(info: &MediaInfo, streams: &[StreamPlan], bitrate_kbps: Option<u32>) -> Vec<AudioOutputPlan> {
+    streams
+        .iter()
+        .zip(&info.streams)
+        .filter(|(plan, _)| plan.kind == "audio" && plan.action != StreamAction::Drop)
+        .map(|(plan, stream)| AudioOutputPlan {
+            source_index: stream.index,
+            action: plan.action,
+            codec: plan.target_codec.clone().unwrap_or_else(|| stream.codec.clone()),
+            channels: stream.channels,
+            sample_rate: stream.sample_rate,
+            bitrate_kbps: bitrate_kbps.filter(|_| plan.action == StreamAction::Transcode),
+        })
+        .collect()
+}
+
+/// Resolve requested audio tracks against the source, updating the audio stream decisions to match
+fn custom_audio_outputs(
+    info: &MediaInfo,
+    streams: &mut [StreamPlan],
+    tracks: &[AudioTrackOutput],
+) -> Result<Vec<AudioOutputPlan>, String> {
+    let sources: Vec<&StreamInfo> = info.streams_of("audio").collect();
+    let mut outputs = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        let source = sources
+            .get(track.source as usize)
+            .ok_or_else(|| format!("Audio track {} uses source track {}, which doesn't exist", i + 1, track.source))?;
+        let output = match track.codec {
+            None if !MP4_AUDIO_CODECS.contains(&source.codec.as_str()) => {
+                return Err(format!(
+                    "Audio track {} copies {} audio, which can't be stored in MP4",
+                    i + 1,
+                    display_codec(source)
+                ));
+            }
+            None => AudioOutputPlan {
+                source_index: source.index,
+                action: StreamAction::Copy,
+                codec: source.codec.clone(),
+                channels: source.channels,
+                sample_rate: source.sample_rate,
+                bitrate_kbps: source.bit_rate.map(|b| (b / 1000) as u32),
+            },
+            Some(codec) => AudioOutputPlan {
+                source_index: source.index,
+                action: StreamAction::Transcode,
+                codec: codec.codec_name().to_string(),
+                channels: track.channels.map(|c| c.channels()).or(source.channels),
+                sample_rate: track.sample_rate.or(source.sample_rate),
+                bitrate_kbps: track.bitrate_kbps,
+            },
+        };
+        outputs.push(output);
+    }
+
+    for plan in streams.iter_mut().filter(|p| p.kind == "audio") {
+        let used: Vec<&AudioOutputPlan> = outputs.iter().filter(|o| o.source_index == plan.index).collect();
+        if used.is_empty() {
+            plan.action = StreamAction::Drop;
+            plan.target_codec = None;
+            plan.reason = "not used by any output audio track".to_string();
+            plan.reason_code = ReasonCode::UnusedByTracks;
+        } else if let Some(encoded) = used.iter().find(|o| o.action == StreamAction::Transcode) {
+            plan.action = if used.iter().any(|o| o.action == StreamAction::Copy) {
+                StreamAction::Copy
+            } else {
+                StreamAction::Transcode
+            };
+            plan.target_codec = Some(encoded.codec.clone());
+            plan.reason = format!("feeds {} output audio track(s)", used.len());
+            plan.reason_code = ReasonCode::FeedsTracks;
+        } else {
+            plan.action = StreamAction::Copy;
+            plan.target_codec = None;
+            plan.reason = format!("feeds {} output audio track(s)", used.len());
+            plan.reason_code = ReasonCode::FeedsTracks;
+        }
+    }
+    Ok(outputs)
+}
+
+/// Maps and per-stream codecs that carry out the plan's stream decisions on top of a
+/// `-codec copy`: copied streams stay as they are, the rest are encoded to something MP4
+/// can hold, and dropped streams aren't mapped at all. `audio_fallback` must be the one
+/// the plan was built with. Encoded video follows `quality`.
+pub fn stream_args(
+    plan: &ConversionPlan,
+    audio_fallback: Option<&AudioTarget>,
+    quality: Option<&EncodeQuality>,
+) -> Result<Vec<String>, String> {
+    let audio_fallback = audio_fallback.unwrap_or(&DEFAULT_AUDIO_FALLBACK);
+    let mut args = Vec::new();
+    let kept = plan.streams.iter().filter(|s| s.action != StreamAction::Drop);
+    for (output, stream) in kept.enumerate() {
+        args.extend(["-map".to_string(), format!("0:{}", stream.index)]);
+        if stream.attached_pic {
+            args.extend([format!("-disposition:{}", output), "attached_pic".to_string()]);
+        }
+        if stream.action != StreamAction::Transcode {
+            continue;
+        }
+        match (stream.kind.as_str(), stream.target_codec.as_deref()) {
+            ("audio", _) => args.extend(audio::stream_encode_args(audio_fallback, "mp4", output)?),
+            (_, Some("h264")) => {
+                args.extend([format!("-c:{}", output), "libx264".to_string()]);
+                args.extend(presets::x264_rate_args(quality, 18, Some(output)));
+                args.extend([format!("-pix_fmt:{}", output), "yuv420p".to_string()]);
+            }
+            (_, Some(codec)) => args.extend([format!("-c:{}", output), codec.to_string()]),
+            (_, None) => {}
+        }
+    }
+    Ok(args)
+}
+
+/// Re-encode the `kind` streams ("audio" or "video") the plan would copy, after copying
+/// them failed; audio goes to `audio_fallback`, video to H.264. Cover art is left as is.
+pub fn transcode_copied(plan: &mut ConversionPlan, kind: &str, audio_fallback: Option<&AudioTarget>) {
+    let fallback = audio_fallback.unwrap_or(&DEFAULT_AUDIO_FALLBACK);
+    let target = if kind == "audio" { fallback.codec.codec_name() } else { "h264" };
+    for stream in plan
+        .streams
+        .iter_mut()
+        .filter(|s| s.kind == kind && s.action == StreamAction::Copy && !s.attached_pic)
+    {
+        stream.action = StreamAction::Transcode;
+        stream.target_codec = Some(target.to_string());
+        stream.reason = "copying it into MP4 failed, so it's re-encoded".to_string();
+        stream.reason_code = ReasonCode::CopyFailed;
+        if let Some(output) = plan.audio_outputs.iter_mut().find(|o| o.source_index == stream.index) {
+            output.action = StreamAction::Transcode;
+            output.codec = target.to_string();
+            output.bitrate_kbps = fallback.bitrate_kbps;
+        }
+    }
+}
+
+/// The video stream a conversion is built around: the first that isn't cover art,
+/// preferring one marked as default
+pub fn primary_video(info: &MediaInfo) -> Option<&StreamInfo> {
+    let moving: Vec<&StreamInfo> = info.streams_of("video").filter(|v| !v.attached_pic).collect();
+    moving.iter().find(|v| v.is_default).or(moving.first()).copied()
+}
+
+/// Whether a plain remux of `info` has to re-encode the video, see `plan_stream`
+pub fn transcodes_video(info: &MediaInfo) -> bool {
+    primary_video(info).is_some_and(|v| !MP4_VIDEO_CODECS.contains(&v.codec.as_str()))
+}
+
+/// The "compatibility + original" layout: a stereo AAC track every device can play, as the
+/// default, followed by the original track copied untouched (or as E-AC-3 when MP4 can't
+/// hold it). A source that's already stereo AAC or MP3 is just copied.
+pub fn compatibility_tracks(info: &MediaInfo) -> Result<Vec<AudioTrackOutput>, String> {
+    let sources: Vec<&StreamInfo> = info.streams_of("audio").collect();
+    let position = sources.iter().position(|s| s.is_default).unwrap_or(0);
+    let original = sources.get(position).ok_or("No audio track to make a compatibility track from")?;
+    let track = |codec, channels, title: Option<String>, default| AudioTrackOutput {
+        source: position as u32,
+        codec,
+        bitrate_kbps: None,
+        sample_rate: None,
+        channels,
+        title,
+        default,
+    };
+
+    if matches!(original.codec.as_str(), "aac" | "mp3") && original.channels.is_some_and(|c| c <= 2) {
+        return Ok(vec![track(None, None, original.title.clone(), true)]);
+    }
+    let original_codec = if MP4_AUDIO_CODECS.contains(&original.codec.as_str()) {
+        None
+    } else {
+        Some(AudioCodec::Eac3)
+    };
+    let original_title = original.title.clone().unwrap_or_else(|| display_codec(original));
+    Ok(vec![
+        track(Some(AudioCodec::Aac), Some(ChannelLayout::Stereo), Some("Stereo".to_string()), true),
+        track(original_codec, None, Some(original_title), false),
+    ])
+}
+
+/// Build a conversion plan for already-probed media, optionally with the output's audio
+/// tracks laid out one by one. Audio MP4 can't carry is planned as `audio_fallback`, AAC
+/// by default. Only the primary video is kept, plus the other video streams whose input
+/// indices are in `include_video`.
+pub fn build_plan(
+    info: &MediaInfo,
+    audio_tracks: Option<&[AudioTrackOutput]>,
+    audio_streams: Option<&[u32]>,
+    audio_fallback: Option<&AudioTarget>,
+    subtitles: SubtitleMode,
+    include_video: &[u32],
+) -> Result<ConversionPlan, String> {
+    let fallback = audio_fallback.unwrap_or(&DEFAULT_AUDIO_FALLBACK).codec;
+    let mut streams: Vec<StreamPlan> = info.streams.iter().map(|s| plan_stream(s, fallback, subtitles)).collect();
+
+    let primary = primary_video(info).map(|v| v.index);
+    if let Some(index) = include_video.iter().find(|&&i| info.streams_of("video").all(|v| v.index != i)) {
+        return Err(format!("Stream {} isn't a video stream of this file", index));
+    }
+    let optional_video: Vec<u32> = info
+        .streams_of("video")
+        .map(|v| v.index)
+        .filter(|&i| Some(i) != primary)
+        .collect();
+    let mut skipped = 0;
+    for plan in streams.iter_mut().filter(|p| optional_video.contains(&p.index)) {
+        if !include_video.contains(&plan.index) && plan.action != StreamAction::Drop {
+            plan.action = StreamAction::Drop;
+            plan.target_codec = None;
+            (plan.reason, plan.reason_code) = if plan.attached_pic {
+                ("cover art is only kept when included".to_string(), ReasonCode::ExtraCoverArt)
+            } else {
+                ("only the primary video stream is kept unless others are included".to_string(), ReasonCode::ExtraVideo)
+            };
+            skipped += 1;
+        }
+    }
+    if let Some(keep) = audio_streams {
+        if audio_tracks.is_some() {
+            return Err("Choose either audio streams to keep or per-track audio, not both".to_string());
+        }
+        check_audio_streams(info, keep)?;
+        for plan in streams.iter_mut().filter(|p| p.kind == "audio" && !keep.contains(&p.index)) {
+            plan.action = StreamAction::Drop;
+            plan.target_codec = None;
+            plan.reason = "audio track not selected".to_string();
+            plan.reason_code = ReasonCode::NotSelected;
+        }
+    }
+    let (audio_outputs, audio_args) = match audio_tracks {
+        Some(tracks) => (
+            custom_audio_outputs(info, &mut streams, tracks)?,
+            audio::track_args(tracks, "mp4")?,
+        ),
+        None => (default_audio_outputs(info, &streams, audio_fallback.and_then(|f| f.bitrate_kbps)), Vec::new()),
+    };
+    let (confidence, mut recommendations) = score_plan(info, &streams);
+    if skipped > 0 {
+        recommendations.push(format!(
+            "{} extra video stream(s), such as cover art or a second angle, won't be kept; include them to keep them",
+            skipped
+        ));
+    }
+
+    Ok(ConversionPlan {
+        input_path: info.path.clone(),
+        streams,
+        audio_outputs,
+        audio_args,
+        optional_video,
+        confidence,
+        recommendations,
+    })
+}
+
+/// One decision of a plan in words, e.g. "Audio track 2 (FLAC) transcoded to AAC because
+/// FLAC audio is not supported in MP4". `text` is English; the frontend can put `action`,
+/// `reason_code` and `args` into the user's language instead.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanExplanation {
+    /// Input stream the decision is about
+    pub index: u32,
+    pub action: StreamAction,
+    pub reason_code: ReasonCode,
+    /// Values in `text`: kind, track (1 = first of its kind), codec, and target and
+    /// outputs when there are any
+    pub args: BTreeMap<&'static str, String>,
+    pub text: String,
+}
+
+fn explain_stream(plan: &ConversionPlan, stream: &StreamPlan, track: usize) -> PlanExplanation {
+    let mut kind = stream.kind.clone();
+    if let Some(first) = kind.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    let codec = codec_label(&stream.codec);
+    let mut args = BTreeMap::from([("kind", stream.kind.clone()), ("track", track.to_string()), ("codec", codec.clone())]);
+    let outcome = match (stream.action, &stream.target_codec) {
+        (StreamAction::Copy, Some(target)) => {
+            args.insert("target", codec_label(target));
+            format!("copied and also transcoded to {}", codec_label(target))
+        }
+        (StreamAction::Copy, None) => "copied".to_string(),
+        (StreamAction::Transcode, Some(target)) => {
+            args.insert("target", codec_label(target));
+            format!("transcoded to {}", codec_label(target))
+        }
+        (StreamAction::Transcode, None) => "transcoded".to_string(),
+        (StreamAction::Drop, _) => "dropped".to_string(),
+    };
+    if stream.reason_code == ReasonCode::FeedsTracks {
+        let outputs = plan.audio_outputs.iter().filter(|o| o.source_index == stream.index).count();
+        args.insert("outputs", outputs.to_string());
+    }
+    PlanExplanation {
+        index: stream.index,
+        action: stream.action,
+        reason_code: stream.reason_code,
+        text: format!("{} track {} ({}) {} because {}", kind, track, codec, outcome, stream.reason),
+        args,
+    }
+}
+
+/// Why the plan copies, transcodes or drops each stream of the source, one explanation
+/// per stream in input order
+#[tauri::command]
+pub fn explain_plan(plan: ConversionPlan) -> Vec<PlanExplanation> {
+    let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+    plan.streams
+        .iter()
+        .map(|stream| {
+            let track = seen.entry(stream.kind.as_str()).or_default();
+            *track += 1;
+            explain_stream(&plan, stream, *track)
+        })
+        .collect()
+}
+
+/// Error unless every index in `indices` is one of the file's audio streams
+pub fn check_audio_streams(info: &MediaInfo, indices: &[u32]) -> Result<(), String> {
+    match indices.iter().find(|&&i| info.streams_of("audio").all(|a| a.index != i)) {
+        Some(index) => Err(format!("Stream {} isn't an audio stream of this file", index)),
+        None => Ok(()),
+    }
+}
+
+/// Probe a file and return the conversion plan with its confidence score. With
+/// `audio_tracks`, the plan shows those tracks and the ffmpeg graph that makes them; with
+/// `audio_streams`, only those audio streams are kept; with `audio_fallback`, audio MP4 can't carry is planned as that codec instead of AAC. Extra
+/// video streams listed in `include_video` are kept alongside the primary one.
+#[tauri::command(async)]
+pub fn plan_conversion(
+    path: String,
+    audio_tracks: Option<Vec<AudioTrackOutput>>,
+    audio_streams: Option<Vec<u32>>,
+    audio_fallback: Option<AudioTarget>,
+    subtitles: Option<SubtitleMode>,
+    include_video: Option<Vec<u32>>,
+) -> Result<ConversionPlan, String> {
+    let info = probe::probe(&path)?;
+    build_plan(
+        &info,
+        audio_tracks.as_deref(),
+        audio_streams.as_deref(),
+        audio_fallback.as_ref(),
+        subtitles.unwrap_or_default(),
+        include_video.as_deref().unwrap_or_default(),
+    )
+}
@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Common mpv locations to check on macOS
+#[cfg(target_os = "macos")]
+const MPV_PATHS: &[&str] = &[
+    "/opt/homebrew/bin/mpv",
+    "/usr/local/bin/mpv",
+    "/Applications/mpv.app/Contents/MacOS/mpv",
+];
+
+/// Common mpv locations to check on Windows
+#[cfg(target_os = "windows")]
+const MPV_PATHS: &[&str] = &[
+    "mpv",  // In PATH
+    "C:\\Program Files\\mpv\\mpv.exe",
+];
+
+/// Common mpv locations to check on Linux
+#[cfg(target_os = "linux")]
+const MPV_PATHS: &[&str] = &[
+    "/usr/bin/mpv",
+    "/usr/local/bin/mpv",
+    "/snap/bin/mpv",
+];
+
+fn find_mpv() -> Option<String> {
+    MPV_PATHS
+        .iter()
+        .find(|path| (PathBuf::from(path).exists() || **path == "mpv") && crate::tool_runs(path, "--version"))
+        .map(|path| path.to_string())
+}
+
+/// Open a file in mpv (preferred) or ffplay, starting at `start` seconds.
+/// Returns the name of the player that was launched.
+#[tauri::command]
+pub fn preview_with_player(path: String, start: Option<f64>) -> Result<String, String> {
+    if !Path::new(&path).exists() {
+        return Err(format!("File not found: {}", path));
+    }
+    let start = format!("{:.3}", start.unwrap_or(0.0).max(0.0));
+
+    let (player, name, args) = if let Some(mpv) = find_mpv() {
+        (mpv, "mpv", vec![format!("--start={}", start), path])
+    } else if let Some(ffplay) = crate::find_tool("ffplay") {
+        (ffplay, "ffplay", vec!["-ss".to_string(), start, "-autoexit".to_string(), path])
+    } else {
+        return Err("No player found. Install mpv or an ffmpeg build that includes ffplay.".to_string());
+    };
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &player])
+        .args(&args)
+        .spawn();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&player)
+        .args(&args)
+        .spawn();
+
+    result.map_err(|e| format!("Failed to launch {}: {}", name, e))?;
+    tracing::info!(player = name, "launched preview");
+    Ok(name.to_string())
+}
@@ -0,0 +1,355 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{sandbox, store, suspend};
+
+/// Names of plugins the user has switched on; discovered plugins are off by default
+pub const ENABLED_PLUGINS_FILE: &str = "plugins.json";
+
+const MANIFEST_FILE: &str = "plugin.json";
+
+const DEFAULT_PLUGIN_TIMEOUT_SECS: u64 = 300;
+
+/// Plugins report results as JSON on stdout; cap it so a runaway plugin can't eat memory
+const MAX_PLUGIN_OUTPUT_BYTES: u64 = 1024 * 1024;
+
+/// Where in the pipeline a plugin step runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginStage {
+    /// Before ffmpeg runs; sees the source only
+    PreConvert,
+    /// After a successful conversion; sees the source and the output
+    PostConvert,
+}
+
+/// How a plugin is executed. Only native executables are supported for now;
+/// WASM modules are recognized so they can be reported rather than silently ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginRuntime {
+    Executable,
+    Wasm,
+}
+
+/// Contents of `plugins/<name>/plugin.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    pub runtime: PluginRuntime,
+    /// Path relative to the plugin directory
+    pub entry: String,
+    pub stage: PluginStage,
+    /// Which paths the step needs: "source" and/or "output"
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// What the step produces: "sidecar" files next to the output, after converting, or
+    /// "metadata" tags for the output, before converting
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    pub timeout_secs: Option<u64>,
+}
+
+/// A discovered plugin, including ones that failed to load
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub dir: String,
+    pub manifest: Option<PluginManifest>,
+    pub enabled: bool,
+    pub error: Option<String>,
+}
+
+/// JSON sent to the plugin on stdin
+#[derive(Serialize)]
+struct PluginRequest<'a> {
+    stage: PluginStage,
+    source: Option<&'a str>,
+    output: Option<&'a str>,
+}
+
+/// JSON the plugin prints on stdout
+#[derive(Debug, Default, Deserialize)]
+pub struct PluginResponse {
+    /// Files the plugin wrote to its working directory, by name, to keep next to the output
+    /// as `<output name>.<file name>`
+    #[serde(default)]
+    pub sidecars: Vec<String>,
+    /// Tags to set on the output
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Logged with the step's result
+    pub message: Option<String>,
+}
+
+/// What the enabled plugins of a stage added to the conversion
+#[derive(Debug, Default)]
+pub struct StageResults {
+    /// Tags from pre_convert steps, for ffmpeg to write into the output
+    pub metadata: BTreeMap<String, String>,
+}
+
+fn plugins_dir() -> Result<PathBuf, String> {
+    let dir = crate::app_data_dir()?.join("plugins");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create plugins directory: {}", e))?;
+    Ok(dir)
+}
+
+fn load_manifest(dir: &Path) -> Result<PluginManifest, String> {
+    let json = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .map_err(|e| format!("Failed to read {}: {}", MANIFEST_FILE, e))?;
+    let manifest: PluginManifest = serde_json::from_str(&json)
+        .map_err(|e| format!("Invalid {}: {}", MANIFEST_FILE, e))?;
+
+    // The name goes into the scratch directory's path, which is deleted afterwards
+    let valid_name = !manifest.name.is_empty()
+        && manifest.name.len() <= 64
+        && manifest.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_name {
+        return Err("Plugin names may only use letters, digits, - and _".to_string());
+    }
+    if manifest.runtime == PluginRuntime::Wasm {
+        return Err("WASM plugins are not supported yet".to_string());
+    }
+    if manifest.entry.contains("..") || Path::new(&manifest.entry).is_absolute() {
+        return Err("Plugin entry must be inside the plugin directory".to_string());
+    }
+    if let Some(input) = manifest.inputs.iter().find(|i| !matches!(i.as_str(), "source" | "output")) {
+        return Err(format!("Unknown plugin input: {}", input));
+    }
+    if manifest.stage == PluginStage::PreConvert && manifest.inputs.iter().any(|i| i == "output") {
+        return Err("pre_convert plugins can't read the output".to_string());
+    }
+    for output in &manifest.outputs {
+        match (output.as_str(), manifest.stage) {
+            ("sidecar", PluginStage::PostConvert) | ("metadata", PluginStage::PreConvert) => {}
+            ("sidecar", _) => return Err("Only post_convert plugins can write sidecars".to_string()),
+            // The output's already written by then
+            ("metadata", _) => return Err("Only pre_convert plugins can set metadata".to_string()),
+            (other, _) => return Err(format!("Unknown plugin output: {}", other)),
+        }
+    }
+    if !dir.join(&manifest.entry).is_file() {
+        return Err(format!("Plugin entry not found: {}", manifest.entry));
+    }
+
+    Ok(manifest)
+}
+
+/// Scan the plugins directory for plugin manifests
+pub fn discover_plugins() -> Vec<PluginInfo> {
+    let enabled: Vec<String> = store::load(ENABLED_PLUGINS_FILE);
+    let Ok(entries) = plugins_dir().and_then(|dir| fs::read_dir(dir).map_err(|e| e.to_string())) else {
+        return Vec::new();
+    };
+
+    let mut plugins: Vec<PluginInfo> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .map(|dir| {
+            let (manifest, error) = match load_manifest(&dir) {
+                Ok(manifest) => (Some(manifest), None),
+                Err(e) => (None, Some(e)),
+            };
+            PluginInfo {
+                enabled: manifest.as_ref().is_some_and(|m| enabled.contains(&m.name)),
+                dir: dir.to_string_lossy().to_string(),
+                manifest,
+                error,
+            }
+        })
+        .collect();
+
+    plugins.sort_by(|a, b| a.dir.cmp(&b.dir));
+    plugins
+}
+
+/// Where a plugin's sidecar `name` goes: next to the output, after its name
+fn sidecar_path(output: &str, name: &str) -> PathBuf {
+    let mut path = Path::new(output).with_extension("").into_os_string();
+    path.push(format!(".{}", name));
+    PathBuf::from(path)
+}
+
+/// Keep the sidecars a plugin wrote to `scratch` next to `output`. Only plain files named
+/// directly in `scratch` are taken: a symlink could point at anything the app can read.
+fn keep_sidecars(scratch: &Path, names: &[String], output: &str) -> Result<Vec<PathBuf>, String> {
+    let mut kept = Vec::new();
+    for name in names {
+        let plain = !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\']);
+        let file = scratch.join(name);
+        if !plain || !fs::symlink_metadata(&file).is_ok_and(|m| m.is_file()) {
+            return Err(format!("Plugin sidecar {} isn't a file in its working directory", name));
+        }
+        let target = sidecar_path(output, name);
+        fs::copy(&file, &target).map_err(|e| format!("Failed to copy sidecar to {}: {}", target.display(), e))?;
+        kept.push(target);
+    }
+    Ok(kept)
+}
+
+/// Run one plugin sandboxed, see `sandbox::restricted_command`: it can read the plugin's
+/// own directory and the paths its manifest asks for, write only to a scratch working
+/// directory, and has no network. It also gets a cleared environment and a timeout.
+fn invoke_plugin(dir: &Path, manifest: &PluginManifest, source: &str, output: Option<&str>) -> Result<PluginResponse, String> {
+    let wants = |input: &str| manifest.inputs.iter().any(|i| i == input);
+    let request = PluginRequest {
+        stage: manifest.stage,
+        source: wants("source").then_some(source),
+        output: output.filter(|_| wants("output")),
+    };
+    let mut readable = vec![dir];
+    readable.extend(request.source.map(Path::new));
+    readable.extend(request.output.map(Path::new));
+    let request = serde_json::to_vec(&request).map_err(|e| format!("Failed to serialize request: {}", e))?;
+
+    let scratch = crate::unique_temp_path(&format!("plugin-{}", manifest.name), "d");
+    fs::create_dir_all(&scratch).map_err(|e| format!("Failed to create plugin scratch dir: {}", e))?;
+    let result = run_restricted(dir, manifest, &readable, &scratch, &request).and_then(|mut response| {
+        let declares = |kind: &str| manifest.outputs.iter().any(|o| o == kind);
+        if !declares("metadata") && !response.metadata.is_empty() {
+            tracing::warn!("plugin set metadata without declaring it, ignoring it");
+            response.metadata.clear();
+        }
+        match output.filter(|_| declares("sidecar")) {
+            Some(output) => {
+                for sidecar in keep_sidecars(&scratch, &response.sidecars, output)? {
+                    tracing::info!(sidecar = %sidecar.display(), "kept plugin sidecar");
+                }
+            }
+            None if !response.sidecars.is_empty() => tracing::warn!("plugin wrote sidecars without declaring them, ignoring them"),
+            None => {}
+        }
+        Ok(response)
+    });
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+/// Start the plugin under the sandbox, send it `request` and wait for its response
+fn run_restricted(
+    dir: &Path,
+    manifest: &PluginManifest,
+    readable: &[&Path],
+    scratch: &Path,
+    request: &[u8],
+) -> Result<PluginResponse, String> {
+    let mut command = sandbox::restricted_command(&dir.join(&manifest.entry), readable, scratch)?;
+    command
+        .env_clear()
+        .current_dir(scratch)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+
+    // Keep just enough environment for the plugin's own interpreter to start
+    for key in ["PATH", "SYSTEMROOT"] {
+        if let Some(value) = std::env::var_os(key) {
+            command.env(key, value);
+        }
+    }
+    // The scratch directory is the only place it can write temp files
+    for key in ["TMPDIR", "TEMP", "TMP"] {
+        command.env(key, scratch);
+    }
+
+    let mut child = command.spawn().map_err(|e| format!("Failed to start plugin: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(request);
+    }
+
+    let stdout = child.stdout.take();
+    let reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(stdout) = stdout {
+            let _ = stdout.take(MAX_PLUGIN_OUTPUT_BYTES).read_to_end(&mut buf);
+        }
+        buf
+    });
+
+    let timeout = Duration::from_secs(manifest.timeout_secs.unwrap_or(DEFAULT_PLUGIN_TIMEOUT_SECS));
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() > timeout => {
+                suspend::kill(&mut child);
+                let _ = child.wait();
+                return Err(format!("Plugin timed out after {}s", timeout.as_secs()));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(100)),
+            Err(e) => return Err(format!("Failed to wait for plugin: {}", e)),
+        }
+    };
+
+    let stdout = reader.join().unwrap_or_default();
+
+    if !status.success() {
+        return Err(format!("Plugin exited with {}", status));
+    }
+    if stdout.iter().all(u8::is_ascii_whitespace) {
+        return Ok(PluginResponse::default());
+    }
+    serde_json::from_slice(&stdout).map_err(|e| format!("Invalid plugin response: {}", e))
+}
+
+/// Run every enabled plugin registered for `stage`. Failures are logged, never fatal.
+pub fn run_stage(stage: PluginStage, source: &str, output: Option<&str>) -> StageResults {
+    let mut results = StageResults::default();
+    for plugin in discover_plugins().into_iter().filter(|p| p.enabled) {
+        let Some(manifest) = plugin.manifest.filter(|m| m.stage == stage) else {
+            continue;
+        };
+
+        let _span = tracing::info_span!("plugin", name = %manifest.name).entered();
+        match invoke_plugin(Path::new(&plugin.dir), &manifest, source, output) {
+            Ok(response) => {
+                tracing::info!(message = response.message.as_deref().unwrap_or(""), "plugin step finished");
+                for (key, value) in response.metadata {
+                    // The key goes into ffmpeg's -metadata key=value
+                    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                        tracing::warn!(%key, "ignoring plugin metadata with an invalid key");
+                        continue;
+                    }
+                    results.metadata.insert(key, value);
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "plugin step failed"),
+        }
+    }
+    results
+}
+
+/// List plugins found in the plugins directory
+#[tauri::command]
+pub fn list_plugins() -> Vec<PluginInfo> {
+    discover_plugins()
+}
+
+/// Enable or disable a plugin by name
+#[tauri::command]
+pub fn set_plugin_enabled(name: String, enabled: bool) -> Result<(), String> {
+    let known = discover_plugins()
+        .iter()
+        .any(|p| p.manifest.as_ref().is_some_and(|m| m.name == name));
+    if enabled && !known {
+        return Err(format!("No valid plugin named {}", name));
+    }
+
+    let mut names: Vec<String> = store::load(ENABLED_PLUGINS_FILE);
+    names.retain(|n| n != &name);
+    if enabled {
+        names.push(name);
+    }
+    store::save(ENABLED_PLUGINS_FILE, &names)
+}
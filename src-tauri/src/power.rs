@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Emitted every second of the countdown with a `Countdown`
+const COUNTDOWN_EVENT: &str = "batch-end-countdown";
+
+/// Emitted with the action when a countdown is cancelled, by the user or by new work arriving
+const COUNTDOWN_CANCELLED_EVENT: &str = "batch-end-cancelled";
+
+/// Time the user gets to cancel before the action runs
+const COUNTDOWN_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchEndAction {
+    #[default]
+    None,
+    Sleep,
+    Hibernate,
+    Shutdown,
+    QuitApp,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Countdown {
+    pub action: BatchEndAction,
+    pub seconds_left: u64,
+}
+
+/// Not saved: a shutdown chosen for one overnight run shouldn't fire after the next one
+static ACTION: Mutex<BatchEndAction> = Mutex::new(BatchEndAction::None);
+
+/// Id of the countdown in progress, if any; cancelling clears it
+static COUNTDOWN: Mutex<Option<u64>> = Mutex::new(None);
+
+static NEXT_COUNTDOWN_ID: AtomicU64 = AtomicU64::new(1);
+
+fn run(program: &str, args: &[&str]) -> Result<(), String> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", program, status));
+    }
+    Ok(())
+}
+
+fn perform(app: &AppHandle, action: BatchEndAction) -> Result<(), String> {
+    tracing::info!(?action, "running end-of-batch action");
+    match action {
+        BatchEndAction::None => Ok(()),
+        BatchEndAction::QuitApp => {
+            app.exit(0);
+            Ok(())
+        }
+        #[cfg(target_os = "windows")]
+        BatchEndAction::Sleep => run("rundll32.exe", &["powrprof.dll,SetSuspendState", "0,1,0"]),
+        #[cfg(target_os = "windows")]
+        BatchEndAction::Hibernate => run("shutdown", &["/h"]),
+        #[cfg(target_os = "windows")]
+        BatchEndAction::Shutdown => run("shutdown", &["/s", "/t", "0"]),
+        #[cfg(target_os = "macos")]
+        BatchEndAction::Sleep => run("pmset", &["sleepnow"]),
+        #[cfg(target_os = "macos")]
+        BatchEndAction::Hibernate => Err("Hibernate isn't available on macOS; use sleep".to_string()),
+        #[cfg(target_os = "macos")]
+        BatchEndAction::Shutdown => run("osascript", &["-e", "tell application \"System Events\" to shut down"]),
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        BatchEndAction::Sleep => run("systemctl", &["suspend"]),
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        BatchEndAction::Hibernate => run("systemctl", &["hibernate"]),
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        BatchEndAction::Shutdown => run("systemctl", &["poweroff"]),
+    }
+}
+
+/// Whether the machine is running on battery rather than mains power
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> bool {
+    // First line is "Now drawing from 'Battery Power'" or "'AC Power'"
+    Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
+
+/// Whether the machine is running on battery rather than mains power
+#[cfg(target_os = "windows")]
+pub fn on_battery() -> bool {
+    // BatteryStatus 1 means discharging; desktops have no Win32_Battery at all
+    Command::new("powershell")
+        .args(["-NoProfile", "-Command", "(Get-CimInstance Win32_Battery).BatteryStatus"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "1")
+}
+
+/// Whether the machine is running on battery rather than mains power
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> bool {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let mut supplies = entries.flatten().map(|e| e.path()).filter(|p| {
+        std::fs::read_to_string(p.join("type")).is_ok_and(|t| t.trim() == "Mains")
+    });
+    // No mains adapter listed means a desktop, not an unplugged laptop
+    supplies.next().is_some_and(|mains| std::fs::read_to_string(mains.join("online")).is_ok_and(|o| o.trim() == "0"))
+}
+
+/// Called when the queue runs dry: start the countdown for the chosen action, if any
+pub fn batch_finished(app: &AppHandle) {
+    let action = *ACTION.lock().unwrap_or_else(|e| e.into_inner());
+    if action == BatchEndAction::None {
+        return;
+    }
+
+    let id = NEXT_COUNTDOWN_ID.fetch_add(1, Ordering::Relaxed);
+    *COUNTDOWN.lock().unwrap_or_else(|e| e.into_inner()) = Some(id);
+    let app = app.clone();
+    thread::spawn(move || {
+        for seconds_left in (1..=COUNTDOWN_SECS).rev() {
+            if *COUNTDOWN.lock().unwrap_or_else(|e| e.into_inner()) != Some(id) {
+                return;
+            }
+            let _ = app.emit(COUNTDOWN_EVENT, &Countdown { action, seconds_left });
+            thread::sleep(Duration::from_secs(1));
+        }
+        // Claim the countdown so a cancel arriving now can't race the action
+        {
+            let mut countdown = COUNTDOWN.lock().unwrap_or_else(|e| e.into_inner());
+            if *countdown != Some(id) {
+                return;
+            }
+            *countdown = None;
+        }
+        *ACTION.lock().unwrap_or_else(|e| e.into_inner()) = BatchEndAction::None;
+        if let Err(e) = perform(&app, action) {
+            tracing::error!(?action, error = %e, "end-of-batch action failed");
+        }
+    });
+}
+
+/// Stop the countdown if one is running; the action stays selected for the next batch
+pub fn cancel_countdown(app: &AppHandle) {
+    if COUNTDOWN.lock().unwrap_or_else(|e| e.into_inner()).take().is_some() {
+        let action = *ACTION.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = app.emit(COUNTDOWN_CANCELLED_EVENT, action);
+    }
+}
+
+/// Choose what happens when the queue finishes
+#[tauri::command]
+pub fn set_batch_end_action(action: BatchEndAction) {
+    *ACTION.lock().unwrap_or_else(|e| e.into_inner()) = action;
+}
+
+/// The action that will run when the queue finishes
+#[tauri::command]
+pub fn get_batch_end_action() -> BatchEndAction {
+    *ACTION.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Cancel the countdown shown after a batch finishes
+#[tauri::command]
+pub fn cancel_batch_end_action(app: AppHandle) {
+    cancel_countdown(&app);
+}
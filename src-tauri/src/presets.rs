@@ -0,0 +1,435 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+
+use crate::h264::H264Constraints;
+use crate::store;
+
+const PRESETS_FILE: &str = "presets.json";
+
+/// Base64 ed25519 public keys whose signed presets may be imported
+pub const TRUSTED_KEYS_FILE: &str = "trusted_preset_keys.json";
+
+/// Video encoders a preset may select
+const ALLOWED_VIDEO_CODECS: &[&str] = &[
+    "copy", "libx264", "libx265", "libsvtav1", "libvpx-vp9",
+    "h264_videotoolbox", "hevc_videotoolbox",
+    "h264_nvenc", "hevc_nvenc", "h264_qsv", "hevc_qsv", "h264_amf", "hevc_amf",
+];
+
+/// Audio encoders a preset may select
+const ALLOWED_AUDIO_CODECS: &[&str] = &[
+    "copy", "aac", "libfdk_aac", "ac3", "eac3", "libmp3lame", "libopus", "alac", "flac",
+];
+
+/// x264/x265-style speed presets; SVT-AV1 presets are numeric and checked separately
+const ALLOWED_ENCODER_PRESETS: &[&str] = &[
+    "ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow",
+];
+
+/// Extra ffmpeg flags a preset may pass through, each followed by exactly one value.
+/// Anything that can name files, add inputs or build filter graphs is deliberately absent.
+const ALLOWED_EXTRA_FLAGS: &[&str] = &[
+    "-movflags", "-tune", "-profile:v", "-level", "-pix_fmt", "-g", "-bf", "-refs",
+    "-maxrate", "-bufsize", "-ac", "-ar", "-tag:v",
+];
+
+/// Shared presets are small; refuse anything bigger before parsing
+const MAX_PRESET_DOWNLOAD_BYTES: usize = 64 * 1024;
+
+fn default_copy() -> String {
+    "copy".to_string()
+}
+
+/// A named set of conversion options. Unknown keys are refused, so a typo in a saved or
+/// imported preset is an error rather than a setting that silently does nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// "copy" or an encoder from the allowed list
+    #[serde(default = "default_copy")]
+    pub video_codec: String,
+    #[serde(default = "default_copy")]
+    pub audio_codec: String,
+    pub crf: Option<u8>,
+    pub video_bitrate_kbps: Option<u32>,
+    pub audio_bitrate_kbps: Option<u32>,
+    pub encoder_preset: Option<String>,
+    pub max_height: Option<u32>,
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// Device limits for H.264 output, enforced while encoding and checked afterwards
+    #[serde(default)]
+    pub h264: Option<H264Constraints>,
+    /// SVT-AV1 film-grain synthesis strength (1-50): grain is removed before encoding and
+    /// re-synthesized on playback, which saves a lot of bits on grainy film
+    #[serde(default)]
+    pub film_grain: Option<u8>,
+    /// Keep the preset's software encoder even when a hardware one would save battery
+    #[serde(default)]
+    pub quality_critical: bool,
+}
+
+/// Rate control and speed for re-encodes the app decides on itself: video that MP4 can't
+/// carry, burned-in subtitles, social framing and guardrails. These use libx264, and AAC
+/// unless `audio_fallback` says otherwise. Presets set their own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncodeQuality {
+    /// 0-51, lower is better; each re-encode has its own default
+    pub crf: Option<u8>,
+    /// Target this bitrate instead of a CRF
+    pub video_bitrate_kbps: Option<u32>,
+    /// x264 speed, "ultrafast" to "veryslow"; defaults to "medium"
+    pub encoder_preset: Option<String>,
+    /// For encoded audio that doesn't set its own bitrate
+    pub audio_bitrate_kbps: Option<u32>,
+}
+
+impl EncodeQuality {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.crf.is_some() && self.video_bitrate_kbps.is_some() {
+            return Err("Choose either a CRF or a target bitrate, not both".to_string());
+        }
+        if self.crf.is_some_and(|crf| crf > 51) {
+            return Err("CRF must be between 0 and 51".to_string());
+        }
+        if self.video_bitrate_kbps.is_some_and(|kbps| !(100..=200_000).contains(&kbps)) {
+            return Err("Video bitrate must be between 100 and 200000 kbps".to_string());
+        }
+        if let Some(preset) = self.encoder_preset.as_deref().filter(|p| !ALLOWED_ENCODER_PRESETS.contains(p)) {
+            return Err(format!("Encoder preset not allowed: {}", preset));
+        }
+        if self.audio_bitrate_kbps.is_some_and(|kbps| !(32..=640).contains(&kbps)) {
+            return Err("Audio bitrate must be between 32 and 640 kbps".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// libx264 speed and rate control for a re-encode whose own default is `default_crf`,
+/// adjusted by `quality`. With `output` the options apply to that output stream only.
+pub fn x264_rate_args(quality: Option<&EncodeQuality>, default_crf: u8, output: Option<usize>) -> Vec<String> {
+    let flag = |name: &str| match output {
+        Some(i) => format!("-{}:{}", name, i),
+        None if name == "b" => "-b:v".to_string(),
+        None => format!("-{}", name),
+    };
+    let quality = quality.cloned().unwrap_or_default();
+    let speed = quality.encoder_preset.unwrap_or_else(|| "medium".to_string());
+    let mut args = vec![flag("preset"), speed];
+    match quality.video_bitrate_kbps {
+        Some(kbps) => args.extend([flag("b"), format!("{}k", kbps)]),
+        None => args.extend([flag("crf"), quality.crf.unwrap_or(default_crf).to_string()]),
+    }
+    args
+}
+
+/// A preset as distributed: the preset plus an ed25519 signature over its canonical JSON
+#[derive(Deserialize)]
+struct SignedPreset {
+    preset: serde_json::Value,
+    /// Base64 ed25519 public key of the author
+    public_key: String,
+    /// Base64 signature over `preset` serialized with sorted keys and no whitespace
+    signature: String,
+}
+
+impl Preset {
+    /// Reject anything outside the allowed option set
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.name.len() > 64 {
+            return Err("Preset name must be 1-64 characters".to_string());
+        }
+        if !ALLOWED_VIDEO_CODECS.contains(&self.video_codec.as_str()) {
+            return Err(format!("Video codec not allowed: {}", self.video_codec));
+        }
+        if !ALLOWED_AUDIO_CODECS.contains(&self.audio_codec.as_str()) {
+            return Err(format!("Audio codec not allowed: {}", self.audio_codec));
+        }
+        if self.crf.is_some_and(|crf| crf > 63) {
+            return Err("CRF must be between 0 and 63".to_string());
+        }
+        if let Some(preset) = &self.encoder_preset {
+            let numeric = preset.parse::<u8>().is_ok_and(|p| p <= 13);
+            if !numeric && !ALLOWED_ENCODER_PRESETS.contains(&preset.as_str()) {
+                return Err(format!("Encoder preset not allowed: {}", preset));
+            }
+        }
+
+        if let Some(h264) = &self.h264 {
+            if !self.video_codec.starts_with("h264_") && self.video_codec != "libx264" {
+                return Err("H.264 limits need an H.264 video codec".to_string());
+            }
+            h264.validate()?;
+        }
+
+        if let Some(grain) = self.film_grain {
+            if self.video_codec != "libsvtav1" {
+                return Err("Film grain synthesis needs the libsvtav1 encoder".to_string());
+            }
+            if !(1..=50).contains(&grain) {
+                return Err("Film grain must be between 1 and 50".to_string());
+            }
+        }
+
+        if !self.extra_args.len().is_multiple_of(2) {
+            return Err("Extra arguments must be flag/value pairs".to_string());
+        }
+        for pair in self.extra_args.chunks(2) {
+            let (flag, value) = (&pair[0], &pair[1]);
+            if !ALLOWED_EXTRA_FLAGS.contains(&flag.as_str()) {
+                return Err(format!("Argument not allowed in presets: {}", flag));
+            }
+            let value_ok = !value.is_empty()
+                && !value.starts_with('-')
+                && value.chars().all(|c| c.is_ascii_alphanumeric() || "_.:+".contains(c));
+            if !value_ok {
+                return Err(format!("Invalid value for {}: {}", flag, value));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Output size for a `width`x`height` source: scaled down to `max_height`, never up
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.max_height {
+            Some(max) if height > max => ((width * max / height) & !1, max),
+            _ => (width, height),
+        }
+    }
+
+    /// Encoding arguments for this preset, given the source's frame size
+    pub fn ffmpeg_args(&self, source_size: Option<(u32, u32)>) -> Result<Vec<String>, String> {
+        let mut args = vec!["-c:v".to_string(), self.video_codec.clone()];
+        if self.video_codec != "copy" {
+            if let Some(crf) = self.crf {
+                args.extend(["-crf".to_string(), crf.to_string()]);
+            }
+            if let Some(kbps) = self.video_bitrate_kbps {
+                args.extend(["-b:v".to_string(), format!("{}k", kbps)]);
+            }
+            if let Some(preset) = &self.encoder_preset {
+                args.extend(["-preset".to_string(), preset.clone()]);
+            }
+            if let Some(max) = self.max_height {
+                args.extend(["-vf".to_string(), format!("scale=-2:'min(ih,{})'", max)]);
+            }
+            if let Some(h264) = &self.h264 {
+                let (width, height) = source_size.ok_or("Can't apply H.264 limits: source frame size is unknown")?;
+                let (width, height) = self.output_size(width, height);
+                args.extend(h264.args(width, height)?);
+            }
+            if let Some(grain) = self.film_grain {
+                args.extend(["-svtav1-params".to_string(), format!("film-grain={}:film-grain-denoise=1", grain)]);
+            }
+        }
+
+        args.extend(["-c:a".to_string(), self.audio_codec.clone()]);
+        if let Some(kbps) = self.audio_bitrate_kbps.filter(|_| self.audio_codec != "copy") {
+            args.extend(["-b:a".to_string(), format!("{}k", kbps)]);
+        }
+        // Last, so a preset's own -maxrate etc. win over the defaults above
+        args.extend(self.extra_args.iter().cloned());
+        Ok(args)
+    }
+}
+
+/// Presets that ship with the app; custom presets can't reuse their names
+fn builtin_presets() -> Vec<Preset> {
+    let preset = |name: &str, description: &str, video_codec: &str, audio_codec: &str| Preset {
+        name: name.to_string(),
+        description: description.to_string(),
+        video_codec: video_codec.to_string(),
+        audio_codec: audio_codec.to_string(),
+        crf: None,
+        video_bitrate_kbps: None,
+        audio_bitrate_kbps: None,
+        encoder_preset: None,
+        max_height: None,
+        extra_args: Vec::new(),
+        h264: None,
+        film_grain: None,
+        quality_critical: false,
+    };
+    vec![
+        preset("Remux only", "Copy every stream into MP4 without re-encoding", "copy", "copy"),
+        Preset {
+            crf: Some(20),
+            audio_bitrate_kbps: Some(160),
+            encoder_preset: Some("medium".to_string()),
+            extra_args: ["-pix_fmt", "yuv420p", "-movflags", "+faststart"].map(String::from).to_vec(),
+            ..preset("Compatibility H.264/AAC", "Plays on nearly any device or browser", "libx264", "aac")
+        },
+        Preset {
+            crf: Some(26),
+            audio_bitrate_kbps: Some(128),
+            encoder_preset: Some("medium".to_string()),
+            // Apple players only accept HEVC in MP4 tagged hvc1
+            extra_args: ["-tag:v", "hvc1", "-pix_fmt", "yuv420p"].map(String::from).to_vec(),
+            ..preset("Small file HEVC", "About half the size of H.264 at similar quality; slower to encode", "libx265", "aac")
+        },
+    ]
+}
+
+/// User-installed presets
+pub fn load_custom_presets() -> Vec<Preset> {
+    store::load_profile(PRESETS_FILE)
+}
+
+/// A built-in or custom preset by name
+pub fn find_preset(name: &str) -> Option<Preset> {
+    builtin_presets().into_iter().chain(load_custom_presets()).find(|p| p.name == name)
+}
+
+/// Check a preset can be saved as a custom one
+fn validate_custom(preset: &Preset) -> Result<(), String> {
+    preset.validate()?;
+    if builtin_presets().iter().any(|p| p.name == preset.name) {
+        return Err(format!("\"{}\" is a built-in preset; save yours under another name", preset.name));
+    }
+    Ok(())
+}
+
+/// Replace every custom preset at once
+pub fn replace_custom_presets(presets: &[Preset]) -> Result<(), String> {
+    for (i, preset) in presets.iter().enumerate() {
+        validate_custom(preset)?;
+        if presets[..i].iter().any(|p| p.name == preset.name) {
+            return Err(format!("Two presets are named {}", preset.name));
+        }
+    }
+    store::save_profile(PRESETS_FILE, &presets)
+}
+
+/// Add or replace a user preset by name
+pub fn install_preset(preset: Preset) -> Result<(), String> {
+    validate_custom(&preset)?;
+    let mut presets = load_custom_presets();
+    presets.retain(|p| p.name != preset.name);
+    presets.push(preset);
+    store::save_profile(PRESETS_FILE, &presets)
+}
+
+/// Built-in presets followed by the user's own
+#[tauri::command]
+pub fn list_presets() -> Vec<Preset> {
+    let mut presets = builtin_presets();
+    presets.extend(load_custom_presets());
+    presets
+}
+
+/// Add a custom preset, or replace the one with the same name
+#[tauri::command]
+pub fn save_preset(preset: Preset) -> Result<(), String> {
+    install_preset(preset)
+}
+
+#[tauri::command]
+pub fn delete_preset(name: String) -> Result<(), String> {
+    let mut presets = load_custom_presets();
+    let before = presets.len();
+    presets.retain(|p| p.name != name);
+    if presets.len() == before {
+        return Err(format!("No custom preset named {}", name));
+    }
+    store::save_profile(PRESETS_FILE, &presets)
+}
+
+/// Serialize JSON with object keys sorted and no whitespace, the form preset authors sign
+fn canonical_json(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|key| format!("{}:{}", serde_json::Value::from(key.as_str()), canonical_json(&map[key])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        serde_json::Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Check the envelope's signature against a trusted key and return the preset inside
+fn verify_signed_preset(bytes: &[u8]) -> Result<Preset, String> {
+    let signed: SignedPreset = serde_json::from_slice(bytes)
+        .map_err(|e| format!("Invalid preset file: {}", e))?;
+
+    let trusted: Vec<String> = store::load(TRUSTED_KEYS_FILE);
+    if !trusted.contains(&signed.public_key) {
+        return Err("Preset is signed by an untrusted key".to_string());
+    }
+
+    let public_key = BASE64
+        .decode(&signed.public_key)
+        .map_err(|e| format!("Invalid public key: {}", e))?;
+    let signature = BASE64
+        .decode(&signed.signature)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+
+    let message = canonical_json(&signed.preset);
+    UnparsedPublicKey::new(&ED25519, &public_key)
+        .verify(message.as_bytes(), &signature)
+        .map_err(|_| "Preset signature does not match".to_string())?;
+
+    serde_json::from_value(signed.preset).map_err(|e| format!("Invalid preset: {}", e))
+}
+
+/// Trust an author's base64 ed25519 public key for preset imports
+#[tauri::command]
+pub fn add_trusted_preset_key(public_key: String) -> Result<(), String> {
+    let decoded = BASE64
+        .decode(public_key.trim())
+        .map_err(|e| format!("Invalid public key: {}", e))?;
+    if decoded.len() != 32 {
+        return Err("Public key must be a 32-byte ed25519 key".to_string());
+    }
+
+    let mut trusted: Vec<String> = store::load(TRUSTED_KEYS_FILE);
+    if !trusted.iter().any(|k| k == public_key.trim()) {
+        trusted.push(public_key.trim().to_string());
+    }
+    store::save(TRUSTED_KEYS_FILE, &trusted)
+}
+
+/// Download a signed preset, verify and validate it, then install it
+#[tauri::command]
+pub async fn import_preset_from_url(url: String) -> Result<Preset, String> {
+    if !url.starts_with("https://") {
+        return Err("Presets can only be imported over HTTPS".to_string());
+    }
+
+    let response = reqwest::get(&url)
+        .await
+        .map_err(|e| format!("Failed to download preset: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Download failed with status: {}", response.status()));
+    }
+    if response.content_length().is_some_and(|len| len as usize > MAX_PRESET_DOWNLOAD_BYTES) {
+        return Err("Preset file is too large".to_string());
+    }
+
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read preset: {}", e))?;
+    if bytes.len() > MAX_PRESET_DOWNLOAD_BYTES {
+        return Err("Preset file is too large".to_string());
+    }
+
+    let preset = verify_signed_preset(&bytes)?;
+    install_preset(preset.clone())?;
+    tracing::info!(name = %preset.name, %url, "imported preset");
+    Ok(preset)
+}
@@ -0,0 +1,559 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// A single stream in the input file
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamInfo {
+    pub index: u32,
+    /// "video", "audio", "subtitle", "attachment" or "data"
+    pub kind: String,
+    pub codec: String,
+    pub profile: Option<String>,
+    /// H.264/HEVC level times ten, e.g. 41 for 4.1
+    pub level: Option<i32>,
+    pub refs: Option<u32>,
+    pub pix_fmt: Option<String>,
+    /// Average frames per second
+    pub frame_rate: Option<f64>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub channels: Option<u32>,
+    pub sample_rate: Option<u32>,
+    /// From the stream, or the Matroska statistics tags mkvmerge writes
+    pub bit_rate: Option<u64>,
+    /// Bytes the stream takes up in the file, from the Matroska statistics tags or, when
+    /// `probe_file` is asked to measure, its packet sizes
+    pub size_bytes: Option<u64>,
+    /// `size_bytes` as a share of the whole file, 0-100
+    pub size_percent: Option<f64>,
+    pub is_default: bool,
+    /// Cover art or a thumbnail stored as a one-frame video stream
+    pub attached_pic: bool,
+    /// Video carries embedded CEA-608/708 captions in its bitstream
+    pub closed_captions: bool,
+}
+
+/// Container-level information plus all streams
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaInfo {
+    pub path: String,
+    pub container: String,
+    /// The most trustworthy duration, see `DurationSource`
+    pub duration: Option<f64>,
+    pub duration_source: DurationSource,
+    /// What the container header claims, when that's not the duration used
+    pub reported_duration: Option<f64>,
+    pub size: Option<u64>,
+    pub bit_rate: Option<u64>,
+    /// SMPTE start timecode (HH:MM:SS:FF) from container or stream tags
+    pub timecode: Option<String>,
+    pub streams: Vec<StreamInfo>,
+    pub chapters: Vec<Chapter>,
+}
+
+/// Where `MediaInfo::duration` comes from. Broken muxers write headers that are off by
+/// hours, which throws off progress, ETAs and output checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationSource {
+    /// The container header, agreeing with the streams
+    Container,
+    /// The longest audio or video stream's own duration
+    Streams,
+    /// Where the last packets in the file end, read because the others disagreed
+    LastPacket,
+    Unknown,
+}
+
+/// Durations this close count as agreeing, or 2%, whichever is more
+const DURATION_AGREEMENT_SECS: f64 = 2.0;
+
+/// Packets read from the end of the file to find where it really ends
+const LAST_PACKETS: u32 = 200;
+
+/// The file's size and modification time when `last_packet_end` read it, and what it found
+type PacketEnd = (u64, SystemTime, Option<f64>);
+
+/// `last_packet_end` results by path
+static LAST_PACKET_ENDS: Mutex<BTreeMap<String, PacketEnd>> = Mutex::new(BTreeMap::new());
+
+/// Errors for inputs ffprobe can't make sense of, such as a corrupt header, start with this
+pub const UNREADABLE_INPUT: &str = "Unreadable input";
+
+/// Errors for inputs without a single audio or video stream start with this
+pub const NO_STREAMS: &str = "No audio or video streams";
+
+/// What kind of file an input is, going by its streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Composition {
+    Video,
+    /// Audio with no video, or only cover art, such as an MKA
+    AudioOnly,
+    /// Nothing to convert: no streams, or only subtitles, attachments or data
+    Empty,
+}
+
+/// A chapter marker, with times in seconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+impl MediaInfo {
+    fn fill_size_percents(&mut self) {
+        let Some(total) = self.size.filter(|&s| s > 0) else {
+            return;
+        };
+        for stream in &mut self.streams {
+            stream.size_percent = stream.size_bytes.map(|b| b as f64 / total as f64 * 100.0);
+        }
+    }
+
+    pub fn streams_of<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a StreamInfo> + 'a {
+        self.streams.iter().filter(move |s| s.kind == kind)
+    }
+
+    pub fn composition(&self) -> Composition {
+        if self.streams_of("video").any(|v| !v.attached_pic) {
+            Composition::Video
+        } else if self.streams_of("audio").next().is_some() {
+            Composition::AudioOnly
+        } else {
+            Composition::Empty
+        }
+    }
+}
+
+/// A program (channel) in a multi-program transport stream
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramInfo {
+    pub program_id: u32,
+    pub program_num: Option<u32>,
+    pub service_name: Option<String>,
+    pub service_provider: Option<String>,
+    pub streams: Vec<StreamInfo>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    #[serde(default)]
+    programs: Vec<FfprobeProgram>,
+    #[serde(default)]
+    packets: Vec<FfprobePacket>,
+    #[serde(default)]
+    chapters: Vec<FfprobeChapter>,
+    format: Option<FfprobeFormat>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeChapter {
+    start_time: Option<String>,
+    end_time: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct FfprobePacket {
+    pts_time: Option<String>,
+    duration_time: Option<String>,
+    #[serde(default)]
+    flags: String,
+}
+
+#[derive(Deserialize)]
+struct FfprobeProgram {
+    program_id: u32,
+    program_num: Option<u32>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeStream {
+    index: u32,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    profile: Option<String>,
+    level: Option<i32>,
+    refs: Option<u32>,
+    pix_fmt: Option<String>,
+    avg_frame_rate: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    channels: Option<u32>,
+    sample_rate: Option<String>,
+    bit_rate: Option<String>,
+    duration: Option<String>,
+    nb_read_packets: Option<String>,
+    closed_captions: Option<i64>,
+    #[serde(default)]
+    disposition: HashMap<String, i64>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeFormat {
+    format_name: Option<String>,
+    start_time: Option<String>,
+    duration: Option<String>,
+    size: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+/// Tag lookup ignoring case; Matroska tags often come through uppercase
+fn tag<'a>(tags: &'a HashMap<String, String>, name: &str) -> Option<&'a String> {
+    tags.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v)
+}
+
+/// A Matroska statistics tag: mkvmerge writes "BPS" and the like in some versions as
+/// "BPS-eng"
+fn statistics_tag(tags: &HashMap<String, String>, name: &str) -> Option<u64> {
+    tags.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name) || k.split_once('-').is_some_and(|(k, _)| k.eq_ignore_ascii_case(name)))
+        .and_then(|(_, v)| v.trim().parse().ok())
+}
+
+/// Run ffprobe and return what it printed
+fn ffprobe_stdout(args: &[&str]) -> Result<Vec<u8>, String> {
+    let ffprobe_path = crate::find_tool("ffprobe").ok_or("ffprobe not found")?;
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffprobe_path])
+        .args(args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffprobe_path)
+        .args(args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe failed: {}", stderr));
+    }
+    Ok(output.stdout)
+}
+
+/// Probe a file that's about to be converted, turning a header ffprobe can't read or a
+/// file with nothing to convert into an `UNREADABLE_INPUT` or `NO_STREAMS` error up front,
+/// instead of an ffmpeg failure or an empty MP4 later
+pub fn probe_input(path: &str) -> Result<MediaInfo, String> {
+    let info = probe(path).map_err(|e| {
+        if e.starts_with("ffprobe failed") || e.starts_with("Failed to parse ffprobe output") {
+            format!("{}: {}", UNREADABLE_INPUT, e.trim())
+        } else {
+            e
+        }
+    })?;
+    if info.composition() == Composition::Empty {
+        return Err(format!("{}: {} has nothing to convert", NO_STREAMS, path));
+    }
+    Ok(info)
+}
+
+/// Run ffprobe and parse its JSON output
+fn run_ffprobe(args: &[&str]) -> Result<FfprobeOutput, String> {
+    serde_json::from_slice(&ffprobe_stdout(args)?)
+        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))
+}
+
+/// ffprobe rates are fractions like "24000/1001"; "0/0" means unknown
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (num, den) = rate.split_once('/')?;
+    let (num, den): (f64, f64) = (num.parse().ok()?, den.parse().ok()?);
+    (num > 0.0 && den > 0.0).then(|| num / den)
+}
+
+fn stream_info(s: FfprobeStream) -> StreamInfo {
+    StreamInfo {
+        index: s.index,
+        language: tag(&s.tags, "language").cloned(),
+        title: tag(&s.tags, "title").cloned(),
+        kind: s.codec_type.unwrap_or_else(|| "data".to_string()),
+        codec: s.codec_name.unwrap_or_else(|| "unknown".to_string()),
+        profile: s.profile,
+        // ffprobe reports -99 when the codec has no level
+        level: s.level.filter(|&l| l > 0),
+        refs: s.refs,
+        pix_fmt: s.pix_fmt,
+        frame_rate: s.avg_frame_rate.as_deref().and_then(parse_rate),
+        width: s.width,
+        height: s.height,
+        channels: s.channels,
+        sample_rate: s.sample_rate.and_then(|r| r.parse().ok()),
+        bit_rate: s.bit_rate.and_then(|b| b.parse().ok()).or_else(|| statistics_tag(&s.tags, "BPS")),
+        size_bytes: statistics_tag(&s.tags, "NUMBER_OF_BYTES"),
+        size_percent: None,
+        is_default: s.disposition.get("default") == Some(&1),
+        attached_pic: s.disposition.get("attached_pic") == Some(&1),
+        closed_captions: s.closed_captions == Some(1),
+    }
+}
+
+/// "01:23:45.678000000", as mkvmerge writes stream DURATION tags
+fn parse_clock(clock: &str) -> Option<f64> {
+    let mut secs = 0.0;
+    for part in clock.trim().split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(secs)
+}
+
+/// A playing stream's own duration, from ffprobe or the Matroska DURATION tag
+fn stream_duration(s: &FfprobeStream) -> Option<f64> {
+    let playing = matches!(s.codec_type.as_deref(), Some("video" | "audio")) && s.disposition.get("attached_pic") != Some(&1);
+    if !playing {
+        return None;
+    }
+    s.duration
+        .as_deref()
+        .and_then(|d| d.parse().ok())
+        .or_else(|| {
+            s.tags
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("DURATION") || k.to_uppercase().starts_with("DURATION-"))
+                .and_then(|(_, v)| parse_clock(v))
+        })
+        .filter(|&d: &f64| d > 0.0)
+}
+
+/// Where the file's packets end, from a seek past the end: demuxers land on the last
+/// keyframe they can find, so only the final stretch is read
+fn last_packet_end(path: &str, start_time: f64) -> Option<f64> {
+    let interval = format!("999999999%+#{}", LAST_PACKETS);
+    let args = [
+        "-v", "error",
+        "-read_intervals", &interval,
+        "-show_entries", "packet=pts_time,duration_time",
+        "-print_format", "json",
+        path,
+    ];
+    let parsed = run_ffprobe(&args).ok()?;
+    parsed
+        .packets
+        .iter()
+        .filter_map(|p| {
+            let pts: f64 = p.pts_time.as_deref()?.parse().ok()?;
+            let duration: f64 = p.duration_time.as_deref().and_then(|d| d.parse().ok()).unwrap_or_default();
+            Some(pts + duration - start_time)
+        })
+        .max_by(|a, b| a.total_cmp(b))
+        .filter(|&end| end > 0.0)
+}
+
+/// `last_packet_end`, read again only once the file changes. Files are probed over and over,
+/// for names, estimates and the queue view, and the seek is an extra ffprobe run each time.
+fn cached_last_packet_end(path: &str, start_time: f64) -> Option<f64> {
+    let Some((len, modified)) = fs::metadata(path).ok().and_then(|m| Some((m.len(), m.modified().ok()?))) else {
+        return last_packet_end(path, start_time);
+    };
+    let cached = LAST_PACKET_ENDS.lock().unwrap_or_else(|e| e.into_inner()).get(path).copied();
+    if let Some((_, _, end)) = cached.filter(|&(l, m, _)| l == len && m == modified) {
+        return end;
+    }
+    let end = last_packet_end(path, start_time);
+    LAST_PACKET_ENDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(path.to_string(), (len, modified, end));
+    end
+}
+
+fn agrees(a: f64, b: f64) -> bool {
+    (a - b).abs() <= DURATION_AGREEMENT_SECS.max(a.max(b) * 0.02)
+}
+
+/// Cross-check the header's duration with the streams', and when they disagree or either
+/// is missing, with where the packets really end
+fn reconcile_duration(path: &str, container: Option<f64>, streams: Option<f64>, start_time: f64) -> (Option<f64>, DurationSource) {
+    match (container.filter(|&d| d > 0.0), streams) {
+        (Some(container), Some(streams)) if agrees(container, streams) => return (Some(container), DurationSource::Container),
+        _ => {}
+    }
+    if let Some(end) = cached_last_packet_end(path, start_time) {
+        let (chosen, source) = match (container, streams) {
+            // Reading from a seek can stop a keyframe short, so a header that agrees wins
+            (Some(container), _) if agrees(container, end) => (container, DurationSource::Container),
+            (_, Some(streams)) if agrees(streams, end) => (streams, DurationSource::Streams),
+            _ => (end, DurationSource::LastPacket),
+        };
+        if source != DurationSource::Container {
+            tracing::warn!(path, ?container, ?streams, last_packet = end, chosen, "container duration looks wrong");
+        }
+        return (Some(chosen), source);
+    }
+    match (container, streams) {
+        (Some(container), _) => (Some(container), DurationSource::Container),
+        (None, Some(streams)) => (Some(streams), DurationSource::Streams),
+        (None, None) => (None, DurationSource::Unknown),
+    }
+}
+
+/// Inspect a media file with ffprobe
+pub fn probe(path: &str) -> Result<MediaInfo, String> {
+    let parsed = run_ffprobe(&["-v", "error", "-print_format", "json", "-show_format", "-show_streams", "-show_chapters", path])?;
+
+    let format = parsed.format;
+
+    // Timecode can live on the container, a video stream or a tmcd data track
+    let timecode = format
+        .as_ref()
+        .and_then(|f| tag(&f.tags, "timecode"))
+        .or_else(|| parsed.streams.iter().find_map(|s| tag(&s.tags, "timecode")))
+        .cloned();
+
+    let stream_secs = parsed.streams.iter().filter_map(stream_duration).max_by(|a, b| a.total_cmp(b));
+    let container_secs: Option<f64> = format.as_ref().and_then(|f| f.duration.as_ref()?.parse().ok());
+    let start_time = format.as_ref().and_then(|f| f.start_time.as_ref()?.parse().ok()).unwrap_or_default();
+    let (duration, duration_source) = reconcile_duration(path, container_secs, stream_secs, start_time);
+
+    let streams = parsed.streams.into_iter().map(stream_info).collect();
+    let chapters = parsed
+        .chapters
+        .into_iter()
+        .map(|c| Chapter {
+            start: c.start_time.and_then(|t| t.parse().ok()).unwrap_or_default(),
+            end: c.end_time.and_then(|t| t.parse().ok()).unwrap_or_default(),
+            title: tag(&c.tags, "title").cloned(),
+        })
+        .collect();
+
+    let mut info = MediaInfo {
+        path: path.to_string(),
+        container: format
+            .as_ref()
+            .and_then(|f| f.format_name.clone())
+            .unwrap_or_else(|| "unknown".to_string()),
+        duration,
+        duration_source,
+        reported_duration: container_secs.filter(|_| duration_source != DurationSource::Container),
+        size: format.as_ref().and_then(|f| f.size.as_ref()?.parse().ok()),
+        bit_rate: format.as_ref().and_then(|f| f.bit_rate.as_ref()?.parse().ok()),
+        timecode,
+        streams,
+        chapters,
+    };
+    info.fill_size_percents();
+    Ok(info)
+}
+
+/// Sum the packet sizes of every stream by reading the whole file. Returns bytes by stream index.
+fn measure_stream_sizes(path: &str) -> Result<HashMap<u32, u64>, String> {
+    // CSV, since JSON for every packet of a long film runs to hundreds of megabytes
+    let output = ffprobe_stdout(&["-v", "error", "-show_entries", "packet=stream_index,size", "-of", "csv=p=0", path])?;
+    let mut sizes = HashMap::new();
+    for line in String::from_utf8_lossy(&output).lines() {
+        let Some((index, size)) = line.trim().split_once(',') else {
+            continue;
+        };
+        if let (Ok(index), Ok(size)) = (index.parse::<u32>(), size.parse::<u64>()) {
+            *sizes.entry(index).or_insert(0) += size;
+        }
+    }
+    Ok(sizes)
+}
+
+/// Count packets (frames, for audio) in every stream of one kind by reading the whole file.
+/// `selector` is an ffprobe stream specifier such as "a" or "v:0". Returns (index, count) pairs.
+pub fn count_packets(path: &str, selector: &str) -> Result<Vec<(u32, u64)>, String> {
+    let parsed = run_ffprobe(&[
+        "-v", "error",
+        "-select_streams", selector,
+        "-count_packets",
+        "-show_entries", "stream=index,nb_read_packets",
+        "-print_format", "json",
+        path,
+    ])?;
+
+    Ok(parsed
+        .streams
+        .into_iter()
+        .filter_map(|s| Some((s.index, s.nb_read_packets?.parse().ok()?)))
+        .collect())
+}
+
+/// Presentation times of video keyframes between `from` and `to` seconds, in order.
+/// ffprobe starts reading at the keyframe before `from`, so that one is included.
+pub fn keyframe_times(path: &str, from: f64, to: f64) -> Result<Vec<f64>, String> {
+    let interval = format!("{}%{}", from, to);
+    let parsed = run_ffprobe(&[
+        "-v", "error",
+        "-select_streams", "v:0",
+        "-read_intervals", &interval,
+        "-show_entries", "packet=pts_time,flags",
+        "-print_format", "json",
+        path,
+    ])?;
+
+    let mut times: Vec<f64> = parsed
+        .packets
+        .into_iter()
+        .filter(|p| p.flags.contains('K'))
+        .filter_map(|p| p.pts_time?.parse().ok())
+        .collect();
+    times.sort_by(|a, b| a.total_cmp(b));
+    Ok(times)
+}
+
+/// Inspect a file before converting it: container, duration, and every stream with its
+/// codec, resolution, bitrate, language and size. With `measure_sizes`, streams whose
+/// size isn't tagged (most files not made by mkvmerge) are measured by reading the whole
+/// file, which takes about as long as a remux.
+#[tauri::command(async)]
+pub fn probe_file(path: String, measure_sizes: Option<bool>) -> Result<MediaInfo, String> {
+    let mut info = probe(&path)?;
+    let untagged = info.streams.iter().any(|s| s.size_bytes.is_none() && s.kind != "attachment");
+    if measure_sizes.unwrap_or(false) && untagged {
+        let sizes = measure_stream_sizes(&path)?;
+        let duration = info.duration.filter(|&d| d > 0.0);
+        for stream in info.streams.iter_mut().filter(|s| s.size_bytes.is_none()) {
+            let Some(&bytes) = sizes.get(&stream.index) else {
+                continue;
+            };
+            stream.size_bytes = Some(bytes);
+            if stream.bit_rate.is_none() {
+                stream.bit_rate = duration.map(|d| (bytes as f64 * 8.0 / d) as u64);
+            }
+        }
+        info.fill_size_percents();
+        tracing::info!(path, streams = sizes.len(), "measured stream sizes");
+    }
+    Ok(info)
+}
+
+/// List the programs in a transport stream (empty for single-program containers)
+#[tauri::command(async)]
+pub fn list_programs(path: String) -> Result<Vec<ProgramInfo>, String> {
+    let parsed = run_ffprobe(&["-v", "error", "-print_format", "json", "-show_programs", &path])?;
+
+    Ok(parsed
+        .programs
+        .into_iter()
+        .map(|p| ProgramInfo {
+            program_id: p.program_id,
+            program_num: p.program_num,
+            service_name: tag(&p.tags, "service_name").cloned(),
+            service_provider: tag(&p.tags, "service_provider").cloned(),
+            streams: p.streams.into_iter().map(stream_info).collect(),
+        })
+        .collect())
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::store;
+
+/// Which profile is active and which exist; always shared by all profiles
+const PROFILES_FILE: &str = "profiles.json";
+
+/// Lives directly in the app data directory, so data from before profiles existed belongs to it
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Serializes read-modify-write cycles on the profiles file
+static PROFILES_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ProfileState {
+    /// None means the default profile
+    active: Option<String>,
+    /// Named profiles besides the default one
+    profiles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.trim().is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    if !valid {
+        return Err("Profile names must be 1-32 letters, digits, spaces, - or _".to_string());
+    }
+    Ok(())
+}
+
+fn active_profile() -> String {
+    let state: ProfileState = store::load(PROFILES_FILE);
+    state.active.unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+fn dir_for(name: &str) -> Result<PathBuf, String> {
+    if name == DEFAULT_PROFILE {
+        return crate::app_data_dir();
+    }
+    let dir = crate::app_data_dir()?.join("profiles").join(name);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create profile directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Data directory of the active profile, for per-profile files like presets
+pub fn profile_dir() -> Result<PathBuf, String> {
+    dir_for(&active_profile())
+}
+
+/// List profiles, default first, with the active one
+#[tauri::command]
+pub fn list_profiles() -> ProfileList {
+    let _guard = PROFILES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let state: ProfileState = store::load(PROFILES_FILE);
+    let mut profiles = vec![DEFAULT_PROFILE.to_string()];
+    profiles.extend(state.profiles);
+    ProfileList {
+        active: state.active.unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+        profiles,
+    }
+}
+
+/// Add a named profile; it starts with no presets or remembered options
+#[tauri::command]
+pub fn create_profile(name: String) -> Result<(), String> {
+    validate_name(&name)?;
+    let _guard = PROFILES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut state: ProfileState = store::load(PROFILES_FILE);
+    if name == DEFAULT_PROFILE || state.profiles.contains(&name) {
+        return Err(format!("Profile {} already exists", name));
+    }
+    dir_for(&name)?;
+    state.profiles.push(name);
+    store::save(PROFILES_FILE, &state)
+}
+
+/// Make `name` the active profile
+#[tauri::command]
+pub fn switch_profile(name: String) -> Result<(), String> {
+    let _guard = PROFILES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut state: ProfileState = store::load(PROFILES_FILE);
+    if name != DEFAULT_PROFILE && !state.profiles.contains(&name) {
+        return Err(format!("No profile named {}", name));
+    }
+    state.active = (name != DEFAULT_PROFILE).then(|| name.clone());
+    store::save(PROFILES_FILE, &state)?;
+    tracing::info!(profile = %name, "switched profile");
+    Ok(())
+}
+
+/// Delete a profile and its data. The default and active profiles can't be deleted.
+#[tauri::command]
+pub fn delete_profile(name: String) -> Result<(), String> {
+    let _guard = PROFILES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut state: ProfileState = store::load(PROFILES_FILE);
+    if name == DEFAULT_PROFILE {
+        return Err("The default profile can't be deleted".to_string());
+    }
+    if state.active.as_deref() == Some(name.as_str()) {
+        return Err("Switch to another profile before deleting this one".to_string());
+    }
+    if !state.profiles.contains(&name) {
+        return Err(format!("No profile named {}", name));
+    }
+
+    let dir = crate::app_data_dir()?.join("profiles").join(&name);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete profile data: {}", e))?;
+    }
+    state.profiles.retain(|p| p != &name);
+    store::save(PROFILES_FILE, &state)
+}
@@ -0,0 +1,95 @@
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read};
+use tauri::{AppHandle, Emitter};
+
+use crate::overlay;
+
+/// Emitted with a `ConversionProgress` about twice a second while ffmpeg runs
+const CONVERSION_PROGRESS_EVENT: &str = "conversion-progress";
+
+/// ffmpeg arguments that write machine-readable progress to stdout for `report`
+pub fn args() -> Vec<String> {
+    ["-progress", "pipe:1", "-nostats", "-stats_period", "0.5"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionProgress {
+    /// The conversion's id, for `cancel_conversion`
+    pub id: u64,
+    pub input_path: String,
+    /// Playable before the conversion ends when it was asked to be fragmented
+    pub output_path: String,
+    /// 0-100, or None when the source duration is unknown
+    pub percent: Option<f64>,
+    /// Position reached in the output, in seconds
+    pub current_secs: f64,
+    /// Multiple of real time, e.g. 4.2
+    pub speed: Option<f64>,
+    pub bitrate_kbps: Option<f64>,
+    pub dropped_frames: u64,
+    pub duplicated_frames: u64,
+    /// 1 or 2 in a two-pass encode, whose `percent` covers both passes
+    pub pass: Option<u8>,
+}
+
+/// Parse ffmpeg's `-progress` output and emit an event per block. Each block is a run of
+/// key=value lines ending with `progress=continue` or `progress=end`. Returns the last block.
+pub fn report(
+    app: &AppHandle,
+    id: u64,
+    input_path: &str,
+    output_path: &str,
+    duration: Option<f64>,
+    pass: Option<u8>,
+    output: impl Read,
+) -> ConversionProgress {
+    let mut progress = ConversionProgress {
+        id,
+        input_path: input_path.to_string(),
+        output_path: output_path.to_string(),
+        percent: None,
+        current_secs: 0.0,
+        speed: None,
+        bitrate_kbps: None,
+        dropped_frames: 0,
+        duplicated_frames: 0,
+        pass,
+    };
+    // Each pass of a two-pass encode is half the work
+    let (offset, share) = match pass {
+        Some(pass) => (f64::from(pass - 1) * 50.0, 0.5),
+        None => (0.0, 1.0),
+    };
+    for line in BufReader::new(output).lines().map_while(Result::ok) {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        match key {
+            // Microseconds, despite the name of the older out_time_ms key
+            "out_time_us" => {
+                if let Ok(us) = value.parse::<i64>() {
+                    progress.current_secs = us.max(0) as f64 / 1_000_000.0;
+                }
+            }
+            "speed" => progress.speed = value.trim_end_matches('x').trim().parse().ok(),
+            "bitrate" => progress.bitrate_kbps = value.trim_end_matches("kbits/s").trim().parse().ok(),
+            "drop_frames" => progress.dropped_frames = value.parse().unwrap_or(progress.dropped_frames),
+            "dup_frames" => progress.duplicated_frames = value.parse().unwrap_or(progress.duplicated_frames),
+            "progress" => {
+                progress.percent = match (value, duration) {
+                    ("end", _) => Some(100.0),
+                    (_, Some(total)) if total > 0.0 => Some((progress.current_secs / total * 100.0).min(100.0)),
+                    _ => None,
+                }
+                .map(|percent| offset + percent * share);
+                let _ = app.emit(CONVERSION_PROGRESS_EVENT, &progress);
+                overlay::record_progress(app, &progress);
+            }
+            _ => {}
+        }
+    }
+    progress
+}
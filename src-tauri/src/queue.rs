@@ -0,0 +1,1080 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+use std::thread;
+use tauri::{AppHandle, Emitter};
+
+use crate::convert::{self, ConvertOptions};
+use crate::milestones::{self, BatchWork, Milestone, MilestoneSettings};
+use crate::rules::{self, RuleMatch};
+use crate::{cancel, exit, launcher, power, probe, settings, store};
+
+/// Emitted with a `QueueItem` whenever an item changes state
+const ITEM_STATUS_EVENT: &str = "queue-item-status";
+
+/// Emitted with an item's id when it's taken out of the queue
+const ITEM_REMOVED_EVENT: &str = "queue-item-removed";
+
+/// Emitted once with a `GroupSummary` when every item in a group has finished
+const GROUP_FINISHED_EVENT: &str = "queue-group-finished";
+
+/// Rough speed of a stream-copy remux, which is bound by disk throughput
+const REMUX_BYTES_PER_SEC: f64 = 150.0 * 1024.0 * 1024.0;
+
+/// The queue as it was when last changed, so it survives quitting and updating the app
+pub const QUEUE_FILE: &str = "queue.json";
+
+/// Saved groups and items that couldn't be read back, kept for the user instead of lost
+pub const QUARANTINE_FILE: &str = "queue-quarantine.json";
+
+/// Upgrades a saved queue's JSON by one format version
+type QueueUpgrade = fn(&mut serde_json::Value) -> Result<(), String>;
+
+/// One step per format version: QUEUE_UPGRADES[n] upgrades a saved queue from version n to
+/// n + 1. Add new steps at the end; never change or remove old ones.
+const QUEUE_UPGRADES: &[QueueUpgrade] = &[adopt_queue_versioning];
+
+/// The `QUEUE_FILE` format version this build reads and writes
+const QUEUE_FORMAT_VERSION: u32 = QUEUE_UPGRADES.len() as u32;
+
+/// 0 -> 1: queues saved before the format had a version are already in the version 1 layout
+fn adopt_queue_versioning(_queue: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl ItemStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, ItemStatus::Done | ItemStatus::Failed | ItemStatus::Cancelled)
+    }
+}
+
+/// Which pending item of a batch converts next
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOrder {
+    /// In the order they were added
+    #[default]
+    Fifo,
+    /// Quick wins first
+    SmallestFirst,
+    LargestFirst,
+    /// By estimated conversion time, see `estimate_secs`
+    ShortestFirst,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub id: u64,
+    pub input_path: String,
+    pub group_id: Option<u64>,
+    /// Options for this item alone; when unset the group's options apply
+    pub options: Option<ConvertOptions>,
+    pub status: ItemStatus,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub size_bytes: u64,
+    /// Filled in shortly after the item is added, once the file has been probed
+    #[serde(default)]
+    pub estimated_secs: Option<f64>,
+    /// Folder the output goes to instead of the configured one, e.g. from an enqueue rule
+    #[serde(default)]
+    pub output_directory: Option<String>,
+    /// The enqueue rule that picked this item's preset or destination, if one matched
+    #[serde(default)]
+    pub matched_rule: Option<RuleMatch>,
+}
+
+/// A set of queue items that share options, e.g. one project or one season
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueGroup {
+    pub id: u64,
+    pub name: String,
+    pub options: Option<ConvertOptions>,
+    /// Collapsed groups are listed by `get_queue` with counts only
+    #[serde(default)]
+    pub collapsed: bool,
+    #[serde(default)]
+    pub order: QueueOrder,
+    #[serde(skip)]
+    summarized: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupSummary {
+    pub group_id: u64,
+    pub name: String,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupView {
+    #[serde(flatten)]
+    pub group: QueueGroup,
+    pub summary: GroupSummary,
+    pub items: Vec<QueueItem>,
+}
+
+/// The queue as the frontend sees it: groups with their items, then ungrouped items
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueView {
+    pub groups: Vec<GroupView>,
+    pub items: Vec<QueueItem>,
+    /// Set by `pause_queue`; the running conversion is suspended and no new one starts
+    pub paused: bool,
+}
+
+/// `QUEUE_FILE` contents. Groups and items stay raw JSON until they're parsed one by one,
+/// so one bad entry doesn't take the rest of the queue with it. Layout changes that need
+/// more than a defaulted field get a step in `QUEUE_UPGRADES`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedQueue {
+    /// Orders snapshots taken under the queue lock, so an older one is never written last
+    #[serde(skip)]
+    generation: u64,
+    /// Format version, 0 for queues saved before there was one
+    version: u32,
+    next_id: u64,
+    order: QueueOrder,
+    groups: Vec<serde_json::Value>,
+    items: Vec<serde_json::Value>,
+}
+
+/// A saved entry that couldn't be loaded, with the reason
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QuarantinedEntry {
+    quarantined_at: String,
+    error: String,
+    entry: serde_json::Value,
+}
+
+struct QueueState {
+    next_id: u64,
+    items: Vec<QueueItem>,
+    groups: Vec<QueueGroup>,
+    /// Worker threads converting items, at most `settings::queue_concurrency()`
+    workers: usize,
+    /// Paused with `pause_queue`; not saved, so a relaunch carries on converting
+    paused: bool,
+    /// Id of the first item in the current batch, for dock progress
+    batch_start: u64,
+    /// Order of items that aren't in a group
+    order: QueueOrder,
+    /// Milestones already notified for the current batch
+    milestones_sent: Vec<Milestone>,
+    /// Snapshots taken for saving
+    snapshots: u64,
+}
+
+/// Generation of the last snapshot written to `QUEUE_FILE`
+static LAST_SAVED: Mutex<u64> = Mutex::new(0);
+
+static QUEUE: Mutex<QueueState> = Mutex::new(QueueState::empty());
+
+fn lock() -> std::sync::MutexGuard<'static, QueueState> {
+    QUEUE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl QueueState {
+    const fn empty() -> Self {
+        QueueState {
+            next_id: 1,
+            items: Vec::new(),
+            groups: Vec::new(),
+            workers: 0,
+            paused: false,
+            batch_start: 0,
+            order: QueueOrder::Fifo,
+            milestones_sent: Vec::new(),
+            snapshots: 0,
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Add a restored queue ahead of anything enqueued before it loaded, e.g. remotely at
+    /// launch. Those keep their ids, and the restored entries are given new ones if needed.
+    fn merge_restored(&mut self, saved: &SavedQueue, mut groups: Vec<QueueGroup>, mut items: Vec<QueueItem>) {
+        self.next_id = resumed_next_id(saved, &groups, &items, self.next_id);
+        if !self.items.is_empty() || !self.groups.is_empty() {
+            let mut group_ids = Vec::new();
+            for group in groups.iter_mut() {
+                let id = self.next_id();
+                group_ids.push((group.id, id));
+                group.id = id;
+            }
+            for item in items.iter_mut() {
+                item.id = self.next_id();
+                item.group_id = item
+                    .group_id
+                    .and_then(|old| group_ids.iter().find(|(from, _)| *from == old).map(|(_, to)| *to));
+            }
+        }
+        self.order = saved.order;
+        groups.append(&mut self.groups);
+        items.append(&mut self.items);
+        self.groups = groups;
+        self.items = items;
+    }
+
+    fn summary(&self, group: &QueueGroup) -> GroupSummary {
+        let items: Vec<&QueueItem> = self.items.iter().filter(|i| i.group_id == Some(group.id)).collect();
+        let count = |status: ItemStatus| items.iter().filter(|i| i.status == status).count();
+        GroupSummary {
+            group_id: group.id,
+            name: group.name.clone(),
+            total: items.len(),
+            succeeded: count(ItemStatus::Done),
+            failed: count(ItemStatus::Failed),
+            cancelled: count(ItemStatus::Cancelled),
+        }
+    }
+
+    /// (finished, total) items of the batch the workers are on
+    fn batch_progress(&self) -> (usize, usize) {
+        let batch: Vec<&QueueItem> = self.items.iter().filter(|i| i.id >= self.batch_start).collect();
+        (batch.iter().filter(|i| i.status.is_finished()).count(), batch.len())
+    }
+
+    /// Finished items and estimated work of the current batch, for milestone notifications
+    fn batch_work(&self) -> BatchWork {
+        let batch: Vec<&QueueItem> = self.items.iter().filter(|i| i.id >= self.batch_start).collect();
+        let finished: Vec<&&QueueItem> = batch.iter().filter(|i| i.status.is_finished()).collect();
+        BatchWork {
+            finished: finished.len(),
+            total: batch.len(),
+            done_secs: finished.iter().map(|i| self.estimate(i)).sum(),
+            total_secs: batch.iter().map(|i| self.estimate(i)).sum(),
+            last_started: batch.iter().all(|i| i.status != ItemStatus::Pending),
+        }
+    }
+
+    /// Milestones the batch has just reached, marked as sent
+    fn take_milestones(&mut self, settings: &MilestoneSettings) -> (Vec<Milestone>, BatchWork) {
+        let work = self.batch_work();
+        let due = milestones::due(settings, &work, &self.milestones_sent);
+        self.milestones_sent.extend(&due);
+        (due, work)
+    }
+
+    /// Summary for a group whose items have all finished, the first time it's asked for
+    fn take_finished_summary(&mut self, group_id: u64) -> Option<GroupSummary> {
+        let done = self
+            .items
+            .iter()
+            .filter(|i| i.group_id == Some(group_id))
+            .all(|i| i.status.is_finished());
+        let index = self.groups.iter().position(|g| g.id == group_id)?;
+        if !done || self.groups[index].summarized {
+            return None;
+        }
+        self.groups[index].summarized = true;
+        Some(self.summary(&self.groups[index]))
+    }
+
+    fn effective_options(&self, item: &QueueItem) -> Option<ConvertOptions> {
+        item.options.clone().or_else(|| {
+            self.groups
+                .iter()
+                .find(|g| Some(g.id) == item.group_id)
+                .and_then(|g| g.options.clone())
+        })
+    }
+
+    fn order_of(&self, group_id: Option<u64>) -> QueueOrder {
+        group_id
+            .and_then(|id| self.groups.iter().find(|g| g.id == id))
+            .map_or(self.order, |g| g.order)
+    }
+
+    /// Items of one group (or the ungrouped ones): finished and running first, then pending
+    /// items in the order they'll convert
+    fn ordered(&self, group_id: Option<u64>) -> Vec<QueueItem> {
+        let mut items: Vec<QueueItem> = self.items.iter().filter(|i| i.group_id == group_id).cloned().collect();
+        let order = self.order_of(group_id);
+        // Stable, so ties and FIFO keep the order items were added in
+        items.sort_by(|a, b| {
+            let pending = |i: &QueueItem| i.status == ItemStatus::Pending;
+            pending(a).cmp(&pending(b)).then_with(|| match order {
+                QueueOrder::Fifo => std::cmp::Ordering::Equal,
+                QueueOrder::SmallestFirst => a.size_bytes.cmp(&b.size_bytes),
+                QueueOrder::LargestFirst => b.size_bytes.cmp(&a.size_bytes),
+                QueueOrder::ShortestFirst => self.estimate(a).total_cmp(&self.estimate(b)),
+            })
+        });
+        items
+    }
+
+    /// Until the item has been probed only the remux time is known, which needs no preset lookup
+    fn estimate(&self, item: &QueueItem) -> f64 {
+        item.estimated_secs.unwrap_or_else(|| estimate_secs(item.size_bytes, None, &None))
+    }
+
+    /// Batches take turns in the order they were started; within one, its order decides
+    fn next_pending(&self) -> Option<u64> {
+        let oldest = self.items.iter().find(|i| i.status == ItemStatus::Pending)?;
+        self.ordered(oldest.group_id)
+            .into_iter()
+            .find(|i| i.status == ItemStatus::Pending)
+            .map(|i| i.id)
+    }
+
+    /// The queue as it's saved; taken after every change and written with `persist` once
+    /// the lock is released
+    fn snapshot(&mut self) -> SavedQueue {
+        self.snapshots += 1;
+        SavedQueue {
+            generation: self.snapshots,
+            version: QUEUE_FORMAT_VERSION,
+            next_id: self.next_id,
+            order: self.order,
+            groups: self.groups.iter().filter_map(|g| serde_json::to_value(g).ok()).collect(),
+            items: self.items.iter().filter_map(|i| serde_json::to_value(i).ok()).collect(),
+        }
+    }
+
+    fn view(&self) -> QueueView {
+        let groups = self
+            .groups
+            .iter()
+            .map(|group| GroupView {
+                group: group.clone(),
+                summary: self.summary(group),
+                items: if group.collapsed { Vec::new() } else { self.ordered(Some(group.id)) },
+            })
+            .collect();
+
+        QueueView { groups, items: self.ordered(None), paused: self.paused }
+    }
+}
+
+/// Seconds a conversion will roughly take: re-encoding and transcription run at about
+/// real time, twice over for two-pass encodes, and a plain remux at disk speed
+fn estimate_secs(size_bytes: u64, duration: Option<f64>, options: &Option<ConvertOptions>) -> f64 {
+    let remux = size_bytes as f64 / REMUX_BYTES_PER_SEC;
+    let options = options.clone().and_then(|o| o.with_named_preset().ok());
+    let passes = if options.as_ref().is_some_and(|o| o.two_pass) { 2.0 } else { 1.0 };
+    let realtime = options.is_some_and(|o| {
+        o.social_format.is_some()
+            || o.transcribe.is_some()
+            || o.preset.as_ref().is_some_and(|p| p.video_codec != "copy")
+    });
+    match duration {
+        Some(duration) if realtime => remux + duration * passes,
+        _ => remux,
+    }
+}
+
+/// Rough seconds of conversion left in the queue, the item converting now included
+pub fn remaining_secs() -> f64 {
+    let state = lock();
+    state
+        .items
+        .iter()
+        .filter(|i| matches!(i.status, ItemStatus::Pending | ItemStatus::Running))
+        .map(|i| state.estimate(i))
+        .sum()
+}
+
+/// Probe newly added items for their duration so shortest-first ordering has real estimates
+fn fill_estimates(ids: Vec<u64>) {
+    for id in ids {
+        let found = {
+            let state = lock();
+            state
+                .items
+                .iter()
+                .find(|i| i.id == id)
+                .map(|i| (i.input_path.clone(), i.size_bytes, state.effective_options(i)))
+        };
+        let Some((path, size_bytes, options)) = found else {
+            continue;
+        };
+        // Probe and look up the preset outside the lock, both can take a while on network drives
+        let duration = probe::probe(&path).ok().and_then(|info| info.duration);
+        let estimated = estimate_secs(size_bytes, duration, &options);
+
+        if let Some(item) = lock().items.iter_mut().find(|i| i.id == id) {
+            item.estimated_secs = Some(estimated);
+        }
+    }
+}
+
+/// Write a snapshot to `QUEUE_FILE`, unless a newer one was written already
+fn persist(saved: SavedQueue) {
+    let mut last = LAST_SAVED.lock().unwrap_or_else(|e| e.into_inner());
+    if saved.generation <= *last {
+        return;
+    }
+    if let Err(e) = store::save(QUEUE_FILE, &saved) {
+        tracing::warn!(error = %e, "failed to save the queue");
+    }
+    *last = saved.generation;
+}
+
+/// Set aside saved entries that couldn't be loaded, adding to any quarantined before
+fn quarantine(entries: Vec<(String, serde_json::Value)>) {
+    if entries.is_empty() {
+        return;
+    }
+    let mut quarantined: Vec<QuarantinedEntry> = store::load(QUARANTINE_FILE);
+    let now = chrono::Local::now().to_rfc3339();
+    for (error, entry) in entries {
+        tracing::warn!(%error, "quarantining unreadable queue entry");
+        quarantined.push(QuarantinedEntry { quarantined_at: now.clone(), error, entry });
+    }
+    if let Err(e) = store::save(QUARANTINE_FILE, &quarantined) {
+        tracing::error!(error = %e, "failed to save quarantined queue entries");
+    }
+}
+
+/// Parse saved entries one by one, collecting the ones that don't parse into `bad`
+fn parse_entries<T: serde::de::DeserializeOwned>(
+    entries: &[serde_json::Value],
+    kind: &str,
+    bad: &mut Vec<(String, serde_json::Value)>,
+) -> Vec<T> {
+    entries
+        .iter()
+        .filter_map(|entry| match serde_json::from_value(entry.clone()) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                bad.push((format!("unreadable queue {}: {}", kind, e), entry.clone()));
+                None
+            }
+        })
+        .collect()
+}
+
+/// Bring a saved queue from its format version up to the last of `upgrades`. A queue from a
+/// newer build is refused rather than read as something it isn't.
+fn upgrade_saved(queue: &mut serde_json::Value, upgrades: &[QueueUpgrade]) -> Result<(), String> {
+    let latest = upgrades.len() as u32;
+    let version = match queue.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("queue file has an unreadable version: {}", version))?,
+    };
+    if version > latest {
+        return Err(format!("queue file is format version {}, newer than this app reads ({})", version, latest));
+    }
+    for (step, upgrade) in upgrades.iter().enumerate().skip(version as usize) {
+        upgrade(queue).map_err(|e| format!("Failed to upgrade the queue file to version {}: {}", step + 1, e))?;
+        if let Some(object) = queue.as_object_mut() {
+            object.insert("version".to_string(), serde_json::Value::from(step as u32 + 1));
+        }
+    }
+    Ok(())
+}
+
+/// A saved queue, if any of it could be read, and the entries to quarantine
+type ParsedQueue = (Option<(SavedQueue, Vec<QueueGroup>, Vec<QueueItem>)>, Vec<(String, serde_json::Value)>);
+
+/// Parse a saved queue file. Entries that don't parse are set aside and the rest are kept;
+/// a file that isn't a queue at all is set aside whole.
+fn parse_saved(json: &str) -> ParsedQueue {
+    parse_saved_with(json, QUEUE_UPGRADES)
+}
+
+fn parse_saved_with(json: &str, upgrades: &[QueueUpgrade]) -> ParsedQueue {
+    let mut value: serde_json::Value = match serde_json::from_str(json) {
+        Ok(value) => value,
+        Err(e) => {
+            let entry = serde_json::Value::String(json.to_string());
+            return (None, vec![(format!("queue file isn't valid JSON: {}", e), entry)]);
+        }
+    };
+    if let Err(e) = upgrade_saved(&mut value, upgrades) {
+        return (None, vec![(e, value)]);
+    }
+    let saved: SavedQueue = match serde_json::from_value(value.clone()) {
+        Ok(saved) => saved,
+        Err(e) => return (None, vec![(format!("queue file has an unknown layout: {}", e), value)]),
+    };
+
+    let mut bad = Vec::new();
+    let groups: Vec<QueueGroup> = parse_entries(&saved.groups, "group", &mut bad);
+    let items: Vec<QueueItem> = parse_entries(&saved.items, "item", &mut bad);
+    (Some((saved, groups, items)), bad)
+}
+
+/// Read `QUEUE_FILE`, quarantining what can't be read
+fn load_saved() -> Option<(SavedQueue, Vec<QueueGroup>, Vec<QueueItem>)> {
+    let path = crate::app_data_dir().ok()?.join(QUEUE_FILE);
+    let json = fs::read_to_string(path).ok()?;
+    let (queue, bad) = parse_saved(&json);
+    quarantine(bad);
+    queue
+}
+
+/// The first id a restored queue hands out: past every loaded entry and past the saved
+/// counter, so ids of removed or quarantined entries aren't given out again
+fn resumed_next_id(saved: &SavedQueue, groups: &[QueueGroup], items: &[QueueItem], current: u64) -> u64 {
+    let highest = groups.iter().map(|g| g.id).chain(items.iter().map(|i| i.id)).max().unwrap_or(0);
+    saved.next_id.max(highest + 1).max(current)
+}
+
+/// Load the queue saved before the app last quit and carry on with its pending items.
+/// An item that was converting when the app quit is converted again from the start.
+pub fn restore(app: &AppHandle) {
+    let Some((saved, mut groups, mut items)) = load_saved() else {
+        return;
+    };
+
+    for item in items.iter_mut().filter(|i| i.status == ItemStatus::Running) {
+        item.status = ItemStatus::Pending;
+    }
+    // An item whose group didn't load would never show up in `get_queue`
+    for item in items.iter_mut() {
+        if item.group_id.is_some_and(|id| !groups.iter().any(|g| g.id == id)) {
+            item.group_id = None;
+        }
+    }
+    for group in groups.iter_mut() {
+        // Finished groups were summarized before the app quit
+        group.summarized = items.iter().filter(|i| i.group_id == Some(group.id)).all(|i| i.status.is_finished());
+    }
+
+    let (pending, saved) = {
+        let mut state = lock();
+        state.merge_restored(&saved, groups, items);
+        (state.items.iter().any(|i| i.status == ItemStatus::Pending), state.snapshot())
+    };
+    persist(saved);
+    tracing::info!(pending, "restored the saved queue");
+    if pending {
+        ensure_worker(app);
+    }
+}
+
+/// Convert pending items one at a time until none are left
+fn run_worker(app: AppHandle) {
+    let send_milestones = |(due, work): (Vec<Milestone>, BatchWork)| {
+        for milestone in due {
+            milestones::notify(&app, milestone, &work);
+        }
+    };
+    loop {
+        let milestone_settings = milestones::load();
+        let next = {
+            let mut state = lock();
+            // Also stops the extra workers when the concurrency setting is lowered
+            if exit::finishing() || state.paused || state.workers > settings::queue_concurrency() {
+                // Pending items stay saved for the next launch or `resume_queue`
+                state.workers -= 1;
+                return;
+            }
+            let Some(index) = state.next_pending().and_then(|id| state.items.iter().position(|i| i.id == id)) else {
+                state.workers -= 1;
+                // The last worker to run out of items finishes the batch
+                if state.workers > 0 {
+                    return;
+                }
+                // Under the lock, so a batch started meanwhile cancels any countdown this starts
+                power::batch_finished(&app);
+                // Clearing the dock badge talks to D-Bus on Linux, so not under the queue lock
+                drop(state);
+                launcher::clear();
+                return;
+            };
+            state.items[index].status = ItemStatus::Running;
+            let item = state.items[index].clone();
+            let options = state.effective_options(&item);
+            (item, options, state.batch_progress(), state.take_milestones(&milestone_settings), state.snapshot())
+        };
+        let (mut item, options, (finished, total), reached, saved) = next;
+        persist(saved);
+        let _ = app.emit(ITEM_STATUS_EVENT, &item);
+        launcher::set_progress(finished, total);
+        send_milestones(reached);
+
+        let result = cancel::for_queue_item(item.id, || {
+            convert::convert_file(app.clone(), item.input_path.clone(), options, item.output_directory.clone())
+        });
+
+        let (summary, reached, saved) = {
+            let mut state = lock();
+            let Some(stored) = state.items.iter_mut().find(|i| i.id == item.id) else {
+                continue;
+            };
+            match result {
+                Ok(output) => {
+                    stored.status = ItemStatus::Done;
+                    stored.output_path = Some(output);
+                }
+                // Cut off to quit, so it converts again at the next launch
+                Err(e) if cancel::is_cancelled(&e) && exit::exiting_now() => stored.status = ItemStatus::Pending,
+                Err(e) => {
+                    stored.status = if cancel::is_cancelled(&e) { ItemStatus::Cancelled } else { ItemStatus::Failed };
+                    stored.error = Some(e);
+                }
+            }
+            item = stored.clone();
+            let summary = item.group_id.and_then(|id| state.take_finished_summary(id));
+            (summary, state.take_milestones(&milestone_settings), state.snapshot())
+        };
+        persist(saved);
+
+        let _ = app.emit(ITEM_STATUS_EVENT, &item);
+        send_milestones(reached);
+        if let Some(summary) = summary {
+            let _ = app.emit(GROUP_FINISHED_EVENT, &summary);
+        }
+    }
+}
+
+/// Whether a worker is converting an item or about to record how one went
+pub fn worker_busy() -> bool {
+    lock().workers > 0
+}
+
+pub fn pending_items() -> usize {
+    lock().items.iter().filter(|i| i.status == ItemStatus::Pending).count()
+}
+
+/// Carry on with pending items after the workers stopped for an exit that was withdrawn,
+/// or with more workers after the concurrency setting was raised
+pub fn resume(app: &AppHandle) {
+    if pending_items() > 0 {
+        ensure_worker(app);
+    }
+}
+
+fn ensure_worker(app: &AppHandle) {
+    let mut state = lock();
+    if state.paused {
+        return;
+    }
+    if state.workers == 0 {
+        // Items before the first pending one are all finished, so a new batch starts there
+        state.batch_start = state
+            .items
+            .iter()
+            .find(|i| i.status == ItemStatus::Pending)
+            .map_or(state.next_id, |i| i.id);
+        state.milestones_sent.clear();
+    }
+    spawn_workers(&mut state, app);
+}
+
+/// Start workers up to the concurrency setting, but no more than there are items for
+fn spawn_workers(state: &mut QueueState, app: &AppHandle) {
+    let pending = state.items.iter().filter(|i| i.status == ItemStatus::Pending).count();
+    let wanted = settings::queue_concurrency().min(state.workers + pending);
+    if state.workers >= wanted {
+        return;
+    }
+    power::cancel_countdown(app);
+    while state.workers < wanted {
+        state.workers += 1;
+        let app = app.clone();
+        thread::spawn(move || run_worker(app));
+    }
+}
+
+/// Suspend the running conversion where it is and start no new ones until `resume_queue`,
+/// e.g. to free up the CPU for a while without losing progress
+#[tauri::command]
+pub fn pause_queue() -> QueueView {
+    let view = {
+        let mut state = lock();
+        state.paused = true;
+        state.view()
+    };
+    let suspended = cancel::pause_queue_jobs(true);
+    tracing::info!(suspended, "paused the queue");
+    view
+}
+
+/// Carry on converting from where `pause_queue` left off
+#[tauri::command]
+pub fn resume_queue(app: AppHandle) -> QueueView {
+    let resumed = cancel::pause_queue_jobs(false);
+    tracing::info!(resumed, "resumed the queue");
+    let mut state = lock();
+    state.paused = false;
+    // Workers stop if they finish an item while paused; the batch they were on carries on
+    if !exit::finishing() {
+        spawn_workers(&mut state, &app);
+    }
+    state.view()
+}
+
+/// Start a new group; its options apply to every item added without options of its own
+#[tauri::command]
+pub fn create_group(name: String, options: Option<ConvertOptions>) -> QueueGroup {
+    let (group, saved) = {
+        let mut state = lock();
+        let group = QueueGroup {
+            id: state.next_id(),
+            name,
+            options,
+            collapsed: false,
+            order: QueueOrder::default(),
+            summarized: false,
+        };
+        state.groups.push(group.clone());
+        (group, state.snapshot())
+    };
+    persist(saved);
+    group
+}
+
+/// Replace a group's options; items that already started keep what they had
+#[tauri::command]
+pub fn set_group_options(group_id: u64, options: Option<ConvertOptions>) -> Result<(), String> {
+    let saved = {
+        let mut state = lock();
+        let group = state
+            .groups
+            .iter_mut()
+            .find(|g| g.id == group_id)
+            .ok_or_else(|| format!("No queue group with id {}", group_id))?;
+        group.options = options;
+        state.snapshot()
+    };
+    persist(saved);
+    Ok(())
+}
+
+/// Collapse or expand a group in `get_queue` results
+#[tauri::command]
+pub fn set_group_collapsed(group_id: u64, collapsed: bool) -> Result<(), String> {
+    let saved = {
+        let mut state = lock();
+        let group = state
+            .groups
+            .iter_mut()
+            .find(|g| g.id == group_id)
+            .ok_or_else(|| format!("No queue group with id {}", group_id))?;
+        group.collapsed = collapsed;
+        state.snapshot()
+    };
+    persist(saved);
+    Ok(())
+}
+
+/// Set how a group's pending items are ordered, or the ungrouped items' when `group_id` is unset.
+/// Takes effect for the next item a worker picks.
+#[tauri::command]
+pub fn set_queue_order(group_id: Option<u64>, order: QueueOrder) -> Result<(), String> {
+    let saved = {
+        let mut state = lock();
+        match group_id {
+            Some(id) => {
+                let group = state
+                    .groups
+                    .iter_mut()
+                    .find(|g| g.id == id)
+                    .ok_or_else(|| format!("No queue group with id {}", id))?;
+                group.order = order;
+            }
+            None => state.order = order,
+        }
+        state.snapshot()
+    };
+    persist(saved);
+    Ok(())
+}
+
+/// Cancel every pending item in a group. An item already converting runs to completion.
+#[tauri::command]
+pub fn cancel_group(app: AppHandle, group_id: u64) -> Result<(), String> {
+    let (cancelled, summary, saved) = {
+        let mut state = lock();
+        if !state.groups.iter().any(|g| g.id == group_id) {
+            return Err(format!("No queue group with id {}", group_id));
+        }
+        let mut cancelled = Vec::new();
+        for item in state.items.iter_mut() {
+            if item.group_id == Some(group_id) && item.status == ItemStatus::Pending {
+                item.status = ItemStatus::Cancelled;
+                cancelled.push(item.clone());
+            }
+        }
+        let summary = state.take_finished_summary(group_id);
+        (cancelled, summary, state.snapshot())
+    };
+    persist(saved);
+
+    for item in &cancelled {
+        let _ = app.emit(ITEM_STATUS_EVENT, item);
+    }
+    if let Some(summary) = summary {
+        let _ = app.emit(GROUP_FINISHED_EVENT, &summary);
+    }
+    Ok(())
+}
+
+/// Add files to the queue, optionally into a group, and start converting
+#[tauri::command]
+pub fn enqueue_files(
+    app: AppHandle,
+    paths: Vec<String>,
+    group_id: Option<u64>,
+    options: Option<ConvertOptions>,
+) -> Result<Vec<QueueItem>, String> {
+    // Stat outside the lock, it can take a while on network drives
+    let files: Vec<(String, u64)> = paths
+        .into_iter()
+        .map(|path| {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            (path, size)
+        })
+        .collect();
+    let rules = rules::load();
+
+    let (added, saved) = {
+        let mut state = lock();
+        if let Some(id) = group_id {
+            let group = state
+                .groups
+                .iter_mut()
+                .find(|g| g.id == id)
+                .ok_or_else(|| format!("No queue group with id {}", id))?;
+            // New items reopen a finished group, so it gets a fresh summary
+            group.summarized = false;
+        }
+
+        let group_options = group_id
+            .and_then(|id| state.groups.iter().find(|g| g.id == id))
+            .and_then(|g| g.options.clone());
+        let mut added = Vec::new();
+        for (path, size_bytes) in files {
+            let routing = rules::route(&rules, &path, options.as_ref().or(group_options.as_ref()));
+            let item = QueueItem {
+                id: state.next_id(),
+                input_path: path,
+                group_id,
+                options: routing.options.or_else(|| options.clone()),
+                status: ItemStatus::Pending,
+                output_path: None,
+                error: None,
+                size_bytes,
+                estimated_secs: None,
+                output_directory: routing.output_directory,
+                matched_rule: routing.matched,
+            };
+            state.items.push(item.clone());
+            added.push(item);
+        }
+        (added, state.snapshot())
+    };
+    persist(saved);
+
+    for item in &added {
+        let _ = app.emit(ITEM_STATUS_EVENT, item);
+    }
+    let ids: Vec<u64> = added.iter().map(|i| i.id).collect();
+    thread::spawn(move || fill_estimates(ids));
+    ensure_worker(&app);
+    Ok(added)
+}
+
+/// Take an item out of the queue. One that's converting is cancelled and its partial
+/// output deleted.
+#[tauri::command]
+pub fn remove_from_queue(app: AppHandle, id: u64) -> Result<(), String> {
+    let (summary, saved) = {
+        let mut state = lock();
+        let index = state
+            .items
+            .iter()
+            .position(|i| i.id == id)
+            .ok_or_else(|| format!("No queue item with id {}", id))?;
+        let item = state.items.remove(index);
+        if item.status == ItemStatus::Running && !cancel::cancel_queue_item(item.id) {
+            tracing::warn!(id, input = %item.input_path, "running queue item had no conversion to cancel");
+        }
+        // Removing the last unfinished item finishes its group
+        let summary = item.group_id.and_then(|group_id| state.take_finished_summary(group_id));
+        (summary, state.snapshot())
+    };
+    persist(saved);
+
+    let _ = app.emit(ITEM_REMOVED_EVENT, id);
+    if let Some(summary) = summary {
+        let _ = app.emit(GROUP_FINISHED_EVENT, &summary);
+    }
+    Ok(())
+}
+
+/// Current queue contents, grouped
+#[tauri::command]
+pub fn get_queue() -> QueueView {
+    lock().view()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loaded_items(parsed: &ParsedQueue) -> &[QueueItem] {
+        &parsed.0.as_ref().expect("queue should load").2
+    }
+
+    #[test]
+    fn loads_a_queue_saved_before_versioning() {
+        let json = r#"{"next_id": 2, "items": [{"id": 1, "input_path": "/a.mkv", "status": "pending"}]}"#;
+        let (saved, _, items) = parse_saved(json).0.expect("queue should load");
+        assert_eq!(saved.version, QUEUE_FORMAT_VERSION);
+        assert_eq!(items.len(), 1);
+    }
+
+    #[test]
+    fn runs_each_upgrade_from_the_saved_version() {
+        fn rename_order(queue: &mut serde_json::Value) -> Result<(), String> {
+            let object = queue.as_object_mut().ok_or("not an object")?;
+            if let Some(order) = object.remove("sort") {
+                object.insert("order".to_string(), order);
+            }
+            Ok(())
+        }
+        fn bump_next_id(queue: &mut serde_json::Value) -> Result<(), String> {
+            queue["next_id"] = serde_json::Value::from(queue["next_id"].as_u64().unwrap_or(0) + 100);
+            Ok(())
+        }
+        let upgrades: &[QueueUpgrade] = &[adopt_queue_versioning, rename_order, bump_next_id];
+
+        let json = r#"{"version": 1, "next_id": 3, "sort": "largest_first"}"#;
+        let (saved, _, _) = parse_saved_with(json, upgrades).0.expect("queue should load");
+        assert_eq!(saved.version, 3);
+        assert_eq!(saved.order, QueueOrder::LargestFirst);
+        assert_eq!(saved.next_id, 103);
+
+        // Already past the rename, so only the last step runs
+        let json = r#"{"version": 2, "next_id": 3, "sort": "largest_first"}"#;
+        let (saved, _, _) = parse_saved_with(json, upgrades).0.expect("queue should load");
+        assert_eq!(saved.order, QueueOrder::Fifo);
+        assert_eq!(saved.next_id, 103);
+    }
+
+    #[test]
+    fn quarantines_a_queue_from_a_newer_version() {
+        let json = format!(r#"{{"version": {}, "next_id": 2, "items": []}}"#, QUEUE_FORMAT_VERSION + 1);
+        let (queue, bad) = parse_saved(&json);
+        assert!(queue.is_none());
+        assert_eq!(bad.len(), 1);
+        assert!(bad[0].0.contains("newer than this app reads"));
+        assert_eq!(bad[0].1["version"], QUEUE_FORMAT_VERSION + 1);
+    }
+
+    #[test]
+    fn writes_the_format_version() {
+        let saved = QueueState::empty().snapshot();
+        let json = serde_json::to_value(&saved).expect("queue should serialize");
+        assert_eq!(json["version"], QUEUE_FORMAT_VERSION);
+    }
+
+    #[test]
+    fn loads_a_version_1_queue_file() {
+        let json = r#"{
+            "version": 1,
+            "next_id": 4,
+            "order": "smallest_first",
+            "groups": [{"id": 1, "name": "Season 1", "options": null}],
+            "items": [
+                {"id": 2, "input_path": "/videos/e01.mkv", "group_id": 1, "options": null,
+                 "status": "done", "output_path": "/videos/e01.mp4", "error": null},
+                {"id": 3, "input_path": "/videos/e02.mkv", "group_id": 1, "options": null,
+                 "status": "pending", "output_path": null, "error": null}
+            ]
+        }"#;
+        let parsed = parse_saved(json);
+        assert!(parsed.1.is_empty());
+        let (saved, groups, items) = parsed.0.expect("queue should load");
+        assert_eq!(saved.next_id, 4);
+        assert_eq!(saved.order, QueueOrder::SmallestFirst);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].status, ItemStatus::Pending);
+        assert_eq!(items[1].size_bytes, 0);
+    }
+
+    #[test]
+    fn quarantines_only_the_malformed_item() {
+        let json = r#"{
+            "next_id": 4,
+            "items": [
+                {"id": 1, "input_path": "/videos/a.mkv", "status": "pending"},
+                {"id": 2, "input_path": "/videos/b.mkv", "status": "exploded"},
+                {"id": 3, "input_path": "/videos/c.mkv", "status": "failed"}
+            ]
+        }"#;
+        let parsed = parse_saved(json);
+        let ids: Vec<u64> = loaded_items(&parsed).iter().map(|i| i.id).collect();
+        assert_eq!(ids, [1, 3]);
+        assert_eq!(parsed.1.len(), 1);
+        assert_eq!(parsed.1[0].1["id"], 2);
+    }
+
+    #[test]
+    fn quarantines_a_file_that_isnt_json_whole() {
+        let (queue, bad) = parse_saved("{\"next_id\": 3, \"items\": [");
+        assert!(queue.is_none());
+        assert_eq!(bad.len(), 1);
+        assert_eq!(bad[0].1, serde_json::Value::String("{\"next_id\": 3, \"items\": [".to_string()));
+    }
+
+    #[test]
+    fn restoring_keeps_items_enqueued_first() {
+        let mut state = QueueState::empty();
+        let early = state.next_id();
+        state.items.push(QueueItem { id: early, ..parse_item(r#"{"id": 0, "input_path": "/remote.mkv", "status": "pending"}"#) });
+
+        let json = r#"{"next_id": 3, "groups": [{"id": 1, "name": "g", "options": null}],
+            "items": [{"id": 2, "input_path": "/saved.mkv", "group_id": 1, "status": "pending"}]}"#;
+        let (saved, groups, items) = parse_saved(json).0.expect("queue should load");
+        state.merge_restored(&saved, groups, items);
+
+        let paths: Vec<&str> = state.items.iter().map(|i| i.input_path.as_str()).collect();
+        assert_eq!(paths, ["/saved.mkv", "/remote.mkv"]);
+        assert_eq!(state.items[1].id, early);
+        // The restored entries moved past every id handed out, and stay grouped
+        assert_eq!(state.groups[0].id, 3);
+        assert_eq!(state.items[0].id, 4);
+        assert_eq!(state.items[0].group_id, Some(3));
+        assert_eq!(state.next_id, 5);
+    }
+
+    fn parse_item(json: &str) -> QueueItem {
+        serde_json::from_str(json).expect("item should parse")
+    }
+
+    #[test]
+    fn never_reuses_ids() {
+        // Item 7 was removed before saving, so 7 is still taken
+        let json = r#"{"next_id": 8, "items": [{"id": 5, "input_path": "/a.mkv", "status": "done"}]}"#;
+        let (saved, groups, items) = parse_saved(json).0.expect("queue should load");
+        assert_eq!(resumed_next_id(&saved, &groups, &items, 1), 8);
+
+        // A counter behind the entries, e.g. from a file edited by hand
+        let json = r#"{"next_id": 2, "groups": [{"id": 9, "name": "g", "options": null}],
+            "items": [{"id": 5, "input_path": "/a.mkv", "status": "done"}]}"#;
+        let (saved, groups, items) = parse_saved(json).0.expect("queue should load");
+        assert_eq!(resumed_next_id(&saved, &groups, &items, 1), 10);
+
+        // Items added before the saved queue was restored keep their ids too
+        assert_eq!(resumed_next_id(&saved, &groups, &items, 12), 12);
+    }
+}
@@ -0,0 +1,324 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::scan::{self, ScanResult};
+use crate::{queue, store};
+
+pub const REMOTE_FILE: &str = "remote.json";
+
+const DEFAULT_PORT: u16 = 8737;
+
+/// Requests only carry a few paths; anything bigger is refused
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// The request line and headers together, so a client that never ends a line can't grow them
+const MAX_HEAD_BYTES: u64 = 8 * 1024;
+
+/// Connections handled at once; more are turned away until one finishes
+const MAX_CONNECTIONS: usize = 16;
+
+/// How long a client gets to send its whole request, however it paces the bytes
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shorter user-chosen tokens are too easy to guess from the LAN
+const MIN_TOKEN_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteBind {
+    /// Only this machine can connect
+    #[default]
+    Localhost,
+    /// Phones and other machines on the same network can connect
+    Lan,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteSettings {
+    pub enabled: bool,
+    pub bind: RemoteBind,
+    pub port: u16,
+    /// Sent as `Authorization: Bearer <token>` or `?token=<token>`
+    pub token: String,
+}
+
+impl Default for RemoteSettings {
+    fn default() -> Self {
+        Self { enabled: false, bind: RemoteBind::default(), port: DEFAULT_PORT, token: String::new() }
+    }
+}
+
+/// The saved settings file's `contents` with the token left out, for backups
+pub fn without_token(contents: &[u8]) -> Result<Vec<u8>, String> {
+    let mut settings: RemoteSettings =
+        serde_json::from_slice(contents).map_err(|e| format!("Failed to read remote settings: {}", e))?;
+    settings.token.clear();
+    serde_json::to_vec_pretty(&settings).map_err(|e| format!("Failed to write remote settings: {}", e))
+}
+
+struct Server {
+    stop: Arc<AtomicBool>,
+    addr: SocketAddr,
+    /// The accept loop, which owns the listener; the port is free once it's joined
+    accept: JoinHandle<()>,
+}
+
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Deserialize)]
+struct EnqueueRequest {
+    /// Files or folders on this machine, e.g. in a shared folder
+    paths: Vec<String>,
+    recursive: Option<bool>,
+    group_id: Option<u64>,
+}
+
+/// What `/enqueue` did with one of its paths: the scan, or why it couldn't be enqueued
+#[derive(Debug, Serialize)]
+struct EnqueueOutcome {
+    path: String,
+    result: Option<ScanResult>,
+    error: Option<String>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+fn generate_token() -> Result<String, String> {
+    let mut bytes = [0u8; 24];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| "Failed to generate a token".to_string())?;
+    Ok(BASE64.encode(bytes))
+}
+
+/// Compare without returning early, so response timing doesn't reveal how much of a guess was right
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Reads from a connection that fail once `deadline` has passed
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Request took too long"));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// A line of the request head, refused if it's cut off by `MAX_HEAD_BYTES` or the connection closing
+fn read_head_line(reader: &mut impl BufRead) -> Result<String, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    if !line.ends_with('\n') {
+        return Err("Request head too large or incomplete".to_string());
+    }
+    Ok(line)
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let deadline = DeadlineReader { stream, deadline: Instant::now() + REQUEST_TIMEOUT };
+    let mut reader = BufReader::new(deadline.take(MAX_HEAD_BYTES));
+    let line = read_head_line(&mut reader)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("Empty request")?.to_string();
+    let target = parts.next().ok_or("Missing request target")?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    let mut authorization = None;
+    let mut content_length = 0;
+    loop {
+        let header = read_head_line(&mut reader)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "authorization" => authorization = value.strip_prefix("Bearer ").map(str::to_string),
+            "content-length" => content_length = value.parse().map_err(|_| "Bad Content-Length")?,
+            _ => {}
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err("Request body too large".to_string());
+    }
+
+    // Some of the body may already be buffered; the limit only needs to let the rest through
+    reader.get_mut().set_limit(content_length as u64);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok(Request { method, path, query, authorization, body })
+}
+
+fn respond(mut stream: &TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn route(app: &AppHandle, request: &Request) -> Result<String, (&'static str, String)> {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/queue") => serde_json::to_string(&queue::get_queue())
+            .map_err(|e| ("500 Internal Server Error", e.to_string())),
+        ("POST", "/enqueue") => {
+            let body: EnqueueRequest = serde_json::from_slice(&request.body)
+                .map_err(|e| ("400 Bad Request", format!("Invalid enqueue request: {}", e)))?;
+            // One bad path doesn't undo or stop the others
+            let results: Vec<EnqueueOutcome> = body
+                .paths
+                .into_iter()
+                .map(|path| match scan::enqueue_folder(app.clone(), path.clone(), body.recursive, body.group_id, None) {
+                    Ok(result) => EnqueueOutcome { path, result: Some(result), error: None },
+                    Err(error) => EnqueueOutcome { path, result: None, error: Some(error) },
+                })
+                .collect();
+            serde_json::to_string(&results).map_err(|e| ("500 Internal Server Error", e.to_string()))
+        }
+        _ => Err(("404 Not Found", "Unknown endpoint".to_string())),
+    }
+}
+
+fn handle_connection(app: &AppHandle, stream: TcpStream, token: &str) {
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(e) => return respond(&stream, "400 Bad Request", &error_body(&e)),
+    };
+
+    let given = request
+        .authorization
+        .clone()
+        .or_else(|| request.query.split('&').find_map(|p| p.strip_prefix("token=")).map(str::to_string));
+    if !given.is_some_and(|t| tokens_match(&t, token)) {
+        tracing::warn!(peer = ?stream.peer_addr().ok(), path = %request.path, "rejected remote request without a valid token");
+        return respond(&stream, "401 Unauthorized", &error_body("Missing or wrong token"));
+    }
+
+    tracing::debug!(method = %request.method, path = %request.path, "remote request");
+    match route(app, &request) {
+        Ok(body) => respond(&stream, "200 OK", &body),
+        Err((status, message)) => respond(&stream, status, &error_body(&message)),
+    }
+}
+
+fn stop_server() {
+    if let Some(server) = SERVER.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        server.stop.store(true, Ordering::Relaxed);
+        // Wake the accept loop so it sees the flag, then wait for it to drop the listener
+        let _ = TcpStream::connect_timeout(&server.addr, Duration::from_secs(1));
+        let _ = server.accept.join();
+    }
+}
+
+fn start_server(app: AppHandle, settings: &RemoteSettings) -> Result<(), String> {
+    let ip = match settings.bind {
+        RemoteBind::Localhost => Ipv4Addr::LOCALHOST,
+        RemoteBind::Lan => Ipv4Addr::UNSPECIFIED,
+    };
+    let listener = TcpListener::bind((ip, settings.port))
+        .map_err(|e| format!("Failed to listen on port {}: {}", settings.port, e))?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, settings.port));
+
+    let token = settings.token.clone();
+    let stop_flag = stop.clone();
+    let accept = thread::spawn(move || {
+        for stream in listener.incoming() {
+            if stop_flag.load(Ordering::Relaxed) {
+                break;
+            }
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if CONNECTIONS.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+                tracing::warn!(peer = ?stream.peer_addr().ok(), "too many remote connections, turning one away");
+                respond(&stream, "503 Service Unavailable", &error_body("Too many connections"));
+                continue;
+            }
+            let app = app.clone();
+            let token = token.clone();
+            thread::spawn(move || {
+                handle_connection(&app, stream, &token);
+                CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    tracing::info!(bind = ?settings.bind, port = settings.port, "remote control listening");
+    *SERVER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Server { stop, addr, accept });
+    Ok(())
+}
+
+/// Start the listener at launch if the user turned it on
+pub fn start(app: AppHandle) {
+    let settings: RemoteSettings = store::load(REMOTE_FILE);
+    if settings.enabled && !settings.token.is_empty() {
+        if let Err(e) = start_server(app, &settings) {
+            tracing::warn!(error = %e, "failed to start remote control");
+        }
+    }
+}
+
+/// Get the remote control settings, including the token to enter on the phone
+#[tauri::command]
+pub fn get_remote_settings() -> RemoteSettings {
+    store::load(REMOTE_FILE)
+}
+
+/// Save remote control settings and restart the listener. An empty token generates a new one.
+#[tauri::command]
+pub fn set_remote_settings(app: AppHandle, mut settings: RemoteSettings) -> Result<RemoteSettings, String> {
+    if settings.token.is_empty() {
+        settings.token = generate_token()?;
+    } else if settings.token.len() < MIN_TOKEN_LEN {
+        return Err(format!("Token must be at least {} characters", MIN_TOKEN_LEN));
+    }
+
+    // Saved first, so a port that won't bind doesn't lose the new settings or token
+    store::save(REMOTE_FILE, &settings)?;
+    stop_server();
+    if settings.enabled {
+        start_server(app, &settings)?;
+    }
+    Ok(settings)
+}
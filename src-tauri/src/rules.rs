@@ -0,0 +1,141 @@
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::convert::ConvertOptions;
+use crate::{folders, presets, store};
+
+const RULES_FILE: &str = "enqueue_rules.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternKind {
+    /// Shell-style wildcards, e.g. "*-screencast.mkv", ignoring case
+    #[default]
+    Glob,
+    /// A regular expression, case-sensitive unless it starts with (?i)
+    Regex,
+}
+
+/// Picks a preset and destination for files whose name matches, when they're enqueued
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnqueueRule {
+    pub name: String,
+    /// Matched against the file name, not the whole path
+    pub pattern: String,
+    #[serde(default)]
+    pub kind: PatternKind,
+    /// A built-in or saved preset
+    pub preset_name: Option<String>,
+    pub output_directory: Option<String>,
+}
+
+/// Which rule picked what for a queue item, so the queue can show why a file is
+/// converting the way it is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleMatch {
+    pub rule: String,
+    pub pattern: String,
+    /// What the rule did, e.g. "preset Small file HEVC", or what it left alone and why
+    pub applied: Vec<String>,
+}
+
+/// What the rules decided for one file
+#[derive(Debug, Default)]
+pub struct Routing {
+    /// The file's options with the rule's preset, when the rule set one
+    pub options: Option<ConvertOptions>,
+    pub output_directory: Option<String>,
+    pub matched: Option<RuleMatch>,
+}
+
+impl EnqueueRule {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Rules need a name".to_string());
+        }
+        match self.kind {
+            PatternKind::Glob => {
+                Pattern::new(&self.pattern).map_err(|e| format!("Invalid pattern in rule {}: {}", self.name, e))?;
+            }
+            PatternKind::Regex => {
+                Regex::new(&self.pattern).map_err(|e| format!("Invalid pattern in rule {}: {}", self.name, e))?;
+            }
+        }
+        if let Some(name) = self.preset_name.as_deref().filter(|n| presets::find_preset(n).is_none()) {
+            return Err(format!("Rule {} uses preset {}, which doesn't exist", self.name, name));
+        }
+        if let Some(dir) = self.output_directory.as_deref().filter(|d| !Path::new(d).is_dir()) {
+            return Err(format!("Rule {} writes to {}, which isn't a folder", self.name, dir));
+        }
+        Ok(())
+    }
+
+    fn matches(&self, file_name: &str) -> bool {
+        match self.kind {
+            PatternKind::Glob => Pattern::new(&self.pattern).is_ok_and(|p| {
+                p.matches_with(file_name, MatchOptions { case_sensitive: false, ..Default::default() })
+            }),
+            PatternKind::Regex => Regex::new(&self.pattern).is_ok_and(|r| r.is_match(file_name)),
+        }
+    }
+}
+
+pub fn load() -> Vec<EnqueueRule> {
+    store::load_profile(RULES_FILE)
+}
+
+/// Apply the first of `rules` matching `path`'s file name. `options` are the ones the file
+/// would otherwise convert with; a preset chosen there wins over the rule's.
+pub fn route(rules: &[EnqueueRule], path: &str, options: Option<&ConvertOptions>) -> Routing {
+    let file_name = Path::new(path).file_name().unwrap_or_default().to_string_lossy();
+    let Some(rule) = rules.iter().find(|r| r.matches(&file_name)) else {
+        return Routing::default();
+    };
+
+    let mut routing = Routing::default();
+    let mut applied = Vec::new();
+    if let Some(preset_name) = &rule.preset_name {
+        let mut options = options.cloned().or_else(|| folders::recall(path)).unwrap_or_default();
+        if options.preset.is_some() || options.preset_name.is_some() {
+            applied.push(format!("preset {} not used: the file already has a preset", preset_name));
+        } else {
+            options.preset_name = Some(preset_name.clone());
+            routing.options = Some(options);
+            applied.push(format!("preset {}", preset_name));
+        }
+    }
+    if let Some(dir) = &rule.output_directory {
+        routing.output_directory = Some(dir.clone());
+        applied.push(format!("output to {}", dir));
+    }
+
+    tracing::info!(rule = %rule.name, path, ?applied, "enqueue rule matched");
+    routing.matched = Some(RuleMatch {
+        rule: rule.name.clone(),
+        pattern: rule.pattern.clone(),
+        applied,
+    });
+    routing
+}
+
+#[tauri::command]
+pub fn get_enqueue_rules() -> Vec<EnqueueRule> {
+    load()
+}
+
+/// Replace the rules; they're tried in order and the first match wins
+#[tauri::command]
+pub fn set_enqueue_rules(rules: Vec<EnqueueRule>) -> Result<(), String> {
+    for rule in &rules {
+        rule.validate()?;
+    }
+    store::save_profile(RULES_FILE, &rules)
+}
+
+/// Which rule enqueueing `path` would match and what it would do, without enqueueing it
+#[tauri::command]
+pub fn test_enqueue_rules(path: String) -> Option<RuleMatch> {
+    route(&load(), &path, None).matched
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::audio::{self, AudioCodec, AudioTarget, ChannelLayout};
+
+/// Longest sample that can be generated; test signals are only useful short
+const MAX_SAMPLE_SECS: f64 = 3600.0;
+
+/// Video codecs a sample can be encoded to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleVideoCodec {
+    #[default]
+    H264,
+    Hevc,
+    Vp9,
+    Av1,
+    /// MPEG-2, for testing the re-encode path of codecs MP4 players rarely handle
+    Mpeg2,
+}
+
+impl SampleVideoCodec {
+    fn encoder(self) -> &'static str {
+        match self {
+            SampleVideoCodec::H264 => "libx264",
+            SampleVideoCodec::Hevc => "libx265",
+            SampleVideoCodec::Vp9 => "libvpx-vp9",
+            SampleVideoCodec::Av1 => "libsvtav1",
+            SampleVideoCodec::Mpeg2 => "mpeg2video",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleContainer {
+    /// The usual input, so the sample can be converted like any other
+    #[default]
+    Mkv,
+    Mp4,
+}
+
+impl SampleContainer {
+    fn extension(self) -> &'static str {
+        match self {
+            SampleContainer::Mkv => "mkv",
+            SampleContainer::Mp4 => "mp4",
+        }
+    }
+}
+
+/// What a generated test video looks like
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SampleSpec {
+    pub duration_secs: f64,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: u32,
+    pub video_codec: SampleVideoCodec,
+    /// No audio track when unset
+    pub audio_codec: Option<AudioCodec>,
+    pub channels: ChannelLayout,
+    pub container: SampleContainer,
+    /// The Videos folder when unset
+    pub output_dir: Option<String>,
+}
+
+impl Default for SampleSpec {
+    fn default() -> Self {
+        SampleSpec {
+            duration_secs: 10.0,
+            width: 1920,
+            height: 1080,
+            frame_rate: 30,
+            video_codec: SampleVideoCodec::default(),
+            audio_codec: Some(AudioCodec::Aac),
+            channels: ChannelLayout::Stereo,
+            container: SampleContainer::default(),
+            output_dir: None,
+        }
+    }
+}
+
+impl SampleSpec {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.duration_secs > 0.0 && self.duration_secs <= MAX_SAMPLE_SECS) {
+            return Err(format!("Sample duration must be between 0 and {} seconds", MAX_SAMPLE_SECS));
+        }
+        // yuv420p needs even dimensions
+        if self.width == 0 || self.height == 0 || self.width % 2 == 1 || self.height % 2 == 1 {
+            return Err("Sample width and height must be even and above zero".to_string());
+        }
+        if self.width > 7680 || self.height > 4320 {
+            return Err("Samples can be at most 7680x4320".to_string());
+        }
+        if !(1..=120).contains(&self.frame_rate) {
+            return Err("Sample frame rate must be between 1 and 120".to_string());
+        }
+        Ok(())
+    }
+
+    /// A lavfi tone per channel, each a different pitch (440 Hz, 550 Hz, ...) so a swapped
+    /// or missing speaker is easy to hear
+    fn audio_source(&self) -> String {
+        let tones: Vec<String> = (0..self.channels.channels())
+            .map(|i| format!("0.5*sin({}*2*PI*t)", 440 + 110 * i))
+            .collect();
+        format!(
+            "aevalsrc={}:c={}:s=48000:d={}",
+            tones.join("|"),
+            self.channels.ffmpeg_name(),
+            self.duration_secs
+        )
+    }
+
+    fn args(&self, output: &str) -> Result<Vec<String>, String> {
+        let container = self.container.extension();
+        let video = format!(
+            "testsrc2=size={}x{}:rate={}:duration={}",
+            self.width, self.height, self.frame_rate, self.duration_secs
+        );
+        let mut args: Vec<String> = ["-hide_banner", "-loglevel", "error", "-f", "lavfi", "-i", &video]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        if self.audio_codec.is_some() {
+            args.extend(["-f".to_string(), "lavfi".to_string(), "-i".to_string(), self.audio_source()]);
+        }
+
+        args.extend([
+            "-c:v".to_string(),
+            self.video_codec.encoder().to_string(),
+            "-pix_fmt".to_string(),
+            "yuv420p".to_string(),
+        ]);
+        if self.video_codec == SampleVideoCodec::Hevc && self.container == SampleContainer::Mp4 {
+            // Apple players only accept HEVC in MP4 tagged hvc1
+            args.extend(["-tag:v".to_string(), "hvc1".to_string()]);
+        }
+        if let Some(codec) = self.audio_codec {
+            args.extend(audio::encode_args(&AudioTarget { codec, bitrate_kbps: None }, container)?);
+        }
+        args.extend(["-y".to_string(), output.to_string()]);
+        Ok(args)
+    }
+
+    /// e.g. sample-1920x1080-h264-aac-5.1.mkv, numbered if that's taken
+    fn output_path(&self) -> Result<PathBuf, String> {
+        let dir = self
+            .output_dir
+            .clone()
+            .map(PathBuf::from)
+            .or_else(dirs::video_dir)
+            .or_else(dirs::home_dir)
+            .ok_or("No output directory")?;
+        let audio = match self.audio_codec {
+            Some(codec) => format!("{}-{}", codec.codec_name(), self.channels.ffmpeg_name()),
+            None => "silent".to_string(),
+        };
+        let stem = format!("sample-{}x{}-{:?}-{}", self.width, self.height, self.video_codec, audio).to_lowercase();
+        let extension = self.container.extension();
+
+        let mut path = dir.join(format!("{}.{}", stem, extension));
+        let mut counter = 1;
+        while path.exists() {
+            path = dir.join(format!("{}_{}.{}", stem, counter, extension));
+            counter += 1;
+        }
+        Ok(path)
+    }
+}
+
+/// Generate a synthetic test video (a moving test pattern plus a tone per audio channel)
+/// for checking a playback chain or the conversion itself. Returns the file's path.
+#[tauri::command(async)]
+pub fn generate_sample(spec: SampleSpec) -> Result<String, String> {
+    spec.validate()?;
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let output = spec.output_path()?.to_string_lossy().to_string();
+    let args = spec.args(&output)?;
+    tracing::debug!(?args, "ffmpeg arguments");
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(&args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(&args)
+        .output();
+
+    let result = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("ffmpeg failed: {}", stderr.trim()));
+    }
+    tracing::info!(%output, "generated sample");
+    Ok(output)
+}
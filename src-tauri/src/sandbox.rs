@@ -0,0 +1,240 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// How the OS is confining the app. Confined apps can't rely on paths outside their own
+/// container: the home folder may be a private copy, and writes elsewhere fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confinement {
+    None,
+    /// macOS runs a quarantined app that hasn't been moved out of Downloads from a
+    /// random read-only mount
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    Translocated,
+    /// macOS App Sandbox, Flatpak or Snap
+    Sandboxed,
+}
+
+impl Confinement {
+    pub fn is_confined(self) -> bool {
+        self != Confinement::None
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeEnvironment {
+    pub confinement: Confinement,
+    /// Where a downloaded ffmpeg goes
+    pub install_dir: Option<String>,
+    pub temp_dir: String,
+    /// Where outputs go when no output folder is set; None means next to the source
+    pub default_output_dir: Option<String>,
+    /// Tools copied from the old install folder into `install_dir` at startup
+    pub migrated_tools: Vec<String>,
+    /// What the user can do about the confinement, if anything
+    pub advice: Option<String>,
+}
+
+static MIGRATED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// What a restricted process may read besides the paths it's given: the system's libraries
+/// and interpreters, never the user's files. `/dev/null` and friends are allowed separately.
+#[cfg(target_os = "macos")]
+const SANDBOX_PROFILE: &str = r#"(version 1)
+(deny default)
+(allow process-fork)
+(allow process-exec)
+(allow signal (target same-sandbox))
+(allow sysctl-read)
+(allow file-read-metadata)
+(allow file-read*
+    (subpath "/usr") (subpath "/bin") (subpath "/System") (subpath "/Library")
+    (subpath "/opt/homebrew") (subpath "/private/etc") (subpath "/private/var/db/dyld")
+    (literal "/dev/null") (literal "/dev/random") (literal "/dev/urandom"))
+(allow file-write* (literal "/dev/null"))
+"#;
+
+#[cfg(target_os = "linux")]
+const BWRAP_PATHS: [&str; 2] = ["/usr/bin/bwrap", "/usr/local/bin/bwrap"];
+
+/// System folders a restricted process sees read-only; missing ones are skipped
+#[cfg(target_os = "linux")]
+const SYSTEM_DIRS: [&str; 7] = ["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc"];
+
+#[cfg(target_os = "macos")]
+fn detect() -> Confinement {
+    if std::env::var_os("APP_SANDBOX_CONTAINER_ID").is_some() {
+        return Confinement::Sandboxed;
+    }
+    let translocated = std::env::current_exe().is_ok_and(|exe| exe.to_string_lossy().contains("/AppTranslocation/"));
+    if translocated {
+        Confinement::Translocated
+    } else {
+        Confinement::None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn detect() -> Confinement {
+    let flatpak = std::env::var_os("FLATPAK_ID").is_some() || std::path::Path::new("/.flatpak-info").exists();
+    if flatpak || std::env::var_os("SNAP").is_some() {
+        Confinement::Sandboxed
+    } else {
+        Confinement::None
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn detect() -> Confinement {
+    Confinement::None
+}
+
+/// Detected once; it can't change while the app runs
+pub fn confinement() -> Confinement {
+    static CONFINEMENT: OnceLock<Confinement> = OnceLock::new();
+    *CONFINEMENT.get_or_init(detect)
+}
+
+/// Temp folder for our intermediate files. Confined apps get one in their own cache
+/// folder: a sandbox's private /tmp is often small or in memory, too little for staged
+/// outputs and local copies of sources.
+pub fn temp_dir() -> PathBuf {
+    static TEMP: OnceLock<PathBuf> = OnceLock::new();
+    TEMP.get_or_init(|| {
+        let own = confinement()
+            .is_confined()
+            .then(|| Some(dirs::cache_dir()?.join(crate::APP_IDENTIFIER).join("tmp")))
+            .flatten()
+            .filter(|dir| fs::create_dir_all(dir).is_ok());
+        own.unwrap_or_else(std::env::temp_dir)
+    })
+    .clone()
+}
+
+/// Where outputs go when no output folder is set. Sandboxed apps can read the files they're
+/// handed but not write next to them, so they get the Movies or Videos folder instead.
+pub fn default_output_dir() -> Option<PathBuf> {
+    if confinement() != Confinement::Sandboxed {
+        return None;
+    }
+    dirs::video_dir().or_else(dirs::download_dir).filter(|dir| dir.is_dir())
+}
+
+/// Copy a previously downloaded ffmpeg and ffprobe from the old install folder into the
+/// confined one, so an install from before isn't lost. Runs at startup.
+pub fn migrate_tools() {
+    if !confinement().is_confined() {
+        return;
+    }
+    let (Some(old), Some(new)) = (crate::home_bin_dir(), crate::local_bin_dir()) else {
+        return;
+    };
+    if old == new {
+        return;
+    }
+
+    #[cfg(target_os = "windows")]
+    let names = ["ffmpeg.exe", "ffprobe.exe"];
+
+    #[cfg(not(target_os = "windows"))]
+    let names = ["ffmpeg", "ffprobe"];
+
+    for name in names {
+        let (from, to) = (old.join(name), new.join(name));
+        if !from.is_file() || to.exists() {
+            continue;
+        }
+        // fs::copy keeps the executable bit
+        let copied = fs::create_dir_all(&new).and_then(|_| fs::copy(&from, &to));
+        match copied {
+            Ok(_) => {
+                tracing::info!(from = %from.display(), to = %to.display(), "copied tool into the app's container");
+                MIGRATED.lock().unwrap_or_else(|e| e.into_inner()).push(name.to_string());
+            }
+            Err(e) => tracing::warn!(error = %e, from = %from.display(), "failed to copy tool into the app's container"),
+        }
+    }
+}
+
+/// A command that runs `program` with no network, reading only `readable` and the system's
+/// own files and writing only to `writable`, which is also its working directory. That's
+/// sandbox-exec on macOS and bubblewrap on Linux; where neither is available this fails
+/// rather than running `program` unrestricted.
+#[cfg(target_os = "macos")]
+pub fn restricted_command(program: &Path, readable: &[&Path], writable: &Path) -> Result<Command, String> {
+    // Rules match real paths, and /tmp and /var are symlinks into /private
+    let quoted = |path: &Path| -> Result<String, String> {
+        let real = path.canonicalize().map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+        Ok(format!("\"{}\"", real.to_string_lossy().replace('\\', "\\\\").replace('"', "\\\"")))
+    };
+    let mut profile = SANDBOX_PROFILE.to_string();
+    for path in readable {
+        profile.push_str(&format!("(allow file-read* (subpath {}))\n", quoted(path)?));
+    }
+    profile.push_str(&format!("(allow file-read* file-write* (subpath {}))\n", quoted(writable)?));
+
+    let mut command = Command::new("/usr/bin/sandbox-exec");
+    command.arg("-p").arg(profile).arg(program).current_dir(writable);
+    Ok(command)
+}
+
+/// A command that runs `program` with no network, reading only `readable` and the system's
+/// own files and writing only to `writable`, which is also its working directory. That's
+/// sandbox-exec on macOS and bubblewrap on Linux; where neither is available this fails
+/// rather than running `program` unrestricted.
+#[cfg(target_os = "linux")]
+pub fn restricted_command(program: &Path, readable: &[&Path], writable: &Path) -> Result<Command, String> {
+    let bwrap = BWRAP_PATHS
+        .iter()
+        .find(|path| Path::new(path).is_file())
+        .ok_or("Plugins run under bubblewrap (bwrap), which isn't installed")?;
+    let mut command = Command::new(bwrap);
+    command.args(["--unshare-all", "--die-with-parent", "--new-session"]);
+    for dir in SYSTEM_DIRS {
+        command.args(["--ro-bind-try", dir, dir]);
+    }
+    command.args(["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"]);
+    for path in readable {
+        command.arg("--ro-bind").arg(path).arg(path);
+    }
+    command.arg("--bind").arg(writable).arg(writable);
+    command.arg("--chdir").arg(writable).arg("--").arg(program);
+    Ok(command)
+}
+
+/// A command that runs `program` with no network, reading only `readable` and the system's
+/// own files and writing only to `writable`. Windows has no equivalent that works without
+/// admin rights, so this always fails there rather than running `program` unrestricted.
+#[cfg(target_os = "windows")]
+pub fn restricted_command(program: &Path, readable: &[&Path], writable: &Path) -> Result<Command, String> {
+    let _ = (program, readable, writable);
+    Err("Plugins can't be sandboxed on Windows, so they don't run there".to_string())
+}
+
+/// How the app is confined and which folders it uses because of that
+#[tauri::command]
+pub fn get_runtime_environment() -> RuntimeEnvironment {
+    let confinement = confinement();
+    let advice = match confinement {
+        Confinement::None => None,
+        Confinement::Translocated => Some(
+            "macOS is running the app from a temporary copy. Move it to the Applications folder and reopen it."
+                .to_string(),
+        ),
+        Confinement::Sandboxed => Some(
+            "The app is sandboxed, so outputs go to your Videos folder unless you choose an output folder."
+                .to_string(),
+        ),
+    };
+    RuntimeEnvironment {
+        confinement,
+        install_dir: crate::local_bin_dir().map(|d| d.to_string_lossy().to_string()),
+        temp_dir: temp_dir().to_string_lossy().to_string(),
+        default_output_dir: default_output_dir().map(|d| d.to_string_lossy().to_string()),
+        migrated_tools: MIGRATED.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        advice,
+    }
+}
@@ -0,0 +1,235 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::AppHandle;
+
+use crate::convert::ConvertOptions;
+use crate::filters::{self, ScanFilter};
+use crate::queue;
+
+/// Same list the file picker offers, plus transport streams
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mkv", "avi", "mov", "wmv", "flv", "webm", "m4v", "mpeg", "mpg", "3gp", "ts", "m2ts",
+];
+
+/// Suffixes download clients put on files they're still writing
+const PARTIAL_SUFFIXES: &[&str] = &[".part", ".!qB", ".!ut", ".crdownload", ".download", ".partial", ".aria2"];
+
+/// A file modified this recently may still be being written
+const SETTLE_SECS: u64 = 60;
+
+/// How often held-back files are checked again
+const RECHECK_SECS: u64 = 30;
+
+/// Give up waiting on a held-back file after this long
+const MAX_WAIT_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IncompleteFile {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanResult {
+    /// Videos that look complete and can be converted now
+    pub ready: Vec<String>,
+    /// Videos that look like downloads in progress
+    pub incomplete: Vec<IncompleteFile>,
+    /// Videos the folder's `ScanFilter` leaves out, with the reason
+    pub filtered: Vec<IncompleteFile>,
+}
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// Preallocated but not fully written: fewer blocks on disk than the length needs
+#[cfg(unix)]
+fn is_sparse(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    // Small files and filesystems that report 0 blocks can't be judged this way
+    meta.len() > 1024 * 1024 && meta.blocks() > 0 && meta.blocks() * 512 < meta.len() * 9 / 10
+}
+
+#[cfg(not(unix))]
+fn is_sparse(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+/// Why a video file looks like it's still downloading, if it does
+pub fn incomplete_reason(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    // qBittorrent and others keep "movie.mkv.!qB" or "movie.mkv.part" until the download finishes
+    for suffix in PARTIAL_SUFFIXES {
+        let marker = path.with_file_name(format!("{}{}", name, suffix));
+        if marker.exists() {
+            return Some(format!("download marker {} present", marker.display()));
+        }
+    }
+
+    let meta = fs::metadata(path).ok()?;
+    if is_sparse(&meta) {
+        return Some("file is preallocated but not fully written".to_string());
+    }
+    let age = meta
+        .modified()
+        .ok()
+        .and_then(|m| SystemTime::now().duration_since(m).ok())
+        .unwrap_or_default();
+    if age < Duration::from_secs(SETTLE_SECS) {
+        return Some("file was modified in the last minute".to_string());
+    }
+    None
+}
+
+fn collect(dir: &Path, recursive: bool, filter: &ScanFilter, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            if recursive {
+                collect(&path, recursive, filter, found);
+            }
+        } else if filter.is_candidate(&path) {
+            found.push(path);
+        }
+    }
+}
+
+/// Find videos in a folder, separating ones that are still downloading and ones the
+/// folder's filter leaves out. Files that are themselves partial (movie.mkv.part) never
+/// show up since they aren't videos yet.
+pub fn scan(dir: &Path, recursive: bool) -> ScanResult {
+    let filter = filters::for_folder(dir);
+    let mut found = Vec::new();
+    collect(dir, recursive, &filter, &mut found);
+    found.sort();
+
+    let mut result = ScanResult { ready: Vec::new(), incomplete: Vec::new(), filtered: Vec::new() };
+    for path in found {
+        let path_str = path.to_string_lossy().to_string();
+        // Sizes are only final once a download is done, so those wait for `wait_for_completion`
+        if let Some(reason) = filter.name_rejection(dir, &path) {
+            result.filtered.push(IncompleteFile { path: path_str, reason });
+        } else if let Some(reason) = incomplete_reason(&path) {
+            result.incomplete.push(IncompleteFile { path: path_str, reason });
+        } else if let Some(reason) = filter.size_rejection(&path) {
+            result.filtered.push(IncompleteFile { path: path_str, reason });
+        } else {
+            result.ready.push(path_str);
+        }
+    }
+    if !result.filtered.is_empty() {
+        tracing::info!(folder = %dir.display(), filtered = result.filtered.len(), "left out files by the folder's filter");
+    }
+    result
+}
+
+/// Poll held-back files and enqueue each one once it looks complete, unless `filter` then
+/// leaves it out by size
+fn wait_for_completion(
+    app: AppHandle,
+    mut pending: Vec<String>,
+    filter: ScanFilter,
+    group_id: Option<u64>,
+    options: Option<ConvertOptions>,
+) {
+    let started = Instant::now();
+    while !pending.is_empty() && started.elapsed() < Duration::from_secs(MAX_WAIT_SECS) {
+        thread::sleep(Duration::from_secs(RECHECK_SECS));
+
+        let (ready, waiting): (Vec<String>, Vec<String>) = pending
+            .into_iter()
+            .filter(|p| Path::new(p).exists())
+            .partition(|p| incomplete_reason(Path::new(p)).is_none());
+        pending = waiting;
+        let ready: Vec<String> = ready
+            .into_iter()
+            .filter(|p| match filter.size_rejection(Path::new(p)) {
+                Some(reason) => {
+                    tracing::info!(path = %p, %reason, "download finished but the folder's filter leaves it out");
+                    false
+                }
+                None => true,
+            })
+            .collect();
+
+        if !ready.is_empty() {
+            tracing::info!(files = ?ready, "downloads finished, enqueueing");
+            if let Err(e) = queue::enqueue_files(app.clone(), ready, group_id, options.clone()) {
+                tracing::warn!(error = %e, "failed to enqueue finished downloads");
+                return;
+            }
+        }
+    }
+    if !pending.is_empty() {
+        tracing::warn!(files = ?pending, "gave up waiting for downloads to finish");
+    }
+}
+
+/// List videos in a folder, flagging ones that are still downloading
+#[tauri::command(async)]
+pub fn scan_folder(path: String, recursive: Option<bool>) -> Result<ScanResult, String> {
+    let dir = PathBuf::from(&path);
+    if !dir.is_dir() {
+        return Err(format!("Not a folder: {}", path));
+    }
+    Ok(scan(&dir, recursive.unwrap_or(true)))
+}
+
+/// Enqueue every finished video in a folder (or a single file). Ones still downloading
+/// are enqueued automatically when they complete.
+#[tauri::command(async)]
+pub fn enqueue_folder(
+    app: AppHandle,
+    path: String,
+    recursive: Option<bool>,
+    group_id: Option<u64>,
+    options: Option<ConvertOptions>,
+) -> Result<ScanResult, String> {
+    let target = PathBuf::from(&path);
+    // A file picked on its own is converted whatever its folder's filter says
+    let (result, filter) = if target.is_dir() {
+        (scan(&target, recursive.unwrap_or(true)), filters::for_folder(&target))
+    } else if is_video(&target) {
+        let (ready, incomplete) = match incomplete_reason(&target) {
+            Some(reason) => (Vec::new(), vec![IncompleteFile { path, reason }]),
+            None => (vec![path], Vec::new()),
+        };
+        let result = ScanResult { ready, incomplete, filtered: Vec::new() };
+        (result, ScanFilter::default())
+    } else {
+        return Err(format!("Not a folder or video file: {}", path));
+    };
+
+    if !result.ready.is_empty() {
+        queue::enqueue_files(app.clone(), result.ready.clone(), group_id, options.clone())?;
+    }
+    if !result.incomplete.is_empty() {
+        let pending = result.incomplete.iter().map(|f| f.path.clone()).collect();
+        thread::spawn(move || wait_for_completion(app, pending, filter, group_id, options));
+    }
+    Ok(result)
+}
+
+/// Command line for qBittorrent's "Run external program on torrent finished" setting
+#[tauri::command]
+pub fn qbittorrent_command() -> Result<String, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to find the app executable: {}", e))?;
+    Ok(format!("\"{}\" --enqueue \"%F\"", exe.display()))
+}
+
+/// Paths passed as `--enqueue <path>` on the command line, e.g. by a download client
+pub fn enqueue_args() -> Vec<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2)
+        .filter(|pair| pair[0] == "--enqueue")
+        .map(|pair| pair[1].clone())
+        .collect()
+}
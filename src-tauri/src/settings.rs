@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+use crate::energy::{self, EnergySettings};
+use crate::guardrails::{self, Guardrails};
+use crate::naming::{self, OutputSettings};
+use crate::presets::{self, Preset};
+use crate::{queue, store};
+
+/// Settings that don't belong to any one feature
+const GENERAL_SETTINGS_FILE: &str = "settings.json";
+
+/// Most conversions the queue runs at once
+pub const MAX_CONCURRENCY: u32 = 8;
+
+/// `GeneralSettings` as last read or saved, since `find_tool` asks for the ffmpeg path many
+/// times per conversion. None until first read.
+static GENERAL: Mutex<Option<GeneralSettings>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct GeneralSettings {
+    ffmpeg_path: Option<String>,
+    concurrency: Option<u32>,
+}
+
+/// Everything the settings screen edits, in one place. Each part is still stored by the
+/// feature that owns it, so the schema migrations in `migrate` cover all of them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub output: OutputSettings,
+    /// The user's own presets; the built-in ones aren't editable
+    pub custom_presets: Vec<Preset>,
+    pub guardrails: Guardrails,
+    pub energy: EnergySettings,
+    /// ffmpeg to use instead of searching the usual install locations. ffprobe and ffplay
+    /// are looked for next to it first.
+    pub ffmpeg_path: Option<String>,
+    /// Conversions the queue runs at once, up to `MAX_CONCURRENCY`; one when unset
+    pub concurrency: Option<u32>,
+}
+
+fn general<T>(read: impl FnOnce(&GeneralSettings) -> T) -> T {
+    let mut general = GENERAL.lock().unwrap_or_else(|e| e.into_inner());
+    read(general.get_or_insert_with(|| store::load(GENERAL_SETTINGS_FILE)))
+}
+
+/// The ffmpeg chosen in settings, if any
+pub fn ffmpeg_path() -> Option<String> {
+    general(|g| g.ffmpeg_path.clone())
+}
+
+/// How many conversions the queue runs at once
+pub fn queue_concurrency() -> usize {
+    general(|g| g.concurrency.unwrap_or(1).clamp(1, MAX_CONCURRENCY) as usize)
+}
+
+/// Read settings.json again next time, after something other than `update_settings`
+/// replaced it, such as an import
+pub fn forget_cached() {
+    *GENERAL.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+#[tauri::command]
+pub fn get_settings() -> Settings {
+    Settings {
+        output: naming::get_output_directory(),
+        custom_presets: presets::load_custom_presets(),
+        guardrails: guardrails::load(),
+        energy: energy::get_energy_settings(),
+        ffmpeg_path: ffmpeg_path(),
+        concurrency: general(|g| g.concurrency),
+    }
+}
+
+/// Save every part of `settings`. When one part is refused, the parts already saved are
+/// put back, so a bad value leaves all settings as they were.
+#[tauri::command]
+pub fn update_settings(app: AppHandle, settings: Settings) -> Result<(), String> {
+    if let Some(path) = settings.ffmpeg_path.as_deref().filter(|p| !Path::new(p).is_file()) {
+        return Err(format!("ffmpeg not found at {}", path));
+    }
+    if let Some(dir) = settings.output.directory.as_deref().filter(|d| !Path::new(d).is_dir()) {
+        return Err(format!("Not a folder: {}", dir));
+    }
+    if settings.concurrency.is_some_and(|n| !(1..=MAX_CONCURRENCY).contains(&n)) {
+        return Err(format!("Run between 1 and {} conversions at once", MAX_CONCURRENCY));
+    }
+
+    let previous = get_settings();
+    let result = save(&settings);
+    if result.is_err() {
+        // Put back whatever was written before the failing part
+        if let Err(e) = save(&previous) {
+            tracing::error!(error = %e, "failed to restore settings");
+        }
+    }
+    // A raised concurrency starts more workers on what's already queued
+    queue::resume(&app);
+    result
+}
+
+fn save(settings: &Settings) -> Result<(), String> {
+    naming::set_filename_template(settings.output.filename_template.clone())?;
+    naming::set_output_directory(settings.output.directory.clone())?;
+    presets::replace_custom_presets(&settings.custom_presets)?;
+    guardrails::set_guardrails(settings.guardrails.clone())?;
+    energy::set_energy_settings(settings.energy.clone())?;
+    let general = GeneralSettings { ffmpeg_path: settings.ffmpeg_path.clone(), concurrency: settings.concurrency };
+    let result = store::save(GENERAL_SETTINGS_FILE, &general);
+    forget_cached();
+    result
+}
@@ -0,0 +1,243 @@
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::probe::Composition;
+use crate::{keychain, probe, store};
+
+const SHARE_FILE: &str = "share.json";
+
+/// Keychain account the share destination's token is kept under
+const TOKEN_ACCOUNT: &str = "share-token";
+
+/// Emitted with a `PreviewShare` once a conversion's preview is uploaded, or failed to be
+const PREVIEW_SHARED_EVENT: &str = "preview-shared";
+
+/// Previews are scaled down to at most this tall
+const PREVIEW_HEIGHT: u32 = 540;
+
+const PREVIEW_VIDEO_KBPS: u32 = 800;
+
+const CONNECT_TIMEOUT_SECS: u64 = 30;
+
+/// Slowest upload speed put up with, in bytes per second. The blocking client only has a
+/// timeout for the whole request, so an upload gets as long as the preview takes at this
+/// speed, and a server that stops reading fails it instead of hanging the share for good.
+const MIN_UPLOAD_BYTES_PER_SEC: u64 = 32 * 1024;
+
+/// Where previews are uploaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShareBackend {
+    /// PUT to `upload_url` plus the file name, shared as `public_url` plus the file name:
+    /// WebDAV, or a web server or bucket that accepts uploads. Links don't expire.
+    Static { upload_url: String, public_url: String },
+    /// PUT to `url` plus the file name, shared as the link the response body gives back:
+    /// transfer.sh and services like it, which take the expiry as a Max-Days header
+    Transfer { url: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShareSettings {
+    pub backend: Option<ShareBackend>,
+    /// Days a link stays up, with backends that can expire them
+    pub expires_days: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedLink {
+    pub url: String,
+    /// RFC 3339; None when the link doesn't expire
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewShare {
+    pub input_path: String,
+    pub output_path: String,
+    pub link: Option<SharedLink>,
+    pub error: Option<String>,
+}
+
+fn load() -> ShareSettings {
+    store::load_profile(SHARE_FILE)
+}
+
+/// Encode a small H.264/AAC copy of `path` that streams from the first byte, or an AAC-only
+/// .m4a of an audio-only output
+fn render_preview(path: &str) -> Result<PathBuf, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let audio_only = probe::probe(path).is_ok_and(|info| info.composition() == Composition::AudioOnly);
+    let preview = crate::unique_temp_path("preview", if audio_only { "m4a" } else { "mp4" });
+    let preview_str = preview.to_string_lossy().to_string();
+    let scale = format!("scale=-2:'min({},ih)'", PREVIEW_HEIGHT);
+    let video_kbps = format!("{}k", PREVIEW_VIDEO_KBPS);
+    let bufsize = format!("{}k", PREVIEW_VIDEO_KBPS * 2);
+    let mut args = vec!["-hide_banner", "-i", path];
+    if audio_only {
+        args.extend(["-map", "0:a:0", "-vn", "-sn"]);
+    } else {
+        args.extend(["-map", "0:v:0", "-map", "0:a:0?", "-sn", "-vf", &scale]);
+        args.extend(["-c:v", "libx264", "-preset", "veryfast", "-b:v", &video_kbps, "-maxrate", &video_kbps]);
+        args.extend(["-bufsize", &bufsize, "-pix_fmt", "yuv420p"]);
+    }
+    args.extend(["-c:a", "aac", "-b:a", "96k", "-ac", "2"]);
+    // The mp4 muxer, since ffmpeg would pick the ipod one for .m4a
+    args.extend(["-movflags", "+faststart", "-f", "mp4", "-y", &preview_str]);
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&preview);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Preview encode failed: {}", stderr));
+    }
+    Ok(preview)
+}
+
+/// "<stem>-preview-<random>.<ext>", safe in a URL and hard to guess
+fn upload_name(path: &str, ext: &str) -> Result<String, String> {
+    let stem: String = Path::new(path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let mut bytes = [0u8; 6];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| "Failed to generate a file name".to_string())?;
+    let suffix: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("{}-preview-{}.{}", stem.trim_matches('-'), suffix, ext))
+}
+
+fn joined(base: &str, name: &str) -> String {
+    format!("{}/{}", base.trim_end_matches('/'), name)
+}
+
+fn upload(settings: &ShareSettings, token: Option<&str>, file: &Path, name: &str) -> Result<SharedLink, String> {
+    let backend = settings.backend.as_ref().ok_or("No share destination is set up")?;
+    let size = fs::metadata(file).map_err(|e| format!("Failed to read preview: {}", e))?.len();
+    let body = File::open(file).map_err(|e| format!("Failed to read preview: {}", e))?;
+    // Uploads of a few hundred MB take longer than the default timeout
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS))
+        .timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS + size / MIN_UPLOAD_BYTES_PER_SEC))
+        .build()
+        .map_err(|e| format!("Failed to upload preview: {}", e))?;
+    let url = match backend {
+        ShareBackend::Static { upload_url, .. } => joined(upload_url, name),
+        ShareBackend::Transfer { url } => joined(url, name),
+    };
+    let mut request = client.put(&url).body(reqwest::blocking::Body::sized(body, size));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let expires_days = settings.expires_days.filter(|_| matches!(backend, ShareBackend::Transfer { .. }));
+    if let Some(days) = expires_days {
+        request = request.header("Max-Days", days.to_string());
+    }
+    let response = request.send().map_err(|e| format!("Failed to upload preview: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Upload failed with status: {}", response.status()));
+    }
+
+    let link = match backend {
+        ShareBackend::Static { public_url, .. } => joined(public_url, name),
+        ShareBackend::Transfer { .. } => {
+            let text = response.text().map_err(|e| format!("Failed to read the upload's link: {}", e))?;
+            text.lines().next().unwrap_or_default().trim().to_string()
+        }
+    };
+    if !link.starts_with("http") {
+        return Err("The share destination didn't give back a link".to_string());
+    }
+    let expires_at = expires_days.map(|days| (chrono::Local::now() + chrono::Duration::days(i64::from(days))).to_rfc3339());
+    Ok(SharedLink { url: link, expires_at })
+}
+
+/// Encode a low-bitrate preview of `path` and upload it to the share destination, for
+/// sending review copies without the full file
+pub fn share(path: &str) -> Result<SharedLink, String> {
+    let settings = load();
+    if settings.backend.is_none() {
+        return Err("No share destination is set up".to_string());
+    }
+    let preview = render_preview(path)?;
+    let ext = preview.extension().unwrap_or_default().to_string_lossy().to_string();
+    let name = upload_name(path, &ext)?;
+    let token = keychain::load_secret(TOKEN_ACCOUNT).filter(|t| !t.is_empty());
+    // reqwest's blocking client can't be used from the async runtime's threads
+    let upload_file = preview.clone();
+    let result = thread::spawn(move || upload(&settings, token.as_deref(), &upload_file, &name))
+        .join()
+        .unwrap_or_else(|_| Err("Preview upload crashed".to_string()));
+    let _ = fs::remove_file(&preview);
+    if let Ok(link) = &result {
+        tracing::info!(path, link = %link.url, expires_at = ?link.expires_at, "shared preview");
+    }
+    result
+}
+
+/// Share a preview of a finished output on a thread of its own and tell the frontend how it
+/// went. The conversion, and the queue behind it, don't wait for the encode or the upload.
+pub fn share_output(app: &AppHandle, input_path: &str, output_path: &str) {
+    let (app, input_path, output_path) = (app.clone(), input_path.to_string(), output_path.to_string());
+    thread::spawn(move || {
+        let result = share(&output_path);
+        if let Err(e) = &result {
+            tracing::warn!(error = %e, "failed to share preview");
+        }
+        let (link, error) = match result {
+            Ok(link) => (Some(link), None),
+            Err(e) => (None, Some(e)),
+        };
+        let _ = app.emit(PREVIEW_SHARED_EVENT, &PreviewShare { input_path, output_path, link, error });
+    });
+}
+
+#[tauri::command]
+pub fn get_share_settings() -> ShareSettings {
+    load()
+}
+
+/// Set where previews go. `token`, sent as a bearer token with uploads, is kept in the OS
+/// keychain; an empty one clears it and leaving it out keeps the current one.
+#[tauri::command]
+pub fn set_share_settings(settings: ShareSettings, token: Option<String>) -> Result<(), String> {
+    let urls: Vec<&String> = match &settings.backend {
+        Some(ShareBackend::Static { upload_url, public_url }) => vec![upload_url, public_url],
+        Some(ShareBackend::Transfer { url }) => vec![url],
+        None => Vec::new(),
+    };
+    if let Some(url) = urls.iter().find(|u| !u.starts_with("https://")) {
+        return Err(format!("Share destinations must use HTTPS, not {}", url));
+    }
+    if let Some(token) = token {
+        keychain::store_secret(TOKEN_ACCOUNT, &token)?;
+    }
+    store::save_profile(SHARE_FILE, &settings)
+}
+
+/// Upload a preview of an already converted file and return its link
+#[tauri::command(async)]
+pub fn share_preview(path: String) -> Result<SharedLink, String> {
+    share(&path)
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::presets::{self, EncodeQuality};
+
+/// Target frame shape for social platforms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocialAspect {
+    /// 1080x1920, for shorts/reels/stories
+    #[serde(rename = "9:16")]
+    Vertical,
+    /// 1080x1080
+    #[serde(rename = "1:1")]
+    Square,
+}
+
+/// How to fill the frame when the source is a different shape
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SocialFill {
+    /// Fit the whole picture over a blurred, zoomed copy of itself
+    #[default]
+    Blur,
+    /// Scale to cover the frame and cut off the sides
+    Crop,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SocialFormat {
+    pub aspect: SocialAspect,
+    #[serde(default)]
+    pub fill: SocialFill,
+}
+
+impl SocialFormat {
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self.aspect {
+            SocialAspect::Vertical => (1080, 1920),
+            SocialAspect::Square => (1080, 1080),
+        }
+    }
+
+    fn filter_graph(&self) -> String {
+        let (w, h) = self.dimensions();
+        match self.fill {
+            SocialFill::Blur => format!(
+                "[0:v:0]split=2[bg][fg];\
+                 [bg]scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h},boxblur=20:5[bgb];\
+                 [fg]scale={w}:{h}:force_original_aspect_ratio=decrease[fgs];\
+                 [bgb][fgs]overlay=(W-w)/2:(H-h)/2,setsar=1[v]",
+                w = w,
+                h = h,
+            ),
+            SocialFill::Crop => format!(
+                "[0:v:0]scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h},setsar=1[v]",
+                w = w,
+                h = h,
+            ),
+        }
+    }
+
+    /// ffmpeg output arguments: the filter graph plus an H.264/AAC encode platforms accept,
+    /// CRF 20 and 160 kbps audio unless `quality` says otherwise
+    pub fn args(&self, quality: Option<&EncodeQuality>) -> Vec<String> {
+        let mut args: Vec<String> = ["-filter_complex", &self.filter_graph(), "-map", "[v]", "-map", "0:a:0?", "-c:v", "libx264"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        args.extend(presets::x264_rate_args(quality, 20, None));
+        let audio_kbps = quality.and_then(|q| q.audio_bitrate_kbps).unwrap_or(160);
+        args.extend(
+            ["-pix_fmt", "yuv420p", "-c:a", "aac", "-b:a", &format!("{}k", audio_kbps), "-movflags", "+faststart"]
+                .iter()
+                .map(|s| s.to_string()),
+        );
+        args
+    }
+}
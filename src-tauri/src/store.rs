@@ -0,0 +1,60 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn read_from<T: DeserializeOwned + Default>(dir: Result<PathBuf, String>, file_name: &str) -> T {
+    let Some(path) = dir.ok().map(|dir| dir.join(file_name)) else {
+        return T::default();
+    };
+    let Ok(json) = fs::read_to_string(&path) else {
+        return T::default();
+    };
+    match serde_json::from_str(&json) {
+        Ok(value) => value,
+        Err(e) => {
+            quarantine(&path, &e.to_string());
+            T::default()
+        }
+    }
+}
+
+/// Move a file that doesn't parse to `<name>.corrupt-<time>`, so the next save starts afresh
+/// instead of overwriting what the user had
+fn quarantine(path: &Path, error: &str) {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".corrupt-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let target = path.with_file_name(name);
+    match fs::rename(path, &target) {
+        Ok(()) => tracing::warn!(%error, file = %path.display(), moved_to = %target.display(), "set aside unreadable settings file"),
+        Err(e) => tracing::error!(%error, rename_error = %e, file = %path.display(), "failed to set aside unreadable settings file"),
+    }
+}
+
+fn write_to<T: Serialize>(dir: Result<PathBuf, String>, file_name: &str, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", file_name, e))?;
+    fs::write(dir?.join(file_name), json)
+        .map_err(|e| format!("Failed to write {}: {}", file_name, e))
+}
+
+/// Read a JSON file from the app data directory, falling back to the default if missing or
+/// unreadable. Unreadable files are set aside first.
+pub fn load<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    read_from(crate::app_data_dir(), file_name)
+}
+
+/// Write a value as pretty JSON into the app data directory
+pub fn save<T: Serialize>(file_name: &str, value: &T) -> Result<(), String> {
+    write_to(crate::app_data_dir(), file_name, value)
+}
+
+/// Like `load`, but from the active profile's directory
+pub fn load_profile<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    read_from(crate::profiles::profile_dir(), file_name)
+}
+
+/// Like `save`, but into the active profile's directory
+pub fn save_profile<T: Serialize>(file_name: &str, value: &T) -> Result<(), String> {
+    write_to(crate::profiles::profile_dir(), file_name, value)
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::naming::{self, FilenamePolicy};
+
+/// How long to let ffmpeg finish on its own after the writer should be done
+const STOP_GRACE_SECS: u64 = 10;
+
+/// Fragmented MP4 is playable while it's still being written and survives being cut off
+pub const LIVE_MOVFLAGS: &str = "+frag_keyframe+empty_moov+default_base_moof";
+
+/// An open streamed input: another program writes into `source`, ffmpeg remuxes to `output_path`
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamInput {
+    pub id: u64,
+    /// FIFO path on macOS/Linux, tcp:// URL on Windows
+    pub source: String,
+    pub output_path: String,
+}
+
+struct Session {
+    input: StreamInput,
+    child: Child,
+    fifo: Option<PathBuf>,
+}
+
+static SESSIONS: Mutex<BTreeMap<u64, Session>> = Mutex::new(BTreeMap::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Create the thing the capture tool writes to, returning ffmpeg's input and any FIFO to clean up
+#[cfg(not(target_os = "windows"))]
+fn create_source() -> Result<(String, Option<PathBuf>), String> {
+    let fifo = crate::unique_temp_path("stream", "fifo");
+    let status = Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .map_err(|e| format!("Failed to run mkfifo: {}", e))?;
+    if !status.success() {
+        return Err("Failed to create named pipe".to_string());
+    }
+    Ok((fifo.to_string_lossy().to_string(), Some(fifo)))
+}
+
+/// Windows FIFOs need a pipe server, so ffmpeg listens on a loopback socket instead
+#[cfg(target_os = "windows")]
+fn create_source() -> Result<(String, Option<PathBuf>), String> {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map_err(|e| format!("Failed to find a free port: {}", e))?
+        .port();
+    Ok((format!("tcp://127.0.0.1:{}?listen=1", port), None))
+}
+
+/// Open a pipe (or loopback socket) that ffmpeg remuxes to MP4 as data arrives
+#[tauri::command]
+pub fn open_stream_input(
+    name: Option<String>,
+    output_dir: Option<String>,
+    filename_policy: Option<FilenamePolicy>,
+) -> Result<StreamInput, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let dir = output_dir
+        .map(PathBuf::from)
+        .or_else(dirs::video_dir)
+        .or_else(dirs::home_dir)
+        .ok_or("No output directory")?;
+    let name = name.unwrap_or_else(|| format!("stream-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let output_path = naming::get_unique_output_path(
+        &dir.join(&name).to_string_lossy(),
+        "mp4",
+        filename_policy.unwrap_or_default(),
+    );
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let (source, fifo) = create_source()?;
+    let args = [
+        "-hide_banner", "-loglevel", "error",
+        "-i", &source,
+        "-codec", "copy",
+        "-movflags", LIVE_MOVFLAGS,
+        "-y", &output_str,
+    ];
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let child = result.map_err(|e| {
+        if let Some(fifo) = &fifo {
+            let _ = fs::remove_file(fifo);
+        }
+        format!("Failed to run ffmpeg: {}", e)
+    })?;
+
+    let input = StreamInput {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        source,
+        output_path: output_str,
+    };
+    tracing::info!(id = input.id, source = %input.source, output = %input.output_path, "opened stream input");
+    SESSIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(input.id, Session { input: input.clone(), child, fifo });
+    Ok(input)
+}
+
+/// Streamed inputs that haven't been closed yet
+#[tauri::command]
+pub fn list_stream_inputs() -> Vec<StreamInput> {
+    SESSIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .map(|s| s.input.clone())
+        .collect()
+}
+
+/// Finish a streamed input. ffmpeg gets a few seconds to flush after the writer closes;
+/// if it's still running after that it's stopped, keeping whatever was written.
+#[tauri::command(async)]
+pub fn close_stream_input(id: u64) -> Result<String, String> {
+    let mut session = SESSIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id)
+        .ok_or_else(|| format!("No stream input with id {}", id))?;
+
+    let started = Instant::now();
+    let status = loop {
+        match session.child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if started.elapsed() > Duration::from_secs(STOP_GRACE_SECS) => {
+                let _ = session.child.kill();
+                let _ = session.child.wait();
+                break None;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(200)),
+            Err(e) => return Err(format!("Failed to wait for ffmpeg: {}", e)),
+        }
+    };
+
+    let mut stderr = String::new();
+    if let Some(mut pipe) = session.child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    if let Some(fifo) = &session.fifo {
+        let _ = fs::remove_file(fifo);
+    }
+
+    let output = session.input.output_path;
+    match status {
+        Some(status) if !status.success() => Err(format!("ffmpeg failed: {}", stderr)),
+        _ if !PathBuf::from(&output).exists() => Err("Nothing was received on the stream".to_string()),
+        _ => {
+            tracing::info!(id, %output, "closed stream input");
+            Ok(output)
+        }
+    }
+}
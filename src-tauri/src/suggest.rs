@@ -0,0 +1,186 @@
+use serde::Deserialize;
+
+use crate::h264::H264Constraints;
+use crate::presets::Preset;
+
+/// Where the user says the converted file will be played
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackTarget {
+    Browser,
+    Phone,
+    /// A TV or set-top box streaming over DLNA/UPnP
+    Tv,
+    /// Importing into a video editor
+    Editing,
+}
+
+/// Answers from the "where will you play this?" questions
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PresetAnswers {
+    pub target: PlaybackTarget,
+    /// Trade quality for a smaller file
+    #[serde(default)]
+    pub prefer_small: bool,
+}
+
+/// One row of the suggestions table
+struct Rule {
+    target: PlaybackTarget,
+    prefer_small: bool,
+    name: &'static str,
+    description: &'static str,
+    crf: u8,
+    encoder_preset: &'static str,
+    max_height: Option<u32>,
+    audio_codec: &'static str,
+    audio_bitrate_kbps: u32,
+    /// H.264 profile and level the target's decoder is certified for
+    h264: Option<(&'static str, &'static str)>,
+    extra_args: &'static [&'static str],
+}
+
+/// H.264 is the one codec every target below decodes everywhere; the rows differ in
+/// quality, resolution, audio and how the stream is laid out
+const RULES: &[Rule] = &[
+    Rule {
+        target: PlaybackTarget::Browser,
+        prefer_small: false,
+        name: "Web",
+        description: "Plays in any browser and starts before it's fully downloaded",
+        crf: 22,
+        encoder_preset: "medium",
+        max_height: Some(1080),
+        audio_codec: "aac",
+        audio_bitrate_kbps: 160,
+        h264: None,
+        extra_args: &["-pix_fmt", "yuv420p", "-profile:v", "high", "-movflags", "+faststart"],
+    },
+    Rule {
+        target: PlaybackTarget::Browser,
+        prefer_small: true,
+        name: "Web (small)",
+        description: "720p for sharing links and slow connections",
+        crf: 26,
+        encoder_preset: "medium",
+        max_height: Some(720),
+        audio_codec: "aac",
+        audio_bitrate_kbps: 128,
+        h264: None,
+        extra_args: &["-pix_fmt", "yuv420p", "-profile:v", "high", "-movflags", "+faststart", "-ac", "2"],
+    },
+    Rule {
+        target: PlaybackTarget::Phone,
+        prefer_small: false,
+        name: "Phone",
+        description: "1080p with stereo audio for iPhone and Android",
+        crf: 23,
+        encoder_preset: "medium",
+        max_height: Some(1080),
+        audio_codec: "aac",
+        audio_bitrate_kbps: 160,
+        h264: Some(("high", "4.1")),
+        extra_args: &["-ac", "2", "-movflags", "+faststart"],
+    },
+    Rule {
+        target: PlaybackTarget::Phone,
+        prefer_small: true,
+        name: "Phone (small)",
+        description: "720p to save storage on the device",
+        crf: 27,
+        encoder_preset: "medium",
+        max_height: Some(720),
+        audio_codec: "aac",
+        audio_bitrate_kbps: 96,
+        h264: None,
+        extra_args: &["-pix_fmt", "yuv420p", "-profile:v", "main", "-ac", "2", "-movflags", "+faststart"],
+    },
+    Rule {
+        target: PlaybackTarget::Tv,
+        prefer_small: false,
+        name: "TV (DLNA)",
+        description: "High-quality 1080p with 5.1 AC-3 that TVs and receivers pass through",
+        crf: 19,
+        encoder_preset: "slow",
+        max_height: Some(1080),
+        audio_codec: "ac3",
+        audio_bitrate_kbps: 640,
+        h264: Some(("high", "4.1")),
+        extra_args: &[],
+    },
+    Rule {
+        target: PlaybackTarget::Tv,
+        prefer_small: true,
+        name: "TV (DLNA, small)",
+        description: "1080p at a lower bitrate for Wi-Fi streaming",
+        crf: 23,
+        encoder_preset: "medium",
+        max_height: Some(1080),
+        audio_codec: "ac3",
+        audio_bitrate_kbps: 384,
+        h264: Some(("high", "4.1")),
+        extra_args: &["-maxrate", "8M", "-bufsize", "16M"],
+    },
+    Rule {
+        target: PlaybackTarget::Editing,
+        prefer_small: false,
+        name: "Editing",
+        description: "Near-lossless with short GOPs so timelines scrub smoothly",
+        crf: 16,
+        encoder_preset: "fast",
+        max_height: None,
+        audio_codec: "aac",
+        audio_bitrate_kbps: 320,
+        h264: None,
+        extra_args: &["-pix_fmt", "yuv420p", "-g", "12", "-bf", "0"],
+    },
+    Rule {
+        target: PlaybackTarget::Editing,
+        prefer_small: true,
+        name: "Editing (proxy)",
+        description: "Lightweight 720p proxy for offline editing",
+        crf: 23,
+        encoder_preset: "veryfast",
+        max_height: Some(720),
+        audio_codec: "aac",
+        audio_bitrate_kbps: 192,
+        h264: None,
+        extra_args: &["-pix_fmt", "yuv420p", "-g", "12", "-bf", "0"],
+    },
+];
+
+impl Rule {
+    fn to_preset(&self) -> Preset {
+        Preset {
+            name: self.name.to_string(),
+            description: self.description.to_string(),
+            video_codec: "libx264".to_string(),
+            audio_codec: self.audio_codec.to_string(),
+            crf: Some(self.crf),
+            video_bitrate_kbps: None,
+            audio_bitrate_kbps: Some(self.audio_bitrate_kbps),
+            encoder_preset: Some(self.encoder_preset.to_string()),
+            max_height: self.max_height,
+            extra_args: self.extra_args.iter().map(|s| s.to_string()).collect(),
+            h264: self.h264.map(|(profile, level)| H264Constraints {
+                profile: profile.to_string(),
+                level: level.to_string(),
+            }),
+            film_grain: None,
+            quality_critical: false,
+        }
+    }
+}
+
+/// Pick a concrete preset from the user's answers about where they'll watch
+#[tauri::command]
+pub fn suggest_preset(answers: PresetAnswers) -> Result<Preset, String> {
+    let rule = RULES
+        .iter()
+        .find(|rule| rule.target == answers.target && rule.prefer_small == answers.prefer_small)
+        .ok_or("No suggestion for these answers")?;
+
+    let preset = rule.to_preset();
+    preset.validate()?;
+    Ok(preset)
+}
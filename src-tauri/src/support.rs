@@ -0,0 +1,202 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::history::{self, HistoryEntry};
+use crate::{logging, migrate, probe};
+
+/// Settings files left out of a bundle: they list every file converted, not how the app is set up
+const PRIVATE_FILES: &[&str] = &["history.json", "metrics.json", "queue.json", "queue-quarantine.json"];
+
+/// Keys whose values are replaced in settings, e.g. the remote control token
+const SECRET_KEYS: &[&str] = &["token", "secret", "password", "key"];
+
+/// ffmpeg arguments whose next argument is a secret
+const SECRET_ARGS: &[&str] = &["-encryption_key", "-decryption_key"];
+
+/// Log bytes included, from the end of the current log
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+
+const REDACTED: &str = "[redacted]";
+
+/// About the app and the machine it runs on
+#[derive(Debug, Clone, Serialize)]
+struct Diagnostics {
+    app_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    schema_version: u32,
+    ffmpeg_path: Option<String>,
+    /// First line of `ffmpeg -version`
+    ffmpeg_version: Option<String>,
+    /// Whether the app writes its log to a file; without it there are no logs in the bundle
+    file_logging: bool,
+}
+
+fn ffmpeg_version(path: &str) -> Option<String> {
+    #[cfg(target_os = "windows")]
+    let output = Command::new("cmd").args(["/C", path, "-version"]).output().ok()?;
+
+    #[cfg(not(target_os = "windows"))]
+    let output = Command::new(path).arg("-version").output().ok()?;
+
+    String::from_utf8_lossy(&output.stdout).lines().next().map(str::to_string)
+}
+
+fn diagnostics() -> Diagnostics {
+    let ffmpeg_path = crate::find_ffmpeg();
+    Diagnostics {
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        schema_version: migrate::SCHEMA_VERSION,
+        ffmpeg_version: ffmpeg_path.as_deref().and_then(ffmpeg_version),
+        ffmpeg_path,
+        file_logging: logging::get_log_settings().file_output,
+    }
+}
+
+fn is_secret(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_KEYS.iter().any(|s| key.contains(s))
+}
+
+/// Replace the values of secret-looking keys anywhere in `value`
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_secret(key) && !value.is_null() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// The failed job with any encryption key ffmpeg was given taken out
+fn redact_failure(mut entry: HistoryEntry) -> HistoryEntry {
+    let secret_at: Vec<usize> = entry
+        .command
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| SECRET_ARGS.contains(&arg.as_str()))
+        .map(|(i, _)| i + 1)
+        .collect();
+    for i in secret_at {
+        if let Some(arg) = entry.command.get_mut(i) {
+            *arg = REDACTED.to_string();
+        }
+    }
+    entry
+}
+
+/// The last `MAX_LOG_BYTES` of a log file, from the start of a line
+fn log_tail(path: &Path) -> Result<Vec<u8>, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let size = file.metadata().map_err(|e| format!("Failed to read log size: {}", e))?.len();
+    file.seek(SeekFrom::Start(size.saturating_sub(MAX_LOG_BYTES)))
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if size > MAX_LOG_BYTES {
+        let start = tail.iter().position(|&b| b == b'\n').map_or(0, |i| i + 1);
+        tail.drain(..start);
+    }
+    Ok(tail)
+}
+
+/// Settings files, redacted, named like in `export_app_data`
+fn settings_files() -> Result<Vec<(String, Vec<u8>)>, String> {
+    let root = crate::app_data_dir()?;
+    let mut files = Vec::new();
+    for dir in migrate::data_dirs(&root) {
+        for file in migrate::json_files(&dir) {
+            let name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if PRIVATE_FILES.contains(&name.as_str()) {
+                continue;
+            }
+            let contents = fs::read(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+            // Unparseable files could hold anything, so they're left out rather than sent as is
+            let Ok(mut value) = serde_json::from_slice::<Value>(&contents) else {
+                tracing::warn!(file = %file.display(), "leaving unreadable settings file out of support bundle");
+                continue;
+            };
+            redact(&mut value);
+            let relative = file.strip_prefix(&root).unwrap_or(&file);
+            let entry = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((format!("settings/{}", entry), json(&value)?));
+        }
+    }
+    Ok(files)
+}
+
+fn json(value: &impl Serialize) -> Result<Vec<u8>, String> {
+    serde_json::to_vec_pretty(value).map_err(|e| format!("Failed to write bundle: {}", e))
+}
+
+fn default_bundle_path() -> Result<PathBuf, String> {
+    let dir = dirs::download_dir().or_else(dirs::home_dir).ok_or("No folder to save the bundle in")?;
+    Ok(dir.join(format!("mkv-to-mp4-support-{}.zip", chrono::Local::now().format("%Y%m%d-%H%M%S"))))
+}
+
+/// Zip up what an issue report needs: app and ffmpeg versions, recent logs, settings with
+/// secrets redacted, and the latest failed conversion (of `input_path` when given) with its
+/// ffmpeg command, stderr and the source's probe. Returns the zip's path, in Downloads
+/// unless `path` is given.
+#[tauri::command(async)]
+pub fn create_support_bundle(input_path: Option<String>, path: Option<String>) -> Result<String, String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => default_bundle_path()?,
+    };
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    files.push(("diagnostics.json".to_string(), json(&diagnostics())?));
+    files.extend(settings_files()?);
+
+    let log_dir = logging::log_dir()?;
+    for name in ["app.log", "app.1.log"] {
+        let log = log_dir.join(name);
+        if log.is_file() {
+            files.push((format!("logs/{}", name), log_tail(&log)?));
+        }
+    }
+
+    let failure = history::latest_failure(input_path.as_deref()).map(redact_failure);
+    let source = input_path.or_else(|| failure.as_ref().map(|f| f.input_path.clone()));
+    if let Some(failure) = &failure {
+        files.push(("failed_job.json".to_string(), json(failure)?));
+        let log = failure.log.join("\n");
+        files.push(("ffmpeg_stderr.txt".to_string(), log.into_bytes()));
+    }
+    if let Some(source) = source {
+        match probe::probe(&source) {
+            Ok(info) => files.push(("ffprobe.json".to_string(), json(&info)?)),
+            Err(e) => tracing::warn!(error = %e, source, "failed to probe source for support bundle"),
+        }
+    }
+
+    let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, contents) in &files {
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| format!("Failed to write zip: {}", e))?;
+        zip.write_all(contents).map_err(|e| format!("Failed to write zip: {}", e))?;
+    }
+    zip.finish().map_err(|e| format!("Failed to write zip: {}", e))?;
+
+    tracing::info!(path = %path.display(), files = files.len(), "created support bundle");
+    Ok(path.to_string_lossy().to_string())
+}
@@ -0,0 +1,125 @@
+use std::process::Child;
+
+/// Stop `pid` from being scheduled until `resume` is called
+#[cfg(unix)]
+pub fn suspend(pid: u32) -> Result<(), String> {
+    signal(pid, libc::SIGSTOP)
+}
+
+#[cfg(unix)]
+pub fn resume(pid: u32) -> Result<(), String> {
+    signal(pid, libc::SIGCONT)
+}
+
+#[cfg(unix)]
+fn signal(pid: u32, signal: i32) -> Result<(), String> {
+    // SAFETY: kill only sends a signal; a pid that's gone makes it fail with ESRCH
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(format!("Failed to signal ffmpeg: {}", std::io::Error::last_os_error()))
+    }
+}
+
+#[cfg(windows)]
+pub fn suspend(pid: u32) -> Result<(), String> {
+    windows::for_tree(pid, windows::NtSuspendProcess, "suspend")
+}
+
+#[cfg(windows)]
+pub fn resume(pid: u32) -> Result<(), String> {
+    windows::for_tree(pid, windows::NtResumeProcess, "resume")
+}
+
+/// Kill `child` and, on Windows, every process under it. Tools run through `cmd /C` there,
+/// and killing cmd.exe alone leaves ffmpeg running and holding its output open.
+pub fn kill(child: &mut Child) {
+    #[cfg(windows)]
+    windows::terminate_tree(child.id());
+    let _ = child.kill();
+}
+
+#[cfg(windows)]
+mod windows {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    };
+    use windows_sys::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_SUSPEND_RESUME, PROCESS_TERMINATE};
+
+    // Undocumented but stable since XP; the documented way is suspending every thread
+    #[link(name = "ntdll")]
+    extern "system" {
+        pub fn NtSuspendProcess(process: HANDLE) -> i32;
+        pub fn NtResumeProcess(process: HANDLE) -> i32;
+    }
+
+    /// `pid` and every process under it: conversions run ffmpeg through `cmd /C`, so the
+    /// process the app started isn't the one doing the work
+    fn process_tree(pid: u32) -> Vec<u32> {
+        let mut parents: Vec<(u32, u32)> = Vec::new();
+        // SAFETY: the entry is plain data sized as the API asks, and the snapshot is closed
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return vec![pid];
+            }
+            let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+            let mut more = Process32FirstW(snapshot, &mut entry) != 0;
+            while more {
+                parents.push((entry.th32ProcessID, entry.th32ParentProcessID));
+                more = Process32NextW(snapshot, &mut entry) != 0;
+            }
+            CloseHandle(snapshot);
+        }
+
+        let mut tree = vec![pid];
+        let mut i = 0;
+        while i < tree.len() {
+            let parent = tree[i];
+            for &(child, _) in parents.iter().filter(|(_, p)| *p == parent) {
+                // Parent ids of exited processes get reused, which could otherwise loop
+                if !tree.contains(&child) {
+                    tree.push(child);
+                }
+            }
+            i += 1;
+        }
+        tree
+    }
+
+    pub fn for_tree(pid: u32, action: unsafe extern "system" fn(HANDLE) -> i32, what: &str) -> Result<(), String> {
+        for pid in process_tree(pid) {
+            // SAFETY: the handle is checked before use and closed after
+            let status = unsafe {
+                let handle = OpenProcess(PROCESS_SUSPEND_RESUME, 0, pid);
+                if handle.is_null() {
+                    return Err(format!("Failed to {} ffmpeg: {}", what, std::io::Error::last_os_error()));
+                }
+                let status = action(handle);
+                CloseHandle(handle);
+                status
+            };
+            if status < 0 {
+                return Err(format!("Failed to {} ffmpeg: NTSTATUS {:#x}", what, status));
+            }
+        }
+        Ok(())
+    }
+
+    /// Kill `pid` and everything under it, children first so nothing is left to start more
+    pub fn terminate_tree(pid: u32) {
+        for pid in process_tree(pid).into_iter().rev() {
+            // SAFETY: the handle is checked before use and closed after
+            unsafe {
+                let handle = OpenProcess(PROCESS_TERMINATE, 0, pid);
+                if handle.is_null() {
+                    continue;
+                }
+                TerminateProcess(handle, 1);
+                CloseHandle(handle);
+            }
+        }
+    }
+}
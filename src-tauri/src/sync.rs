@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tauri::{AppHandle, Emitter};
+
+use crate::convert::{self, ConvertOptions, Destination};
+use crate::naming;
+use crate::probe::{self, MediaInfo};
+use crate::scan::{self, IncompleteFile};
+
+/// Emitted with a `SyncProgress` before each file is converted
+const SYNC_PROGRESS_EVENT: &str = "sync-progress";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SyncOptions {
+    pub recursive: bool,
+    /// Delete MP4s in the destination whose source is gone
+    pub delete_orphans: bool,
+    /// Only report what would happen
+    pub dry_run: bool,
+    pub convert: Option<ConvertOptions>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self { recursive: true, delete_orphans: false, dry_run: false, convert: None }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    /// Sources converted because the destination had no output for them
+    pub added: Vec<String>,
+    /// Sources converted because they changed after their output was written
+    pub updated: Vec<String>,
+    pub up_to_date: usize,
+    pub failed: Vec<SyncFailure>,
+    /// Sources left alone: still downloading, or clashing with another source's output name
+    pub skipped: Vec<IncompleteFile>,
+    /// Destination MP4s with no source; deleted when `delete_orphans` is set
+    pub orphans: Vec<String>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncProgress {
+    pub path: String,
+    pub index: usize,
+    pub total: usize,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// What outputs are named: .mp4, or .m4a for audio-only sources
+const OUTPUT_EXTENSIONS: [&str; 2] = ["mp4", "m4a"];
+
+/// Where a source's output lives, without its extension: the same relative folder under
+/// the destination
+fn destination_for(source_root: &Path, dest_root: &Path, source: &Path, options: &ConvertOptions) -> PathBuf {
+    let relative = source.strip_prefix(source_root).unwrap_or(source);
+    let stem = naming::sanitize_stem(&source.file_stem().unwrap_or_default().to_string_lossy(), options.filename_policy);
+    dest_root.join(relative).with_file_name(stem)
+}
+
+/// `base` with `.ext` added; `with_extension` would cut a stem containing dots
+fn with_ext(base: &Path, ext: &str) -> PathBuf {
+    let name = base.file_name().unwrap_or_default().to_string_lossy();
+    base.with_file_name(format!("{}.{}", name, ext))
+}
+
+/// Split outputs are written as `<stem>_part000.mp4`, `<stem>_part001.mp4`, ...
+fn unsplit_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    match stem.rsplit_once("_part") {
+        Some((base, n)) if n.len() == 3 && n.bytes().all(|b| b.is_ascii_digit()) => {
+            path.with_file_name(format!("{}.{}", base, ext))
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Where a source is converted before it replaces `output`: a hidden name beside it, so a
+/// failed conversion leaves the previous output alone
+fn staging_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = output.extension().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!(".{}.syncing.{}", stem, ext))
+}
+
+/// Files a conversion to `staged` wrote, split parts, sidecars and an encrypted copy included,
+/// with the names they take once moved over `output`
+fn staged_files(staged: &Path, output: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let staged_stem = staged.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let Some(dir) = output.parent() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let rest = name.strip_prefix(&staged_stem)?;
+            Some((entry.path(), dir.join(format!("{}{}", stem, rest))))
+        })
+        .collect()
+}
+
+fn collect_outputs(dir: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            collect_outputs(&path, found);
+        } else if path.extension().is_some_and(|e| OUTPUT_EXTENSIONS.iter().any(|ext| e.eq_ignore_ascii_case(ext))) {
+            found.push(path);
+        }
+    }
+}
+
+/// Mirror a source tree into a destination tree of MP4s (M4As for audio): convert only
+/// sources whose output is missing or older than they are, and optionally delete outputs
+/// whose source is gone
+#[tauri::command(async)]
+pub fn sync_library(
+    app: AppHandle,
+    source: String,
+    destination: String,
+    options: Option<SyncOptions>,
+) -> Result<SyncReport, String> {
+    let options = options.unwrap_or_default();
+    let source_root = PathBuf::from(&source);
+    let dest_root = PathBuf::from(&destination);
+    if !source_root.is_dir() {
+        return Err(format!("Not a folder: {}", source));
+    }
+    if dest_root.starts_with(&source_root) && options.recursive {
+        return Err("The destination can't be inside the source".to_string());
+    }
+    let convert_options = options.convert.clone().unwrap_or_default();
+
+    let scanned = scan::scan(&source_root, options.recursive);
+    let mut report = SyncReport { skipped: scanned.incomplete, dry_run: options.dry_run, ..Default::default() };
+
+    // (source, its probe, output, output already exists)
+    let mut work: Vec<(String, Option<MediaInfo>, PathBuf, bool)> = Vec::new();
+    let mut bases = BTreeSet::new();
+    let mut expected = BTreeSet::new();
+    // Filtered sources still exist, so their earlier outputs aren't orphans, whichever extension they have
+    for file in scanned.filtered {
+        let base = destination_for(&source_root, &dest_root, Path::new(&file.path), &convert_options);
+        expected.extend(OUTPUT_EXTENSIONS.map(|ext| with_ext(&base, ext)));
+        report.skipped.push(file);
+    }
+    for path in scanned.ready {
+        let base = destination_for(&source_root, &dest_root, Path::new(&path), &convert_options);
+        if !bases.insert(base.clone()) {
+            report.skipped.push(IncompleteFile {
+                reason: format!("another source already maps to {}", base.display()),
+                path,
+            });
+            continue;
+        }
+        // An output newer than its source is up to date whatever its extension, without probing
+        let source_modified = modified(Path::new(&path));
+        let current = OUTPUT_EXTENSIONS
+            .map(|ext| with_ext(&base, ext))
+            .into_iter()
+            .find(|output| matches!((source_modified, modified(output)), (Some(src), Some(dst)) if dst >= src));
+        if let Some(output) = current {
+            expected.insert(output);
+            report.up_to_date += 1;
+            continue;
+        }
+        let info = probe::probe(&path).ok();
+        if info.is_none() {
+            // Which extension it had can't be told, so neither output is an orphan
+            expected.extend(OUTPUT_EXTENSIONS.map(|ext| with_ext(&base, ext)));
+        }
+        let output = with_ext(&base, convert::output_extension(info.as_ref()));
+        expected.insert(output.clone());
+        let existed = modified(&output).is_some();
+        work.push((path, info, output, existed));
+    }
+
+    tracing::info!(source = %source, destination = %destination, convert = work.len(), up_to_date = report.up_to_date, "syncing library");
+    let total = work.len();
+    for (index, (path, info, output, existed)) in work.into_iter().enumerate() {
+        if !options.dry_run {
+            let _ = app.emit(SYNC_PROGRESS_EVENT, &SyncProgress { path: path.clone(), index, total });
+            let result = output
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .map_err(|e| format!("Failed to create destination folder: {}", e))
+                .and_then(|_| {
+                    let destination = Destination::File(staging_path(&output));
+                    convert::convert_probed(&app, path.clone(), info, Some(convert_options.clone()), destination)
+                })
+                .and_then(|_| {
+                    for (staged, target) in staged_files(&staging_path(&output), &output) {
+                        fs::rename(&staged, &target)
+                            .map_err(|e| format!("Failed to move {} into place: {}", target.display(), e))?;
+                    }
+                    Ok(())
+                });
+            if let Err(error) = result {
+                // Only what this run wrote goes; the previous output stays until a conversion succeeds
+                for (staged, _) in staged_files(&staging_path(&output), &output) {
+                    let _ = fs::remove_file(staged);
+                }
+                report.failed.push(SyncFailure { path, error });
+                continue;
+            }
+        }
+        if existed {
+            report.updated.push(path);
+        } else {
+            report.added.push(path);
+        }
+    }
+
+    let mut outputs = Vec::new();
+    collect_outputs(&dest_root, &mut outputs);
+    outputs.sort();
+    for output in outputs {
+        if expected.contains(&output) || expected.contains(&unsplit_name(&output)) {
+            continue;
+        }
+        if options.delete_orphans && !options.dry_run {
+            if let Err(e) = fs::remove_file(&output) {
+                tracing::warn!(path = %output.display(), error = %e, "failed to delete orphaned output");
+                continue;
+            }
+        }
+        report.orphans.push(output.to_string_lossy().to_string());
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,72 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::fs;
+use std::process::Command;
+
+use crate::{planner, probe};
+
+/// Thumbnails are scaled down to at most this wide
+const THUMBNAIL_WIDTH: u32 = 640;
+
+/// Without a timestamp the frame comes from this far in, past black openings and logos
+const DEFAULT_POSITION: f64 = 0.1;
+
+/// Grab one frame of `path`'s video as a JPEG, at `timestamp` seconds or a tenth of the
+/// way in. Returns the temp file's path, or with `base64` a data URL and no file left behind.
+#[tauri::command(async)]
+pub fn generate_thumbnail(path: String, timestamp: Option<f64>, base64: Option<bool>) -> Result<String, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let info = probe::probe(&path)?;
+    let video = planner::primary_video(&info).ok_or("The file has no video to take a thumbnail from")?;
+    let duration = info.duration.unwrap_or_default();
+    let at = match timestamp {
+        Some(t) if t < 0.0 || (duration > 0.0 && t > duration) => {
+            return Err(format!("{:.1}s is outside the video ({:.1}s long)", t, duration));
+        }
+        Some(t) => t,
+        None => duration * DEFAULT_POSITION,
+    };
+
+    let thumbnail = crate::unique_temp_path("thumbnail", "jpg");
+    let thumbnail_str = thumbnail.to_string_lossy().to_string();
+    let seek = format!("{:.3}", at);
+    let map = format!("0:{}", video.index);
+    let scale = format!("scale='min({},iw)':-2", THUMBNAIL_WIDTH);
+    // -ss before -i seeks by keyframe index instead of decoding up to the timestamp
+    let args = [
+        "-hide_banner", "-loglevel", "error",
+        "-ss", &seek,
+        "-i", &path,
+        "-map", &map,
+        "-frames:v", "1",
+        "-vf", &scale,
+        "-q:v", "3",
+        "-y", &thumbnail_str,
+    ];
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    // A seek into the last GOP can come up empty without ffmpeg calling it an error
+    if !output.status.success() || !thumbnail.is_file() {
+        let _ = fs::remove_file(&thumbnail);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Thumbnail failed: {}", stderr.trim()));
+    }
+
+    if !base64.unwrap_or(false) {
+        return Ok(thumbnail_str);
+    }
+    let bytes = fs::read(&thumbnail).map_err(|e| format!("Failed to read thumbnail: {}", e));
+    let _ = fs::remove_file(&thumbnail);
+    Ok(format!("data:image/jpeg;base64,{}", BASE64.encode(bytes?)))
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Common whisper.cpp locations to check on macOS
+#[cfg(target_os = "macos")]
+const WHISPER_PATHS: &[&str] = &[
+    "/opt/homebrew/bin/whisper-cli",
+    "/usr/local/bin/whisper-cli",
+    "/opt/homebrew/bin/whisper-cpp",
+];
+
+/// Common whisper.cpp locations to check on Windows
+#[cfg(target_os = "windows")]
+const WHISPER_PATHS: &[&str] = &[
+    "whisper-cli",  // In PATH
+    "C:\\Program Files\\whisper.cpp\\whisper-cli.exe",
+];
+
+/// Common whisper.cpp locations to check on Linux
+#[cfg(target_os = "linux")]
+const WHISPER_PATHS: &[&str] = &[
+    "/usr/bin/whisper-cli",
+    "/usr/local/bin/whisper-cli",
+    "/usr/bin/whisper-cpp",
+    "/snap/bin/whisper-cpp",
+];
+
+/// Speech-to-text settings for a conversion
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transcription {
+    /// ggml model file; defaults to the first one in the app's `whisper` folder
+    pub model_path: Option<String>,
+    /// Spoken language code, e.g. "en"; whisper detects it when unset
+    pub language: Option<String>,
+    /// Mux the subtitles into the MP4 instead of writing an .srt next to it
+    pub mux: bool,
+}
+
+/// Find a whisper.cpp CLI binary
+pub fn find_whisper() -> Option<String> {
+    let found = WHISPER_PATHS
+        .iter()
+        .find(|path| (PathBuf::from(path).exists() || **path == "whisper-cli") && crate::tool_runs(path, "--help"))
+        .map(|path| path.to_string());
+    if found.is_some() {
+        return found;
+    }
+
+    // Same place we drop our own ffmpeg download
+    let local = dirs::home_dir()?.join(".local/bin/whisper-cli");
+    let local = local.to_string_lossy().to_string();
+    crate::tool_runs(&local, "--help").then_some(local)
+}
+
+/// The model to use: the configured one or the first ggml-*.bin in the app data `whisper` folder
+pub fn find_model(configured: Option<&str>) -> Result<PathBuf, String> {
+    if let Some(path) = configured {
+        let path = PathBuf::from(path);
+        return if path.is_file() {
+            Ok(path)
+        } else {
+            Err(format!("Whisper model not found: {}", path.display()))
+        };
+    }
+
+    let dir = crate::app_data_dir()?.join("whisper");
+    let mut models: Vec<PathBuf> = fs::read_dir(&dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    models.retain(|p| {
+        let name = p.file_name().unwrap_or_default().to_string_lossy();
+        name.starts_with("ggml-") && name.ends_with(".bin")
+    });
+    models.sort();
+    models
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("No whisper model found; put a ggml-*.bin model in {}", dir.display()))
+}
+
+/// Run ffmpeg or whisper and return its output, failing on a non-zero exit
+pub fn run_tool(path: &str, args: &[&str], what: &str) -> Result<Output, String> {
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", path])
+        .args(args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(path)
+        .args(args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run {}: {}", what, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} failed: {}", what, stderr));
+    }
+    Ok(output)
+}
+
+/// Sidecar path for generated subtitles next to a converted file, e.g. movie.srt or movie.en.srt
+pub fn sidecar_path(output_path: &str, language: Option<&str>) -> PathBuf {
+    let path = Path::new(output_path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match language {
+        Some(lang) => path.with_file_name(format!("{}.{}.srt", stem, lang)),
+        None => path.with_file_name(format!("{}.srt", stem)),
+    }
+}
+
+/// Transcribe the first audio track of `input_path` into an SRT at `srt_path`
+pub fn transcribe_to_srt(input_path: &str, srt_path: &Path, options: &Transcription) -> Result<PathBuf, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let whisper_path = find_whisper().ok_or("whisper.cpp not found; install whisper-cli to generate subtitles")?;
+    let model = find_model(options.model_path.as_deref())?;
+
+    // whisper.cpp only reads 16 kHz mono WAV
+    let wav = crate::unique_temp_path("whisper", "wav");
+    let wav_str = wav.to_string_lossy().to_string();
+    let extracted = run_tool(
+        &ffmpeg_path,
+        &["-i", input_path, "-map", "0:a:0", "-vn", "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le", "-y", &wav_str],
+        "Audio extraction",
+    );
+    if let Err(e) = extracted {
+        let _ = fs::remove_file(&wav);
+        return Err(e);
+    }
+
+    // -of takes the output path without the .srt extension
+    let srt_base = srt_path.with_extension("").to_string_lossy().to_string();
+    let model_str = model.to_string_lossy().to_string();
+    let language = options.language.as_deref().unwrap_or("auto");
+    tracing::info!(model = %model.display(), language, "transcribing audio");
+    let transcribed = run_tool(
+        &whisper_path,
+        &["-m", &model_str, "-f", &wav_str, "-l", language, "-osrt", "-of", &srt_base, "-np"],
+        "Transcription",
+    );
+    let _ = fs::remove_file(&wav);
+    transcribed?;
+
+    if !srt_path.is_file() {
+        return Err("Transcription produced no subtitle file".to_string());
+    }
+    Ok(srt_path.to_path_buf())
+}
+
+/// Generate subtitles for a file with whisper.cpp and write them next to it (or at `output_path`)
+#[tauri::command(async)]
+pub fn transcribe_audio(
+    path: String,
+    options: Option<Transcription>,
+    output_path: Option<String>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let srt_path = output_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| sidecar_path(&path, options.language.as_deref()));
+    transcribe_to_srt(&path, &srt_path, &options).map(|p| p.to_string_lossy().to_string())
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::convert;
+use crate::naming::{self, FilenamePolicy};
+use crate::probe;
+
+/// Times closer than this are treated as the same frame
+const TIME_EPSILON: f64 = 0.001;
+
+/// Default clip name; {name} is the source stem, {n} the clip number, {start}/{end} as HH-MM-SS
+const DEFAULT_CLIP_TEMPLATE: &str = "{name}_clip{n}";
+
+/// Quality for the few re-encoded frames around smart cuts; high so the seam isn't visible
+const SMART_CUT_CRF: &str = "16";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrimMode {
+    /// Stream copy: fast, but cut points snap to keyframes
+    #[default]
+    Copy,
+    /// Re-encode only the partial GOPs at each end and copy the rest: frame-accurate
+    SmartCut,
+}
+
+/// A start/end pair in seconds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl TimeRange {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.start >= 0.0 && self.end > self.start) {
+            return Err(format!("Invalid time range {:.3}-{:.3}", self.start, self.end));
+        }
+        Ok(())
+    }
+
+    fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+fn run_ffmpeg(args: &[String]) -> Result<(), String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    tracing::debug!(?args, "ffmpeg arguments");
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .output();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .output();
+
+    let output = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffmpeg failed: {}", stderr));
+    }
+    Ok(())
+}
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+/// Keyframe-snapped cut of every stream
+fn copy_cut(input_path: &str, range: TimeRange, output: &Path) -> Result<(), String> {
+    let start = format!("{:.3}", range.start);
+    let duration = format!("{:.3}", range.duration());
+    run_ffmpeg(&strings(&[
+        "-ss", &start,
+        "-i", input_path,
+        "-t", &duration,
+        "-codec", "copy",
+        "-avoid_negative_ts", "make_zero",
+        "-movflags", "+faststart",
+        "-y", &output.to_string_lossy(),
+    ]))
+}
+
+/// Encoder that produces a stream the copied middle section can be spliced onto, and the MP4
+/// sample entry tag that tells players to read parameter sets from the stream itself
+fn matching_encoder(codec: &str) -> Result<(&'static str, &'static str), String> {
+    match codec {
+        "h264" => Ok(("libx264", "avc3")),
+        "hevc" => Ok(("libx265", "hev1")),
+        other => Err(format!("Smart cut supports H.264 and HEVC video, not {}", other)),
+    }
+}
+
+fn smart_cut(input_path: &str, range: TimeRange, output: &Path) -> Result<(), String> {
+    let info = probe::probe(input_path)?;
+    let video = info.streams_of("video").next().ok_or("Smart cut needs a video stream")?;
+    let (encoder, inband_tag) = matching_encoder(&video.codec)?;
+
+    let keyframes = probe::keyframe_times(input_path, range.start, range.end)?;
+    let first_key = keyframes.iter().copied().find(|&t| t >= range.start - TIME_EPSILON);
+    let last_key = keyframes.iter().copied().rfind(|&t| t <= range.end + TIME_EPSILON);
+
+    // (start, end, copy?) pieces of the video, in order
+    let mut pieces: Vec<(f64, f64, bool)> = Vec::new();
+    match (first_key, last_key) {
+        (Some(k1), Some(k2)) if k2 > k1 => {
+            if k1 > range.start + TIME_EPSILON {
+                pieces.push((range.start, k1, false));
+            }
+            pieces.push((k1, k2, true));
+            if range.end > k2 + TIME_EPSILON {
+                pieces.push((k2, range.end, false));
+            }
+        }
+        // The whole range sits inside one GOP
+        _ => pieces.push((range.start, range.end, false)),
+    }
+
+    let work_dir = crate::unique_temp_path("smartcut", "d");
+    fs::create_dir_all(&work_dir).map_err(|e| format!("Failed to create work directory: {}", e))?;
+    let result = (|| {
+        // MPEG-TS pieces repeat their SPS/PPS in-band, so they concatenate cleanly, but the re-encoded
+        // edges have different parameter sets from the copied middle. An MP4 avcC/hvcC box holds only
+        // one set, so the output is tagged avc3/hev1 and players use the in-band sets of each piece.
+        let mut list = String::new();
+        for (i, (start, end, copy)) in pieces.iter().enumerate() {
+            let piece = work_dir.join(format!("piece{}.ts", i));
+            let start_str = format!("{:.3}", start);
+            let duration = format!("{:.3}", end - start);
+            let mut args = strings(&[
+                "-ss", &start_str,
+                "-i", input_path,
+                "-t", &duration,
+                "-map", "0:v:0", "-an", "-sn",
+            ]);
+            if *copy {
+                args.extend(strings(&["-c:v", "copy"]));
+            } else {
+                args.extend(strings(&["-c:v", encoder, "-crf", SMART_CUT_CRF, "-preset", "medium"]));
+            }
+            args.extend(strings(&["-f", "mpegts", "-y", &piece.to_string_lossy()]));
+            run_ffmpeg(&args)?;
+            list.push_str(&format!("file '{}'\n", piece.to_string_lossy().replace('\'', "'\\''")));
+        }
+
+        let list_path = work_dir.join("pieces.txt");
+        fs::write(&list_path, list).map_err(|e| format!("Failed to write concat list: {}", e))?;
+
+        // Audio frames are short, so a copied cut is already close to exact
+        let start = format!("{:.3}", range.start);
+        let duration = format!("{:.3}", range.duration());
+        run_ffmpeg(&strings(&[
+            "-f", "concat", "-safe", "0", "-i", &list_path.to_string_lossy(),
+            "-ss", &start, "-i", input_path,
+            "-t", &duration,
+            "-map", "0:v:0", "-map", "1:a?",
+            "-codec", "copy",
+            "-tag:v", inband_tag,
+            "-movflags", "+faststart",
+            "-y", &output.to_string_lossy(),
+        ]))
+    })();
+
+    let _ = fs::remove_dir_all(&work_dir);
+    result
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ClipOptions {
+    pub mode: TrimMode,
+    /// Output name pattern, see DEFAULT_CLIP_TEMPLATE
+    pub name_template: Option<String>,
+    pub filename_policy: FilenamePolicy,
+}
+
+/// Seconds as HH-MM-SS, safe to put in a file name
+fn timestamp_for_name(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    format!("{:02}-{:02}-{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn clip_output(input_path: &str, template: &str, n: usize, range: TimeRange, ext: &str, policy: FilenamePolicy) -> PathBuf {
+    let path = Path::new(input_path);
+    let name = template
+        .replace("{name}", &path.file_stem().unwrap_or_default().to_string_lossy())
+        .replace("{n}", &format!("{:02}", n))
+        .replace("{start}", &timestamp_for_name(range.start))
+        .replace("{end}", &timestamp_for_name(range.end));
+    // The extension keeps dots in the rendered name from being read as one
+    let base = path.with_file_name(format!("{}.{}", name, ext));
+    naming::get_unique_output_path(&base.to_string_lossy(), ext, policy)
+}
+
+/// Copy-cut every range in one ffmpeg run: one seeked input per range, one output per input
+fn copy_clips(input_path: &str, clips: &[(TimeRange, PathBuf)]) -> Result<(), String> {
+    let mut args = Vec::new();
+    for (range, _) in clips {
+        args.extend([
+            "-ss".to_string(),
+            format!("{:.3}", range.start),
+            "-t".to_string(),
+            format!("{:.3}", range.duration()),
+            "-i".to_string(),
+            input_path.to_string(),
+        ]);
+    }
+    for (i, (_, output)) in clips.iter().enumerate() {
+        args.extend([
+            "-map".to_string(),
+            format!("{}:V?", i),
+            "-map".to_string(),
+            format!("{}:a?", i),
+            "-codec".to_string(),
+            "copy".to_string(),
+            "-avoid_negative_ts".to_string(),
+            "make_zero".to_string(),
+            "-movflags".to_string(),
+            "+faststart".to_string(),
+            "-y".to_string(),
+            output.to_string_lossy().to_string(),
+        ]);
+    }
+    run_ffmpeg(&args)
+}
+
+/// Cut `range` out of `input_path` into `output`
+pub fn cut(input_path: &str, range: TimeRange, output: &Path, mode: TrimMode) -> Result<(), String> {
+    range.validate()?;
+    tracing::info!(start = range.start, end = range.end, ?mode, output = %output.display(), "trimming");
+    match mode {
+        TrimMode::Copy => copy_cut(input_path, range, output),
+        TrimMode::SmartCut => smart_cut(input_path, range, output),
+    }
+}
+
+/// Trim a file to `start`-`end` seconds, writing `<name>_trim.mp4` (or .m4a) next to it
+#[tauri::command(async)]
+pub fn trim_file(input_path: String, start: f64, end: f64, mode: Option<TrimMode>) -> Result<String, String> {
+    let ext = convert::output_extension(probe::probe(&input_path).ok().as_ref());
+    let output = naming::output_with_suffix(&input_path, "_trim", ext, FilenamePolicy::default());
+    cut(&input_path, TimeRange { start, end }, &output, mode.unwrap_or_default())?;
+    Ok(output.to_string_lossy().to_string())
+}
+
+/// Cut several ranges from one source, one MP4 per range. Copy mode does it in a single
+/// ffmpeg run; smart cuts are done one at a time.
+#[tauri::command(async)]
+pub fn extract_clips(path: String, ranges: Vec<TimeRange>, options: Option<ClipOptions>) -> Result<Vec<String>, String> {
+    if ranges.is_empty() {
+        return Err("No clip ranges given".to_string());
+    }
+    for range in &ranges {
+        range.validate()?;
+    }
+
+    let options = options.unwrap_or_default();
+    let template = options.name_template.as_deref().unwrap_or(DEFAULT_CLIP_TEMPLATE);
+    let ext = convert::output_extension(probe::probe(&path).ok().as_ref());
+    let mut clips: Vec<(TimeRange, PathBuf)> = Vec::new();
+    for (i, range) in ranges.into_iter().enumerate() {
+        let output = clip_output(&path, template, i + 1, range, ext, options.filename_policy);
+        if clips.iter().any(|(_, o)| *o == output) {
+            return Err("Clip name template must produce a different name for each clip".to_string());
+        }
+        clips.push((range, output));
+    }
+
+    tracing::info!(count = clips.len(), mode = ?options.mode, "extracting clips");
+    match options.mode {
+        TrimMode::Copy => copy_clips(&path, &clips)?,
+        TrimMode::SmartCut => {
+            for (range, output) in &clips {
+                smart_cut(&path, *range, output)?;
+            }
+        }
+    }
+
+    Ok(clips.into_iter().map(|(_, o)| o.to_string_lossy().to_string()).collect())
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::Path;
+
+use crate::presets::Preset;
+use crate::progress;
+
+/// Encoders with a two-pass mode ffmpeg can drive
+const TWO_PASS_ENCODERS: &[&str] = &["libx264", "libx265", "libvpx-vp9"];
+
+/// Output options, each followed by one value, that only apply to the real output. The
+/// first pass writes to the null muxer, which would refuse them.
+const OUTPUT_ONLY_FLAGS: &[&str] = &[
+    "-f", "-movflags", "-segment_time", "-segment_format", "-reset_timestamps", "-map_metadata", "-map_chapters",
+    "-encryption_scheme", "-encryption_key", "-encryption_kid",
+];
+
+/// Check `preset` can be encoded in two passes: it targets a bitrate, with an encoder
+/// that has a two-pass mode
+pub fn check(preset: Option<&Preset>) -> Result<(), String> {
+    let preset = preset.ok_or("Two-pass encoding needs a preset to encode with")?;
+    if preset.video_bitrate_kbps.is_none() {
+        return Err("Two-pass encoding needs a preset with a target video bitrate".to_string());
+    }
+    if !TWO_PASS_ENCODERS.contains(&preset.video_codec.as_str()) {
+        return Err(format!("{} has no two-pass mode; use libx264, libx265 or libvpx-vp9", preset.video_codec));
+    }
+    Ok(())
+}
+
+/// x265 options are ':'-separated, and Windows paths have one after the drive letter
+fn escape_x265_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(':', "\\:")
+}
+
+/// Arguments that make `codec` run pass `pass` (1 or 2), sharing statistics through
+/// files named after `log_prefix`
+pub fn pass_args(codec: &str, pass: u8, log_prefix: &Path) -> Vec<String> {
+    let log = log_prefix.to_string_lossy();
+    if codec == "libx265" {
+        let params = format!("pass={}:stats={}", pass, escape_x265_value(&log));
+        return vec!["-x265-params".to_string(), params];
+    }
+    vec!["-pass".to_string(), pass.to_string(), "-passlogfile".to_string(), log.to_string()]
+}
+
+/// The first pass's arguments, from the second pass's `args` up to their progress and
+/// output arguments: the same input, streams and video encoding, with only the video
+/// analyzed and nothing written but the statistics
+pub fn first_pass_args(args: &[String], codec: &str, log_prefix: &Path) -> Vec<String> {
+    let mut first = Vec::with_capacity(args.len());
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if OUTPUT_ONLY_FLAGS.contains(&arg.as_str()) || arg.starts_with("-metadata") {
+            rest.next();
+            continue;
+        }
+        first.push(arg.clone());
+    }
+    first.extend(pass_args(codec, 1, log_prefix));
+    first.extend(["-an", "-sn", "-dn", "-f", "null"].map(String::from));
+    first.extend(progress::args());
+    first.extend(["-y", "-"].map(String::from));
+    first
+}
+
+/// Remove the statistics files the encoder wrote next to `log_prefix`, such as x264's
+/// `<prefix>-0.log` and `<prefix>-0.log.mbtree`
+pub fn remove_logs(log_prefix: &Path) {
+    let (Some(dir), Some(prefix)) = (log_prefix.parent(), log_prefix.file_name()) else {
+        return;
+    };
+    let prefix = prefix.to_string_lossy();
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(prefix.as_ref()) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use serde::Serialize;
+
+use crate::probe::{self, StreamInfo};
+
+/// Bitstream audio formats receivers decode themselves, so framing must survive the remux
+const PASSTHROUGH_AUDIO_CODECS: &[&str] = &["ac3", "eac3", "dts", "truehd"];
+
+/// Frame counts may differ by a couple of packets where ffmpeg trims a partial frame at the end
+const PACKET_COUNT_TOLERANCE: u64 = 2;
+
+/// Result of comparing one passthrough audio track between source and output
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioIntegrity {
+    pub source_index: Option<u32>,
+    pub output_index: u32,
+    pub codec: String,
+    pub source_packets: Option<u64>,
+    pub output_packets: Option<u64>,
+    pub problems: Vec<String>,
+    pub ok: bool,
+}
+
+fn packet_count(counts: &[(u32, u64)], index: u32) -> Option<u64> {
+    counts.iter().find(|(i, _)| *i == index).map(|(_, count)| *count)
+}
+
+fn compare_params(source: &StreamInfo, output: &StreamInfo) -> Vec<String> {
+    let mut problems = Vec::new();
+    if source.channels != output.channels {
+        problems.push(format!("channel count changed from {:?} to {:?}", source.channels, output.channels));
+    }
+    if source.sample_rate != output.sample_rate {
+        problems.push(format!("sample rate changed from {:?} to {:?}", source.sample_rate, output.sample_rate));
+    }
+    if source.profile != output.profile {
+        problems.push(format!("profile changed from {:?} to {:?}", source.profile, output.profile));
+    }
+    problems
+}
+
+/// Check that AC3/EAC3/DTS/TrueHD tracks in `output_path` match their source track
+/// in codec parameters and frame count
+pub fn verify_audio_passthrough(source_path: &str, output_path: &str) -> Result<Vec<AudioIntegrity>, String> {
+    let source = probe::probe(source_path)?;
+    let output = probe::probe(output_path)?;
+
+    let passthrough: Vec<&StreamInfo> = output
+        .streams_of("audio")
+        .filter(|s| PASSTHROUGH_AUDIO_CODECS.contains(&s.codec.as_str()))
+        .collect();
+    if passthrough.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let source_counts = probe::count_packets(source_path, "a")?;
+    let output_counts = probe::count_packets(output_path, "a")?;
+
+    let mut used = Vec::new();
+    let mut results = Vec::new();
+    for out in passthrough {
+        // Match to the first unused source track with the same codec
+        let matched = source
+            .streams_of("audio")
+            .find(|s| s.codec == out.codec && !used.contains(&s.index));
+
+        let Some(src) = matched else {
+            results.push(AudioIntegrity {
+                source_index: None,
+                output_index: out.index,
+                codec: out.codec.clone(),
+                source_packets: None,
+                output_packets: packet_count(&output_counts, out.index),
+                problems: vec!["no matching source track".to_string()],
+                ok: false,
+            });
+            continue;
+        };
+        used.push(src.index);
+
+        let mut problems = compare_params(src, out);
+        let source_packets = packet_count(&source_counts, src.index);
+        let output_packets = packet_count(&output_counts, out.index);
+        match (source_packets, output_packets) {
+            (Some(a), Some(b)) if a.abs_diff(b) > PACKET_COUNT_TOLERANCE => {
+                problems.push(format!("frame count changed from {} to {}", a, b));
+            }
+            (_, None) => problems.push("output frames could not be counted".to_string()),
+            _ => {}
+        }
+
+        results.push(AudioIntegrity {
+            source_index: Some(src.index),
+            output_index: out.index,
+            codec: out.codec.clone(),
+            source_packets,
+            output_packets,
+            ok: problems.is_empty(),
+            problems,
+        });
+    }
+
+    Ok(results)
+}
+
+/// Compare passthrough audio tracks of a converted file against its source
+#[tauri::command(async)]
+pub fn verify_audio_bitstream(source_path: String, output_path: String) -> Result<Vec<AudioIntegrity>, String> {
+    verify_audio_passthrough(&source_path, &output_path)
+}
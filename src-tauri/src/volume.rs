@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Largest file FAT12/16/32 can hold (4 GiB - 1 byte). exFAT has no such limit.
+pub const FAT_MAX_FILE_SIZE: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+/// Aim each split segment at this fraction of the limit, since cuts land on keyframes
+const SEGMENT_TARGET_RATIO: f64 = 0.9;
+
+/// What we know about the volume a conversion will write to
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeCheck {
+    /// Lowercase filesystem name as reported by the OS, e.g. "msdos", "fat32", "exfat", "apfs"
+    pub filesystem: Option<String>,
+    pub max_file_size: Option<u64>,
+    pub estimated_size: u64,
+    pub exceeds_limit: bool,
+}
+
+/// Filesystem type of the volume containing `dir`
+#[cfg(target_os = "linux")]
+pub fn filesystem_type(dir: &Path) -> Option<String> {
+    let output = Command::new("stat")
+        .args(["-f", "-c", "%T"])
+        .arg(dir)
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_lowercase())
+}
+
+/// Filesystem type of the volume containing `dir`
+#[cfg(target_os = "macos")]
+pub fn filesystem_type(dir: &Path) -> Option<String> {
+    // Lines look like: /dev/disk4s1 on /Volumes/USB (msdos, local, nodev, nosuid)
+    let output = Command::new("mount").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let dir = fs::canonicalize(dir).ok()?;
+
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, details) = rest.rsplit_once(" (")?;
+            let fs_type = details.split(',').next()?.trim().to_lowercase();
+            Some((mount_point.to_string(), fs_type))
+        })
+        .filter(|(mount_point, _)| dir.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, fs_type)| fs_type)
+}
+
+/// Filesystem type of the volume containing `dir`
+#[cfg(target_os = "windows")]
+pub fn filesystem_type(dir: &Path) -> Option<String> {
+    let letter = drive_letter(dir)?;
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!("(Get-Volume -DriveLetter {}).FileSystem", letter),
+        ])
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_lowercase())
+}
+
+/// Drive letter of the volume containing `dir`
+#[cfg(target_os = "windows")]
+fn drive_letter(dir: &Path) -> Option<char> {
+    use std::path::{Component, Prefix};
+
+    let dir = fs::canonicalize(dir).ok()?;
+    match dir.components().next()? {
+        Component::Prefix(prefix) => match prefix.kind() {
+            Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => Some(letter as char),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Bytes free for new files on the volume containing `dir`
+#[cfg(unix)]
+pub fn free_space(dir: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid statvfs to write into
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // f_bavail leaves out the blocks reserved for root
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Bytes free for new files on the volume containing `dir`
+#[cfg(target_os = "windows")]
+pub fn free_space(dir: &Path) -> Option<u64> {
+    let letter = drive_letter(dir)?;
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!("(Get-Volume -DriveLetter {}).SizeRemaining", letter),
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Per-file size limit for a filesystem, if it has one worth worrying about
+pub fn max_file_size(filesystem: &str) -> Option<u64> {
+    match filesystem {
+        "msdos" | "vfat" | "fat" | "fat16" | "fat32" => Some(FAT_MAX_FILE_SIZE),
+        _ => None,
+    }
+}
+
+/// Check whether a remux of `input_path` will fit on the volume holding `output_dir`
+pub fn check_volume(input_path: &str, output_dir: &Path) -> Result<VolumeCheck, String> {
+    // A remux is about the same size as its source
+    let estimated_size = fs::metadata(input_path)
+        .map_err(|e| format!("Failed to read input file: {}", e))?
+        .len();
+
+    let filesystem = filesystem_type(output_dir);
+    let max_file_size = filesystem.as_deref().and_then(max_file_size);
+
+    Ok(VolumeCheck {
+        exceeds_limit: max_file_size.is_some_and(|max| estimated_size > max),
+        filesystem,
+        max_file_size,
+        estimated_size,
+    })
+}
+
+/// Segment length in seconds that keeps each part under the volume's file size limit
+pub fn segment_seconds(check: &VolumeCheck, duration: f64) -> Option<f64> {
+    let max = check.max_file_size? as f64;
+    let bytes_per_second = check.estimated_size as f64 / duration;
+    (bytes_per_second > 0.0).then(|| max * SEGMENT_TARGET_RATIO / bytes_per_second)
+}
+
+/// Check the output volume for a file before converting, so the UI can warn up front
+#[tauri::command]
+pub fn check_output_volume(input_path: String) -> Result<VolumeCheck, String> {
+    let output_dir = Path::new(&input_path)
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf();
+    check_volume(&input_path, &output_dir)
+}
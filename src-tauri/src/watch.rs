@@ -0,0 +1,210 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::convert::ConvertOptions;
+use crate::filters::ScanFilter;
+use crate::{filters, queue, scan, store};
+
+/// Emitted with a `WatchedFile` when a new video shows up in a watched folder
+const FILE_DETECTED_EVENT: &str = "watch-file-detected";
+
+/// Emitted with a `WatchedFile` once a detected video has finished being written and is queued
+const FILE_QUEUED_EVENT: &str = "watch-file-queued";
+
+/// Folders watched the last time the app ran, so watching resumes after a restart
+const WATCH_FILE: &str = "watch_folders.json";
+
+/// How often detected files are checked for whether they're still being written
+const RECHECK_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchFolder {
+    pub path: String,
+    #[serde(default)]
+    pub recursive: bool,
+    /// Options new files are converted with; the usual per-folder recall when unset
+    pub options: Option<ConvertOptions>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchedFile {
+    pub folder: String,
+    pub path: String,
+}
+
+struct ActiveWatch {
+    folder: WatchFolder,
+    /// Dropping the watcher stops it
+    _watcher: RecommendedWatcher,
+}
+
+/// A detected file waiting until it's no longer being written
+struct PendingFile {
+    path: PathBuf,
+    folder: String,
+}
+
+static WATCHES: Mutex<Vec<ActiveWatch>> = Mutex::new(Vec::new());
+
+static PENDING: Mutex<Vec<PendingFile>> = Mutex::new(Vec::new());
+
+/// Files already queued, so the events a conversion or a later touch causes don't queue them again
+static QUEUED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+static CHECKER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+fn save_folders(watches: &[ActiveWatch]) {
+    let folders: Vec<&WatchFolder> = watches.iter().map(|w| &w.folder).collect();
+    if let Err(e) = store::save_profile(WATCH_FILE, &folders) {
+        tracing::warn!(error = %e, "failed to save watch folders");
+    }
+}
+
+/// Note a new or changed video to queue once it's complete
+fn detected(app: &AppHandle, folder: &str, path: PathBuf) {
+    if QUEUED.lock().unwrap_or_else(|e| e.into_inner()).contains(&path) {
+        return;
+    }
+    let filter = filters::for_folder(Path::new(folder));
+    if !filter.is_candidate(&path) || filter.name_rejection(Path::new(folder), &path).is_some() {
+        return;
+    }
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    if pending.iter().any(|p| p.path == path) {
+        return;
+    }
+    tracing::info!(folder, path = %path.display(), "new file in watched folder");
+    let file = WatchedFile { folder: folder.to_string(), path: path.to_string_lossy().to_string() };
+    let _ = app.emit(FILE_DETECTED_EVENT, &file);
+    pending.push(PendingFile { path, folder: folder.to_string() });
+}
+
+/// Queue detected files once they stop changing, like `scan::enqueue_folder` does for downloads
+fn check_pending(app: AppHandle) {
+    loop {
+        thread::sleep(Duration::from_secs(RECHECK_SECS));
+        let ready: Vec<PendingFile> = {
+            let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+            pending.retain(|p| p.path.exists());
+            let (ready, waiting) = pending
+                .drain(..)
+                .partition(|p| scan::incomplete_reason(&p.path).is_none());
+            *pending = waiting;
+            ready
+        };
+
+        for file in ready {
+            let options = WATCHES
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .find(|w| w.folder.path == file.folder)
+                .map(|w| w.folder.options.clone());
+            // The folder stopped being watched while the file was being written
+            let Some(options) = options else {
+                continue;
+            };
+            let path = file.path.to_string_lossy().to_string();
+            if let Some(reason) = filters::for_folder(Path::new(&file.folder)).size_rejection(&file.path) {
+                tracing::info!(path, %reason, "watched file left out by the folder's filter");
+                continue;
+            }
+            if let Err(e) = queue::enqueue_files(app.clone(), vec![path.clone()], None, options) {
+                tracing::warn!(error = %e, path, "failed to queue file from watched folder");
+                continue;
+            }
+            QUEUED.lock().unwrap_or_else(|e| e.into_inner()).push(file.path);
+            let _ = app.emit(FILE_QUEUED_EVENT, &WatchedFile { folder: file.folder, path });
+        }
+    }
+}
+
+fn start(app: &AppHandle, folder: WatchFolder) -> Result<ActiveWatch, String> {
+    let handler_app = app.clone();
+    let folder_path = folder.path.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+            for path in event.paths {
+                detected(&handler_app, &folder_path, path);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!(error = %e, folder = %folder_path, "watch error"),
+    })
+    .map_err(|e| format!("Failed to start watching {}: {}", folder.path, e))?;
+
+    let mode = if folder.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+    watcher
+        .watch(Path::new(&folder.path), mode)
+        .map_err(|e| format!("Failed to start watching {}: {}", folder.path, e))?;
+
+    if !CHECKER_RUNNING.swap(true, Ordering::SeqCst) {
+        let app = app.clone();
+        thread::spawn(move || check_pending(app));
+    }
+    tracing::info!(folder = %folder.path, recursive = folder.recursive, "watching folder");
+    Ok(ActiveWatch { folder, _watcher: watcher })
+}
+
+/// Resume watching the folders that were watched when the app last quit
+pub fn restore(app: &AppHandle) {
+    let folders: Vec<WatchFolder> = store::load_profile(WATCH_FILE);
+    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    for folder in folders {
+        match start(app, folder) {
+            Ok(watch) => watches.push(watch),
+            Err(e) => tracing::warn!(error = %e, "failed to resume watching folder"),
+        }
+    }
+}
+
+/// Queue every new video that lands in `path` once it's finished being written. Files
+/// already there are left alone; enqueue the folder for those. `filter` replaces the
+/// folder's `ScanFilter`, which otherwise decides what's picked up.
+#[tauri::command]
+pub fn start_watching(
+    app: AppHandle,
+    path: String,
+    recursive: Option<bool>,
+    options: Option<ConvertOptions>,
+    filter: Option<ScanFilter>,
+) -> Result<(), String> {
+    if !Path::new(&path).is_dir() {
+        return Err(format!("Not a folder: {}", path));
+    }
+    if let Some(filter) = filter {
+        filters::set_scan_filter(path.clone(), Some(filter))?;
+    }
+    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    // Watching again replaces the old settings
+    watches.retain(|w| w.folder.path != path);
+    let folder = WatchFolder { path, recursive: recursive.unwrap_or(false), options };
+    watches.push(start(&app, folder)?);
+    save_folders(&watches);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn stop_watching(path: String) -> Result<(), String> {
+    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    let before = watches.len();
+    watches.retain(|w| w.folder.path != path);
+    if watches.len() == before {
+        return Err(format!("Not watching {}", path));
+    }
+    PENDING.lock().unwrap_or_else(|e| e.into_inner()).retain(|p| p.folder != path);
+    tracing::info!(folder = %path, "stopped watching folder");
+    save_folders(&watches);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_watch_folders() -> Vec<WatchFolder> {
+    WATCHES.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|w| w.folder.clone()).collect()
+}
@@ -0,0 +1,102 @@
+use serde::Serialize;
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+use crate::probe;
+
+/// Decode at a low rate; peaks for a waveform don't need more
+const WAVEFORM_SAMPLE_RATE: u32 = 8000;
+
+/// Upper bound on returned points so a bad request can't produce a huge payload
+const MAX_WAVEFORM_POINTS: usize = 20_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Waveform {
+    /// Audio track number among the file's audio streams (0 = first)
+    pub track: u32,
+    pub duration: f64,
+    /// Peak amplitude per bucket, 0.0-1.0, evenly spread over `duration`
+    pub peaks: Vec<f32>,
+}
+
+/// Return downsampled peak data for one audio track so the trim UI can draw a waveform
+#[tauri::command(async)]
+pub fn get_waveform(path: String, track: Option<u32>, samples: Option<usize>) -> Result<Waveform, String> {
+    let ffmpeg_path = crate::find_ffmpeg().ok_or("ffmpeg not found")?;
+    let track = track.unwrap_or(0);
+    let points = samples.unwrap_or(1000).clamp(1, MAX_WAVEFORM_POINTS);
+
+    let info = probe::probe(&path)?;
+    if info.streams_of("audio").nth(track as usize).is_none() {
+        return Err(format!("No audio track {}", track));
+    }
+    let duration = info.duration.ok_or("Can't draw a waveform: duration is unknown")?;
+
+    let total_samples = (duration * WAVEFORM_SAMPLE_RATE as f64).ceil().max(1.0) as usize;
+    let bucket_size = total_samples.div_ceil(points);
+
+    // Mono 16-bit PCM on stdout, read as it's decoded so long files don't sit in memory
+    let map = format!("0:a:{}", track);
+    let rate = WAVEFORM_SAMPLE_RATE.to_string();
+    let args = [
+        "-hide_banner", "-loglevel", "error",
+        "-i", &path,
+        "-map", &map,
+        "-ac", "1", "-ar", &rate,
+        "-f", "s16le", "-",
+    ];
+
+    #[cfg(target_os = "windows")]
+    let result = Command::new("cmd")
+        .args(["/C", &ffmpeg_path])
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+
+    #[cfg(not(target_os = "windows"))]
+    let result = Command::new(&ffmpeg_path)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+
+    let mut child = result.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    let mut stdout = child.stdout.take().ok_or("Failed to read ffmpeg output")?;
+
+    let mut peaks = Vec::with_capacity(points);
+    let mut peak: i32 = 0;
+    let mut in_bucket = 0;
+    let mut buf = [0u8; 64 * 1024];
+    // Bytes left over when a read ends halfway through a sample
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let n = stdout.read(&mut buf).map_err(|e| format!("Failed to read ffmpeg output: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buf[..n]);
+        let whole = pending.len() / 2 * 2;
+        for pair in pending[..whole].chunks_exact(2) {
+            let sample = i16::from_le_bytes([pair[0], pair[1]]);
+            peak = peak.max((sample as i32).abs());
+            in_bucket += 1;
+            if in_bucket == bucket_size {
+                peaks.push(peak as f32 / 32768.0);
+                peak = 0;
+                in_bucket = 0;
+            }
+        }
+        pending.drain(..whole);
+    }
+    if in_bucket > 0 {
+        peaks.push(peak as f32 / 32768.0);
+    }
+
+    let status = child.wait().map_err(|e| format!("Failed to wait for ffmpeg: {}", e))?;
+    if !status.success() {
+        return Err("Failed to decode audio for the waveform".to_string());
+    }
+
+    Ok(Waveform { track, duration, peaks })
+}